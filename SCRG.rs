@@ -2,120 +2,16316 @@
 // Author: azaM & Copilot 🛠️
 // Concepts: Typestate, Rc<RefCell>, HashMap metadata, FnOnce hook, fluent chaining
 
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+// The whole registry API lives in this one binary and `main` only walks through part of it
+#![allow(dead_code, unused_macros)]
+// pyo3's `#[pymethods]` wrappers convert each `PyResult` error into itself
+#![cfg_attr(feature = "python", allow(clippy::useless_conversion))]
 
-// Typestate markers
-struct Init;
-struct Validated;
-struct Deployed;
+// Without `std` only `lib.rs` builds this file, as a `no_std` module. The parts of `std` the
+// core uses are re-exported from `core` and `alloc` so paths read the same in both builds;
+// ordered maps stand in for hash maps.
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use alloc::{borrow, fmt, rc};
+    pub use core::{cell, cmp, convert, error, hash, marker, mem, ops, str};
 
-type Metadata = Rc<RefCell<HashMap<String, String>>>;
+    pub mod collections {
+        pub use alloc::collections::*;
 
-struct ContractBuilder<State> {
-    name: String,
-    metadata: Metadata,
-    _state: std::marker::PhantomData<State>,
+        pub type HashMap<K, V> = BTreeMap<K, V>;
+        pub type HashSet<T> = BTreeSet<T>;
+
+        pub mod hash_map {
+            pub use alloc::collections::btree_map::Entry;
+        }
+    }
+
+    // Single-threaded stand-ins: without `std` there are no threads to share these with
+    pub mod sync {
+        pub use alloc::sync::Arc;
+        pub use core::{cell::OnceCell as OnceLock, sync::atomic};
+
+        use core::cell::{RefCell, RefMut};
+
+        #[derive(Debug, Default)]
+        pub struct Mutex<T>(RefCell<T>);
+
+        impl<T> Mutex<T> {
+            pub const fn new(value: T) -> Self {
+                Self(RefCell::new(value))
+            }
+
+            pub fn lock(&self) -> Result<RefMut<'_, T>, PoisonError<RefMut<'_, T>>> {
+                Ok(self.0.borrow_mut())
+            }
+
+            pub fn into_inner(self) -> Result<T, PoisonError<T>> {
+                Ok(self.0.into_inner())
+            }
+        }
+
+        // Never constructed; keeps `unwrap_or_else(PoisonError::into_inner)` compiling
+        pub struct PoisonError<T>(T);
+
+        impl<T> PoisonError<T> {
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+    }
+
+    // No wall clock either: the embedder sets the time with `set_now`, e.g. to the block
+    // timestamp, and it stays put until set again
+    pub mod time {
+        pub use core::time::Duration;
+
+        use core::sync::atomic::{AtomicI64, Ordering};
+
+        static NOW_MILLIS: AtomicI64 = AtomicI64::new(0);
+
+        pub fn set_now(time: SystemTime) {
+            let millis = time
+                .0
+                .div_euclid(1_000_000)
+                .clamp(i64::MIN.into(), i64::MAX.into());
+            NOW_MILLIS.store(millis as i64, Ordering::Relaxed);
+        }
+
+        // Nanoseconds since the Unix epoch
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct SystemTime(i128);
+
+        impl SystemTime {
+            pub const UNIX_EPOCH: Self = Self(0);
+
+            pub fn now() -> Self {
+                Self(i128::from(NOW_MILLIS.load(Ordering::Relaxed)) * 1_000_000)
+            }
+
+            pub fn duration_since(&self, earlier: Self) -> Result<Duration, SystemTimeError> {
+                let nanos = |n: i128| {
+                    let secs = (n / 1_000_000_000).try_into().unwrap_or(u64::MAX);
+                    Duration::new(secs, (n % 1_000_000_000) as u32)
+                };
+                match self.0 - earlier.0 {
+                    n if n >= 0 => Ok(nanos(n)),
+                    n => Err(SystemTimeError(nanos(-n))),
+                }
+            }
+
+            pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+                self.0
+                    .checked_add(duration.as_nanos().try_into().ok()?)
+                    .map(Self)
+            }
+
+            pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+                self.0
+                    .checked_sub(duration.as_nanos().try_into().ok()?)
+                    .map(Self)
+            }
+        }
+
+        impl core::ops::Add<Duration> for SystemTime {
+            type Output = Self;
+
+            fn add(self, duration: Duration) -> Self {
+                self.checked_add(duration)
+                    .expect("overflow when adding duration to instant")
+            }
+        }
+
+        #[derive(Debug, Clone)]
+        pub struct SystemTimeError(Duration);
+
+        impl SystemTimeError {
+            pub fn duration(&self) -> Duration {
+                self.0
+            }
+        }
+
+        // Reads the same embedder-set clock, so hook timings only advance between `set_now`s
+        #[derive(Debug, Clone, Copy)]
+        pub struct Instant(SystemTime);
+
+        impl Instant {
+            pub fn now() -> Self {
+                Self(SystemTime::now())
+            }
+
+            pub fn elapsed(&self) -> Duration {
+                SystemTime::now().duration_since(self.0).unwrap_or_default()
+            }
+        }
+    }
+
+    pub mod prelude {
+        pub use alloc::{
+            borrow::ToOwned,
+            boxed::Box,
+            format,
+            string::{String, ToString},
+            vec,
+            vec::Vec,
+        };
+    }
 }
 
-impl ContractBuilder<Init> {
-    fn new(name: &str) -> Self {
-        Self {
-            name: name.into(),
-            metadata: Rc::new(RefCell::new(HashMap::new())),
-            _state: std::marker::PhantomData,
+#[cfg(not(feature = "std"))]
+use std::prelude::*;
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    ops::{Bound, Index, RangeBounds},
+    rc::{Rc, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime},
+};
+
+#[cfg(feature = "std")]
+use std::sync::{mpsc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+use std::time::Instant;
+
+// Enters a `tracing` span until the end of the enclosing block; expands to nothing without
+// the `tracing` feature
+macro_rules! trace_span {
+    ($name:literal, $($field:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($name, $($field)*).entered();
+    };
+}
+
+// `tracing` event at `$level`; expands to nothing without the `tracing` feature
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+
+// Typestate markers (uninhabited: they only ever appear inside PhantomData)
+struct Init<Author = NoAuthor>(std::convert::Infallible, std::marker::PhantomData<Author>);
+enum Validated {}
+enum Deployed {}
+enum Upgraded {}
+enum Paused {}
+enum Deprecated {}
+enum Archived {}
+
+// `Init` parameters: `validate()` only exists once `with_author` has moved the builder to
+// `Init<AuthorSet>`
+enum NoAuthor {}
+enum AuthorSet {}
+
+// Lowercase state name shown by `Display`/`Debug` on `ContractBuilder`. Sealed to the
+// built-in markers unless the `custom-states` feature is on, in which case `custom_state!`
+// declares new ones.
+trait BuilderState: sealed::Sealed {
+    const NAME: &'static str;
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    #[cfg(feature = "custom-states")]
+    impl<T: ?Sized> Sealed for T {}
+}
+
+// Edges `advance()` may take. The built-in methods move with `transition()` directly, so
+// only moves into or out of custom states need declaring.
+trait TransitionTo<Next: BuilderState>: BuilderState {}
+
+#[cfg(not(feature = "custom-states"))]
+impl<Author> sealed::Sealed for Init<Author> {}
+
+impl<Author> BuilderState for Init<Author> {
+    const NAME: &'static str = "init";
+}
+
+macro_rules! builder_states {
+    ($($state:ident => $name:literal),* $(,)?) => {
+        $(
+            #[cfg(not(feature = "custom-states"))]
+            impl sealed::Sealed for $state {}
+
+            impl BuilderState for $state {
+                const NAME: &'static str = $name;
+            }
+        )*
+    };
+}
+
+builder_states! {
+    Validated => "validated",
+    Deployed => "deployed",
+    Upgraded => "upgraded",
+    Paused => "paused",
+    Deprecated => "deprecated",
+    Archived => "archived",
+}
+
+// Declares a state outside the built-in pipeline and the states that may `advance()` into
+// it; its methods go in an ordinary `impl ContractBuilder<Audited>` block:
+//   custom_state!(Audited, "audited", from: [Deployed]);
+//   impl TransitionTo<Upgraded> for Audited {}
+#[cfg(feature = "custom-states")]
+macro_rules! custom_state {
+    ($state:ident, $name:literal $(, from: [$($from:ty),* $(,)?])? $(,)?) => {
+        enum $state {}
+
+        impl BuilderState for $state {
+            const NAME: &'static str = $name;
+        }
+
+        $($(impl TransitionTo<$state> for $from {})*)?
+    };
+}
+
+// Value of the `status` key once a contract has left the builder pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum LifecycleStatus {
+    Deployed,
+    Upgraded,
+    Paused,
+    Deprecated,
+    Archived,
+    RolledBack,
+}
+
+impl LifecycleStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Deployed => "deployed",
+            Self::Upgraded => "upgraded",
+            Self::Paused => "paused",
+            Self::Deprecated => "deprecated",
+            Self::Archived => "archived",
+            Self::RolledBack => "rolled_back",
         }
     }
 
-    fn with_author(self, author: &str) -> Self {
-        self.metadata
-            .borrow_mut()
-            .insert("author".into(), author.into());
-        self
+    fn parse(status: &str) -> Option<Self> {
+        [
+            Self::Deployed,
+            Self::Upgraded,
+            Self::Paused,
+            Self::Deprecated,
+            Self::Archived,
+            Self::RolledBack,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == status)
     }
+}
 
-    fn validate(self) -> ContractBuilder<Validated> {
-        self.metadata
-            .borrow_mut()
-            .insert("validated".into(), "true".into());
-        ContractBuilder {
-            name: self.name,
-            metadata: self.metadata,
-            _state: std::marker::PhantomData,
+impl LifecycleStatus {
+    // Still deployed on-chain, so other contracts may depend on it
+    fn is_live(self) -> bool {
+        !matches!(self, Self::Archived | Self::RolledBack)
+    }
+}
+
+impl fmt::Display for LifecycleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Minimal SemVer 2.0 version (`1.2.0`, `2.0.0-rc.1+build.5`) with spec precedence rules.
+// Build metadata is kept for display but ignored by `Eq`, `Ord` and `Hash` (SemVer §10), so
+// `1.0.0+a` and `1.0.0+b` are the same version.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "String", try_from = "String")
+)]
+struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Vec<String>,
+    build: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct VersionError {
+    input: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid version `{}`: {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for VersionError {}
+
+impl Version {
+    fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre: Vec::new(),
+            build: String::new(),
         }
     }
+
+    fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
 }
 
-impl ContractBuilder<Validated> {
-    fn on_deploy<F>(self, hook: F) -> ContractBuilder<Deployed>
-    where
-        F: FnOnce(&mut HashMap<String, String>),
-    {
-        self.metadata
-            .borrow_mut()
-            .insert("status".into(), "deployed".into());
+impl std::str::FromStr for Version {
+    type Err = VersionError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let err = |reason| VersionError {
+            input: input.into(),
+            reason,
+        };
+        let is_numeric = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+        let no_leading_zero = |part: &str| part == "0" || !part.starts_with('0');
+
+        let (rest, build) = input.split_once('+').unwrap_or((input, ""));
+        let (core, pre) = rest.split_once('-').unwrap_or((rest, ""));
+
+        let mut numbers = core.split('.').map(|part| {
+            if !is_numeric(part) || !no_leading_zero(part) {
+                return Err(err("expected MAJOR.MINOR.PATCH without leading zeros"));
+            }
+            part.parse::<u64>()
+                .map_err(|_| err("version number is too large"))
+        });
+        let mut next = || {
+            numbers
+                .next()
+                .unwrap_or(Err(err("expected MAJOR.MINOR.PATCH")))
+        };
+        let (major, minor, patch) = (next()?, next()?, next()?);
+        if core.split('.').count() != 3 {
+            return Err(err("expected MAJOR.MINOR.PATCH"));
+        }
+
+        let valid_ident = |part: &str| {
+            !part.is_empty() && part.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        };
+        let pre: Vec<String> = if rest.contains('-') {
+            pre.split('.').map(str::to_owned).collect()
+        } else {
+            Vec::new()
+        };
+        if pre
+            .iter()
+            .any(|part| !valid_ident(part) || (is_numeric(part) && !no_leading_zero(part)))
         {
-            let mut meta = self.metadata.borrow_mut();
-            hook(&mut meta); // deploy-time logic (e.g. timestamp, signer)
+            return Err(err("malformed pre-release identifier"));
+        }
+        if input.contains('+') && !build.split('.').all(valid_ident) {
+            return Err(err("malformed build metadata"));
         }
 
-        ContractBuilder {
-            name: self.name,
-            metadata: self.metadata,
-            _state: std::marker::PhantomData,
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            pre,
+            build: build.into(),
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
         }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build)?;
+        }
+        Ok(())
     }
 }
 
-impl ContractBuilder<Deployed> {
-    fn registry(self) -> Metadata {
-        self.metadata
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        // Numeric identifiers sort numerically and before alphanumeric ones
+        fn cmp_ident(a: &str, b: &str) -> Ordering {
+            match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => a.cmp(b),
+            }
+        }
+
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater, // 1.0.0 > 1.0.0-rc.1
+                (false, true) => Ordering::Less,
+                (false, false) => self
+                    .pre
+                    .iter()
+                    .zip(&other.pre)
+                    .map(|(a, b)| cmp_ident(a, b))
+                    .find(|ord| ord.is_ne())
+                    .unwrap_or_else(|| self.pre.len().cmp(&other.pre.len())),
+            })
     }
+}
 
-    fn name(&self) -> &str {
-        &self.name
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
     }
+}
 
-    fn metadata(&self) -> Rc<RefCell<HashMap<String, String>>> {
-        Rc::clone(&self.metadata)
+impl Eq for Version {}
+
+impl std::hash::Hash for Version {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (self.major, self.minor, self.patch, &self.pre).hash(state);
     }
+}
 
-    fn borrow(&self) -> std::cell::Ref<HashMap<String, String>> {
-        self.metadata.borrow()
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    fn borrow_mut(&self) -> std::cell::RefMut<HashMap<String, String>> {
-        self.metadata.borrow_mut()
+impl From<Version> for String {
+    fn from(version: Version) -> Self {
+        version.to_string()
     }
+}
 
-    fn into_inner(self) -> HashMap<String, String> {
-        Rc::try_unwrap(self.metadata)
-            .ok()
-            .map(|rc| rc.into_inner())
-            .unwrap_or_else(HashMap::new)
+impl TryFrom<String> for Version {
+    type Error = VersionError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        input.parse()
     }
+}
 
-    fn into_deployed(self) -> Deployed {
-        Deployed {
-            name: self.name,
-            metadata: self.metadata,
-            _state: std::marker::PhantomData,
+// 20-byte Ethereum account; displays in EIP-55 mixed-case checksum form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Address([u8; 20]);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AddressError {
+    input: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid address `{}`: {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+impl Address {
+    // Uppercase each hex letter whose nibble in keccak256(lowercase hex) is >= 8
+    fn to_checksum(self) -> String {
+        let lower: String = self.0.iter().map(|b| format!("{b:02x}")).collect();
+        let hash = keccak256(lower.as_bytes());
+        let mixed: String = lower
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0xf;
+                if nibble >= 8 {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                }
+            })
+            .collect();
+        format!("0x{mixed}")
+    }
+}
+
+impl std::str::FromStr for Address {
+    type Err = AddressError;
+
+    // All-lowercase and all-uppercase inputs carry no checksum; mixed case must match it
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let err = |reason| AddressError {
+            input: input.into(),
+            reason,
+        };
+        let hex = input
+            .strip_prefix("0x")
+            .ok_or_else(|| err("missing 0x prefix"))?;
+        if hex.len() != 40 {
+            return Err(err("expected 40 hex digits"));
+        }
+        let mut bytes = [0u8; 20];
+        for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| err("non-hex character"))?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| err("non-hex character"))?;
+        }
+        let address = Self(bytes);
+        let mixed_case = hex.chars().any(|c| c.is_ascii_lowercase())
+            && hex.chars().any(|c| c.is_ascii_uppercase());
+        if mixed_case && address.to_checksum()[2..] != *hex {
+            return Err(err("EIP-55 checksum mismatch"));
         }
+        Ok(address)
     }
 }
 
-fn main() {
-    let registry = ContractBuilder::new("TokenX")
-        .with_author("azaM")
-        .validate()
-        .on_deploy(|meta| {
-            meta.insert("timestamp".into(), "2025-06-28".into());
-            meta.insert("signer".into(), "0xDEADBEEF".into());
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_checksum())
+    }
+}
+
+impl From<Address> for MetadataValue {
+    fn from(address: Address) -> Self {
+        Self::Address(address.to_string())
+    }
+}
+
+// Original Keccak-256 (0x01 padding), as used by Ethereum rather than FIPS SHA3-256
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    const RATE: usize = 136;
+    const RC: [u64; 24] = [
+        0x0000000000000001,
+        0x0000000000008082,
+        0x800000000000808a,
+        0x8000000080008000,
+        0x000000000000808b,
+        0x0000000080000001,
+        0x8000000080008081,
+        0x8000000000008009,
+        0x000000000000008a,
+        0x0000000000000088,
+        0x0000000080008009,
+        0x000000008000000a,
+        0x000000008000808b,
+        0x800000000000008b,
+        0x8000000000008089,
+        0x8000000000008003,
+        0x8000000000008002,
+        0x8000000000000080,
+        0x000000000000800a,
+        0x800000008000000a,
+        0x8000000080008081,
+        0x8000000000008080,
+        0x0000000080000001,
+        0x8000000080008008,
+    ];
+    const ROTATIONS: [u32; 24] = [
+        1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+    ];
+    const PI: [usize; 24] = [
+        10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+    ];
+
+    let mut padded = data.to_vec();
+    padded.push(0x01);
+    padded.resize(padded.len().div_ceil(RATE) * RATE, 0);
+    if let Some(last) = padded.last_mut() {
+        *last |= 0x80;
+    }
+
+    let mut state = [0u64; 25];
+    for block in padded.chunks_exact(RATE) {
+        for (lane, word) in state.iter_mut().zip(block.chunks_exact(8)) {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(word);
+            *lane ^= u64::from_le_bytes(bytes);
+        }
+        for rc in RC {
+            // θ
+            let mut columns = [0u64; 5];
+            for (x, column) in columns.iter_mut().enumerate() {
+                *column = (0..25).step_by(5).fold(0, |acc, y| acc ^ state[y + x]);
+            }
+            for x in 0..5 {
+                let d = columns[(x + 4) % 5] ^ columns[(x + 1) % 5].rotate_left(1);
+                for y in (0..25).step_by(5) {
+                    state[y + x] ^= d;
+                }
+            }
+            // ρ and π
+            let mut carried = state[1];
+            for (&target, &rotation) in PI.iter().zip(&ROTATIONS) {
+                let next = state[target];
+                state[target] = carried.rotate_left(rotation);
+                carried = next;
+            }
+            // χ
+            for y in (0..25).step_by(5) {
+                let row = [
+                    state[y],
+                    state[y + 1],
+                    state[y + 2],
+                    state[y + 3],
+                    state[y + 4],
+                ];
+                for x in 0..5 {
+                    state[y + x] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+                }
+            }
+            // ι
+            state[0] ^= rc;
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (chunk, lane) in digest.chunks_exact_mut(8).zip(state) {
+        chunk.copy_from_slice(&lane.to_le_bytes());
+    }
+    digest
+}
+
+// UTC at second precision, e.g. `2025-06-28T14:03:00Z`
+fn format_rfc3339(time: SystemTime) -> String {
+    let secs = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        // Floor towards the earlier second for times before the epoch
+        Err(err) => {
+            let before = err.duration();
+            -(before.as_secs() as i64) - i64::from(before.subsec_nanos() > 0)
+        }
+    };
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's `civil_from_days`)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+// `format_rfc3339` plus the fraction of a second, e.g. `2025-06-28T14:03:00.25Z`, for
+// formats that must round-trip exactly
+#[cfg(feature = "serde")]
+fn format_rfc3339_nanos(time: SystemTime) -> String {
+    let nanos = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.subsec_nanos(),
+        Err(err) => (1_000_000_000 - err.duration().subsec_nanos()) % 1_000_000_000,
+    };
+    let whole = format_rfc3339(time);
+    if nanos == 0 {
+        return whole;
+    }
+    let fraction = format!("{nanos:09}");
+    format!(
+        "{}.{}Z",
+        whole.trim_end_matches('Z'),
+        fraction.trim_end_matches('0')
+    )
+}
+
+// `YYYY-MM-DDTHH:MM:SS[.fraction](Z|±HH:MM)`; `None` for anything else, including times
+// without an offset
+#[cfg(feature = "serde")]
+fn parse_rfc3339(input: &str) -> Option<SystemTime> {
+    let number = |part: &str, digits: usize| {
+        (part.len() == digits && part.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| part.parse::<i64>().ok())
+            .flatten()
+    };
+    let (date, time) = input.split_once(['T', 't', ' '])?;
+    let (clock, offset) = match time.strip_suffix(['Z', 'z']) {
+        Some(clock) => (clock, 0),
+        None => {
+            let (clock, offset) = time.split_at(time.rfind(['+', '-'])?);
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = offset[1..].split_once(':')?;
+            let (hours, minutes) = (number(hours, 2)?, number(minutes, 2)?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            (clock, sign * (hours * 3_600 + minutes * 60))
+        }
+    };
+
+    let mut fields = date.split('-');
+    let (year, month, day) = (
+        number(fields.next()?, 4)?,
+        number(fields.next()?, 2)?,
+        number(fields.next()?, 2)?,
+    );
+    let (hms, fraction) = clock.split_once('.').unwrap_or((clock, ""));
+    let mut fields = hms.split(':');
+    let (hour, minute, second) = (
+        number(fields.next()?, 2)?,
+        number(fields.next()?, 2)?,
+        number(fields.next()?, 2)?,
+    );
+    let nanos = if clock.contains('.') {
+        let digits = fraction.len();
+        if !(1..=9).contains(&digits) || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        fraction.parse::<u32>().ok()? * 10u32.pow(9 - digits as u32)
+    } else {
+        0
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    if second > 59 {
+        return None;
+    }
+
+    // Days since 1970-01-01 from a civil date (Howard Hinnant's `days_from_civil`)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days * 86_400 + hour * 3_600 + minute * 60 + second - offset;
+    let whole = if secs >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs.unsigned_abs()))?
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))?
+    };
+    whole.checked_add(Duration::from_nanos(u64::from(nanos)))
+}
+
+// `serde(with = "rfc3339")` for `SystemTime` fields: RFC 3339 strings in UTC instead of
+// serde's `{secs_since_epoch, nanos_since_epoch}` struct
+#[cfg(feature = "serde")]
+mod rfc3339 {
+    use std::time::SystemTime;
+
+    pub(super) fn serialize<S: serde::Serializer>(
+        time: &SystemTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_rfc3339_nanos(*time))
+    }
+
+    pub(super) fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<SystemTime, D::Error> {
+        let raw = <String as serde::Deserialize>::deserialize(deserializer)?;
+        super::parse_rfc3339(&raw).ok_or_else(|| {
+            serde::de::Error::custom(format!("`{raw}` is not an RFC 3339 date-time"))
         })
-        .registry();
+    }
+}
+
+// Wall clock; `now()` panics on wasm32-unknown-unknown, so the browser build reads
+// `Date.now()` instead
+fn now() -> SystemTime {
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    {
+        SystemTime::UNIX_EPOCH + Duration::from_secs_f64(js_sys::Date::now() / 1_000.0)
+    }
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    {
+        SystemTime::now()
+    }
+}
+
+// Stand-in for `std::time::Instant`, which is unavailable in the browser; millisecond precision
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[derive(Clone, Copy)]
+struct Instant(f64);
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl Instant {
+    fn now() -> Self {
+        Self(js_sys::Date::now())
+    }
+
+    fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64((js_sys::Date::now() - self.0).max(0.0) / 1_000.0)
+    }
+}
+
+// RFC 9562 UUID, rendered in the usual hyphenated lowercase form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Uuid(u128);
+
+impl Uuid {
+    // Version 7: 48-bit Unix milliseconds then random bits, so ids sort by creation time
+    fn now_v7() -> Self {
+        let millis = now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        let random = (u128::from(random_u64()) << 64) | u128::from(random_u64());
+        let mut bits = (u128::from(millis & 0xffff_ffff_ffff) << 80) | (random & ((1 << 80) - 1));
+        bits = (bits & !(0xf << 76)) | (0x7 << 76); // version
+        bits = (bits & !(0b11 << 62)) | (0b10 << 62); // variant
+        Self(bits)
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+// Randomly keyed std hasher over a counter; unique enough for ids, not for secrets
+#[cfg(feature = "std")]
+fn random_u64() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed));
+    hasher.finish()
+}
+
+// Without `std` there is no random key: SplitMix64 over a counter, offset by the clock.
+// Ids stay unique within the process and sortable by `set_now` time, but are guessable.
+#[cfg(not(feature = "std"))]
+fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let seed = now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64);
+    let mut z = seed.wrapping_add(
+        COUNTER
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15),
+    );
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Typed metadata values; `Address` is kept as its textual form
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+enum MetadataValue {
+    String(String),
+    Int(i64),
+    Bool(bool),
+    #[cfg_attr(feature = "serde", serde(with = "rfc3339"))]
+    Timestamp(SystemTime),
+    Address(String),
+    Bytes(Vec<u8>),
+    Version(Version),
+    List(Vec<MetadataValue>),
+    Map(MetadataMap),
+}
+
+impl MetadataValue {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) | Self::Address(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "string",
+            Self::Int(_) => "int",
+            Self::Bool(_) => "bool",
+            Self::Timestamp(_) => "timestamp",
+            Self::Address(_) => "address",
+            Self::Bytes(_) => "bytes",
+            Self::Version(_) => "version",
+            Self::List(_) => "list",
+            Self::Map(_) => "map",
+        }
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    fn as_timestamp(&self) -> Option<SystemTime> {
+        match self {
+            Self::Timestamp(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    fn as_version(&self) -> Option<&Version> {
+        match self {
+            Self::Version(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // Approximate payload size in bytes, as checked by `MetadataLimits::max_value_len`;
+    // lists and maps count their keys and items recursively
+    fn payload_len(&self) -> usize {
+        match self {
+            Self::String(s) | Self::Address(s) => s.len(),
+            Self::Bytes(bytes) => bytes.len(),
+            Self::Int(_) | Self::Timestamp(_) => 8,
+            Self::Bool(_) => 1,
+            Self::Version(v) => v.to_string().len(),
+            Self::List(items) => items.iter().map(Self::payload_len).sum(),
+            Self::Map(map) => map
+                .iter()
+                .map(|(key, value)| key.len() + value.payload_len())
+                .sum(),
+        }
+    }
+}
+
+// As derived, except that nested maps are sorted and redacted like top-level metadata
+impl fmt::Debug for MetadataValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(s) => f.debug_tuple("String").field(s).finish(),
+            Self::Int(i) => f.debug_tuple("Int").field(i).finish(),
+            Self::Bool(b) => f.debug_tuple("Bool").field(b).finish(),
+            Self::Timestamp(t) => f.debug_tuple("Timestamp").field(t).finish(),
+            Self::Address(a) => f.debug_tuple("Address").field(a).finish(),
+            Self::Bytes(bytes) => f.debug_tuple("Bytes").field(bytes).finish(),
+            Self::Version(v) => f.debug_tuple("Version").field(v).finish(),
+            Self::List(items) => f.debug_tuple("List").field(items).finish(),
+            Self::Map(map) => f.debug_tuple("Map").field(&redacted_map(map)).finish(),
+        }
+    }
+}
+
+impl fmt::Display for MetadataValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(s) | Self::Address(s) => f.write_str(s),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Timestamp(t) => f.write_str(&format_rfc3339(*t)),
+            Self::Bytes(bytes) => {
+                f.write_str("0x")?;
+                bytes.iter().try_for_each(|b| write!(f, "{b:02x}"))
+            }
+            Self::Version(v) => write!(f, "{v}"),
+            Self::List(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
+            Self::Map(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                f.write_str("{")?;
+                for (i, (k, v)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{k}: {}", redacted(k, v))?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl From<&str> for MetadataValue {
+    fn from(s: &str) -> Self {
+        Self::String(s.into())
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(s: String) -> Self {
+        Self::String(s)
+    }
+}
 
-    println!("📘 Contract Metadata:");
-    for (k, v) in registry.borrow().iter() {
-        println!("  {k}: {v}");
+impl From<i64> for MetadataValue {
+    fn from(i: i64) -> Self {
+        Self::Int(i)
     }
 }
 
+impl From<bool> for MetadataValue {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
+impl From<SystemTime> for MetadataValue {
+    fn from(t: SystemTime) -> Self {
+        Self::Timestamp(t)
+    }
+}
+
+impl From<Version> for MetadataValue {
+    fn from(v: Version) -> Self {
+        Self::Version(v)
+    }
+}
+
+impl From<Vec<u8>> for MetadataValue {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+type MetadataMap = HashMap<String, MetadataValue>;
+type Metadata = Rc<RefCell<MetadataMap>>;
+
+// Inverse of the `From` conversions above; `None` when the variant does not match
+trait FromMetadataValue: Sized {
+    fn from_value(value: &MetadataValue) -> Option<Self>;
+
+    // Why `value` does not convert; the default only names the stored type
+    fn try_from_value(value: &MetadataValue) -> Result<Self, String> {
+        Self::from_value(value).ok_or_else(|| format!("unexpected type {}", value.type_name()))
+    }
+}
+
+impl FromMetadataValue for String {
+    fn from_value(value: &MetadataValue) -> Option<Self> {
+        value.as_str().map(str::to_owned)
+    }
+}
+
+impl FromMetadataValue for i64 {
+    fn from_value(value: &MetadataValue) -> Option<Self> {
+        value.as_int()
+    }
+}
+
+impl FromMetadataValue for bool {
+    fn from_value(value: &MetadataValue) -> Option<Self> {
+        value.as_bool()
+    }
+}
+
+impl FromMetadataValue for SystemTime {
+    fn from_value(value: &MetadataValue) -> Option<Self> {
+        value.as_timestamp()
+    }
+}
+
+impl FromMetadataValue for Version {
+    fn from_value(value: &MetadataValue) -> Option<Self> {
+        value.as_version().cloned()
+    }
+}
+
+// Stored ints convert when in range; strings are parsed, e.g. values taken from the
+// environment or a config file
+macro_rules! from_metadata_int {
+    ($($ty:ty),*) => {
+        $(
+            impl FromMetadataValue for $ty {
+                fn from_value(value: &MetadataValue) -> Option<Self> {
+                    Self::try_from_value(value).ok()
+                }
+
+                fn try_from_value(value: &MetadataValue) -> Result<Self, String> {
+                    let ty = stringify!($ty);
+                    match value {
+                        MetadataValue::Int(i) => Self::try_from(*i)
+                            .map_err(|_| format!("{i} is out of range for {ty}")),
+                        MetadataValue::String(s) => s
+                            .trim()
+                            .parse()
+                            .map_err(|err| format!("`{s}` is not a valid {ty}: {err}")),
+                        other => Err(format!("unexpected type {}", other.type_name())),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_metadata_int!(u64, u32, i32, usize);
+
+impl FromMetadataValue for Address {
+    fn from_value(value: &MetadataValue) -> Option<Self> {
+        Self::try_from_value(value).ok()
+    }
+
+    fn try_from_value(value: &MetadataValue) -> Result<Self, String> {
+        let raw = value
+            .as_str()
+            .ok_or_else(|| format!("unexpected type {}", value.type_name()))?;
+        raw.parse().map_err(|err: AddressError| err.to_string())
+    }
+}
+
+impl FromMetadataValue for Vec<u8> {
+    fn from_value(value: &MetadataValue) -> Option<Self> {
+        match value {
+            MetadataValue::Bytes(bytes) => Some(bytes.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FieldError {
+    key: &'static str,
+    // Type actually stored, `None` if the key is absent
+    found: Option<&'static str>,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.found {
+            Some(found) => write!(
+                f,
+                "metadata field `{}` has unexpected type {found}",
+                self.key
+            ),
+            None => write!(f, "metadata field `{}` is missing", self.key),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+// Why `get_as` could not produce a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GetAsError {
+    Missing(String),
+    Invalid { key: String, reason: String },
+}
+
+impl fmt::Display for GetAsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(key) => write!(f, "metadata field `{key}` is missing"),
+            Self::Invalid { key, reason } => write!(f, "metadata field `{key}`: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for GetAsError {}
+
+fn get_as<T: FromMetadataValue>(value: Option<&MetadataValue>, key: &str) -> Result<T, GetAsError> {
+    let value = value.ok_or_else(|| GetAsError::Missing(key.into()))?;
+    T::try_from_value(value).map_err(|reason| GetAsError::Invalid {
+        key: key.into(),
+        reason,
+    })
+}
+
+fn field<T: FromMetadataValue>(map: &MetadataMap, key: &'static str) -> Result<T, FieldError> {
+    let value = map.get(key).ok_or(FieldError { key, found: None })?;
+    T::from_value(value).ok_or(FieldError {
+        key,
+        found: Some(value.type_name()),
+    })
+}
+
+// A struct whose fields map one-to-one onto metadata keys; implement via `contract_metadata!`
+trait ContractMetadata: Sized {
+    fn into_metadata(self) -> MetadataMap;
+    fn from_metadata(map: &MetadataMap) -> Result<Self, FieldError>;
+}
+
+// Declares a typed metadata struct, e.g.
+//   contract_metadata! { struct TokenInfo { license: String, network: String } }
+// then `builder.with_fields(info)` and `deployed.fields::<TokenInfo>()?.license`.
+// Stands in for a `#[derive(ContractMetadata)]`, which needs a separate proc-macro crate.
+macro_rules! contract_metadata {
+    ($(#[$attr:meta])* struct $name:ident { $($field:ident: $ty:ty),* $(,)? }) => {
+        $(#[$attr])*
+        struct $name {
+            $($field: $ty,)*
+        }
+
+        impl ContractMetadata for $name {
+            fn into_metadata(self) -> MetadataMap {
+                let mut map = MetadataMap::new();
+                $(map.insert(stringify!($field).into(), self.$field.into());)*
+                map
+            }
+
+            fn from_metadata(map: &MetadataMap) -> Result<Self, FieldError> {
+                Ok(Self {
+                    $($field: field(map, stringify!($field))?,)*
+                })
+            }
+        }
+    };
+}
+
+// Structured view of a Solidity JSON ABI; stored under `abi` as a list of maps using the
+// JSON ABI field names, so every store round-trips it without knowing about `Abi`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Abi {
+    functions: Vec<AbiFunction>,
+    events: Vec<AbiEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+struct AbiParam {
+    #[cfg_attr(feature = "serde", serde(default))]
+    name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    kind: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    indexed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AbiFunction {
+    name: String,
+    inputs: Vec<AbiParam>,
+    outputs: Vec<AbiParam>,
+    state_mutability: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AbiEvent {
+    name: String,
+    inputs: Vec<AbiParam>,
+    anonymous: bool,
+}
+
+impl AbiFunction {
+    // Canonical form used for selectors, e.g. `transfer(address,uint256)`
+    fn signature(&self) -> String {
+        let kinds: Vec<&str> = self.inputs.iter().map(|p| p.kind.as_str()).collect();
+        format!("{}({})", self.name, kinds.join(","))
+    }
+}
+
+impl Abi {
+    fn functions(&self) -> &[AbiFunction] {
+        &self.functions
+    }
+
+    fn events(&self) -> &[AbiEvent] {
+        &self.events
+    }
+
+    fn function(&self, name: &str) -> Option<&AbiFunction> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    fn event(&self, name: &str) -> Option<&AbiEvent> {
+        self.events.iter().find(|e| e.name == name)
+    }
+
+    // Constructors, fallbacks and errors are dropped; only functions and events are kept
+    #[cfg(feature = "serde")]
+    fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        #[derive(serde::Deserialize)]
+        struct Entry {
+            #[serde(rename = "type", default)]
+            kind: Option<String>,
+            #[serde(default)]
+            name: String,
+            #[serde(default)]
+            inputs: Vec<AbiParam>,
+            #[serde(default)]
+            outputs: Vec<AbiParam>,
+            #[serde(rename = "stateMutability", default)]
+            state_mutability: String,
+            #[serde(default)]
+            anonymous: bool,
+        }
+
+        let mut abi = Self::default();
+        for entry in serde_json::from_str::<Vec<Entry>>(json)? {
+            // `type` defaults to "function" in the ABI spec
+            match entry.kind.as_deref().unwrap_or("function") {
+                "function" => abi.functions.push(AbiFunction {
+                    name: entry.name,
+                    inputs: entry.inputs,
+                    outputs: entry.outputs,
+                    state_mutability: entry.state_mutability,
+                }),
+                "event" => abi.events.push(AbiEvent {
+                    name: entry.name,
+                    inputs: entry.inputs,
+                    anonymous: entry.anonymous,
+                }),
+                _ => {}
+            }
+        }
+        Ok(abi)
+    }
+
+    fn from_metadata(value: &MetadataValue) -> Option<Self> {
+        let MetadataValue::List(entries) = value else {
+            return None;
+        };
+        let text = |map: &MetadataMap, key: &str| {
+            map.get(key)
+                .and_then(MetadataValue::as_str)
+                .unwrap_or_default()
+                .to_owned()
+        };
+        let params = |map: &MetadataMap, key: &str| -> Option<Vec<AbiParam>> {
+            match map.get(key) {
+                Some(MetadataValue::List(items)) => items
+                    .iter()
+                    .map(|item| match item {
+                        MetadataValue::Map(param) => Some(AbiParam {
+                            name: text(param, "name"),
+                            kind: text(param, "type"),
+                            indexed: param.get("indexed").and_then(MetadataValue::as_bool)
+                                == Some(true),
+                        }),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Some(Vec::new()),
+            }
+        };
+
+        let mut abi = Self::default();
+        for entry in entries {
+            let MetadataValue::Map(map) = entry else {
+                return None;
+            };
+            match map.get("type").and_then(MetadataValue::as_str) {
+                Some("function") => abi.functions.push(AbiFunction {
+                    name: text(map, "name"),
+                    inputs: params(map, "inputs")?,
+                    outputs: params(map, "outputs")?,
+                    state_mutability: text(map, "stateMutability"),
+                }),
+                Some("event") => abi.events.push(AbiEvent {
+                    name: text(map, "name"),
+                    inputs: params(map, "inputs")?,
+                    anonymous: map.get("anonymous").and_then(MetadataValue::as_bool) == Some(true),
+                }),
+                _ => return None,
+            }
+        }
+        Some(abi)
+    }
+}
+
+impl From<Abi> for MetadataValue {
+    fn from(abi: Abi) -> Self {
+        let params = |params: Vec<AbiParam>| {
+            MetadataValue::List(
+                params
+                    .into_iter()
+                    .map(|p| {
+                        MetadataValue::Map(MetadataMap::from([
+                            ("name".into(), p.name.into()),
+                            ("type".into(), p.kind.into()),
+                            ("indexed".into(), p.indexed.into()),
+                        ]))
+                    })
+                    .collect(),
+            )
+        };
+        let functions = abi.functions.into_iter().map(|f| {
+            MetadataValue::Map(MetadataMap::from([
+                ("type".into(), "function".into()),
+                ("name".into(), f.name.into()),
+                ("inputs".into(), params(f.inputs)),
+                ("outputs".into(), params(f.outputs)),
+                ("stateMutability".into(), f.state_mutability.into()),
+            ]))
+        });
+        let events = abi.events.into_iter().map(|e| {
+            MetadataValue::Map(MetadataMap::from([
+                ("type".into(), "event".into()),
+                ("name".into(), e.name.into()),
+                ("inputs".into(), params(e.inputs)),
+                ("anonymous".into(), e.anonymous.into()),
+            ]))
+        });
+        Self::List(functions.chain(events).collect())
+    }
+}
+
+// `0x`-prefixed or bare hex, as produced by compilers
+fn decode_hex(input: &str) -> Result<Vec<u8>, &'static str> {
+    let hex = input.strip_prefix("0x").unwrap_or(input);
+    if !hex.len().is_multiple_of(2) {
+        return Err("odd number of hex digits");
+    }
+    hex.as_bytes()
+        .chunks_exact(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or("non-hex character")
+        })
+        .collect()
+}
+
+// Where a contract lives; stored under `network` as a map with these field names
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Network {
+    name: String,
+    chain_id: u64,
+    rpc_url: Option<String>,
+}
+
+impl Network {
+    fn new(name: &str, chain_id: u64) -> Self {
+        Self {
+            name: name.into(),
+            chain_id,
+            rpc_url: None,
+        }
+    }
+
+    fn with_rpc_url(mut self, rpc_url: &str) -> Self {
+        self.rpc_url = Some(rpc_url.into());
+        self
+    }
+
+    fn from_metadata(value: &MetadataValue) -> Option<Self> {
+        let MetadataValue::Map(map) = value else {
+            return None;
+        };
+        Some(Self {
+            name: map.get("name")?.as_str()?.into(),
+            chain_id: u64::try_from(map.get("chain_id")?.as_int()?).ok()?,
+            rpc_url: map
+                .get("rpc_url")
+                .and_then(MetadataValue::as_str)
+                .map(str::to_owned),
+        })
+    }
+}
+
+// One on-chain instance; a contract keeps these under `deployments`, keyed by chain id
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DeploymentRecord {
+    address: Address,
+    tx_hash: String,
+    block: u64,
+    timestamp: SystemTime,
+}
+
+impl DeploymentRecord {
+    fn from_metadata(value: &MetadataValue) -> Option<Self> {
+        let MetadataValue::Map(map) = value else {
+            return None;
+        };
+        Some(Self {
+            address: map.get("address")?.as_str()?.parse().ok()?,
+            tx_hash: map.get("tx_hash")?.as_str()?.into(),
+            block: u64::try_from(map.get("block")?.as_int()?).ok()?,
+            timestamp: map.get("timestamp")?.as_timestamp()?,
+        })
+    }
+}
+
+impl From<DeploymentRecord> for MetadataValue {
+    fn from(record: DeploymentRecord) -> Self {
+        Self::Map(MetadataMap::from([
+            ("address".into(), record.address.into()),
+            ("tx_hash".into(), record.tx_hash.into()),
+            (
+                "block".into(),
+                i64::try_from(record.block).unwrap_or(i64::MAX).into(),
+            ),
+            ("timestamp".into(), record.timestamp.into()),
+        ]))
+    }
+}
+
+impl From<Network> for MetadataValue {
+    fn from(network: Network) -> Self {
+        let mut map = MetadataMap::from([
+            ("name".into(), network.name.into()),
+            (
+                "chain_id".into(),
+                i64::try_from(network.chain_id).unwrap_or(i64::MAX).into(),
+            ),
+        ]);
+        if let Some(rpc_url) = network.rpc_url {
+            map.insert("rpc_url".into(), rpc_url.into());
+        }
+        Self::Map(map)
+    }
+}
+
+// Keys every contract must carry before it can leave `Init`
+const REQUIRED_KEYS: &[&str] = &[MetaKey::Author.as_str()];
+
+// Markers written by `validate()`, deploy and `seal()`; cleared when a builder returns to `Init`
+const STATE_KEYS: &[&str] = &[
+    MetaKey::Validated.as_str(),
+    MetaKey::ValidatedAt.as_str(),
+    MetaKey::Status.as_str(),
+    MetaKey::DeployedAt.as_str(),
+    MetaKey::Id.as_str(),
+    MetaKey::Signature.as_str(),
+];
+
+// `STATE_KEYS` change only through the builder's own transitions: hooks that write them fail,
+// `validate()` rejects them when set beforehand, and `set()`/`remove()` on deployed builders
+// and contracts and the registry's metadata updates refuse them
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ReservedKey(String);
+
+impl fmt::Display for ReservedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` is managed by the contract lifecycle", self.0)
+    }
+}
+
+impl std::error::Error for ReservedKey {}
+
+fn check_not_reserved(key: &str) -> Result<(), ReservedKey> {
+    match STATE_KEYS.contains(&key) {
+        true => Err(ReservedKey(key.into())),
+        false => Ok(()),
+    }
+}
+
+// Values of every reserved key, to compare before and after a hook runs
+fn reserved_values(meta: &MetadataMap) -> Vec<Option<MetadataValue>> {
+    STATE_KEYS
+        .iter()
+        .map(|key| meta.get(*key).cloned())
+        .collect()
+}
+
+fn check_reserved_unchanged(
+    before: &[Option<MetadataValue>],
+    meta: &MetadataMap,
+) -> Result<(), ReservedKey> {
+    match STATE_KEYS
+        .iter()
+        .zip(before)
+        .find(|(key, old)| meta.get(**key) != old.as_ref())
+    {
+        Some((key, _)) => Err(ReservedKey((*key).into())),
+        None => Ok(()),
+    }
+}
+
+// Per-contract bounds on the metadata map, so a runaway hook cannot grow it without limit.
+// Hook writes are checked as each phase ends, as are `ContractBuilder<Deployed>::set` and
+// the whole map in `validate()`. Deployed contracts keep their builder's limits for `set`,
+// `add_deployment` and the registry's metadata updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct MetadataLimits {
+    max_keys: usize,
+    // In characters
+    max_key_len: usize,
+    // In bytes, see `MetadataValue::payload_len`
+    max_value_len: usize,
+}
+
+// Only key length is bounded by default, as `validate()` always has
+impl Default for MetadataLimits {
+    fn default() -> Self {
+        Self {
+            max_keys: usize::MAX,
+            max_key_len: MAX_KEY_LEN,
+            max_value_len: usize::MAX,
+        }
+    }
+}
+
+impl MetadataLimits {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_max_keys(mut self, max: usize) -> Self {
+        self.max_keys = max;
+        self
+    }
+
+    fn with_max_key_len(mut self, max: usize) -> Self {
+        self.max_key_len = max;
+        self
+    }
+
+    fn with_max_value_len(mut self, max: usize) -> Self {
+        self.max_value_len = max;
+        self
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // `meta` gaining or replacing `key`: the entry itself, plus the key count if it is new
+    fn check_insert(
+        &self,
+        meta: &MetadataMap,
+        key: &str,
+        value: &MetadataValue,
+    ) -> Result<(), LimitExceeded> {
+        self.check_entry(key, value)?;
+        self.check_key_count(meta.len(), !meta.contains_key(key))
+    }
+
+    // A map of `len` keys gaining one more if `is_new`
+    fn check_key_count(&self, len: usize, is_new: bool) -> Result<(), LimitExceeded> {
+        if is_new && len >= self.max_keys {
+            return Err(LimitExceeded::TooManyKeys {
+                count: len + 1,
+                max: self.max_keys,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_entry(&self, key: &str, value: &MetadataValue) -> Result<(), LimitExceeded> {
+        if key.chars().count() > self.max_key_len {
+            return Err(LimitExceeded::KeyTooLong {
+                key: key.into(),
+                max: self.max_key_len,
+            });
+        }
+        let len = value.payload_len();
+        if len > self.max_value_len {
+            return Err(LimitExceeded::ValueTooLarge {
+                key: key.into(),
+                len,
+                max: self.max_value_len,
+            });
+        }
+        Ok(())
+    }
+
+    // Checks only what changed from `before`, so a hook is not blamed for values that were
+    // already there; those are left to `validate()`
+    fn check_changes(
+        &self,
+        before: &MetadataMap,
+        after: &MetadataMap,
+    ) -> Result<(), LimitExceeded> {
+        if after.len() > before.len() && after.len() > self.max_keys {
+            return Err(LimitExceeded::TooManyKeys {
+                count: after.len(),
+                max: self.max_keys,
+            });
+        }
+        after
+            .iter()
+            .filter(|(key, value)| before.get(*key) != Some(*value))
+            .try_for_each(|(key, value)| self.check_entry(key, value))
+    }
+
+    fn check(&self, meta: &MetadataMap) -> Result<(), LimitExceeded> {
+        if meta.len() > self.max_keys {
+            return Err(LimitExceeded::TooManyKeys {
+                count: meta.len(),
+                max: self.max_keys,
+            });
+        }
+        meta.iter()
+            .try_for_each(|(key, value)| self.check_entry(key, value))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LimitExceeded {
+    TooManyKeys { count: usize, max: usize },
+    KeyTooLong { key: String, max: usize },
+    ValueTooLarge { key: String, len: usize, max: usize },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyKeys { count, max } => {
+                write!(f, "metadata holds {count} keys, above the limit of {max}")
+            }
+            Self::KeyTooLong { key, max } => {
+                write!(f, "metadata key `{key}` exceeds {max} characters")
+            }
+            Self::ValueTooLarge { key, len, max } => {
+                write!(
+                    f,
+                    "metadata value `{key}` is {len} bytes, above the {max}-byte limit"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+// Why `ContractBuilder<Deployed>::set` refused a write
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum WriteError {
+    Reserved(ReservedKey),
+    Limit(LimitExceeded),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reserved(err) => write!(f, "{err}"),
+            Self::Limit(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+impl From<ReservedKey> for WriteError {
+    fn from(err: ReservedKey) -> Self {
+        Self::Reserved(err)
+    }
+}
+
+impl From<LimitExceeded> for WriteError {
+    fn from(err: LimitExceeded) -> Self {
+        Self::Limit(err)
+    }
+}
+
+// Lowercased ENS name with at least two non-empty labels (`tokenx.mydao.eth`). Full
+// ENSIP-15 normalization is left to the resolver.
+fn normalize_ens(name: &str) -> Option<String> {
+    let name = name.trim().to_lowercase();
+    let labels: Vec<&str> = name.split('.').collect();
+    let valid = labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty() && !label.chars().any(|c| c.is_whitespace() || c == '/')
+        });
+    valid.then_some(name)
+}
+
+// Keys the crate itself reads or writes. Getters and setters take `impl AsRef<str>`, so
+// `get(MetaKey::Author)` works where `get("author")` does and a misspelt variant won't compile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum MetaKey {
+    Author,
+    Version,
+    Status,
+    Validated,
+    ValidatedAt,
+    DeployedAt,
+    Signer,
+    Signature,
+    Timestamp,
+    Id,
+}
+
+impl MetaKey {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Author => "author",
+            Self::Version => "version",
+            Self::Status => "status",
+            Self::Validated => "validated",
+            Self::ValidatedAt => "validated_at",
+            Self::DeployedAt => "deployed_at",
+            Self::Signer => "signer",
+            Self::Signature => "signature",
+            Self::Timestamp => "timestamp",
+            Self::Id => "id",
+        }
+    }
+}
+
+impl AsRef<str> for MetaKey {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for MetaKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// EIP-170 contract code size limit, in bytes
+const MAX_BYTECODE_LEN: usize = 24_576;
+
+// Generous ceiling for wasm modules; chains with tighter limits can add a `Validator`
+const MAX_WASM_LEN: usize = 4 * 1024 * 1024;
+
+// `\0asm` magic followed by binary format version 1
+const WASM_HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+// Deployable code for one runtime; stored as `runtime` plus the raw bytes under `bytecode`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ContractArtifact {
+    EvmBytecode(Vec<u8>),
+    WasmModule(Vec<u8>),
+    Other { runtime: String, code: Vec<u8> },
+}
+
+impl ContractArtifact {
+    // Inverse of `runtime()`; unknown runtimes become `Other`
+    fn from_parts(runtime: &str, code: Vec<u8>) -> Self {
+        match runtime {
+            "evm" => Self::EvmBytecode(code),
+            "wasm" => Self::WasmModule(code),
+            _ => Self::Other {
+                runtime: runtime.into(),
+                code,
+            },
+        }
+    }
+
+    fn runtime(&self) -> &str {
+        match self {
+            Self::EvmBytecode(_) => "evm",
+            Self::WasmModule(_) => "wasm",
+            Self::Other { runtime, .. } => runtime,
+        }
+    }
+
+    fn code(&self) -> &[u8] {
+        match self {
+            Self::EvmBytecode(code) | Self::WasmModule(code) | Self::Other { code, .. } => code,
+        }
+    }
+
+    fn check(&self) -> Result<(), ValidationError> {
+        let code = self.code();
+        if code.is_empty() {
+            return Err(ValidationError::InvalidBytecode("empty"));
+        }
+        let max = match self {
+            Self::EvmBytecode(_) => MAX_BYTECODE_LEN,
+            Self::WasmModule(_) if !code.starts_with(&WASM_HEADER) => {
+                return Err(ValidationError::InvalidBytecode(
+                    "wasm module does not start with the `\\0asm` version 1 header",
+                ))
+            }
+            Self::WasmModule(_) => MAX_WASM_LEN,
+            Self::Other { .. } => return Ok(()),
+        };
+        if code.len() > max {
+            return Err(ValidationError::BytecodeTooLarge {
+                runtime: self.runtime().into(),
+                len: code.len(),
+                max,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Keys whose values must parse as an `Address`; `validate()` rewrites them checksummed
+const ADDRESS_KEYS: &[&str] = &["signer", "address"];
+const MAX_KEY_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ValidationError {
+    EmptyName,
+    MissingKey(String),
+    KeyTooLong {
+        key: String,
+        max: usize,
+    },
+    InvalidVersion(VersionError),
+    InvalidAddress {
+        key: String,
+        error: AddressError,
+    },
+    InvalidAbi(String),
+    InvalidBytecode(&'static str),
+    BytecodeTooLarge {
+        runtime: String,
+        len: usize,
+        max: usize,
+    },
+    Rules(Vec<RuleViolation>),
+    Schema(Vec<SchemaViolation>),
+    Hook(HookError),
+    UnknownProfile(String),
+    ReservedKey(ReservedKey),
+    LimitExceeded(LimitExceeded),
+    InvalidEnsName(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyName => write!(f, "contract name must not be empty"),
+            Self::MissingKey(key) => write!(f, "required metadata key `{key}` is missing"),
+            Self::KeyTooLong { key, max } => {
+                write!(f, "metadata key `{key}` exceeds {max} characters")
+            }
+            Self::Rules(violations) => {
+                write!(f, "{} validation rule(s) failed", violations.len())?;
+                for violation in violations {
+                    write!(f, "; {violation}")?;
+                }
+                Ok(())
+            }
+            Self::Schema(violations) => {
+                write!(f, "{} schema field(s) failed", violations.len())?;
+                for violation in violations {
+                    write!(f, "; {violation}")?;
+                }
+                Ok(())
+            }
+            Self::InvalidVersion(err) => write!(f, "{err}"),
+            Self::InvalidAddress { key, error } => write!(f, "metadata key `{key}`: {error}"),
+            Self::InvalidAbi(reason) => write!(f, "invalid ABI: {reason}"),
+            Self::InvalidBytecode(reason) => write!(f, "invalid bytecode: {reason}"),
+            Self::BytecodeTooLarge { runtime, len, max } => {
+                write!(
+                    f,
+                    "{runtime} code is {len} bytes, above the {max}-byte limit"
+                )
+            }
+            Self::Hook(err) => write!(f, "{err}"),
+            Self::UnknownProfile(profile) => write!(f, "profile `{profile}` is not defined"),
+            Self::ReservedKey(err) => write!(f, "{err}"),
+            Self::LimitExceeded(err) => write!(f, "{err}"),
+            Self::InvalidEnsName(name) => write!(f, "`{name}` is not a valid ENS name"),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+// Overlong keys keep their dedicated variant
+impl From<LimitExceeded> for ValidationError {
+    fn from(err: LimitExceeded) -> Self {
+        match err {
+            LimitExceeded::KeyTooLong { key, max } => Self::KeyTooLong { key, max },
+            err => Self::LimitExceeded(err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RuleViolation {
+    rule: String,
+    message: String,
+}
+
+impl RuleViolation {
+    fn new(rule: &str, message: impl Into<String>) -> Self {
+        Self {
+            rule: rule.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RuleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.rule, self.message)
+    }
+}
+
+// Custom metadata policy, run by `validate()` after the built-in checks
+trait Validator {
+    fn check(&self, name: &str, metadata: &MetadataMap) -> Result<(), RuleViolation>;
+}
+
+impl<F> Validator for F
+where
+    F: Fn(&str, &MetadataMap) -> Result<(), RuleViolation>,
+{
+    fn check(&self, name: &str, metadata: &MetadataMap) -> Result<(), RuleViolation> {
+        self(name, metadata)
+    }
+}
+
+// Constraints on one metadata key; an empty `FieldSchema` accepts anything, including absence
+#[derive(Debug, Clone, Default)]
+struct FieldSchema {
+    required: bool,
+    // `MetadataValue::type_name()` of the accepted variant
+    kind: Option<&'static str>,
+    allowed: Vec<MetadataValue>,
+    #[cfg(feature = "regex")]
+    pattern: Option<regex::Regex>,
+}
+
+impl FieldSchema {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    // One of the `MetadataValue::type_name()` values, e.g. "string" or "version"
+    fn of_type(mut self, kind: &'static str) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    fn one_of<I, V>(mut self, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<MetadataValue>,
+    {
+        self.allowed.extend(values.into_iter().map(Into::into));
+        self
+    }
+
+    // Matched against the value's display form, so it also applies to versions and addresses
+    #[cfg(feature = "regex")]
+    fn matching(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.pattern = Some(regex::Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    fn check(&self, key: &str, value: Option<&MetadataValue>) -> Option<SchemaViolation> {
+        let key = key.to_owned();
+        let Some(value) = value else {
+            return self.required.then_some(SchemaViolation::Missing { key });
+        };
+        if let Some(expected) = self.kind.filter(|&kind| kind != value.type_name()) {
+            return Some(SchemaViolation::WrongType {
+                key,
+                expected,
+                found: value.type_name(),
+            });
+        }
+        if !self.allowed.is_empty() && !self.allowed.contains(value) {
+            return Some(SchemaViolation::NotAllowed {
+                key,
+                value: value.clone(),
+            });
+        }
+        #[cfg(feature = "regex")]
+        if let Some(pattern) = self.pattern.as_ref() {
+            if !pattern.is_match(&value.to_string()) {
+                return Some(SchemaViolation::PatternMismatch {
+                    key,
+                    pattern: pattern.as_str().into(),
+                });
+            }
+        }
+        None
+    }
+}
+
+// Per-key constraints attached with `with_schema()`; keys outside the schema are unchecked
+#[derive(Debug, Clone, Default)]
+struct MetadataSchema {
+    fields: HashMap<String, FieldSchema>,
+}
+
+impl MetadataSchema {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn field(mut self, key: &str, field: FieldSchema) -> Self {
+        self.fields.insert(key.into(), field);
+        self
+    }
+
+    // Every violating field, sorted by key
+    fn check(&self, metadata: &MetadataMap) -> Result<(), Vec<SchemaViolation>> {
+        let mut violations: Vec<SchemaViolation> = self
+            .fields
+            .iter()
+            .filter_map(|(key, field)| field.check(key, metadata.get(key)))
+            .collect();
+        if violations.is_empty() {
+            return Ok(());
+        }
+        violations.sort_by(|a, b| a.key().cmp(b.key()));
+        Err(violations)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SchemaViolation {
+    Missing {
+        key: String,
+    },
+    WrongType {
+        key: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    NotAllowed {
+        key: String,
+        value: MetadataValue,
+    },
+    PatternMismatch {
+        key: String,
+        pattern: String,
+    },
+}
+
+impl SchemaViolation {
+    fn key(&self) -> &str {
+        match self {
+            Self::Missing { key }
+            | Self::WrongType { key, .. }
+            | Self::NotAllowed { key, .. }
+            | Self::PatternMismatch { key, .. } => key,
+        }
+    }
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { key } => write!(f, "`{key}` is required"),
+            Self::WrongType {
+                key,
+                expected,
+                found,
+            } => write!(f, "`{key}` must be a {expected}, found {found}"),
+            Self::NotAllowed { key, value } => write!(f, "`{key}` does not allow `{value}`"),
+            Self::PatternMismatch { key, pattern } => {
+                write!(f, "`{key}` does not match `{pattern}`")
+            }
+        }
+    }
+}
+
+// Checksum-agnostic EVM address, for `Constraints::matches`
+#[cfg(feature = "regex")]
+const ADDRESS_RE: &str = "^0x[0-9a-fA-F]{40}$";
+
+// Common checks without a hand-written `Validator`; each one reports its own `RuleViolation`
+// once attached with `with_constraints()`
+#[derive(Debug, Clone, Default)]
+struct Constraints {
+    checks: Vec<Constraint>,
+}
+
+#[derive(Debug, Clone)]
+enum Constraint {
+    Required(String),
+    OneOf(String, Vec<MetadataValue>),
+    #[cfg(feature = "regex")]
+    Matches(String, Result<regex::Regex, regex::Error>),
+}
+
+impl Constraints {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn require(mut self, key: &str) -> Self {
+        self.checks.push(Constraint::Required(key.into()));
+        self
+    }
+
+    // Only checked when `key` is present; chain `require` to demand it
+    fn one_of<I, V>(mut self, key: &str, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<MetadataValue>,
+    {
+        let values = values.into_iter().map(Into::into).collect();
+        self.checks.push(Constraint::OneOf(key.into(), values));
+        self
+    }
+
+    // Matched against the value's display form like `FieldSchema::matching`; a pattern that
+    // does not compile is reported as a violation by `validate()` rather than here
+    #[cfg(feature = "regex")]
+    fn matches(mut self, key: &str, pattern: &str) -> Self {
+        self.checks
+            .push(Constraint::Matches(key.into(), regex::Regex::new(pattern)));
+        self
+    }
+}
+
+impl Validator for Constraint {
+    fn check(&self, _name: &str, metadata: &MetadataMap) -> Result<(), RuleViolation> {
+        match self {
+            Self::Required(key) if !metadata.contains_key(key) => Err(RuleViolation::new(
+                "require",
+                format!("`{key}` is required"),
+            )),
+            Self::Required(_) => Ok(()),
+            Self::OneOf(key, allowed) => match metadata.get(key) {
+                Some(value) if !allowed.contains(value) => Err(RuleViolation::new(
+                    "one_of",
+                    format!("`{key}` does not allow `{}`", redacted(key, value)),
+                )),
+                _ => Ok(()),
+            },
+            #[cfg(feature = "regex")]
+            Self::Matches(key, pattern) => {
+                let pattern = pattern
+                    .as_ref()
+                    .map_err(|err| RuleViolation::new("matches", format!("`{key}`: {err}")))?;
+                match metadata.get(key) {
+                    Some(value) if !pattern.is_match(&value.to_string()) => {
+                        Err(RuleViolation::new(
+                            "matches",
+                            format!("`{key}` does not match `{}`", pattern.as_str()),
+                        ))
+                    }
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+// Deploy-time logic (e.g. timestamp, signer), run in registration order.
+// `FnMut` so a failed deployment can be retried with the same hooks.
+type DeployHook = Box<dyn FnMut(&mut MetadataMap) -> Result<(), HookError>>;
+
+// Rest of the chain as seen by a middleware layer: the inner layers, then the queued hooks
+type HookNext<'a> = &'a mut dyn FnMut(&mut MetadataMap) -> Result<(), HookError>;
+
+// Around-advice for the deploy hooks: a layer may act before and after calling `next`, skip
+// it, or call it again to retry
+type HookMiddleware = Box<dyn FnMut(&mut MetadataMap, HookNext<'_>) -> Result<(), HookError>>;
+
+// Runs `layers` outermost first, with the queued hooks innermost
+fn run_hook_chain(
+    layers: &mut [HookMiddleware],
+    hooks: &mut [DeployHook],
+    meta: &mut MetadataMap,
+) -> Result<(), HookError> {
+    match layers.split_first_mut() {
+        Some((layer, inner)) => layer(meta, &mut |meta| run_hook_chain(inner, hooks, meta)),
+        None => hooks.iter_mut().try_for_each(|hook| hook(meta)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HookError {
+    message: String,
+    // Where the failing hook ran: a `Phase` name, `deploy` for queued deploy hooks or
+    // `compensation`; set by the runner, since hooks build errors without knowing it
+    stage: Option<&'static str>,
+}
+
+impl HookError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            stage: None,
+        }
+    }
+
+    // Keeps a stage set closer to the failing hook
+    fn in_stage(mut self, stage: &'static str) -> Self {
+        self.stage.get_or_insert(stage);
+        self
+    }
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stage {
+            Some(stage) => write!(f, "{stage} hook failed: {}", self.message),
+            None => write!(f, "hook failed: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for HookError {}
+
+impl From<ReservedKey> for HookError {
+    fn from(err: ReservedKey) -> Self {
+        Self::new(format!("hook wrote reserved key: {err}"))
+    }
+}
+
+impl From<LimitExceeded> for HookError {
+    fn from(err: LimitExceeded) -> Self {
+        Self::new(format!("hook exceeded metadata limits: {err}"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Signature(Vec<u8>);
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("0x")?;
+        self.0.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+// Deploy-time identity: the signature covers `signing_payload` of the final metadata
+trait Signer {
+    fn address(&self) -> String;
+    fn sign(&self, payload: &[u8]) -> Signature;
+}
+
+// Lets one signer back several builders, e.g. every contract deployed under a `Profile`
+impl<S: Signer + ?Sized> Signer for Rc<S> {
+    fn address(&self) -> String {
+        (**self).address()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Signature {
+        (**self).sign(payload)
+    }
+}
+
+// Name plus every entry not in `skip`, sorted by key as `key:type=value` lines
+fn canonical_bytes(name: &str, meta: &MetadataMap, skip: &[&str]) -> Vec<u8> {
+    let mut entries: Vec<_> = meta
+        .iter()
+        .filter(|(k, _)| !skip.contains(&k.as_str()))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    let mut payload = format!("{name}\n");
+    for (key, value) in entries {
+        payload.push_str(&format!(
+            "{key}:{}={}\n",
+            value.type_name(),
+            Canonical(value)
+        ));
+    }
+    payload.into_bytes()
+}
+
+// `Display` form with nothing redacted, so secrets nested in lists and maps still count
+// towards content ids and signatures
+struct Canonical<'a>(&'a MetadataValue);
+
+impl fmt::Display for Canonical<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            MetadataValue::List(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", Canonical(item))?;
+                }
+                f.write_str("]")
+            }
+            MetadataValue::Map(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                f.write_str("{")?;
+                for (i, (k, v)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{k}: {}", Canonical(v))?;
+                }
+                f.write_str("}")
+            }
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+fn signing_payload(name: &str, meta: &MetadataMap) -> Vec<u8> {
+    canonical_bytes(name, meta, &["signature"])
+}
+
+// Hex SHA-256 of the canonical metadata; the signature is over the id, not the reverse
+fn content_id(name: &str, meta: &MetadataMap) -> String {
+    sha256(&canonical_bytes(name, meta, &["id", "signature"]))
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+// FIPS 180-4 SHA-256, enough for content ids without pulling in a crate
+fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+// Supplies the gas a deployment will use, e.g. from `eth_estimateGas` or a local fork
+trait GasEstimator {
+    fn estimate(&self, name: &str, metadata: &MetadataMap) -> Result<u64, HookError>;
+}
+
+impl<F> GasEstimator for F
+where
+    F: Fn(&str, &MetadataMap) -> Result<u64, HookError>,
+{
+    fn estimate(&self, name: &str, metadata: &MetadataMap) -> Result<u64, HookError> {
+        self(name, metadata)
+    }
+}
+
+// Headroom added to the estimate when no explicit `gas_limit` is configured
+const GAS_LIMIT_MARGIN_PERCENT: u64 = 20;
+
+// Offline lower bound for a creation transaction: base cost, calldata and code deposit.
+// Ignores constructor execution, so pair it with a budget that leaves room for that.
+fn intrinsic_creation_gas(_name: &str, metadata: &MetadataMap) -> Result<u64, HookError> {
+    let Some(MetadataValue::Bytes(code)) = metadata.get("bytecode") else {
+        return Err(HookError::new("no bytecode to estimate"));
+    };
+    let calldata: u64 = code.iter().map(|&b| if b == 0 { 4 } else { 16 }).sum();
+    Ok(53_000 + calldata + 200 * code.len() as u64)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GasError {
+    Estimate(HookError),
+    OverBudget { estimated: u64, budget: u64 },
+    OverLimit { estimated: u64, limit: u64 },
+}
+
+impl fmt::Display for GasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Estimate(err) => write!(f, "gas estimation failed: {}", err.message),
+            Self::OverBudget { estimated, budget } => {
+                write!(
+                    f,
+                    "estimated {estimated} gas exceeds the budget of {budget}"
+                )
+            }
+            Self::OverLimit { estimated, limit } => {
+                write!(
+                    f,
+                    "estimated {estimated} gas exceeds the gas limit of {limit}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for GasError {}
+
+// Lifecycle transitions a `Hooks` callback can attach to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Phase {
+    PreValidate,
+    Validate,
+    PostValidate,
+    PreDeploy,
+    PostDeploy,
+    Upgrade,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::PreValidate => "pre_validate",
+            Self::Validate => "validate",
+            Self::PostValidate => "post_validate",
+            Self::PreDeploy => "pre_deploy",
+            Self::PostDeploy => "post_deploy",
+            Self::Upgrade => "upgrade",
+        }
+    }
+}
+
+type LifecycleHook = Box<dyn FnMut(&str, &mut MetadataMap) -> Result<(), HookError>>;
+
+// Per-phase callbacks, each receiving the contract name and metadata
+#[derive(Default)]
+struct Hooks {
+    callbacks: HashMap<Phase, Vec<LifecycleHook>>,
+}
+
+impl Hooks {
+    fn on(&mut self, phase: Phase, hook: LifecycleHook) {
+        self.callbacks.entry(phase).or_default().push(hook);
+    }
+
+    // Records the stage's timing in `metrics`, if given
+    fn run(
+        &mut self,
+        phase: Phase,
+        name: &str,
+        meta: &mut MetadataMap,
+        metrics: Option<&Metrics>,
+    ) -> Result<(), HookError> {
+        let Some(callbacks) = self.callbacks.get_mut(&phase).filter(|c| !c.is_empty()) else {
+            return Ok(());
+        };
+        trace_span!(
+            "contract.hook",
+            contract = name,
+            stage = phase.as_str(),
+            hooks = callbacks.len()
+        );
+        let started = Instant::now();
+        let reserved = reserved_values(meta);
+        let result = callbacks
+            .iter_mut()
+            .try_for_each(|hook| hook(name, meta))
+            .and_then(|()| Ok(check_reserved_unchanged(&reserved, meta)?))
+            .map_err(|err| err.in_stage(phase.as_str()));
+        if let Some(metrics) = metrics {
+            metrics.record_hooks(phase.as_str(), started.elapsed(), result.is_ok());
+        }
+        result
+    }
+}
+
+// Counters of one `ContractRegistry`. Builders validate and deploy outside it, so they only
+// count once created with `ContractRegistry::builder` (or given `with_metrics`).
+#[derive(Default)]
+struct Metrics {
+    validations: AtomicU64,
+    validation_failures: AtomicU64,
+    deployments: AtomicU64,
+    deployment_failures: AtomicU64,
+    registrations: AtomicU64,
+    hooks: Mutex<Vec<HookTiming>>,
+}
+
+impl Metrics {
+    fn record(total: &AtomicU64, failures: &AtomicU64, ok: bool) {
+        total.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_validation(&self, ok: bool) {
+        Self::record(&self.validations, &self.validation_failures, ok);
+    }
+
+    fn record_deployment(&self, ok: bool) {
+        Self::record(&self.deployments, &self.deployment_failures, ok);
+    }
+
+    // `stage` is a phase name, or "deploy" for hooks queued with `add_hook`
+    fn record_hooks(&self, stage: &'static str, elapsed: Duration, ok: bool) {
+        let mut hooks = self.hooks.lock().unwrap_or_else(PoisonError::into_inner);
+        let timing = match hooks.iter().position(|timing| timing.stage == stage) {
+            Some(i) => &mut hooks[i],
+            None => {
+                hooks.push(HookTiming {
+                    stage,
+                    runs: 0,
+                    failures: 0,
+                    total: Duration::ZERO,
+                });
+                hooks.last_mut().expect("just pushed")
+            }
+        };
+        timing.runs += 1;
+        timing.failures += u64::from(!ok);
+        timing.total += elapsed;
+    }
+
+    fn snapshot(&self) -> MetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let mut hooks = self
+            .hooks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        hooks.sort_by_key(|timing| timing.stage);
+        MetricsSnapshot {
+            validations: load(&self.validations),
+            validation_failures: load(&self.validation_failures),
+            deployments: load(&self.deployments),
+            deployment_failures: load(&self.deployment_failures),
+            registrations: load(&self.registrations),
+            hooks,
+        }
+    }
+}
+
+// Runs of one hook stage; a run covers every hook registered for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HookTiming {
+    stage: &'static str,
+    runs: u64,
+    failures: u64,
+    total: Duration,
+}
+
+// Attempts include failures: `validations - validation_failures` builders reached `Validated`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct MetricsSnapshot {
+    validations: u64,
+    validation_failures: u64,
+    deployments: u64,
+    deployment_failures: u64,
+    registrations: u64,
+    hooks: Vec<HookTiming>,
+}
+
+impl MetricsSnapshot {
+    // Prometheus text exposition format, metric names prefixed `scrg_`
+    fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut series = |name: &str, help: &str, samples: Vec<(String, String)>| {
+            out.push_str(&format!(
+                "# HELP scrg_{name} {help}\n# TYPE scrg_{name} counter\n"
+            ));
+            for (labels, value) in samples {
+                out.push_str(&format!("scrg_{name}{labels} {value}\n"));
+            }
+        };
+        let total = |value: u64| vec![(String::new(), value.to_string())];
+        let per_stage = |value: fn(&HookTiming) -> String| {
+            self.hooks
+                .iter()
+                .map(|timing| (format!("{{stage=\"{}\"}}", timing.stage), value(timing)))
+                .collect()
+        };
+
+        series(
+            "validations_total",
+            "validate() calls.",
+            total(self.validations),
+        );
+        series(
+            "validation_failures_total",
+            "Failed validate() calls.",
+            total(self.validation_failures),
+        );
+        series(
+            "deployments_total",
+            "Deploy attempts.",
+            total(self.deployments),
+        );
+        series(
+            "deployment_failures_total",
+            "Deploy attempts aborted by a hook.",
+            total(self.deployment_failures),
+        );
+        series(
+            "registrations_total",
+            "Contracts accepted by a registry.",
+            total(self.registrations),
+        );
+        series(
+            "hook_runs_total",
+            "Hook stage runs.",
+            per_stage(|timing| timing.runs.to_string()),
+        );
+        series(
+            "hook_failures_total",
+            "Hook stage runs that failed.",
+            per_stage(|timing| timing.failures.to_string()),
+        );
+        series(
+            "hook_duration_seconds_total",
+            "Time spent in hook stages.",
+            per_stage(|timing| timing.total.as_secs_f64().to_string()),
+        );
+        out
+    }
+}
+
+// Frozen, cheaply clonable view of metadata as it was at deploy time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct MetadataSnapshot(Arc<MetadataMap>);
+
+impl MetadataSnapshot {
+    fn capture(metadata: &MetadataMap) -> Self {
+        Self(Arc::new(metadata.clone()))
+    }
+}
+
+impl std::ops::Deref for MetadataSnapshot {
+    type Target = MetadataMap;
+
+    fn deref(&self) -> &MetadataMap {
+        &self.0
+    }
+}
+
+// Keys whose values print as `***` in `Display`, `Debug`, diffs, CSV and
+// `ContractRegistry::redacted()`, at any depth and also as the last segment of a namespaced
+// key (`deploy.private_key`). The defaults always apply; registries add their own through
+// `with_secret_keys`, while plain formatting, which has no registry at hand, uses
+// `BUILTIN_SECRETS`.
+const DEFAULT_SECRET_KEYS: &[&str] = &[
+    "private_key",
+    "mnemonic",
+    "signer_mnemonic",
+    "api_key",
+    "password",
+];
+const REDACTED: &str = "***";
+
+#[derive(Debug, Clone, Default)]
+struct SecretKeys(BTreeSet<String>);
+
+static BUILTIN_SECRETS: SecretKeys = SecretKeys(BTreeSet::new());
+
+impl SecretKeys {
+    fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.0.insert(key.into());
+        self
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        let leaf = key.rsplit(NAMESPACE_SEPARATOR).next().unwrap_or(key);
+        [key, leaf]
+            .into_iter()
+            .any(|key| DEFAULT_SECRET_KEYS.contains(&key) || self.0.contains(key))
+    }
+
+    // Nested maps are redacted entry by entry, including maps inside lists
+    fn redact(&self, key: &str, value: MetadataValue) -> MetadataValue {
+        match value {
+            _ if self.contains(key) => REDACTED.into(),
+            MetadataValue::Map(map) => MetadataValue::Map(
+                map.into_iter()
+                    .map(|(key, value)| {
+                        let value = self.redact(&key, value);
+                        (key, value)
+                    })
+                    .collect(),
+            ),
+            MetadataValue::List(items) => MetadataValue::List(
+                items
+                    .into_iter()
+                    .map(|item| self.redact(key, item))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+
+    fn show<'a>(&'a self, key: &'a str, value: &'a MetadataValue) -> Redacted<'a> {
+        Redacted {
+            key,
+            value,
+            secrets: self,
+        }
+    }
+}
+
+fn is_secret(key: &str) -> bool {
+    BUILTIN_SECRETS.contains(key)
+}
+
+// Borrowed value that formats as `***` under a secret key
+struct Redacted<'a> {
+    key: &'a str,
+    value: &'a MetadataValue,
+    secrets: &'a SecretKeys,
+}
+
+impl Redacted<'_> {
+    fn hidden(&self) -> bool {
+        self.secrets.contains(self.key)
+    }
+}
+
+// Lists and maps are formatted from a redacted copy, so nested keys follow `secrets` too
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            _ if self.hidden() => f.write_str(REDACTED),
+            MetadataValue::List(_) | MetadataValue::Map(_) => {
+                write!(f, "{}", self.secrets.redact(self.key, self.value.clone()))
+            }
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            _ if self.hidden() => f.write_str(REDACTED),
+            MetadataValue::List(_) | MetadataValue::Map(_) => {
+                write!(f, "{:?}", self.secrets.redact(self.key, self.value.clone()))
+            }
+            value => write!(f, "{value:?}"),
+        }
+    }
+}
+
+fn redacted<'a>(key: &'a str, value: &'a MetadataValue) -> Redacted<'a> {
+    BUILTIN_SECRETS.show(key, value)
+}
+
+// Sorted, redacted view of `map` for `Debug` output
+fn redacted_map(map: &MetadataMap) -> std::collections::BTreeMap<&str, Redacted<'_>> {
+    map.iter()
+        .map(|(key, value)| (key.as_str(), redacted(key, value)))
+        .collect()
+}
+
+// Namespaced keys look like `build.compiler` or `audit.firm`: everything before the last
+// separator is the namespace, so `chain.l2.address` sits in `chain.l2` (and under `chain`)
+const NAMESPACE_SEPARATOR: char = '.';
+
+// Read-only view of one namespace; keys come back without the `ns.` prefix
+#[derive(Clone, Copy)]
+struct Namespace<'m> {
+    name: &'m str,
+    map: &'m MetadataMap,
+}
+
+impl<'m> Namespace<'m> {
+    fn strip<'k>(&self, key: &'k str) -> Option<&'k str> {
+        key.strip_prefix(self.name)?
+            .strip_prefix(NAMESPACE_SEPARATOR)
+            .filter(|rest| !rest.is_empty())
+    }
+
+    fn get(&self, key: &str) -> Option<&'m MetadataValue> {
+        self.map
+            .get(&format!("{}{NAMESPACE_SEPARATOR}{key}", self.name))
+    }
+
+    // Includes keys of nested namespaces, e.g. `l2.address` in `chain`
+    fn iter(&self) -> impl Iterator<Item = (&'m str, &'m MetadataValue)> + '_ {
+        self.map
+            .iter()
+            .filter_map(|(key, value)| Some((self.strip(key)?, value)))
+    }
+
+    fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    // Prefix-free copy, with secrets redacted under their full key
+    fn to_map(self) -> MetadataMap {
+        self.iter()
+            .map(|(key, value)| {
+                let full = format!("{}{NAMESPACE_SEPARATOR}{key}", self.name);
+                (key.to_owned(), BUILTIN_SECRETS.redact(&full, value.clone()))
+            })
+            .collect()
+    }
+}
+
+// Writes through to the underlying map under `ns.` keys
+struct NamespaceMut<'m> {
+    name: String,
+    map: &'m mut MetadataMap,
+}
+
+impl NamespaceMut<'_> {
+    fn insert(&mut self, key: &str, value: impl Into<MetadataValue>) -> Option<MetadataValue> {
+        self.map.insert(
+            format!("{}{NAMESPACE_SEPARATOR}{key}", self.name),
+            value.into(),
+        )
+    }
+
+    fn remove(&mut self, key: &str) -> Option<MetadataValue> {
+        self.map
+            .remove(&format!("{}{NAMESPACE_SEPARATOR}{key}", self.name))
+    }
+
+    // Drops every key of the namespace, nested ones included
+    fn clear(&mut self) {
+        let prefix = format!("{}{NAMESPACE_SEPARATOR}", self.name);
+        self.map.retain(|key, _| !key.starts_with(&prefix));
+    }
+}
+
+// `meta.namespace("build")` on any metadata map, hooks included
+trait MetadataNamespaces {
+    fn namespace<'m>(&'m self, name: &'m str) -> Namespace<'m>;
+    fn namespace_mut(&mut self, name: &str) -> NamespaceMut<'_>;
+    // Top-level namespaces in use, sorted
+    fn namespaces(&self) -> BTreeSet<&str>;
+    // Nests every namespaced key into `Map` values: `build.compiler` becomes
+    // `build: { compiler }`. Flat keys stay as they are and win over a namespace of the same
+    // name; secrets are redacted.
+    fn nested(&self) -> MetadataMap;
+}
+
+impl MetadataNamespaces for MetadataMap {
+    fn namespace<'m>(&'m self, name: &'m str) -> Namespace<'m> {
+        Namespace { name, map: self }
+    }
+
+    fn namespace_mut(&mut self, name: &str) -> NamespaceMut<'_> {
+        NamespaceMut {
+            name: name.into(),
+            map: self,
+        }
+    }
+
+    fn namespaces(&self) -> BTreeSet<&str> {
+        self.keys()
+            .filter_map(|key| Some(key.split_once(NAMESPACE_SEPARATOR)?.0))
+            .filter(|ns| !ns.is_empty())
+            .collect()
+    }
+
+    fn nested(&self) -> MetadataMap {
+        let mut out = MetadataMap::new();
+        let mut sorted: Vec<_> = self.iter().collect();
+        // Shallow keys first, so a flat `build` is in place before any `build.*` key
+        sorted.sort_by_key(|(key, _)| (key.matches(NAMESPACE_SEPARATOR).count(), key.as_str()));
+        for (key, value) in sorted {
+            let value = BUILTIN_SECRETS.redact(key, value.clone());
+            if !insert_nested(&mut out, key, value.clone()) {
+                out.insert(key.clone(), value);
+            }
+        }
+        out
+    }
+}
+
+// How `merge_from` treats a key both maps hold with different values. Distinct from
+// `MergeStrategy`, which resolves whole contracts when registries merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetadataMergeStrategy {
+    PreferExisting,
+    Overwrite,
+    // Fail without merging anything
+    ErrorOnConflict,
+}
+
+// Keys sorted; `kept` are conflicts resolved in favour of the existing value
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct MetadataMergeReport {
+    added: Vec<String>,
+    overwritten: Vec<String>,
+    kept: Vec<String>,
+}
+
+// Every conflicting key, reported by `MetadataMergeStrategy::ErrorOnConflict`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MetadataConflict(Vec<String>);
+
+impl fmt::Display for MetadataConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "conflicting metadata keys: {}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for MetadataConflict {}
+
+trait MetadataMerge {
+    // Equal values never conflict. Only `ErrorOnConflict` can fail.
+    fn merge_from(
+        &mut self,
+        other: MetadataMap,
+        strategy: MetadataMergeStrategy,
+    ) -> Result<MetadataMergeReport, MetadataConflict>;
+}
+
+impl MetadataMerge for MetadataMap {
+    fn merge_from(
+        &mut self,
+        other: MetadataMap,
+        strategy: MetadataMergeStrategy,
+    ) -> Result<MetadataMergeReport, MetadataConflict> {
+        use std::collections::hash_map::Entry;
+        let mut conflicts: Vec<String> = other
+            .iter()
+            .filter(|(key, value)| self.get(*key).is_some_and(|existing| existing != *value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        conflicts.sort();
+        if strategy == MetadataMergeStrategy::ErrorOnConflict && !conflicts.is_empty() {
+            return Err(MetadataConflict(conflicts));
+        }
+
+        let mut report = MetadataMergeReport::default();
+        for (key, value) in other {
+            match self.entry(key) {
+                Entry::Vacant(slot) => {
+                    report.added.push(slot.key().clone());
+                    slot.insert(value);
+                }
+                Entry::Occupied(mut slot) if conflicts.binary_search(slot.key()).is_ok() => {
+                    if strategy == MetadataMergeStrategy::Overwrite {
+                        report.overwritten.push(slot.key().clone());
+                        slot.insert(value);
+                    } else {
+                        report.kept.push(slot.key().clone());
+                    }
+                }
+                Entry::Occupied(_) => {}
+            }
+        }
+        report.added.sort();
+        report.overwritten.sort();
+        report.kept.sort();
+        Ok(report)
+    }
+}
+
+// `false` when a non-map value already sits on the path, leaving `map` unchanged
+fn insert_nested(map: &mut MetadataMap, key: &str, value: MetadataValue) -> bool {
+    let Some((head, rest)) = key.split_once(NAMESPACE_SEPARATOR) else {
+        map.insert(key.into(), value);
+        return true;
+    };
+    if !matches!(map.get(head), None | Some(MetadataValue::Map(_))) {
+        return false;
+    }
+    match map
+        .entry(head.into())
+        .or_insert_with(|| MetadataValue::Map(MetadataMap::new()))
+    {
+        MetadataValue::Map(inner) => insert_nested(inner, rest, value),
+        _ => false,
+    }
+}
+
+// One metadata mutation; `old`/`new` are `None` when the key was absent
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct AuditEntry {
+    key: String,
+    old: Option<AuditValue>,
+    new: Option<AuditValue>,
+    #[cfg_attr(feature = "serde", serde(with = "rfc3339"))]
+    timestamp: SystemTime,
+    actor: String,
+}
+
+impl AuditEntry {
+    fn old_value(&self) -> Option<&MetadataValue> {
+        self.old.as_ref()?.value()
+    }
+
+    fn new_value(&self) -> Option<&MetadataValue> {
+        self.new.as_ref()?.value()
+    }
+}
+
+impl fmt::Debug for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditEntry")
+            .field("key", &self.key)
+            .field("old", &self.old_value().map(|v| redacted(&self.key, v)))
+            .field("new", &self.new_value().map(|v| redacted(&self.key, v)))
+            .field("timestamp", &format_rfc3339(self.timestamp))
+            .field("actor", &self.actor)
+            .finish()
+    }
+}
+
+// Audited value; large ones are compressed like live values by `with_compression`
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(untagged)
+)]
+enum AuditValue {
+    Plain(MetadataValue),
+    Compressed { compressed: CompressedValue },
+}
+
+impl AuditValue {
+    // `None` for a compressed value that cannot be unpacked, as `DeployedContract::get`
+    fn value(&self) -> Option<&MetadataValue> {
+        match self {
+            Self::Plain(value) => Some(value),
+            Self::Compressed { compressed } => compressed.value().ok(),
+        }
+    }
+}
+
+impl From<MetadataValue> for AuditValue {
+    fn from(value: MetadataValue) -> Self {
+        Self::Plain(value)
+    }
+}
+
+// Compressed values compare by their bytes, which is all loaded ones have until a codec is
+// attached
+impl PartialEq for AuditValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Compressed { compressed: a }, Self::Compressed { compressed: b }) => {
+                a.bytes == b.bytes
+            }
+            _ => self.value() == other.value(),
+        }
+    }
+}
+
+impl Eq for AuditValue {}
+
+// Changes `DeployedContract::history` keeps per key; the audit log itself keeps them all
+const MAX_KEY_HISTORY: usize = 32;
+
+// Append-only audit log of a deployed contract, plus a bounded per-key index into it for
+// `history`. Formats store the entries only; the index is rebuilt from them on load.
+#[derive(Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "Vec<AuditEntry>", into = "Vec<AuditEntry>")
+)]
+struct AuditLog {
+    entries: Vec<AuditEntry>,
+    // Positions in `entries`, oldest first, at most `MAX_KEY_HISTORY` per key
+    by_key: BTreeMap<String, Vec<usize>>,
+}
+
+impl AuditLog {
+    fn push(&mut self, entry: AuditEntry) {
+        let positions = self.by_key.entry(entry.key.clone()).or_default();
+        positions.push(self.entries.len());
+        if positions.len() > MAX_KEY_HISTORY {
+            positions.remove(0);
+        }
+        self.entries.push(entry);
+    }
+
+    fn history(&self, key: &str) -> Vec<&AuditEntry> {
+        let positions = self.by_key.get(key).map_or(&[][..], Vec::as_slice);
+        positions.iter().map(|&i| &self.entries[i]).collect()
+    }
+
+    // Rewrites values in place; keys, and so the index, stay as they are
+    fn values_mut(&mut self) -> impl Iterator<Item = (&str, &mut Option<AuditValue>)> {
+        self.entries.iter_mut().flat_map(|entry| {
+            let key = entry.key.as_str();
+            [(key, &mut entry.old), (key, &mut entry.new)]
+        })
+    }
+}
+
+impl FromIterator<AuditEntry> for AuditLog {
+    fn from_iter<I: IntoIterator<Item = AuditEntry>>(entries: I) -> Self {
+        let mut log = Self::default();
+        entries.into_iter().for_each(|entry| log.push(entry));
+        log
+    }
+}
+
+impl From<Vec<AuditEntry>> for AuditLog {
+    fn from(entries: Vec<AuditEntry>) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl From<AuditLog> for Vec<AuditEntry> {
+    fn from(log: AuditLog) -> Self {
+        log.entries
+    }
+}
+
+impl std::ops::Deref for AuditLog {
+    type Target = [AuditEntry];
+
+    fn deref(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+}
+
+impl PartialEq for AuditLog {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.entries).finish()
+    }
+}
+
+// Appends an entry for every key whose value differs between `before` and `after`
+fn record_changes(
+    log: &mut Vec<AuditEntry>,
+    before: &MetadataMap,
+    after: &MetadataMap,
+    actor: &str,
+) {
+    let timestamp = now();
+    let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let (old, new) = (before.get(key), after.get(key));
+        if old != new {
+            log.push(AuditEntry {
+                key: key.clone(),
+                old: old.cloned().map(AuditValue::from),
+                new: new.cloned().map(AuditValue::from),
+                timestamp,
+                actor: actor.into(),
+            });
+        }
+    }
+}
+
+// Key-level difference from one metadata map to another; each list is sorted by key
+#[derive(Clone, Default, PartialEq, Eq)]
+struct MetadataDiff {
+    added: Vec<(String, MetadataValue)>,
+    removed: Vec<(String, MetadataValue)>,
+    // (key, old, new)
+    changed: Vec<(String, MetadataValue, MetadataValue)>,
+}
+
+impl MetadataDiff {
+    fn between(before: &MetadataMap, after: &MetadataMap) -> Self {
+        let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        let mut diff = Self::default();
+        for key in keys {
+            match (before.get(key), after.get(key)) {
+                (None, Some(new)) => diff.added.push((key.clone(), new.clone())),
+                (Some(old), None) => diff.removed.push((key.clone(), old.clone())),
+                (Some(old), Some(new)) if old != new => {
+                    diff.changed.push((key.clone(), old.clone(), new.clone()))
+                }
+                _ => {}
+            }
+        }
+        diff
+    }
+
+    // Drops `keys` that differ on every deploy anyway, e.g. `uuid` or `deployed_at`
+    fn ignoring(mut self, keys: &[&str]) -> Self {
+        let kept = |key: &String| !keys.contains(&key.as_str());
+        self.added.retain(|(key, _)| kept(key));
+        self.removed.retain(|(key, _)| kept(key));
+        self.changed.retain(|(key, _, _)| kept(key));
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Debug for MetadataDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn pairs(entries: &[(String, MetadataValue)]) -> Vec<(&str, Redacted<'_>)> {
+            entries
+                .iter()
+                .map(|(key, value)| (key.as_str(), redacted(key, value)))
+                .collect()
+        }
+        let changed: Vec<_> = self
+            .changed
+            .iter()
+            .map(|(key, old, new)| (key, redacted(key, old), redacted(key, new)))
+            .collect();
+        f.debug_struct("MetadataDiff")
+            .field("added", &pairs(&self.added))
+            .field("removed", &pairs(&self.removed))
+            .field("changed", &changed)
+            .finish()
+    }
+}
+
+impl fmt::Display for MetadataDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = Vec::new();
+        lines.extend(
+            self.added
+                .iter()
+                .map(|(key, new)| format!("+ {key}: {}", redacted(key, new))),
+        );
+        lines.extend(
+            self.removed
+                .iter()
+                .map(|(key, old)| format!("- {key}: {}", redacted(key, old))),
+        );
+        lines.extend(self.changed.iter().map(|(key, old, new)| {
+            format!("~ {key}: {} -> {}", redacted(key, old), redacted(key, new))
+        }));
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// What changed from contract `a` to contract `b`, typically two versions of one contract
+fn diff(a: &DeployedContract, b: &DeployedContract) -> MetadataDiff {
+    MetadataDiff::between(a.metadata(), b.metadata())
+}
+
+struct ContractBuilder<State> {
+    name: String,
+    metadata: Metadata,
+    rules: Vec<Box<dyn Validator>>,
+    schema: Option<MetadataSchema>,
+    hooks: Vec<DeployHook>,
+    middleware: Vec<HookMiddleware>,
+    lifecycle: Hooks,
+    audit: Vec<AuditEntry>,
+    snapshot: Option<MetadataSnapshot>,
+    signer: Option<Box<dyn Signer>>,
+    profiles: HashMap<String, Profile>,
+    limits: MetadataLimits,
+    // Shared with the registry that created the builder, if any
+    metrics: Option<Arc<Metrics>>,
+    _state: std::marker::PhantomData<State>,
+}
+
+// Shared, read-only handle on a builder's live metadata. Writes go through the builder
+// (`set`, hooks), where lifecycle keys and limits are checked.
+#[derive(Clone)]
+struct MetadataView(Metadata);
+
+impl MetadataView {
+    fn borrow(&self) -> std::cell::Ref<'_, MetadataMap> {
+        self.0.borrow()
+    }
+}
+
+// Non-owning view of a builder's metadata for observers and caches. Unlike `metadata()`,
+// it does not keep the map alive or force `detach` to copy it, so a hook closure can
+// capture one without forming an `Rc` cycle.
+#[derive(Clone)]
+struct WeakContractHandle {
+    name: String,
+    metadata: Weak<RefCell<MetadataMap>>,
+}
+
+impl WeakContractHandle {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // `None` once the builder has been dropped or detached into a `DeployedContract`
+    fn upgrade(&self) -> Option<MetadataView> {
+        self.metadata.upgrade().map(MetadataView)
+    }
+
+    fn is_alive(&self) -> bool {
+        self.metadata.strong_count() > 0
+    }
+}
+
+impl fmt::Debug for WeakContractHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeakContractHandle")
+            .field("name", &self.name)
+            .field("alive", &self.is_alive())
+            .finish()
+    }
+}
+
+// Shared `Display` body: a header line, then one `key: value` line per entry sorted by key
+fn write_summary(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    state: &str,
+    meta: &MetadataMap,
+) -> fmt::Result {
+    write!(f, "📘 Contract Metadata ({name}, {state}):")?;
+    let mut entries: Vec<_> = meta.iter().collect();
+    entries.sort_by_key(|(key, _)| *key);
+    for (key, value) in entries {
+        write!(f, "\n  {key}: {}", redacted(key, value))?;
+    }
+    Ok(())
+}
+
+impl<State: BuilderState> fmt::Display for ContractBuilder<State> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_summary(f, &self.name, State::NAME, &self.metadata.borrow())
+    }
+}
+
+impl<State: BuilderState> fmt::Debug for ContractBuilder<State> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metadata = self.metadata.borrow();
+        f.debug_struct("ContractBuilder")
+            .field("name", &self.name)
+            .field("state", &State::NAME)
+            .field("metadata", &redacted_map(&metadata))
+            .field("rules", &self.rules.len())
+            .field("hooks", &self.hooks.len())
+            .field("middleware", &self.middleware.len())
+            .field("audit", &self.audit.len())
+            .field("signed", &self.signer.is_some())
+            .finish()
+    }
+}
+
+impl<State> ContractBuilder<State> {
+    // Moves the builder into the next typestate, keeping name and metadata
+    fn transition<Next>(self) -> ContractBuilder<Next> {
+        ContractBuilder {
+            name: self.name,
+            metadata: self.metadata,
+            rules: self.rules,
+            schema: self.schema,
+            hooks: self.hooks,
+            middleware: self.middleware,
+            lifecycle: self.lifecycle,
+            audit: self.audit,
+            snapshot: self.snapshot,
+            signer: self.signer,
+            profiles: self.profiles,
+            limits: self.limits,
+            metrics: self.metrics,
+            _state: std::marker::PhantomData,
+        }
+    }
+
+    // Checked counterpart of `transition` for custom states: compiles only along edges
+    // declared with `TransitionTo`
+    fn advance<Next>(self) -> ContractBuilder<Next>
+    where
+        State: TransitionTo<Next>,
+        Next: BuilderState,
+    {
+        self.transition()
+    }
+
+    fn on_phase<F>(mut self, phase: Phase, hook: F) -> Self
+    where
+        F: FnMut(&str, &mut MetadataMap) -> Result<(), HookError> + 'static,
+    {
+        self.lifecycle.on(phase, Box::new(hook));
+        self
+    }
+
+    fn run_phase(&mut self, phase: Phase) -> Result<(), HookError> {
+        let mut scratch = self.metadata.borrow().clone();
+        self.lifecycle
+            .run(phase, &self.name, &mut scratch, self.metrics.as_deref())?;
+        self.limits
+            .check_changes(&self.metadata.borrow(), &scratch)
+            .map_err(|err| HookError::from(err).in_stage(phase.as_str()))?;
+        self.commit(scratch, &format!("hook:{}", phase.as_str()));
+        Ok(())
+    }
+
+    // Every metadata write made by the builder funnels through here to be audited
+    fn commit(&mut self, updated: MetadataMap, actor: &str) {
+        record_changes(&mut self.audit, &self.metadata.borrow(), &updated, actor);
+        *self.metadata.borrow_mut() = updated;
+    }
+
+    fn edit(&mut self, actor: &str, change: impl FnOnce(&mut MetadataMap)) {
+        let mut updated = self.metadata.borrow().clone();
+        change(&mut updated);
+        self.commit(updated, actor);
+    }
+
+    fn audit_log(&self) -> &[AuditEntry] {
+        &self.audit
+    }
+
+    // Set by `validate()`
+    fn validated_at(&self) -> Option<SystemTime> {
+        self.metadata
+            .borrow()
+            .get("validated_at")
+            .and_then(MetadataValue::as_timestamp)
+    }
+
+    // Set when deploy (or upgrade) hooks start running
+    fn deployed_at(&self) -> Option<SystemTime> {
+        self.metadata
+            .borrow()
+            .get("deployed_at")
+            .and_then(MetadataValue::as_timestamp)
+    }
+
+    fn get_as<T: FromMetadataValue>(&self, key: impl AsRef<str>) -> Result<T, GetAsError> {
+        let key = key.as_ref();
+        get_as(self.metadata.borrow().get(key), key)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> MetadataView {
+        MetadataView(Rc::clone(&self.metadata))
+    }
+
+    // Prefer this over `metadata()` for anything that outlives the call, hooks included
+    fn downgrade(&self) -> WeakContractHandle {
+        WeakContractHandle {
+            name: self.name.clone(),
+            metadata: Rc::downgrade(&self.metadata),
+        }
+    }
+
+    fn borrow(&self) -> std::cell::Ref<'_, MetadataMap> {
+        self.metadata.borrow()
+    }
+
+    // Detaches the metadata from the builder; clones it if a hook kept the Rc alive
+    fn detach(self) -> DeployedContract {
+        let metadata = Rc::try_unwrap(self.metadata)
+            .map(RefCell::into_inner)
+            .unwrap_or_else(|shared| shared.borrow().clone());
+        let metadata = Arc::new(metadata);
+        // A snapshot the metadata still equals becomes the same map rather than a second copy
+        let snapshot = match self.snapshot {
+            Some(snapshot) if *snapshot != *metadata => snapshot,
+            _ => MetadataSnapshot(Arc::clone(&metadata)),
+        };
+        let deployed_at = metadata
+            .get("deployed_at")
+            .and_then(MetadataValue::as_timestamp)
+            .unwrap_or_else(now);
+        DeployedContract {
+            name: self.name,
+            metadata,
+            deployed_at,
+            audit: self.audit.into(),
+            snapshot,
+            limits: self.limits,
+            packed: BTreeMap::new(),
+            snapshot_packed: BTreeMap::new(),
+        }
+    }
+
+    // Back to `Init` with the state markers removed; the author, other metadata, rules,
+    // hooks and audit log carry over
+    fn reset_state(mut self, actor: &str) -> ContractBuilder<Init<AuthorSet>> {
+        self.edit(actor, |meta| {
+            meta.retain(|key, _| !STATE_KEYS.contains(&key.as_str()))
+        });
+        self.snapshot = None;
+        self.transition()
+    }
+
+    // Stamps `id` and, with a signer, `signer`/`signature`; runs after every deploy or upgrade hook
+    fn seal(&self, meta: &mut MetadataMap) {
+        if let Some(signer) = &self.signer {
+            meta.insert("signer".into(), MetadataValue::Address(signer.address()));
+        }
+        meta.insert("id".into(), content_id(&self.name, meta).into());
+        if let Some(signer) = &self.signer {
+            let signature = signer.sign(&signing_payload(&self.name, meta));
+            meta.insert("signature".into(), MetadataValue::Bytes(signature.0));
+        }
+    }
+
+    // Keeps all metadata, bumps `version`, records `previous_version` and runs
+    // `Phase::Upgrade` hooks; nothing changes unless every hook succeeds
+    fn upgrade(mut self, new_version: &str) -> Result<ContractBuilder<Upgraded>, ScrgError> {
+        trace_span!("contract.upgrade", contract = %self.name, to = new_version);
+        let requested: Version = new_version.parse().map_err(UpgradeError::InvalidVersion)?;
+        let current = self
+            .metadata
+            .borrow()
+            .get("version")
+            .and_then(MetadataValue::as_version)
+            .cloned();
+
+        let mut scratch = self.metadata.borrow().clone();
+        if let Some(current) = current {
+            if requested <= current {
+                return Err(UpgradeError::NotNewer {
+                    current: Box::new(current),
+                    requested: Box::new(requested),
+                }
+                .into());
+            }
+            scratch.insert("previous_version".into(), current.into());
+        }
+        scratch.insert("version".into(), requested.into());
+        scratch.insert("status".into(), LifecycleStatus::Upgraded.as_str().into());
+        scratch.insert("uuid".into(), Uuid::now_v7().to_string().into());
+        scratch.insert("deployed_at".into(), now().into());
+        // The deploy-time signature does not cover the upgraded metadata
+        scratch.remove("signature");
+        self.lifecycle
+            .run(
+                Phase::Upgrade,
+                &self.name,
+                &mut scratch,
+                self.metrics.as_deref(),
+            )
+            .and_then(|()| {
+                Ok(self
+                    .limits
+                    .check_changes(&self.metadata.borrow(), &scratch)?)
+            })
+            .map_err(|err| UpgradeError::Hook(err.in_stage(Phase::Upgrade.as_str())))?;
+        self.seal(&mut scratch);
+
+        self.snapshot = Some(MetadataSnapshot::capture(&scratch));
+        self.commit(scratch, "upgrade");
+        Ok(self.transition())
+    }
+}
+
+impl ContractBuilder<Init> {
+    // Every builder starts with a fresh UUIDv7 under `uuid`
+    fn new(name: &str) -> Self {
+        let metadata = HashMap::from([("uuid".into(), Uuid::now_v7().to_string().into())]);
+        Self {
+            name: name.into(),
+            metadata: Rc::new(RefCell::new(metadata)),
+            rules: Vec::new(),
+            schema: None,
+            hooks: Vec::new(),
+            middleware: Vec::new(),
+            lifecycle: Hooks::default(),
+            audit: Vec::new(),
+            snapshot: None,
+            signer: None,
+            profiles: HashMap::new(),
+            limits: MetadataLimits::default(),
+            metrics: None,
+            _state: std::marker::PhantomData,
+        }
+    }
+
+    // Starts from the template's metadata, tags and hooks; records the template under `template`
+    fn from_template(template: &ContractTemplate, name: &str) -> Self {
+        let mut builder = Self::new(name).with_tags(template.tags.iter().cloned());
+        builder.profiles = template.profiles.clone();
+        builder.edit("template", |meta| {
+            let merge = MetadataMergeStrategy::PreferExisting;
+            let _ = meta.merge_from(template.metadata.clone(), merge);
+            meta.insert("template".into(), template.name.as_str().into());
+        });
+        template
+            .hooks
+            .iter()
+            .fold(builder, |builder, (phase, hook)| {
+                let hook = Rc::clone(hook);
+                builder.on_phase(*phase, move |name, meta| hook(name, meta))
+            })
+    }
+}
+
+impl<Author> ContractBuilder<Init<Author>> {
+    fn with_author(self, author: &str) -> ContractBuilder<Init<AuthorSet>> {
+        self.with_metadata(MetaKey::Author, author).transition()
+    }
+
+    // Like `with_author`, but an `author` already present (e.g. from `with_env_defaults`) wins
+    fn with_default_author(self, author: &str) -> ContractBuilder<Init<AuthorSet>> {
+        if self.metadata.borrow().contains_key("author") {
+            return self.transition();
+        }
+        self.with_author(author)
+    }
+
+    // Fills keys that are not set yet from `{prefix}_*` environment variables, lowercased:
+    // `SCRG_AUTHOR` -> `author`, `SCRG_NETWORK` -> `network`. Values parse like CLI `--meta`
+    // values; explicit setters win whether they run before or after this. Variables naming
+    // a lifecycle key (`SCRG_STATUS`, `SCRG_VALIDATED`, ...; see `STATE_KEYS`) are skipped.
+    #[cfg(feature = "std")]
+    fn with_env_defaults(mut self, prefix: &str) -> Self {
+        let prefix = format!("{prefix}_");
+        let defaults: Vec<(String, MetadataValue)> = std::env::vars_os()
+            .filter_map(|(var, raw)| {
+                let key = var.to_str()?.strip_prefix(&prefix)?.to_lowercase();
+                let raw = raw.into_string().ok()?;
+                (!key.is_empty() && check_not_reserved(&key).is_ok())
+                    .then(|| (key, parse_plain_value(&raw)))
+            })
+            .collect();
+        self.edit("env", |meta| {
+            for (key, value) in defaults {
+                meta.entry(key).or_insert(value);
+            }
+        });
+        self
+    }
+
+    fn with_metadata(mut self, key: impl AsRef<str>, value: impl Into<MetadataValue>) -> Self {
+        let value = value.into();
+        self.edit("builder", |meta| {
+            meta.insert(key.as_ref().into(), value);
+        });
+        self
+    }
+
+    // Runs inside `validate()` after the built-in version, address and ABI normalization and
+    // before required keys, schema and rules are checked, so derived fields are validated too.
+    // An error fails validation with `ValidationError::Hook`.
+    fn on_validate<F>(self, mut hook: F) -> Self
+    where
+        F: FnMut(&mut MetadataMap) -> Result<(), HookError> + 'static,
+    {
+        self.on_phase(Phase::Validate, move |_, meta| hook(meta))
+    }
+
+    fn with_profile(mut self, name: &str, profile: Profile) -> Self {
+        self.profiles.insert(name.into(), profile);
+        self
+    }
+
+    // Stored under `ens`; `validate()` checks and lowercases it, and the registry finds the
+    // contract by it with `by_ens`
+    fn with_ens(self, name: &str) -> Self {
+        self.with_metadata("ens", name)
+    }
+
+    // Values already set are checked by `validate()`; hooks are held to the limits as they run
+    fn with_limits(mut self, limits: MetadataLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // Counts validation, deployment and hook runs in `metrics`, usually a registry's (see
+    // `ContractRegistry::builder`); without it nothing is counted
+    fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    // Bulk counterpart of `with_metadata`; overwritten keys show up in the audit log
+    fn merge_metadata(
+        mut self,
+        other: MetadataMap,
+        strategy: MetadataMergeStrategy,
+    ) -> Result<Self, MetadataConflict> {
+        let mut scratch = self.metadata.borrow().clone();
+        scratch.merge_from(other, strategy)?;
+        self.commit(scratch, "merge");
+        Ok(self)
+    }
+
+    // Overlays the profile's metadata and tags, takes its signer if it has one, and records
+    // `profile`. An undefined profile is recorded as well and rejected by `validate()`.
+    fn for_profile(mut self, name: &str) -> Self {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            return self.with_metadata("profile", name);
+        };
+        if let Some(signer) = profile.signer {
+            self.signer = Some(Box::new(signer));
+        }
+        self.edit(&format!("profile:{name}"), |meta| {
+            let _ = meta.merge_from(profile.metadata, MetadataMergeStrategy::Overwrite);
+            meta.insert("profile".into(), name.into());
+        });
+        self.with_tags(profile.tags)
+    }
+
+    // An unparsable version is kept as a string and rejected by `validate()`
+    fn with_version(self, version: &str) -> Self {
+        match version.parse::<Version>() {
+            Ok(parsed) => self.with_metadata("version", parsed),
+            Err(_) => self.with_metadata("version", version),
+        }
+    }
+
+    fn with_network(self, network: Network) -> Self {
+        self.with_metadata("network", network)
+    }
+
+    // EVM bytecode; hex that does not decode is kept as a string and rejected by `validate()`
+    fn with_bytecode(self, hex: &str) -> Self {
+        match decode_hex(hex) {
+            Ok(bytes) => self.with_artifact(ContractArtifact::EvmBytecode(bytes)),
+            Err(_) => self.with_metadata("bytecode", hex),
+        }
+    }
+
+    fn with_artifact(mut self, artifact: ContractArtifact) -> Self {
+        self.edit("builder", |meta| {
+            meta.insert("runtime".into(), artifact.runtime().into());
+            meta.insert(
+                "bytecode".into(),
+                MetadataValue::Bytes(artifact.code().to_vec()),
+            );
+        });
+        self
+    }
+
+    // Raw JSON ABI, parsed by `validate()` (which needs the `serde` feature)
+    fn with_abi(self, json: &str) -> Self {
+        self.with_metadata("abi", json)
+    }
+
+    // Checked by `ContractRegistry::register`: every dependency must already be deployed
+    fn depends_on(mut self, dependency: &str) -> Self {
+        self.edit("builder", |meta| {
+            let entry = meta
+                .entry("dependencies".into())
+                .or_insert_with(|| MetadataValue::List(Vec::new()));
+            if let MetadataValue::List(dependencies) = entry {
+                dependencies.push(dependency.into());
+            }
+        });
+        self
+    }
+
+    fn with_fields(mut self, fields: impl ContractMetadata) -> Self {
+        self.edit("builder", |meta| meta.extend(fields.into_metadata()));
+        self
+    }
+
+    // Duplicate tags are ignored; indexed by `ContractRegistry::by_tag`
+    fn with_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.edit("builder", |meta| {
+            let entry = meta
+                .entry("tags".into())
+                .or_insert_with(|| MetadataValue::List(Vec::new()));
+            if let MetadataValue::List(existing) = entry {
+                for tag in tags {
+                    let tag = MetadataValue::String(tag.into());
+                    if !existing.contains(&tag) {
+                        existing.push(tag);
+                    }
+                }
+            }
+        });
+        self
+    }
+
+    // Upper bound checked by `estimate_gas()` on the validated builder
+    fn with_gas_budget(self, budget: u64) -> Self {
+        self.with_metadata("gas_budget", i64::try_from(budget).unwrap_or(i64::MAX))
+    }
+
+    // Fixed limit for the deployment; otherwise `estimate_gas()` derives one
+    fn with_gas_limit(self, limit: u64) -> Self {
+        self.with_metadata("gas_limit", i64::try_from(limit).unwrap_or(i64::MAX))
+    }
+
+    fn with_rule(mut self, rule: Box<dyn Validator>) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    // Adds each constraint as its own rule, next to any from `with_rule()`
+    fn with_constraints(mut self, constraints: Constraints) -> Self {
+        self.rules.extend(
+            constraints
+                .checks
+                .into_iter()
+                .map(|check| Box::new(check) as Box<dyn Validator>),
+        );
+        self
+    }
+
+    // Replaces any earlier schema; checked by `validate()` after values are normalized
+    fn with_schema(mut self, schema: MetadataSchema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    // Accepts versions set as plain strings, e.g. via `with_metadata("version", "1.0.0")`
+    fn normalize_version(&mut self) -> Result<(), ValidationError> {
+        let raw = match self.metadata.borrow().get("version") {
+            Some(value) if value.as_version().is_none() => value.to_string(),
+            _ => return Ok(()),
+        };
+        let parsed: Version = raw.parse().map_err(ValidationError::InvalidVersion)?;
+        self.edit("validate", |meta| {
+            meta.insert("version".into(), parsed.into());
+        });
+        Ok(())
+    }
+
+    fn normalize_abi(&mut self) -> Result<(), ValidationError> {
+        let raw = match self.metadata.borrow().get("abi") {
+            None => return Ok(()),
+            Some(value) if Abi::from_metadata(value).is_some() => return Ok(()),
+            Some(MetadataValue::String(json)) => json.clone(),
+            Some(other) => {
+                return Err(ValidationError::InvalidAbi(format!(
+                    "expected a JSON string, found {}",
+                    other.type_name()
+                )))
+            }
+        };
+        #[cfg(feature = "serde")]
+        {
+            let abi =
+                Abi::from_json(&raw).map_err(|err| ValidationError::InvalidAbi(err.to_string()))?;
+            self.edit("validate", |meta| {
+                meta.insert("abi".into(), abi.into());
+            });
+            Ok(())
+        }
+        #[cfg(not(feature = "serde"))]
+        {
+            let _ = raw;
+            Err(ValidationError::InvalidAbi(
+                "parsing JSON ABIs requires the `serde` feature".into(),
+            ))
+        }
+    }
+
+    // Bytecode without a `runtime` is treated as EVM
+    fn check_artifact(&self) -> Result<(), ValidationError> {
+        let meta = self.metadata.borrow();
+        let code = match meta.get("bytecode") {
+            None => return Ok(()),
+            Some(MetadataValue::Bytes(bytes)) => bytes.clone(),
+            Some(MetadataValue::String(hex)) => {
+                return Err(ValidationError::InvalidBytecode(
+                    decode_hex(hex).err().unwrap_or("stored as a string"),
+                ))
+            }
+            Some(_) => return Err(ValidationError::InvalidBytecode("expected hex bytes")),
+        };
+        let runtime = meta
+            .get("runtime")
+            .and_then(MetadataValue::as_str)
+            .unwrap_or("evm");
+        ContractArtifact::from_parts(runtime, code).check()
+    }
+
+    fn normalize_addresses(&mut self) -> Result<(), ValidationError> {
+        for key in ADDRESS_KEYS {
+            let Some(value) = self.metadata.borrow().get(*key).cloned() else {
+                continue;
+            };
+            let invalid = |error| ValidationError::InvalidAddress {
+                key: (*key).into(),
+                error,
+            };
+            let raw = value.as_str().ok_or_else(|| {
+                invalid(AddressError {
+                    input: value.to_string(),
+                    reason: "expected a string",
+                })
+            })?;
+            let normalized: MetadataValue = raw.parse::<Address>().map_err(invalid)?.into();
+            if normalized != value {
+                self.edit("validate", |meta| {
+                    meta.insert((*key).into(), normalized);
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ContractBuilder<Init<AuthorSet>> {
+    fn validate(self) -> Result<ContractBuilder<Validated>, ScrgError> {
+        trace_span!(
+            "contract.validate",
+            contract = %self.name,
+            keys = self.metadata.borrow().len()
+        );
+        let metrics = self.metrics.clone();
+        let result = self.run_validation();
+        if let Some(metrics) = metrics {
+            metrics.record_validation(result.is_ok());
+        }
+        trace_event!(
+            debug,
+            ok = result.is_ok(),
+            error = ?result.as_ref().err(),
+            "validate finished"
+        );
+        result.map_err(ScrgError::from)
+    }
+
+    fn run_validation(mut self) -> Result<ContractBuilder<Validated>, ValidationError> {
+        self.run_phase(Phase::PreValidate)
+            .map_err(ValidationError::Hook)?;
+        if self.name.trim().is_empty() {
+            return Err(ValidationError::EmptyName);
+        }
+        // Nothing outside the lifecycle may have set these before the first `validate()`
+        if let Some(key) = STATE_KEYS
+            .iter()
+            .find(|key| self.metadata.borrow().contains_key(**key))
+        {
+            return Err(ValidationError::ReservedKey(ReservedKey((*key).into())));
+        }
+        if let Some(profile) = self
+            .metadata
+            .borrow()
+            .get("profile")
+            .and_then(MetadataValue::as_str)
+        {
+            if !self.profiles.contains_key(profile) {
+                return Err(ValidationError::UnknownProfile(profile.into()));
+            }
+        }
+        let ens = self.metadata.borrow().get("ens").map(ToString::to_string);
+        if let Some(ens) = ens {
+            let normalized =
+                normalize_ens(&ens).ok_or_else(|| ValidationError::InvalidEnsName(ens.clone()))?;
+            if normalized != ens {
+                self.edit("validate", |meta| {
+                    meta.insert("ens".into(), normalized.into());
+                });
+            }
+        }
+        self.normalize_version()?;
+        self.normalize_addresses()?;
+        self.normalize_abi()?;
+        self.check_artifact()?;
+        self.run_phase(Phase::Validate)
+            .map_err(ValidationError::Hook)?;
+        {
+            let meta = self.metadata.borrow();
+            if let Some(key) = REQUIRED_KEYS.iter().find(|key| !meta.contains_key(**key)) {
+                return Err(ValidationError::MissingKey((*key).into()));
+            }
+            self.limits.check(&meta)?;
+
+            if let Some(schema) = &self.schema {
+                schema.check(&meta).map_err(ValidationError::Schema)?;
+            }
+
+            let violations: Vec<RuleViolation> = self
+                .rules
+                .iter()
+                .filter_map(|rule| rule.check(&self.name, &meta).err())
+                .collect();
+            if !violations.is_empty() {
+                return Err(ValidationError::Rules(violations));
+            }
+        }
+
+        self.edit("validate", |meta| {
+            meta.insert("validated".into(), true.into());
+            meta.insert("validated_at".into(), now().into());
+        });
+        self.run_phase(Phase::PostValidate)
+            .map_err(ValidationError::Hook)?;
+        Ok(self.transition())
+    }
+}
+
+type TemplateHook = Rc<dyn Fn(&str, &mut MetadataMap) -> Result<(), HookError>>;
+
+// Per-environment overlay on a contract's base metadata (e.g. `dev`, `staging`, `prod`),
+// applied by `ContractBuilder::for_profile`
+#[derive(Clone, Default)]
+struct Profile {
+    metadata: MetadataMap,
+    tags: Vec<String>,
+    signer: Option<Rc<dyn Signer>>,
+}
+
+impl Profile {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_metadata(mut self, key: &str, value: impl Into<MetadataValue>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    // Carries the RPC endpoint along with the chain
+    fn with_network(self, network: Network) -> Self {
+        self.with_metadata("network", network)
+    }
+
+    fn with_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    fn with_signer(mut self, signer: impl Signer + 'static) -> Self {
+        self.signer = Some(Rc::new(signer));
+        self
+    }
+}
+
+// Reusable preset for `ContractBuilder::from_template`: metadata, tags and lifecycle hooks
+// shared by every contract stamped from it
+#[derive(Clone)]
+struct ContractTemplate {
+    name: String,
+    metadata: MetadataMap,
+    tags: Vec<String>,
+    hooks: Vec<(Phase, TemplateHook)>,
+    profiles: HashMap<String, Profile>,
+}
+
+impl ContractTemplate {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            metadata: MetadataMap::new(),
+            tags: Vec::new(),
+            hooks: Vec::new(),
+            profiles: HashMap::new(),
+        }
+    }
+
+    // Available to every builder stamped from the template via `for_profile`
+    fn with_profile(mut self, name: &str, profile: Profile) -> Self {
+        self.profiles.insert(name.into(), profile);
+        self
+    }
+
+    fn with_metadata(mut self, key: &str, value: impl Into<MetadataValue>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    fn with_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    fn on_phase<F>(mut self, phase: Phase, hook: F) -> Self
+    where
+        F: Fn(&str, &mut MetadataMap) -> Result<(), HookError> + 'static,
+    {
+        self.hooks.push((phase, Rc::new(hook)));
+        self
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Fungible token preset: requires a `symbol` and defaults `decimals` to 18
+    fn erc20() -> Self {
+        Self::new("ERC20")
+            .with_metadata("standard", "ERC20")
+            .with_metadata("decimals", 18)
+            .with_tags(["token", "erc20"])
+            .on_phase(Phase::PreValidate, |name, meta| match meta.get("symbol") {
+                Some(MetadataValue::String(symbol)) if !symbol.is_empty() => Ok(()),
+                _ => Err(HookError::new(format!(
+                    "ERC20 token `{name}` needs a symbol"
+                ))),
+            })
+    }
+}
+
+// Why stored metadata can't resume as a `Validated` builder
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RehydrateError {
+    NotValidated,
+    AlreadyDeployed(String),
+    MissingKey(String),
+}
+
+impl fmt::Display for RehydrateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotValidated => write!(f, "metadata was never validated"),
+            Self::AlreadyDeployed(status) => {
+                write!(f, "metadata is already past validation (status `{status}`)")
+            }
+            Self::MissingKey(key) => write!(f, "missing required key: {key}"),
+        }
+    }
+}
+
+impl std::error::Error for RehydrateError {}
+
+impl ContractBuilder<Validated> {
+    fn add_hook<F>(self, mut hook: F) -> Self
+    where
+        F: FnMut(&mut MetadataMap) + 'static,
+    {
+        self.add_try_hook(move |meta| {
+            hook(meta);
+            Ok(())
+        })
+    }
+
+    // Resumes a contract persisted after `validate()` but before deploy: requires
+    // `validated = true`, no `status` yet and every required key. Rules and hooks are not
+    // stored, so register any deploy hooks again before deploying.
+    fn from_metadata(name: &str, metadata: MetadataMap) -> Result<Self, ScrgError> {
+        if metadata.get("validated").and_then(MetadataValue::as_bool) != Some(true) {
+            return Err(RehydrateError::NotValidated.into());
+        }
+        if let Some(status) = metadata.get("status") {
+            return Err(RehydrateError::AlreadyDeployed(status.to_string()).into());
+        }
+        if let Some(key) = REQUIRED_KEYS
+            .iter()
+            .find(|key| !metadata.contains_key(**key))
+        {
+            return Err(RehydrateError::MissingKey((*key).into()).into());
+        }
+        let builder: Self = ContractBuilder::new(name).transition();
+        *builder.metadata.borrow_mut() = metadata;
+        Ok(builder)
+    }
+
+    // Drops the validation markers so metadata can be fixed and `validate()` run again
+    fn invalidate(self) -> ContractBuilder<Init<AuthorSet>> {
+        self.reset_state("invalidate")
+    }
+
+    // Records `signer` and `signature` once every deploy hook has run
+    fn with_signer(mut self, signer: impl Signer + 'static) -> Self {
+        self.signer = Some(Box::new(signer));
+        self
+    }
+
+    // Records `estimated_gas` and `gas_limit` (estimate plus a margin unless one was set) and
+    // returns the estimate, failing if it is over `gas_budget` or the configured limit. The
+    // builder is borrowed, so it stays usable after a failed estimate.
+    fn estimate_gas(&mut self, estimator: impl GasEstimator) -> Result<u64, ScrgError> {
+        let (estimated, budget, limit) = {
+            let meta = self.metadata.borrow();
+            let estimated = estimator
+                .estimate(&self.name, &meta)
+                .map_err(GasError::Estimate)?;
+            let read = |key| {
+                meta.get(key)
+                    .and_then(MetadataValue::as_int)
+                    .and_then(|n| u64::try_from(n).ok())
+            };
+            (estimated, read("gas_budget"), read("gas_limit"))
+        };
+        if let Some(budget) = budget.filter(|&budget| estimated > budget) {
+            return Err(GasError::OverBudget { estimated, budget }.into());
+        }
+        if let Some(limit) = limit.filter(|&limit| estimated > limit) {
+            return Err(GasError::OverLimit { estimated, limit }.into());
+        }
+
+        let limit =
+            limit.unwrap_or_else(|| estimated.saturating_mul(100 + GAS_LIMIT_MARGIN_PERCENT) / 100);
+        let to_int = |gas: u64| i64::try_from(gas).unwrap_or(i64::MAX);
+        self.edit("estimate_gas", |meta| {
+            meta.insert("estimated_gas".into(), to_int(estimated).into());
+            meta.insert("gas_limit".into(), to_int(limit).into());
+        });
+        Ok(estimated)
+    }
+
+    fn add_try_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&mut MetadataMap) -> Result<(), HookError> + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    // Wraps the queued deploy hooks; layers added first run outermost:
+    //   .with_middleware(|meta, next| next(meta).or_else(|_| next(meta)))  // one retry
+    // The closure passed to `on_deploy` runs after the whole chain, outside it.
+    fn with_middleware<F>(mut self, layer: F) -> Self
+    where
+        F: FnMut(&mut MetadataMap, HookNext<'_>) -> Result<(), HookError> + 'static,
+    {
+        self.middleware.push(Box::new(layer));
+        self
+    }
+
+    // Hooks run against a scratch copy, so a failure leaves the real metadata untouched
+    fn record_deployment(&self, ok: bool) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_deployment(ok);
+        }
+    }
+
+    fn run_hooks(&mut self) -> Result<MetadataMap, HookError> {
+        let metrics = self.metrics.clone();
+        self.run_hooks_recording(metrics.as_deref())
+    }
+
+    fn run_hooks_recording(&mut self, metrics: Option<&Metrics>) -> Result<MetadataMap, HookError> {
+        let mut scratch = self.metadata.borrow().clone();
+        self.lifecycle
+            .run(Phase::PreDeploy, &self.name, &mut scratch, metrics)?;
+        scratch.insert("status".into(), LifecycleStatus::Deployed.as_str().into());
+        scratch.insert("deployed_at".into(), now().into());
+        if !self.hooks.is_empty() || !self.middleware.is_empty() {
+            trace_span!(
+                "contract.hook",
+                contract = %self.name,
+                stage = "deploy",
+                hooks = self.hooks.len(),
+                middleware = self.middleware.len()
+            );
+            let started = Instant::now();
+            let reserved = reserved_values(&scratch);
+            let result = run_hook_chain(&mut self.middleware, &mut self.hooks, &mut scratch)
+                .and_then(|()| Ok(check_reserved_unchanged(&reserved, &scratch)?))
+                .map_err(|err| err.in_stage("deploy"));
+            if let Some(metrics) = metrics {
+                metrics.record_hooks("deploy", started.elapsed(), result.is_ok());
+            }
+            result?;
+        }
+        self.limits
+            .check_changes(&self.metadata.borrow(), &scratch)
+            .map_err(|err| HookError::from(err).in_stage("deploy"))?;
+        Ok(scratch)
+    }
+
+    // Runs PreDeploy, queued and PostDeploy hooks on a copy; the builder stays `Validated`
+    // with its metadata and audit log untouched, and nothing is recorded in `metrics()`. The
+    // signer is not invoked. Hooks themselves do run: anything they do besides editing the
+    // metadata they are handed (I/O, RPC calls, state captured by an `FnMut`) happens for
+    // real, so keep side effects behind a flag the hook can see, or out of hooks.
+    fn deploy_dry_run(&mut self) -> Result<DryRun, ScrgError> {
+        let mut scratch = self.run_hooks_recording(None)?;
+        self.lifecycle
+            .run(Phase::PostDeploy, &self.name, &mut scratch, None)?;
+        let mut changes = Vec::new();
+        record_changes(&mut changes, &self.metadata.borrow(), &scratch, "dry_run");
+        Ok(DryRun {
+            metadata: scratch,
+            changes,
+        })
+    }
+
+    fn deploy(self) -> Result<ContractBuilder<Deployed>, DeployFailure> {
+        self.try_on_deploy(|_| Ok(()))
+    }
+
+    // Runs any queued hooks, then `hook` last
+    fn on_deploy<F>(self, hook: F) -> Result<ContractBuilder<Deployed>, DeployFailure>
+    where
+        F: FnOnce(&mut MetadataMap),
+    {
+        self.try_on_deploy(|meta| {
+            hook(meta);
+            Ok(())
+        })
+    }
+
+    fn try_on_deploy<F>(mut self, hook: F) -> Result<ContractBuilder<Deployed>, DeployFailure>
+    where
+        F: FnOnce(&mut MetadataMap) -> Result<(), HookError>,
+    {
+        trace_span!("contract.deploy", contract = %self.name, keys = self.metadata.borrow().len());
+        let result = self.run_hooks().and_then(|mut scratch| {
+            let reserved = reserved_values(&scratch);
+            hook(&mut scratch)
+                .and_then(|()| Ok(check_reserved_unchanged(&reserved, &scratch)?))
+                .map_err(|err| err.in_stage("deploy"))?;
+            self.lifecycle.run(
+                Phase::PostDeploy,
+                &self.name,
+                &mut scratch,
+                self.metrics.as_deref(),
+            )?;
+            self.limits
+                .check_changes(&self.metadata.borrow(), &scratch)
+                .map_err(|err| HookError::from(err).in_stage("deploy"))?;
+            Ok(scratch)
+        });
+        self.record_deployment(result.is_ok());
+        match result {
+            Ok(mut scratch) => {
+                trace_event!(info, keys = scratch.len(), "deployed");
+                self.seal(&mut scratch);
+                self.snapshot = Some(MetadataSnapshot::capture(&scratch));
+                self.commit(scratch, "deploy");
+                Ok(self.transition())
+            }
+            Err(error) => {
+                trace_event!(warn, error = %error, "deploy aborted");
+                Err(DeployFailure {
+                    contract: Box::new(self),
+                    error,
+                })
+            }
+        }
+    }
+}
+
+// Outcome of `deploy_dry_run`: the metadata a real deploy would produce and how it differs
+#[derive(Clone, PartialEq, Eq)]
+struct DryRun {
+    metadata: MetadataMap,
+    changes: Vec<AuditEntry>,
+}
+
+impl fmt::Debug for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DryRun")
+            .field("metadata", &redacted_map(&self.metadata))
+            .field("changes", &self.changes)
+            .finish()
+    }
+}
+
+impl DryRun {
+    fn changed_keys(&self) -> impl Iterator<Item = &str> {
+        self.changes.iter().map(|entry| entry.key.as_str())
+    }
+}
+
+// A deployment aborted by a hook; the builder is handed back still `Validated`
+struct DeployFailure {
+    contract: Box<ContractBuilder<Validated>>,
+    error: HookError,
+}
+
+impl DeployFailure {
+    fn error(&self) -> &HookError {
+        &self.error
+    }
+
+    fn into_builder(self) -> ContractBuilder<Validated> {
+        *self.contract
+    }
+}
+
+impl fmt::Debug for DeployFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeployFailure")
+            .field("contract", &self.contract.name)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl fmt::Display for DeployFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "deploying `{}` aborted: {}",
+            self.contract.name, self.error
+        )
+    }
+}
+
+impl std::error::Error for DeployFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(feature = "async")]
+impl ContractBuilder<Validated> {
+    // The hook owns the metadata while it runs, so no RefCell borrow is held across `.await`
+    async fn on_deploy_async<F, Fut>(
+        mut self,
+        hook: F,
+    ) -> Result<ContractBuilder<Deployed>, DeployFailure>
+    where
+        F: FnOnce(MetadataMap) -> Fut,
+        Fut: std::future::Future<Output = MetadataMap>,
+    {
+        // Events only: an entered span must not be held across `.await`
+        trace_event!(
+            info,
+            contract = %self.name,
+            keys = self.metadata.borrow().len(),
+            "contract.deploy_async started"
+        );
+        let scratch = match self.run_hooks() {
+            Ok(scratch) => scratch,
+            Err(error) => {
+                self.record_deployment(false);
+                return Err(DeployFailure {
+                    contract: Box::new(self),
+                    error,
+                });
+            }
+        };
+        let reserved = reserved_values(&scratch);
+        let mut meta = hook(scratch).await; // e.g. submit tx, await receipt
+        let result = check_reserved_unchanged(&reserved, &meta)
+            .map_err(|err| HookError::from(err).in_stage("deploy"))
+            .and_then(|()| {
+                let metrics = self.metrics.as_deref();
+                self.lifecycle
+                    .run(Phase::PostDeploy, &self.name, &mut meta, metrics)
+            })
+            .and_then(|()| {
+                self.limits
+                    .check_changes(&self.metadata.borrow(), &meta)
+                    .map_err(|err| HookError::from(err).in_stage("deploy"))
+            });
+        if let Err(error) = result {
+            self.record_deployment(false);
+            return Err(DeployFailure {
+                contract: Box::new(self),
+                error,
+            });
+        }
+        self.record_deployment(true);
+        trace_event!(
+            info,
+            contract = %self.name,
+            keys = meta.len(),
+            "contract.deploy_async finished"
+        );
+        self.seal(&mut meta);
+        self.snapshot = Some(MetadataSnapshot::capture(&meta));
+        self.commit(meta, "deploy");
+
+        Ok(self.transition())
+    }
+
+    // Awaits the hook and detaches straight into the `Send` value type
+    async fn deploy_async<F, Fut>(self, hook: F) -> Result<DeployedContract, DeployFailure>
+    where
+        F: FnOnce(MetadataMap) -> Fut,
+        Fut: std::future::Future<Output = MetadataMap>,
+    {
+        Ok(self.on_deploy_async(hook).await?.into_deployed())
+    }
+}
+
+#[cfg(feature = "ethers")]
+#[derive(Debug)]
+enum OnchainError {
+    // Only `EvmBytecode` artifacts can be submitted as creation transactions
+    NoBytecode,
+    Rpc(String),
+    // The node dropped the transaction before it was mined
+    Dropped,
+    NoContractAddress,
+    // `resolve_ens` on a contract without `with_ens`
+    NoEnsName,
+    // Mined, but a deploy hook rejected the metadata; the contract exists on-chain
+    Recording {
+        failure: Box<DeployFailure>,
+        address: Address,
+        tx_hash: String,
+    },
+}
+
+#[cfg(feature = "ethers")]
+impl fmt::Display for OnchainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoBytecode => f.write_str("contract has no EVM bytecode to deploy"),
+            Self::Rpc(err) => write!(f, "RPC error: {err}"),
+            Self::Dropped => f.write_str("creation transaction was dropped"),
+            Self::NoContractAddress => f.write_str("receipt carries no contract address"),
+            Self::NoEnsName => f.write_str("contract has no ENS name to resolve"),
+            Self::Recording {
+                failure,
+                address,
+                tx_hash,
+            } => write!(
+                f,
+                "deployed at {address} ({tx_hash}) but not recorded: {failure}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "ethers")]
+impl std::error::Error for OnchainError {}
+
+#[cfg(feature = "ethers")]
+impl ContractBuilder<Validated> {
+    // Sends `bytecode` (constructor arguments already ABI-encoded onto it) as a creation
+    // transaction and waits for the receipt. `address`, `tx_hash`, `block_number` and
+    // `deployer` are recorded by a final deploy hook after the queued ones.
+    async fn deploy_onchain<P, S>(
+        self,
+        provider: ethers::providers::Provider<P>,
+        signer: S,
+    ) -> Result<ContractBuilder<Deployed>, OnchainError>
+    where
+        P: ethers::providers::JsonRpcClient + 'static,
+        S: ethers::signers::Signer + 'static,
+    {
+        use ethers::providers::Middleware;
+
+        let code = match self.borrow().get("runtime").and_then(MetadataValue::as_str) {
+            None | Some("evm") => match self.borrow().get("bytecode") {
+                Some(MetadataValue::Bytes(code)) => code.clone(),
+                _ => return Err(OnchainError::NoBytecode),
+            },
+            Some(_) => return Err(OnchainError::NoBytecode),
+        };
+        let deployer = Address(signer.address().0);
+        let client =
+            ethers::middleware::SignerMiddleware::new_with_provider_chain(provider, signer)
+                .await
+                .map_err(|err| OnchainError::Rpc(err.to_string()))?;
+
+        let tx = ethers::types::TransactionRequest::new().data(code);
+        let pending = client
+            .send_transaction(tx, None)
+            .await
+            .map_err(|err| OnchainError::Rpc(err.to_string()))?;
+        let receipt = pending
+            .await
+            .map_err(|err| OnchainError::Rpc(err.to_string()))?
+            .ok_or(OnchainError::Dropped)?;
+
+        let address = Address(
+            receipt
+                .contract_address
+                .ok_or(OnchainError::NoContractAddress)?
+                .0,
+        );
+        let tx_hash = format!("{:#x}", receipt.transaction_hash);
+        let block_number = receipt.block_number.map(|n| n.as_u64() as i64);
+
+        let recorded_hash = tx_hash.clone();
+        self.on_deploy(move |meta| {
+            meta.insert("address".into(), address.into());
+            meta.insert("deployer".into(), deployer.into());
+            meta.insert("tx_hash".into(), recorded_hash.into());
+            if let Some(block_number) = block_number {
+                meta.insert("block_number".into(), block_number.into());
+            }
+        })
+        .map_err(|failure| OnchainError::Recording {
+            failure: Box::new(failure),
+            address,
+            tx_hash,
+        })
+    }
+
+    // Looks up the `ens` name through `provider` and queues a deploy hook recording the
+    // result under `ens_address`. A name without a resolver or address is an error.
+    async fn resolve_ens<P>(
+        self,
+        provider: &ethers::providers::Provider<P>,
+    ) -> Result<Self, OnchainError>
+    where
+        P: ethers::providers::JsonRpcClient,
+    {
+        use ethers::providers::Middleware;
+
+        let name = self
+            .borrow()
+            .get("ens")
+            .and_then(MetadataValue::as_str)
+            .map(str::to_owned)
+            .ok_or(OnchainError::NoEnsName)?;
+        let resolved = provider
+            .resolve_name(&name)
+            .await
+            .map_err(|err| OnchainError::Rpc(err.to_string()))?;
+        let address = Address(resolved.0);
+        Ok(self.add_hook(move |meta| {
+            meta.insert("ens_address".into(), address.into());
+        }))
+    }
+}
+
+// Kubo-compatible IPFS HTTP API (`http://127.0.0.1:5001` for a local node). Requests are
+// blocking, like the deploy hooks they run in.
+#[cfg(feature = "ipfs")]
+#[derive(Debug, Clone)]
+struct IpfsClient {
+    api_url: String,
+    http: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "ipfs")]
+#[derive(Debug)]
+enum IpfsError {
+    Http(reqwest::Error),
+    Response(String),
+}
+
+#[cfg(feature = "ipfs")]
+impl fmt::Display for IpfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(err) => write!(f, "IPFS request failed: {err}"),
+            Self::Response(msg) => write!(f, "unexpected IPFS response: {msg}"),
+        }
+    }
+}
+
+#[cfg(feature = "ipfs")]
+impl std::error::Error for IpfsError {}
+
+#[cfg(feature = "ipfs")]
+impl From<IpfsError> for HookError {
+    fn from(err: IpfsError) -> Self {
+        Self::new(err.to_string())
+    }
+}
+
+#[cfg(feature = "ipfs")]
+impl IpfsClient {
+    fn new(api_url: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').into(),
+            http: reqwest::blocking::Client::new(),
+        }
+    }
+
+    // Adds and pins `data`, returning its CIDv1
+    fn add(&self, data: Vec<u8>) -> Result<String, IpfsError> {
+        #[derive(serde::Deserialize)]
+        struct Added {
+            #[serde(rename = "Hash")]
+            hash: String,
+        }
+        let part = reqwest::blocking::multipart::Part::bytes(data).file_name("metadata.json");
+        let added: Added = self
+            .http
+            .post(format!(
+                "{}/api/v0/add?pin=true&cid-version=1",
+                self.api_url
+            ))
+            .multipart(reqwest::blocking::multipart::Form::new().part("file", part))
+            .send()
+            .and_then(reqwest::blocking::Response::error_for_status)
+            .and_then(reqwest::blocking::Response::json)
+            .map_err(IpfsError::Http)?;
+        match added.hash.is_empty() {
+            true => Err(IpfsError::Response("no CID in the add reply".into())),
+            false => Ok(added.hash),
+        }
+    }
+}
+
+// Where `pin_to_ipfs` records the CID
+#[cfg(feature = "ipfs")]
+const IPFS_CID_KEY: &str = "ipfs_cid";
+
+// Name and metadata as compact JSON with sorted keys, so equal metadata pins to the same
+// CID. Secret keys, the signature and any earlier CID are left out.
+#[cfg(feature = "ipfs")]
+fn canonical_json(name: &str, meta: &MetadataMap) -> Result<Vec<u8>, serde_json::Error> {
+    #[derive(serde::Serialize)]
+    struct Canonical<'a> {
+        name: &'a str,
+        metadata: BTreeMap<&'a str, &'a MetadataValue>,
+    }
+    let skip = ["signature", IPFS_CID_KEY];
+    let metadata = meta
+        .iter()
+        .filter(|(key, _)| !is_secret(key) && !skip.contains(&key.as_str()))
+        .map(|(key, value)| (key.as_str(), value))
+        .collect();
+    serde_json::to_vec(&Canonical { name, metadata })
+}
+
+#[cfg(feature = "ipfs")]
+impl<State> ContractBuilder<State> {
+    // Uploads `canonical_json` of the deployed metadata to `ipfs` as a PostDeploy hook and
+    // records the CID under `ipfs_cid`. Registered like any other PostDeploy hook, so
+    // anything those added before it is covered; a failed upload fails the deploy.
+    fn pin_to_ipfs(self, ipfs: IpfsClient) -> Self {
+        self.on_phase(Phase::PostDeploy, move |name, meta| {
+            let json = canonical_json(name, meta).map_err(|err| HookError::new(err.to_string()))?;
+            let cid = ipfs.add(json)?;
+            meta.insert(IPFS_CID_KEY.into(), cid.into());
+            Ok(())
+        })
+    }
+}
+
+impl ContractBuilder<Deployed> {
+    // Audited replacement for direct `borrow_mut()` edits; lifecycle keys and writes
+    // beyond the contract's `MetadataLimits` are refused
+    fn set(
+        &mut self,
+        key: impl AsRef<str>,
+        value: impl Into<MetadataValue>,
+        actor: &str,
+    ) -> Result<(), WriteError> {
+        let key = key.as_ref();
+        check_not_reserved(key)?;
+        let value = value.into();
+        self.limits
+            .check_insert(&self.metadata.borrow(), key, &value)?;
+        self.edit(actor, |meta| {
+            meta.insert(key.into(), value);
+        });
+        Ok(())
+    }
+
+    fn remove(&mut self, key: impl AsRef<str>, actor: &str) -> Result<(), ReservedKey> {
+        let key = key.as_ref();
+        check_not_reserved(key)?;
+        self.edit(actor, |meta| {
+            meta.remove(key);
+        });
+        Ok(())
+    }
+
+    // Clones the map if a hook kept the `Rc` alive, as `detach` does
+    fn into_inner(self) -> MetadataMap {
+        Rc::try_unwrap(self.metadata)
+            .map(RefCell::into_inner)
+            .unwrap_or_else(|shared| shared.borrow().clone())
+    }
+
+    fn into_deployed(self) -> DeployedContract {
+        self.detach()
+    }
+
+    fn upgrade_to(self, new_version: &str) -> Result<ContractBuilder<Upgraded>, ScrgError> {
+        self.upgrade(new_version)
+    }
+
+    // For local and dev workflows only: nothing on-chain is undone
+    fn reset(self) -> ContractBuilder<Init<AuthorSet>> {
+        self.reset_state("reset")
+    }
+
+    // Emergency stop; the reason and time are kept until `resume()`
+    fn pause(mut self, reason: &str) -> ContractBuilder<Paused> {
+        self.edit("pause", |meta| {
+            meta.insert("status".into(), LifecycleStatus::Paused.as_str().into());
+            meta.insert("pause_reason".into(), reason.into());
+            meta.insert("paused_at".into(), now().into());
+        });
+        self.transition()
+    }
+
+    fn deprecate(mut self, successor: Option<&str>) -> ContractBuilder<Deprecated> {
+        self.edit("deprecate", |meta| mark_deprecated(meta, successor));
+        self.transition()
+    }
+
+    fn archive(mut self) -> ContractBuilder<Archived> {
+        self.edit("archive", mark_archived);
+        self.transition()
+    }
+}
+
+impl ContractBuilder<Deprecated> {
+    fn successor(&self) -> Option<String> {
+        self.metadata
+            .borrow()
+            .get("successor")
+            .and_then(MetadataValue::as_str)
+            .map(str::to_owned)
+    }
+
+    fn archive(mut self) -> ContractBuilder<Archived> {
+        self.edit("archive", mark_archived);
+        self.transition()
+    }
+
+    fn into_deployed(self) -> DeployedContract {
+        self.detach()
+    }
+}
+
+impl ContractBuilder<Archived> {
+    fn into_deployed(self) -> DeployedContract {
+        self.detach()
+    }
+}
+
+// Shared by the builder transitions and the registry, which only holds detached values
+fn mark_deprecated(meta: &mut MetadataMap, successor: Option<&str>) {
+    meta.extend(deprecation(successor).map(|(key, value)| (key.into(), value)));
+}
+
+fn mark_archived(meta: &mut MetadataMap) {
+    meta.extend(archival().map(|(key, value)| (key.into(), value)));
+}
+
+// What deprecating writes, for `mark_deprecated` and `DeployedContract::edit`
+fn deprecation(
+    successor: Option<&str>,
+) -> impl Iterator<Item = (&'static str, MetadataValue)> + '_ {
+    [
+        ("status", LifecycleStatus::Deprecated.as_str().into()),
+        ("deprecated_at", now().into()),
+    ]
+    .into_iter()
+    .chain(successor.map(|successor| ("successor", successor.into())))
+}
+
+fn archival() -> impl Iterator<Item = (&'static str, MetadataValue)> {
+    [
+        ("status", LifecycleStatus::Archived.as_str().into()),
+        ("archived_at", now().into()),
+    ]
+    .into_iter()
+}
+
+impl ContractBuilder<Paused> {
+    fn pause_reason(&self) -> Option<String> {
+        self.metadata
+            .borrow()
+            .get("pause_reason")
+            .and_then(MetadataValue::as_str)
+            .map(str::to_owned)
+    }
+
+    fn resume(mut self) -> ContractBuilder<Deployed> {
+        self.edit("resume", |meta| {
+            meta.insert("status".into(), LifecycleStatus::Deployed.as_str().into());
+            meta.remove("pause_reason");
+            meta.remove("paused_at");
+        });
+        self.transition()
+    }
+
+    fn into_deployed(self) -> DeployedContract {
+        self.detach()
+    }
+}
+
+impl ContractBuilder<Upgraded> {
+    fn into_deployed(self) -> DeployedContract {
+        self.detach()
+    }
+
+    fn upgrade_to(self, new_version: &str) -> Result<ContractBuilder<Upgraded>, ScrgError> {
+        self.upgrade(new_version)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum UpgradeError {
+    InvalidVersion(VersionError),
+    NotNewer {
+        current: Box<Version>,
+        requested: Box<Version>,
+    },
+    Hook(HookError),
+}
+
+impl fmt::Display for UpgradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidVersion(err) => write!(f, "{err}"),
+            Self::NotNewer { current, requested } => {
+                write!(f, "upgrade to {requested} must be newer than {current}")
+            }
+            Self::Hook(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for UpgradeError {}
+
+// Finished contract value with owned metadata
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct DeployedContract {
+    name: String,
+    // Copy-on-write: clones (e.g. the next version) and the deploy snapshot share one map
+    // until an `edit` changes it
+    metadata: Arc<MetadataMap>,
+    #[cfg_attr(feature = "serde", serde(with = "rfc3339"))]
+    deployed_at: SystemTime,
+    #[cfg_attr(feature = "serde", serde(default))]
+    audit: AuditLog,
+    #[cfg_attr(feature = "serde", serde(default))]
+    snapshot: MetadataSnapshot,
+    // From the builder; kept by JSON and the sled and SQLite stores, while the binary, TOML
+    // and protobuf formats restore the defaults
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "MetadataLimits::is_default")
+    )]
+    limits: MetadataLimits,
+    // Live values the registry keeps packed instead of in `metadata` (see `Packed`)
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    packed: BTreeMap<String, Packed>,
+    // Deploy-time values packed out of `snapshot` the same way
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    snapshot_packed: BTreeMap<String, Packed>,
+}
+
+// Packed form of a live or deploy-time metadata value
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+enum Packed {
+    // `Encryption::seal` output; only `ContractRegistry::reveal` opens it
+    Sealed(Vec<u8>),
+    // `Compression::compress` output; `get()` and the other accessors unpack it
+    Compressed(CompressedValue),
+}
+
+// Moves the entries of `map` that `pack` accepts into `packed`; the map is copied only if
+// they exist and it is shared
+fn pack_map(
+    map: &mut Arc<MetadataMap>,
+    packed: &mut BTreeMap<String, Packed>,
+    pack: impl Fn(&str, &MetadataValue) -> Option<Packed>,
+) {
+    let entries: Vec<_> = map
+        .iter()
+        .filter_map(|(key, value)| Some((key.clone(), pack(key, value)?)))
+        .collect();
+    if entries.is_empty() {
+        return;
+    }
+    let map = Arc::make_mut(map);
+    for (key, value) in entries {
+        map.remove(&key);
+        packed.insert(key, value);
+    }
+}
+
+// `map` plus the compressed values of `packed`, unpacked; borrowed if there are none
+fn with_unpacked<'a>(
+    map: &'a MetadataMap,
+    packed: &BTreeMap<String, Packed>,
+) -> std::borrow::Cow<'a, MetadataMap> {
+    let mut unpacked = packed
+        .iter()
+        .filter_map(|(key, packed)| match packed {
+            Packed::Compressed(compressed) => Some((key.clone(), compressed.value().ok()?.clone())),
+            Packed::Sealed(_) => None,
+        })
+        .peekable();
+    if unpacked.peek().is_none() {
+        return std::borrow::Cow::Borrowed(map);
+    }
+    let mut map = map.clone();
+    map.extend(unpacked);
+    std::borrow::Cow::Owned(map)
+}
+
+// Codec output: tag byte, little-endian u64 plaintext length, then the packed bytes. Loaded
+// values have no codec until the registry's `with_compression` attaches one.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+struct CompressedValue {
+    bytes: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    codec: Option<Arc<dyn Codec>>,
+    // Unpacked on first access and kept for later ones
+    #[cfg_attr(feature = "serde", serde(skip))]
+    plain: std::sync::OnceLock<Result<MetadataValue, CodecError>>,
+}
+
+impl CompressedValue {
+    fn new(bytes: Vec<u8>, codec: Option<Arc<dyn Codec>>) -> Self {
+        Self {
+            bytes,
+            codec,
+            plain: std::sync::OnceLock::new(),
+        }
+    }
+
+    fn value(&self) -> Result<&MetadataValue, CodecError> {
+        let Some(codec) = &self.codec else {
+            return Err(CodecError("the registry has no codec".into()));
+        };
+        self.plain
+            .get_or_init(|| decompress(codec.as_ref(), &self.bytes))
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    // Plaintext length recorded in the header
+    fn original_len(&self) -> Option<usize> {
+        let len = self.bytes.get(1..COMPRESSED_HEADER_LEN)?.try_into().ok()?;
+        usize::try_from(u64::from_le_bytes(len)).ok()
+    }
+}
+
+// The state is the recorded `status`, or `deployed` for entries stored without one
+impl fmt::Display for DeployedContract {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.status().map_or("deployed", LifecycleStatus::as_str);
+        write_summary(f, &self.name, state, &self.metadata)
+    }
+}
+
+impl fmt::Debug for DeployedContract {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeployedContract")
+            .field("name", &self.name)
+            .field("status", &self.status())
+            .field("deployed_at", &format_rfc3339(self.deployed_at))
+            .field("metadata", &redacted_map(&self.metadata))
+            .field("audit", &self.audit.len())
+            .finish()
+    }
+}
+
+impl DeployedContract {
+    // For formats that persist metadata only; the audit log starts empty and the snapshot
+    // shares the map
+    fn new(name: String, metadata: MetadataMap, deployed_at: SystemTime) -> Self {
+        let metadata = Arc::new(metadata);
+        Self {
+            name,
+            snapshot: MetadataSnapshot(Arc::clone(&metadata)),
+            metadata,
+            deployed_at,
+            audit: AuditLog::default(),
+            limits: MetadataLimits::default(),
+            packed: BTreeMap::new(),
+            snapshot_packed: BTreeMap::new(),
+        }
+    }
+
+    // Returns the old value. As on a deployed builder, lifecycle keys (`STATE_KEYS`) are
+    // refused, since registries read `status` to decide what is visible, and so are writes
+    // beyond the contract's `MetadataLimits`.
+    fn set(
+        &mut self,
+        key: impl AsRef<str>,
+        value: impl Into<MetadataValue>,
+        actor: &str,
+    ) -> Result<Option<MetadataValue>, WriteError> {
+        let key = key.as_ref();
+        check_not_reserved(key)?;
+        let value = value.into();
+        self.check_insert(key, &value)?;
+        let old = self.unpack(key);
+        self.edit(actor, [(key, Some(value))]);
+        Ok(old)
+    }
+
+    fn remove(
+        &mut self,
+        key: impl AsRef<str>,
+        actor: &str,
+    ) -> Result<Option<MetadataValue>, ReservedKey> {
+        let key = key.as_ref();
+        check_not_reserved(key)?;
+        let old = self.unpack(key);
+        self.edit(actor, [(key, None)]);
+        Ok(old)
+    }
+
+    // As `MetadataLimits::check_insert`, counting packed keys like plain ones
+    fn check_insert(&self, key: &str, value: &MetadataValue) -> Result<(), LimitExceeded> {
+        self.limits.check_entry(key, value)?;
+        let is_new = !self.metadata.contains_key(key) && !self.packed.contains_key(key);
+        self.limits
+            .check_key_count(self.metadata.len() + self.packed.len(), is_new)
+    }
+
+    // Puts `key` back into `metadata` ahead of a write, so the audit log sees its old value,
+    // and returns that value. Sealed values are dropped instead and come back as `None`,
+    // since only the registry's cipher opens them.
+    fn unpack(&mut self, key: &str) -> Option<MetadataValue> {
+        if let Some(Packed::Compressed(compressed)) = self.packed.remove(key) {
+            if let Ok(value) = compressed.value() {
+                Arc::make_mut(&mut self.metadata).insert(key.into(), value.clone());
+            }
+        }
+        self.metadata.get(key).cloned()
+    }
+
+    // Moves every value `pack` accepts out of `metadata` into `packed`. Not an edit, so
+    // nothing is audited.
+    fn pack(&mut self, pack: impl Fn(&str, &MetadataValue) -> Option<Packed>) {
+        pack_map(&mut self.metadata, &mut self.packed, pack);
+    }
+
+    // `pack` for the deploy snapshot
+    fn pack_snapshot(&mut self, pack: impl Fn(&str, &MetadataValue) -> Option<Packed>) {
+        pack_map(&mut self.snapshot.0, &mut self.snapshot_packed, pack);
+    }
+
+    // Sets each key to its value, or removes it for `None`, and audits the keys that end up
+    // changed. The old values come from the writes themselves, so the map is edited in place
+    // and copied only while another version or the snapshot still shares it.
+    fn edit<'a>(
+        &mut self,
+        actor: &str,
+        writes: impl IntoIterator<Item = (&'a str, Option<MetadataValue>)>,
+    ) {
+        let writes: Vec<_> = writes
+            .into_iter()
+            .filter(|(key, value)| self.metadata.get(*key) != value.as_ref())
+            .collect();
+        if writes.is_empty() {
+            return;
+        }
+        let metadata = Arc::make_mut(&mut self.metadata);
+        let mut before = BTreeMap::new();
+        for (key, value) in writes {
+            let old = match value {
+                Some(value) => metadata.insert(key.into(), value),
+                None => metadata.remove(key),
+            };
+            before.entry(key).or_insert(old);
+        }
+        let timestamp = now();
+        for (key, old) in before {
+            let new = metadata.get(key);
+            if old.as_ref() != new {
+                self.audit.push(AuditEntry {
+                    key: key.into(),
+                    old: old.map(AuditValue::from),
+                    new: new.cloned().map(AuditValue::from),
+                    timestamp,
+                    actor: actor.into(),
+                });
+            }
+        }
+    }
+
+    fn audit_log(&self) -> &[AuditEntry] {
+        &self.audit
+    }
+
+    // The latest `MAX_KEY_HISTORY` changes to `key`, oldest first: each entry's `new` held
+    // from its timestamp until the next entry, and `actor` says who set it. Older changes
+    // stay in `audit_log()`.
+    fn history(&self, key: impl AsRef<str>) -> Vec<&AuditEntry> {
+        self.audit.history(key.as_ref())
+    }
+
+    // Metadata exactly as deployed (or last upgraded), unaffected by later edits; compressed
+    // values come back unpacked, sealed ones not at all
+    fn snapshot(&self) -> MetadataSnapshot {
+        match self.unpacked_snapshot() {
+            std::borrow::Cow::Borrowed(_) => self.snapshot.clone(),
+            std::borrow::Cow::Owned(snapshot) => MetadataSnapshot(Arc::new(snapshot)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Without the values a registry packed; `get()` and `unpacked_metadata()` include the
+    // compressed ones
+    fn metadata(&self) -> &MetadataMap {
+        &self.metadata
+    }
+
+    // Live metadata with compressed values unpacked; sealed ones stay out
+    fn unpacked_metadata(&self) -> std::borrow::Cow<'_, MetadataMap> {
+        with_unpacked(&self.metadata, &self.packed)
+    }
+
+    // `unpacked_metadata` for the deploy snapshot
+    fn unpacked_snapshot(&self) -> std::borrow::Cow<'_, MetadataMap> {
+        with_unpacked(&self.snapshot, &self.snapshot_packed)
+    }
+
+    // `key` in the deploy snapshot, unpacked like `get()`
+    fn snapshot_value(&self, key: &str) -> Option<&MetadataValue> {
+        self.snapshot
+            .get(key)
+            .or_else(|| match self.snapshot_packed.get(key)? {
+                Packed::Compressed(compressed) => compressed.value().ok(),
+                Packed::Sealed(_) => None,
+            })
+    }
+
+    // Every compressed value it holds: live, deploy-time and audited
+    fn compressed_values(&self) -> impl Iterator<Item = &CompressedValue> {
+        let audited = self.audit.iter().flat_map(|entry| [&entry.old, &entry.new]);
+        self.packed
+            .values()
+            .chain(self.snapshot_packed.values())
+            .filter_map(|packed| match packed {
+                Packed::Compressed(compressed) => Some(compressed),
+                Packed::Sealed(_) => None,
+            })
+            .chain(audited.filter_map(|value| match value {
+                Some(AuditValue::Compressed { compressed }) => Some(compressed),
+                _ => None,
+            }))
+    }
+
+    // Whether both hold the same metadata allocation, i.e. neither has been edited since one
+    // was cloned from the other
+    fn shares_metadata(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.metadata, &other.metadata)
+    }
+
+    // Compressed values come back unpacked, sealed ones not at all (see
+    // `ContractRegistry::reveal`)
+    fn get(&self, key: impl AsRef<str>) -> Option<&MetadataValue> {
+        let key = key.as_ref();
+        self.metadata
+            .get(key)
+            .or_else(|| match self.packed.get(key)? {
+                Packed::Compressed(compressed) => compressed.value().ok(),
+                Packed::Sealed(_) => None,
+            })
+    }
+
+    // e.g. `contract.namespace("audit").get("firm")` for `audit.firm`
+    fn namespace<'m>(&'m self, name: &'m str) -> Namespace<'m> {
+        self.metadata.namespace(name)
+    }
+
+    fn deployed_at(&self) -> SystemTime {
+        self.deployed_at
+    }
+
+    fn validated_at(&self) -> Option<SystemTime> {
+        self.get("validated_at")
+            .and_then(MetadataValue::as_timestamp)
+    }
+
+    // Ephemeral entry, e.g. a testnet deployment: registries stop returning it `ttl` from now
+    fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.edit("ttl", [("expires_at", Some((now() + ttl).into()))]);
+        self
+    }
+
+    fn expires_at(&self) -> Option<SystemTime> {
+        self.get("expires_at").and_then(MetadataValue::as_timestamp)
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at().is_some_and(|at| at <= now())
+    }
+
+    // Per instance: each upgrade gets its own
+    fn uuid(&self) -> Option<&str> {
+        self.get("uuid").and_then(MetadataValue::as_str)
+    }
+
+    // Content id of this version as deployed (see `content_id`)
+    fn id(&self) -> Option<&str> {
+        self.snapshot_value("id").and_then(MetadataValue::as_str)
+    }
+
+    fn signer(&self) -> Option<&str> {
+        self.get("signer").and_then(MetadataValue::as_str)
+    }
+
+    fn signature(&self) -> Option<Signature> {
+        match self.snapshot_value("signature") {
+            Some(MetadataValue::Bytes(bytes)) => Some(Signature(bytes.clone())),
+            _ => None,
+        }
+    }
+
+    // What the signer signed: taken from the deploy snapshot, so later edits don't change it
+    fn signing_payload(&self) -> Vec<u8> {
+        signing_payload(&self.name, &self.unpacked_snapshot())
+    }
+
+    fn version(&self) -> Option<&Version> {
+        self.get(MetaKey::Version)
+            .and_then(MetadataValue::as_version)
+    }
+
+    fn status(&self) -> Option<LifecycleStatus> {
+        self.get(MetaKey::Status)
+            .and_then(MetadataValue::as_str)
+            .and_then(LifecycleStatus::parse)
+    }
+
+    fn dependencies(&self) -> Vec<&str> {
+        dependency_names(&self.metadata)
+    }
+
+    fn tags(&self) -> Vec<&str> {
+        string_list(&self.metadata, "tags")
+    }
+
+    fn ens(&self) -> Option<&str> {
+        self.get("ens").and_then(MetadataValue::as_str)
+    }
+
+    fn bytecode(&self) -> Option<&[u8]> {
+        match self.get("bytecode") {
+            Some(MetadataValue::Bytes(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    // `None` if no network was set, or it is not a structured `Network` map
+    fn network(&self) -> Option<Network> {
+        Network::from_metadata(self.get("network")?)
+    }
+
+    fn chain_id(&self) -> Option<u64> {
+        self.network().map(|network| network.chain_id)
+    }
+
+    // Replaces any earlier record for the same chain, returning it; nothing is recorded if
+    // `deployments` holds something other than a map
+    fn add_deployment(
+        &mut self,
+        chain_id: u64,
+        record: DeploymentRecord,
+        actor: &str,
+    ) -> Result<Option<DeploymentRecord>, LimitExceeded> {
+        let previous = self.deployment(chain_id);
+        let mut deployments = match self.get("deployments") {
+            None => MetadataMap::new(),
+            Some(MetadataValue::Map(deployments)) => deployments.clone(),
+            Some(_) => return Ok(previous),
+        };
+        deployments.insert(chain_id.to_string(), record.into());
+        let deployments = MetadataValue::Map(deployments);
+        self.check_insert("deployments", &deployments)?;
+        self.edit(actor, [("deployments", Some(deployments))]);
+        Ok(previous)
+    }
+
+    fn deployment(&self, chain_id: u64) -> Option<DeploymentRecord> {
+        match self.get("deployments")? {
+            MetadataValue::Map(deployments) => {
+                DeploymentRecord::from_metadata(deployments.get(&chain_id.to_string())?)
+            }
+            _ => None,
+        }
+    }
+
+    fn deployments(&self) -> std::collections::BTreeMap<u64, DeploymentRecord> {
+        let Some(MetadataValue::Map(deployments)) = self.get("deployments") else {
+            return Default::default();
+        };
+        deployments
+            .iter()
+            .filter_map(|(chain_id, record)| {
+                Some((
+                    chain_id.parse().ok()?,
+                    DeploymentRecord::from_metadata(record)?,
+                ))
+            })
+            .collect()
+    }
+
+    // Configured for `chain_id` via `with_network`, or has a deployment record there
+    fn is_on_chain(&self, chain_id: u64) -> bool {
+        self.chain_id() == Some(chain_id) || self.deployment(chain_id).is_some()
+    }
+
+    fn artifact(&self) -> Option<ContractArtifact> {
+        let runtime = self
+            .get("runtime")
+            .and_then(MetadataValue::as_str)
+            .unwrap_or("evm");
+        Some(ContractArtifact::from_parts(
+            runtime,
+            self.bytecode()?.to_vec(),
+        ))
+    }
+
+    // Keccak-256 of the code for any runtime, hex-encoded with a 0x prefix (as `cast keccak`
+    // prints it)
+    fn bytecode_hash(&self) -> Option<String> {
+        let hash = keccak256(self.bytecode()?);
+        let hex: String = hash.iter().map(|b| format!("{b:02x}")).collect();
+        Some(format!("0x{hex}"))
+    }
+
+    // `None` without an ABI, or if the stored entries are not a valid ABI
+    fn abi(&self) -> Option<Abi> {
+        Abi::from_metadata(self.get("abi")?)
+    }
+
+    fn fields<T: ContractMetadata>(&self) -> Result<T, FieldError> {
+        T::from_metadata(&self.unpacked_metadata())
+    }
+
+    // e.g. `get_as::<u64>("block_number")` or `get_as::<Address>("signer")`
+    fn get_as<T: FromMetadataValue>(&self, key: impl AsRef<str>) -> Result<T, GetAsError> {
+        let key = key.as_ref();
+        get_as(self.get(key), key)
+    }
+
+    // Unique per contract version, e.g. `TokenX@1.2.0`
+    fn storage_key(&self) -> String {
+        match self.version() {
+            Some(version) => format!("{}@{version}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    fn into_metadata(self) -> MetadataMap {
+        match self.unpacked_metadata() {
+            std::borrow::Cow::Owned(metadata) => metadata,
+            std::borrow::Cow::Borrowed(_) => Arc::unwrap_or_clone(self.metadata),
+        }
+    }
+}
+
+// ECDSA over secp256k1; `address()` is the Ethereum address of the public key
+#[cfg(feature = "crypto")]
+struct Secp256k1Signer {
+    key: k256::ecdsa::SigningKey,
+}
+
+#[cfg(feature = "crypto")]
+impl Secp256k1Signer {
+    fn new(key: k256::ecdsa::SigningKey) -> Self {
+        Self { key }
+    }
+
+    fn from_bytes(secret: &[u8]) -> Result<Self, k256::ecdsa::Error> {
+        k256::ecdsa::SigningKey::from_slice(secret).map(Self::new)
+    }
+
+    fn verifying_key(&self) -> k256::ecdsa::VerifyingKey {
+        *self.key.verifying_key()
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl Signer for Secp256k1Signer {
+    fn address(&self) -> String {
+        eth_address(self.key.verifying_key())
+    }
+
+    fn sign(&self, payload: &[u8]) -> Signature {
+        use k256::ecdsa::signature::Signer as _;
+        let signature: k256::ecdsa::Signature = self.key.sign(payload);
+        Signature(signature.to_bytes().to_vec())
+    }
+}
+
+// Last 20 bytes of keccak256 over the uncompressed point, without the 0x04 prefix
+#[cfg(feature = "crypto")]
+fn eth_address(key: &k256::ecdsa::VerifyingKey) -> String {
+    let point = key.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    let mut bytes = [0u8; 20];
+    bytes.copy_from_slice(&hash[12..]);
+    Address(bytes).to_string()
+}
+
+#[cfg(feature = "crypto")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SignatureError {
+    Unsigned,
+    Malformed,
+    // Recorded `signer` is not the address of the verifying key
+    SignerMismatch,
+    Invalid,
+    // Part of the deploy snapshot is sealed and the verifier has no cipher that opens it
+    Sealed,
+}
+
+#[cfg(feature = "crypto")]
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Unsigned => "contract was deployed without a signer",
+            Self::Malformed => "stored signature is not a 64-byte secp256k1 signature",
+            Self::SignerMismatch => "recorded signer does not match the public key",
+            Self::Invalid => "signature does not match the deployed metadata",
+            Self::Sealed => "deployed metadata is sealed; verify through the registry",
+        })
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl std::error::Error for SignatureError {}
+
+#[cfg(feature = "crypto")]
+impl DeployedContract {
+    // Checks the deploy snapshot, so lifecycle changes after deployment keep verifying.
+    // Sealed snapshot values need the cipher: `ContractRegistry::verify_signature` opens them.
+    fn verify_signature(
+        &self,
+        public_key: &k256::ecdsa::VerifyingKey,
+    ) -> Result<(), SignatureError> {
+        if self
+            .snapshot_packed
+            .values()
+            .any(|packed| matches!(packed, Packed::Sealed(_)))
+        {
+            return Err(SignatureError::Sealed);
+        }
+        self.verify_snapshot(&self.unpacked_snapshot(), public_key)
+    }
+
+    // Checks `snapshot` as the complete deploy snapshot of this contract
+    fn verify_snapshot(
+        &self,
+        snapshot: &MetadataMap,
+        public_key: &k256::ecdsa::VerifyingKey,
+    ) -> Result<(), SignatureError> {
+        use k256::ecdsa::signature::Verifier as _;
+        let Some(MetadataValue::Bytes(signature)) = snapshot.get("signature") else {
+            return Err(SignatureError::Unsigned);
+        };
+        let signature =
+            k256::ecdsa::Signature::from_slice(signature).map_err(|_| SignatureError::Malformed)?;
+        let signer = snapshot.get("signer").and_then(MetadataValue::as_str);
+        if signer != Some(eth_address(public_key).as_str()) {
+            return Err(SignatureError::SignerMismatch);
+        }
+        public_key
+            .verify(&signing_payload(&self.name, snapshot), &signature)
+            .map_err(|_| SignatureError::Invalid)
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl ContractRegistry {
+    // `DeployedContract::verify_signature` on the latest version of `name`, with its sealed
+    // snapshot values opened by this registry's cipher. Only says whether the signature
+    // holds, so unlike `reveal` it needs no role. `None` if `name` is not registered.
+    fn verify_signature(
+        &self,
+        name: &str,
+        public_key: &k256::ecdsa::VerifyingKey,
+    ) -> Option<Result<(), SignatureError>> {
+        let contract = self.latest(name)?;
+        let mut snapshot = contract.unpacked_snapshot().into_owned();
+        for (key, packed) in &contract.snapshot_packed {
+            if let Packed::Sealed(ciphertext) = packed {
+                let opened = self
+                    .encryption
+                    .as_ref()
+                    .and_then(|encryption| encryption.open(ciphertext).ok());
+                let Some(value) = opened else {
+                    return Some(Err(SignatureError::Sealed));
+                };
+                snapshot.insert(key.clone(), value);
+            }
+        }
+        Some(contract.verify_snapshot(&snapshot, public_key))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+enum ArtifactError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    Invalid(String),
+}
+
+#[cfg(feature = "serde")]
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "cannot read artifact: {err}"),
+            Self::Parse(err) => write!(f, "artifact is not valid JSON: {err}"),
+            Self::Invalid(reason) => write!(f, "unsupported artifact: {reason}"),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::error::Error for ArtifactError {}
+
+#[cfg(feature = "serde")]
+impl From<std::io::Error> for ArtifactError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for ArtifactError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Parse(err)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ContractBuilder<Init> {
+    // Reads a Hardhat artifact (`contractName`, flat `bytecode`) or a Foundry `out/*.json`
+    // (`bytecode.object`, solc `metadata`); the author still has to be set afterwards
+    fn from_artifact(path: impl AsRef<std::path::Path>) -> Result<Self, ScrgError> {
+        use serde_json::Value;
+        let path = path.as_ref();
+        let json: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        let hardhat = json.get("contractName").is_some();
+        // Older Foundry versions keep solc metadata as a nested JSON string
+        let solc = match json.get("metadata") {
+            Some(Value::String(raw)) => serde_json::from_str(raw)?,
+            Some(metadata) => metadata.clone(),
+            None => Value::Null,
+        };
+        let target = solc["settings"]["compilationTarget"].as_object();
+        let name = json["contractName"]
+            .as_str()
+            .or_else(|| target.and_then(|t| t.values().next()?.as_str()))
+            .or_else(|| path.file_stem()?.to_str())
+            .ok_or_else(|| ArtifactError::Invalid("no contract name".into()))?;
+
+        let mut builder = Self::new(name).with_metadata(
+            "artifact_format",
+            if hardhat { "hardhat" } else { "foundry" },
+        );
+        if let Some(source) = json["sourceName"]
+            .as_str()
+            .or_else(|| target.and_then(|t| t.keys().next().map(String::as_str)))
+        {
+            builder = builder.with_metadata("source", source);
+        }
+        if let Some(abi) = json.get("abi") {
+            builder = builder.with_abi(&abi.to_string());
+        }
+        for (field, key) in [
+            ("bytecode", "bytecode"),
+            ("deployedBytecode", "deployed_bytecode"),
+        ] {
+            let hex = json[field]
+                .as_str()
+                .or_else(|| json[field]["object"].as_str());
+            if let Some(hex) = hex.filter(|hex| !hex.is_empty() && *hex != "0x") {
+                let bytes = decode_hex(hex)
+                    .map_err(|reason| ArtifactError::Invalid(format!("{field}: {reason}")))?;
+                builder = builder.with_metadata(key, bytes);
+            }
+        }
+
+        if let Some(version) = solc["compiler"]["version"].as_str() {
+            builder = builder.with_metadata("compiler_version", version);
+        }
+        let settings = &solc["settings"];
+        if let Some(enabled) = settings["optimizer"]["enabled"].as_bool() {
+            builder = builder.with_metadata("optimizer", enabled);
+        }
+        if let Some(runs) = settings["optimizer"]["runs"].as_i64() {
+            builder = builder.with_metadata("optimizer_runs", runs);
+        }
+        if let Some(evm) = settings["evmVersion"].as_str() {
+            builder = builder.with_metadata("evm_version", evm);
+        }
+        Ok(builder)
+    }
+}
+
+// Whole builder chain in one expression, evaluating to `Result<DeployedContract, Box<dyn Error>>`:
+//   contract! {
+//       name: "TokenX",
+//       author: "azaM",
+//       version: "1.0.0",
+//       tags: ["defi"],
+//       metadata: { "network" => "mainnet" },
+//       deploy: |meta| { meta.insert("signer".into(), "0xDEADBEEF".into()); },
+//   }
+// Every field after `author` is optional but must keep this order.
+macro_rules! contract {
+    (
+        name: $name:expr,
+        author: $author:expr
+        $(, version: $version:expr)?
+        $(, tags: [$($tag:expr),* $(,)?])?
+        $(, depends_on: [$($dependency:expr),* $(,)?])?
+        $(, metadata: { $($key:expr => $value:expr),* $(,)? })?
+        $(, deploy: $hook:expr)?
+        $(,)?
+    ) => {
+        (|| -> Result<DeployedContract, ScrgError> {
+            let builder = ContractBuilder::new($name).with_author($author);
+            $(let builder = builder.with_version($version);)?
+            $(let builder = builder.with_tags([$($tag),*]);)?
+            $($(let builder = builder.depends_on($dependency);)*)?
+            $($(let builder = builder.with_metadata($key, $value);)*)?
+            let validated = builder.validate()?;
+            let deployed = contract!(@deploy validated $(, $hook)?)?;
+            Ok(deployed.into_deployed())
+        })()
+    };
+    (@deploy $validated:ident) => {
+        $validated.deploy()
+    };
+    (@deploy $validated:ident, $hook:expr) => {
+        $validated.on_deploy($hook)
+    };
+}
+
+// String entries of a `List` value; anything else reads as empty
+fn string_list<'m>(meta: &'m MetadataMap, key: &str) -> Vec<&'m str> {
+    match meta.get(key) {
+        Some(MetadataValue::List(items)) => {
+            items.iter().filter_map(MetadataValue::as_str).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Names recorded by `ContractBuilder::depends_on`
+fn dependency_names(meta: &MetadataMap) -> Vec<&str> {
+    string_list(meta, "dependencies")
+}
+
+// `ContractUpgraded` when `to` is newer than the current latest version, else `ContractRegistered`
+fn registration_event(name: String, from: Option<Version>, to: Option<Version>) -> RegistryEvent {
+    match (from, to) {
+        (Some(from), Some(to)) if to > from => RegistryEvent::ContractUpgraded { name, from, to },
+        _ => RegistryEvent::ContractRegistered { name },
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RegistryEvent {
+    ContractRegistered {
+        name: String,
+    },
+    // A newer version than the current latest was registered
+    ContractUpgraded {
+        name: String,
+        from: Version,
+        to: Version,
+    },
+    MetadataUpdated {
+        name: String,
+        key: String,
+        old: Option<MetadataValue>,
+        new: MetadataValue,
+    },
+    ContractRemoved {
+        name: String,
+    },
+}
+
+type EventCallback = Box<dyn FnMut(&RegistryEvent)>;
+
+// Keeps `versions` sorted ascending (unversioned first); a known version is replaced
+fn insert_version(
+    versions: &mut Vec<DeployedContract>,
+    contract: DeployedContract,
+) -> Option<DeployedContract> {
+    match versions.binary_search_by(|probe| probe.version().cmp(&contract.version())) {
+        Ok(i) => Some(std::mem::replace(&mut versions[i], contract)),
+        Err(i) => {
+            versions.insert(i, contract);
+            None
+        }
+    }
+}
+
+// Newest version that has not been rolled back, unless it has expired: expired contracts
+// drop out of every read before `purge_expired` removes them
+fn active_version(versions: &[DeployedContract]) -> Option<usize> {
+    newest_version(versions).filter(|&i| !versions[i].is_expired())
+}
+
+fn newest_version(versions: &[DeployedContract]) -> Option<usize> {
+    versions
+        .iter()
+        .rposition(|contract| contract.status() != Some(LifecycleStatus::RolledBack))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RollbackError {
+    UnknownContract(String),
+    NoPreviousVersion(String),
+}
+
+impl fmt::Display for RollbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownContract(name) => write!(f, "contract `{name}` is not registered"),
+            Self::NoPreviousVersion(name) => {
+                write!(
+                    f,
+                    "contract `{name}` has no earlier version to roll back to"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RollbackError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RegistryError {
+    MissingDependency {
+        contract: String,
+        dependency: String,
+    },
+    DependencyNotDeployed {
+        contract: String,
+        dependency: String,
+        status: Option<LifecycleStatus>,
+    },
+    Rollback(RollbackError),
+    DependencyCycle(DependencyCycle),
+    MergeConflict(MergeConflict),
+    AccessDenied(AccessDenied),
+    Cipher(CipherError),
+    Codec(CodecError),
+    UnknownContract(String),
+    MetadataConflict(MetadataConflict),
+    Write(WriteError),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingDependency {
+                contract,
+                dependency,
+            } => write!(
+                f,
+                "`{contract}` depends on unregistered contract `{dependency}`"
+            ),
+            Self::DependencyNotDeployed {
+                contract,
+                dependency,
+                status,
+            } => {
+                let status = status.map_or("unknown", LifecycleStatus::as_str);
+                write!(
+                    f,
+                    "`{contract}` depends on `{dependency}`, which is {status}"
+                )
+            }
+            Self::Rollback(err) => write!(f, "{err}"),
+            Self::DependencyCycle(err) => write!(f, "{err}"),
+            Self::MergeConflict(err) => write!(f, "{err}"),
+            Self::AccessDenied(err) => write!(f, "{err}"),
+            Self::Cipher(err) => write!(f, "{err}"),
+            Self::Codec(err) => write!(f, "{err}"),
+            Self::UnknownContract(name) => write!(f, "contract `{name}` is not registered"),
+            Self::MetadataConflict(err) => write!(f, "{err}"),
+            Self::Write(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl From<WriteError> for RegistryError {
+    fn from(err: WriteError) -> Self {
+        Self::Write(err)
+    }
+}
+
+// Symmetric cipher for `ContractRegistry::with_encryption`. `encrypt` output must carry
+// whatever `decrypt` needs besides the key (nonce, tag).
+trait Cipher {
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8>;
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, CipherError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CipherError(String);
+
+impl fmt::Display for CipherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "metadata decryption failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for CipherError {}
+
+// AES-256-GCM with a random 96-bit nonce prepended to each ciphertext
+#[cfg(feature = "encryption")]
+struct AesGcmCipher(aes_gcm::Aes256Gcm);
+
+#[cfg(feature = "encryption")]
+impl AesGcmCipher {
+    fn new(key: &[u8; 32]) -> Self {
+        use aes_gcm::KeyInit;
+        Self(aes_gcm::Aes256Gcm::new(key.into()))
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl Cipher for AesGcmCipher {
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        use aes_gcm::aead::{Aead, AeadCore, OsRng};
+        let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+        let sealed = self
+            .0
+            .encrypt(&nonce, plaintext)
+            .expect("metadata values are far below the AES-GCM length limit");
+        [nonce.as_slice(), &sealed].concat()
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, CipherError> {
+        use aes_gcm::aead::Aead;
+        if ciphertext.len() < 12 {
+            return Err(CipherError("ciphertext is too short".into()));
+        }
+        let (nonce, sealed) = ciphertext.split_at(12);
+        self.0
+            .decrypt(aes_gcm::Nonce::from_slice(nonce), sealed)
+            .map_err(|_| CipherError("wrong key or tampered value".into()))
+    }
+}
+
+// Sensitive keys and the cipher sealing them. Strings, addresses and bytes round-trip;
+// other values are sealed in their `Display` form and come back as strings.
+struct Encryption {
+    cipher: Box<dyn Cipher>,
+    keys: BTreeSet<String>,
+}
+
+impl Encryption {
+    fn seal(&self, key: &str, value: &MetadataValue) -> Option<Packed> {
+        if !self.keys.contains(key) {
+            return None;
+        }
+        let (tag, bytes) = match value {
+            MetadataValue::String(s) => (b's', s.as_bytes().to_vec()),
+            MetadataValue::Address(a) => (b'a', a.as_bytes().to_vec()),
+            MetadataValue::Bytes(bytes) => (b'b', bytes.clone()),
+            other => (b's', other.to_string().into_bytes()),
+        };
+        let plaintext = [&[tag][..], &bytes].concat();
+        Some(Packed::Sealed(self.cipher.encrypt(&plaintext)))
+    }
+
+    fn open(&self, ciphertext: &[u8]) -> Result<MetadataValue, CipherError> {
+        let plaintext = self.cipher.decrypt(ciphertext)?;
+        let text = |bytes: &[u8]| {
+            String::from_utf8(bytes.to_vec())
+                .map_err(|_| CipherError("decrypted text is not UTF-8".into()))
+        };
+        match plaintext.split_first() {
+            Some((b's', rest)) => Ok(MetadataValue::String(text(rest)?)),
+            Some((b'a', rest)) => Ok(MetadataValue::Address(text(rest)?)),
+            Some((b'b', rest)) => Ok(MetadataValue::Bytes(rest.to_vec())),
+            _ => Err(CipherError("unknown value tag".into())),
+        }
+    }
+
+    // Seals the live and deploy-time values of the covered keys and blanks them in the audit
+    // log, so no plaintext is left for exports and stores
+    fn seal_contract(&self, contract: &mut DeployedContract) {
+        contract.pack(|key, value| self.seal(key, value));
+        contract.pack_snapshot(|key, value| self.seal(key, value));
+        for (key, value) in contract.audit.values_mut() {
+            if value.is_some() && self.keys.contains(key) {
+                *value = Some(MetadataValue::from(REDACTED).into());
+            }
+        }
+    }
+}
+
+// Byte codec for `ContractRegistry::with_compression`; shared with the contracts holding its
+// output, which may move across threads
+trait Codec: Send + Sync {
+    fn compress(&self, data: &[u8]) -> Vec<u8>;
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, CodecError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CodecError(String);
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "metadata decompression failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+// zstd frames at the given level (1-22; 3 is zstd's own default)
+#[cfg(feature = "compression")]
+struct ZstdCodec(i32);
+
+#[cfg(feature = "compression")]
+impl ZstdCodec {
+    fn new(level: i32) -> Self {
+        Self(level)
+    }
+}
+
+#[cfg(feature = "compression")]
+impl Codec for ZstdCodec {
+    fn compress(&self, data: &[u8]) -> Vec<u8> {
+        zstd::bulk::compress(data, self.0).expect("compressing an in-memory buffer cannot fail")
+    }
+
+    fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        zstd::stream::decode_all(data).map_err(|err| CodecError(err.to_string()))
+    }
+}
+
+// Tag byte and plaintext length ahead of the codec output
+const COMPRESSED_HEADER_LEN: usize = 9;
+
+// Values larger than `threshold` bytes and the codec packing them. Strings, addresses and
+// bytes are compressed when that saves space; other values stay as they are.
+struct Compression {
+    codec: Arc<dyn Codec>,
+    threshold: usize,
+}
+
+impl Compression {
+    fn compress(&self, value: &MetadataValue) -> Option<Packed> {
+        let (tag, bytes) = match value {
+            MetadataValue::String(s) => (b's', s.as_bytes()),
+            MetadataValue::Address(a) => (b'a', a.as_bytes()),
+            MetadataValue::Bytes(bytes) => (b'b', bytes.as_slice()),
+            _ => return None,
+        };
+        if bytes.len() <= self.threshold {
+            return None;
+        }
+        let packed = self.codec.compress(bytes);
+        if packed.len() + COMPRESSED_HEADER_LEN >= bytes.len() {
+            return None;
+        }
+        let len = (bytes.len() as u64).to_le_bytes();
+        Some(Packed::Compressed(CompressedValue::new(
+            [&[tag][..], &len, &packed].concat(),
+            Some(Arc::clone(&self.codec)),
+        )))
+    }
+
+    // Packs live, deploy-time and audited values alike. Also hands the codec to values that
+    // were loaded compressed; those have nothing cached yet, since `CompressedValue::value`
+    // needs a codec to fill the cache.
+    fn compress_contract(&self, contract: &mut DeployedContract) {
+        let attach = |compressed: &mut CompressedValue| {
+            compressed
+                .codec
+                .get_or_insert_with(|| Arc::clone(&self.codec));
+        };
+        for packed in contract
+            .packed
+            .values_mut()
+            .chain(contract.snapshot_packed.values_mut())
+        {
+            if let Packed::Compressed(compressed) = packed {
+                attach(compressed);
+            }
+        }
+        contract.pack(|_, value| self.compress(value));
+        contract.pack_snapshot(|_, value| self.compress(value));
+        for (_, audited) in contract.audit.values_mut() {
+            match audited {
+                Some(AuditValue::Compressed { compressed }) => attach(compressed),
+                Some(AuditValue::Plain(value)) => {
+                    if let Some(Packed::Compressed(compressed)) = self.compress(value) {
+                        *audited = Some(AuditValue::Compressed { compressed });
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+fn decompress(codec: &dyn Codec, packed: &[u8]) -> Result<MetadataValue, CodecError> {
+    if packed.len() < COMPRESSED_HEADER_LEN {
+        return Err(CodecError("value is too short".into()));
+    }
+    let (header, body) = packed.split_at(COMPRESSED_HEADER_LEN);
+    let bytes = codec.decompress(body)?;
+    if (bytes.len() as u64).to_le_bytes()[..] != header[1..] {
+        return Err(CodecError("length does not match the header".into()));
+    }
+    let text = |bytes: Vec<u8>| {
+        String::from_utf8(bytes).map_err(|_| CodecError("text is not UTF-8".into()))
+    };
+    match header[0] {
+        b's' => Ok(MetadataValue::String(text(bytes)?)),
+        b'a' => Ok(MetadataValue::Address(text(bytes)?)),
+        b'b' => Ok(MetadataValue::Bytes(bytes)),
+        _ => Err(CodecError("unknown value tag".into())),
+    }
+}
+
+// Compressed values across every registered version's live metadata, snapshot and audit log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct CompressionStats {
+    values: usize,
+    original_bytes: usize,
+    compressed_bytes: usize,
+}
+
+impl CompressionStats {
+    fn saved_bytes(&self) -> usize {
+        self.original_bytes.saturating_sub(self.compressed_bytes)
+    }
+
+    // Compressed size over original size; 1.0 when nothing is compressed
+    fn ratio(&self) -> f64 {
+        match self.original_bytes {
+            0 => 1.0,
+            original => self.compressed_bytes as f64 / original as f64,
+        }
+    }
+}
+
+// Applies `rewrite` to every plain value of `contract`: live metadata, deploy-time snapshot
+// and audit log
+fn rewrite_values(
+    contract: &mut DeployedContract,
+    rewrite: impl Fn(&str, MetadataValue) -> MetadataValue,
+) {
+    let rewrite_map = |map: MetadataMap| -> MetadataMap {
+        map.into_iter()
+            .map(|(key, value)| {
+                let value = rewrite(&key, value);
+                (key, value)
+            })
+            .collect()
+    };
+    contract.metadata = Arc::new(rewrite_map(Arc::unwrap_or_clone(std::mem::take(
+        &mut contract.metadata,
+    ))));
+    contract.snapshot =
+        MetadataSnapshot::capture(&rewrite_map(MetadataMap::clone(&contract.snapshot)));
+    for (key, audited) in contract.audit.values_mut() {
+        if let Some(value) = audited.as_ref().and_then(AuditValue::value) {
+            *audited = Some(rewrite(key, value.clone()).into());
+        }
+    }
+}
+
+// Caller of a guarded registry operation (`register_as`, `remove_as`, …); `name` is recorded
+// as the audit actor of the changes it makes
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Actor {
+    name: String,
+    role: Role,
+}
+
+// What a registry acts as until `acting_as` narrows it: its owner may do anything
+impl Default for Actor {
+    fn default() -> Self {
+        Self::new("registry", Role::Admin)
+    }
+}
+
+impl Actor {
+    fn new(name: &str, role: Role) -> Self {
+        Self {
+            name: name.into(),
+            role,
+        }
+    }
+
+    // `self` with its role lowered to `bound`'s when that allows less, so a handle acting as
+    // a `Viewer` cannot be used to write by naming an `Admin`
+    fn bounded_by(&self, bound: &Actor) -> Actor {
+        let role = match bound.role.rank() < self.role.rank() {
+            true => bound.role,
+            false => self.role,
+        };
+        Self::new(&self.name, role)
+    }
+
+    // Denial stamped with the current time, or `Ok` if the role allows `operation`
+    fn check(&self, operation: Operation, contract: Option<&str>) -> Result<(), AccessDenied> {
+        if self.role.allows(operation) {
+            return Ok(());
+        }
+        Err(AccessDenied {
+            actor: self.name.clone(),
+            role: self.role,
+            operation,
+            contract: contract.map(str::to_owned),
+            timestamp: now(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+enum Role {
+    Admin,
+    // Registers and maintains contracts but cannot roll back, archive, remove or merge
+    Deployer,
+    Viewer,
+}
+
+impl Role {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::Deployer => "deployer",
+            Self::Viewer => "viewer",
+        }
+    }
+
+    // Each role allows a subset of what the ones ranked above it allow
+    fn rank(self) -> u8 {
+        match self {
+            Self::Admin => 2,
+            Self::Deployer => 1,
+            Self::Viewer => 0,
+        }
+    }
+
+    fn allows(self, operation: Operation) -> bool {
+        match self {
+            Self::Admin => true,
+            Self::Deployer => matches!(
+                operation,
+                Operation::Register
+                    | Operation::UpdateMetadata
+                    | Operation::RecordDeployment
+                    | Operation::Deprecate
+                    | Operation::RevealSecret
+            ),
+            Self::Viewer => false,
+        }
+    }
+}
+
+// Registry mutations checked against an actor's `Role`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+enum Operation {
+    Register,
+    UpdateMetadata,
+    RecordDeployment,
+    Deprecate,
+    Archive,
+    Rollback,
+    Remove,
+    Merge,
+    RevealSecret,
+}
+
+impl Operation {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Register => "register",
+            Self::UpdateMetadata => "update metadata of",
+            Self::RecordDeployment => "record a deployment of",
+            Self::Deprecate => "deprecate",
+            Self::Archive => "archive",
+            Self::Rollback => "roll back",
+            Self::Remove => "remove",
+            Self::Merge => "merge into the registry",
+            Self::RevealSecret => "read encrypted metadata of",
+        }
+    }
+}
+
+// Refused operation; registries keep every one they return (see `denials()`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct AccessDenied {
+    actor: String,
+    role: Role,
+    operation: Operation,
+    // `None` for whole-registry operations such as `merge`
+    contract: Option<String>,
+    #[cfg_attr(feature = "serde", serde(with = "rfc3339"))]
+    timestamp: SystemTime,
+}
+
+impl fmt::Display for AccessDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` ({}) may not {}",
+            self.actor,
+            self.role.as_str(),
+            self.operation.as_str()
+        )?;
+        match &self.contract {
+            Some(contract) => write!(f, " `{contract}`"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for AccessDenied {}
+
+// Contracts on the cycle, with the first repeated at the end: `A -> B -> A`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DependencyCycle(Vec<String>);
+
+impl fmt::Display for DependencyCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dependency cycle: {}", self.0.join(" -> "))
+    }
+}
+
+impl std::error::Error for DependencyCycle {}
+
+#[derive(Debug, Default)]
+struct DeployReport {
+    deployed: Vec<String>,
+    failed: Vec<(String, ScrgError)>,
+    // (contract, upstream dependency that failed or was itself skipped)
+    skipped: Vec<(String, String)>,
+}
+
+impl DeployReport {
+    fn is_success(&self) -> bool {
+        self.failed.is_empty() && self.skipped.is_empty()
+    }
+
+    // First of `dependencies` that failed or was skipped earlier in the batch
+    fn failed_upstream<'a>(
+        &self,
+        dependencies: impl IntoIterator<Item = &'a str>,
+    ) -> Option<String> {
+        dependencies
+            .into_iter()
+            .find(|dependency| {
+                self.failed.iter().any(|(name, _)| name == dependency)
+                    || self.skipped.iter().any(|(name, _)| name == dependency)
+            })
+            .map(str::to_owned)
+    }
+
+    // Records jobs downstream of a failure as skipped and returns the rest
+    fn skip_downstream(&mut self, wave: Vec<DeployJob>) -> Vec<DeployJob> {
+        let mut ready = Vec::new();
+        for job in wave {
+            match self.failed_upstream(job.dependencies.iter().map(String::as_str)) {
+                Some(upstream) => self.skipped.push((job.name, upstream)),
+                None => ready.push(job),
+            }
+        }
+        ready
+    }
+}
+
+// How `ContractRegistry::merge` resolves a name present in both registries with different
+// version histories
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MergeStrategy {
+    KeepOurs,
+    KeepTheirs,
+    // Whichever latest version was deployed later; ties keep ours
+    NewestWins,
+    // Fail without merging anything
+    Error,
+}
+
+// Names sorted; contracts whose histories were identical count as `kept`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct MergeReport {
+    added: Vec<String>,
+    replaced: Vec<String>,
+    kept: Vec<String>,
+}
+
+// Every conflicting name, reported by `MergeStrategy::Error`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MergeConflict(Vec<String>);
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "conflicting contracts: {}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for MergeConflict {}
+
+fn same_history(ours: &[DeployedContract], theirs: &[DeployedContract]) -> bool {
+    ours.len() == theirs.len()
+        && ours
+            .iter()
+            .zip(theirs)
+            .all(|(a, b)| a.metadata() == b.metadata())
+}
+
+// Contract-level difference between two registries, comparing latest versions by name;
+// each list is sorted by name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct RegistryDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<(String, MetadataDiff)>,
+}
+
+impl RegistryDiff {
+    // Applies `MetadataDiff::ignoring` to every modified contract and drops those left unchanged
+    fn ignoring(mut self, keys: &[&str]) -> Self {
+        self.modified = self
+            .modified
+            .into_iter()
+            .map(|(name, diff)| (name, diff.ignoring(keys)))
+            .filter(|(_, diff)| !diff.is_empty())
+            .collect();
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+// Orders builder indices so every builder follows its in-batch dependencies; dependencies
+// outside the batch are left to `ContractRegistry::register`
+fn deploy_order(
+    builders: &[ContractBuilder<Init<AuthorSet>>],
+) -> Result<Vec<usize>, DependencyCycle> {
+    let metas: Vec<_> = builders.iter().map(|builder| builder.borrow()).collect();
+    let nodes: Vec<(&str, Vec<&str>)> = builders
+        .iter()
+        .zip(&metas)
+        .map(|(builder, meta)| (builder.name(), dependency_names(meta)))
+        .collect();
+    dependency_order(&nodes)
+}
+
+// Topological order over `(name, dependencies)` pairs, dependencies first
+fn dependency_order(nodes: &[(&str, Vec<&str>)]) -> Result<Vec<usize>, DependencyCycle> {
+    let index: HashMap<&str, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| (*name, i))
+        .collect();
+    let edges: Vec<Vec<usize>> = nodes
+        .iter()
+        .map(|(_, dependencies)| {
+            dependencies
+                .iter()
+                .filter_map(|name| index.get(name).copied())
+                .collect()
+        })
+        .collect();
+
+    fn visit(
+        i: usize,
+        edges: &[Vec<usize>],
+        done: &mut [bool],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), Vec<usize>> {
+        if done[i] {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|&p| p == i) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(i);
+            return Err(cycle);
+        }
+        path.push(i);
+        for &dependency in &edges[i] {
+            visit(dependency, edges, done, path, order)?;
+        }
+        path.pop();
+        done[i] = true;
+        order.push(i);
+        Ok(())
+    }
+
+    let mut done = vec![false; nodes.len()];
+    let mut order = Vec::with_capacity(nodes.len());
+    for i in 0..nodes.len() {
+        visit(i, &edges, &mut done, &mut Vec::new(), &mut order).map_err(|cycle| {
+            DependencyCycle(cycle.into_iter().map(|i| nodes[i].0.to_owned()).collect())
+        })?;
+    }
+    Ok(order)
+}
+
+// Undoes whatever a contract's deployment did outside the registry (e.g. on-chain), run
+// when its `DeployGroup` rolls back
+type CompensationHook = Box<dyn FnMut(&DeployedContract) -> Result<(), HookError>>;
+
+// Contracts that must all reach `Deployed` or none stay registered; see
+// `ContractRegistry::deploy_group`
+#[derive(Default)]
+struct DeployGroup {
+    builders: Vec<ContractBuilder<Init<AuthorSet>>>,
+    compensations: Vec<CompensationHook>,
+}
+
+impl DeployGroup {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(mut self, builder: ContractBuilder<Init<AuthorSet>>) -> Self {
+        self.builders.push(builder);
+        self
+    }
+
+    // Called once per contract the group had already deployed, newest first
+    fn on_rollback<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&DeployedContract) -> Result<(), HookError> + 'static,
+    {
+        self.compensations.push(Box::new(hook));
+        self
+    }
+}
+
+#[derive(Debug)]
+enum GroupDeployError {
+    DependencyCycle(DependencyCycle),
+    // `contract` failed; `rolled_back` were deployed before it and have been undone
+    Aborted {
+        contract: String,
+        error: Box<ScrgError>,
+        rolled_back: Vec<String>,
+        compensation_failures: Vec<(String, HookError)>,
+    },
+}
+
+impl fmt::Display for GroupDeployError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DependencyCycle(err) => write!(f, "{err}"),
+            Self::Aborted {
+                contract,
+                error,
+                rolled_back,
+                compensation_failures,
+            } => {
+                write!(
+                    f,
+                    "deploy group aborted at `{contract}`: {error}; rolled back {} contract(s)",
+                    rolled_back.len()
+                )?;
+                for (name, err) in compensation_failures {
+                    write!(f, "; compensating `{name}` failed: {err}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for GroupDeployError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::DependencyCycle(err) => Some(err),
+            Self::Aborted { error, .. } => Some(error.as_ref()),
+        }
+    }
+}
+
+// Many related contracts built together: shared metadata (author, license, network, ...)
+// is filled in wherever a contract did not set the key itself, and `build()` deploys the
+// lot in dependency order into one registry
+struct ProjectBuilder {
+    author: String,
+    shared: MetadataMap,
+    contracts: Vec<ContractBuilder<Init<AuthorSet>>>,
+    // (contract, key, referenced contract)
+    references: Vec<(String, String, String)>,
+}
+
+impl ProjectBuilder {
+    fn new(author: &str) -> Self {
+        Self {
+            author: author.into(),
+            shared: MetadataMap::new(),
+            contracts: Vec::new(),
+            references: Vec::new(),
+        }
+    }
+
+    fn with_metadata(mut self, key: &str, value: impl Into<MetadataValue>) -> Self {
+        self.shared.insert(key.into(), value.into());
+        self
+    }
+
+    fn with_license(self, license: &str) -> Self {
+        self.with_metadata("license", license)
+    }
+
+    fn with_network(self, network: Network) -> Self {
+        self.with_metadata("network", network)
+    }
+
+    // `configure` gets a builder with the project author already set
+    fn contract<F>(mut self, name: &str, configure: F) -> Self
+    where
+        F: FnOnce(ContractBuilder<Init<AuthorSet>>) -> ContractBuilder<Init<AuthorSet>>,
+    {
+        self.contracts.push(configure(
+            ContractBuilder::new(name).with_author(&self.author),
+        ));
+        self
+    }
+
+    // Sets `key` on `contract` to `target`'s deployed `address`; `contract` then depends
+    // on `target`, and deploying it fails if `target` ends up without an address
+    fn reference(mut self, contract: &str, key: &str, target: &str) -> Self {
+        self.references
+            .push((contract.into(), key.into(), target.into()));
+        self
+    }
+
+    // Fails before deploying anything if a reference names a contract outside the project
+    // or the dependencies form a cycle; per-contract failures are in the report
+    fn build(self) -> Result<(ContractRegistry, DeployReport), ScrgError> {
+        let names: BTreeSet<&str> = self.contracts.iter().map(|c| c.name()).collect();
+        if let Some((contract, _, target)) = self.references.iter().find(|(contract, _, target)| {
+            !names.contains(contract.as_str()) || !names.contains(target.as_str())
+        }) {
+            return Err(RegistryError::MissingDependency {
+                contract: contract.clone(),
+                dependency: target.clone(),
+            }
+            .into());
+        }
+
+        let addresses: Rc<RefCell<MetadataMap>> = Rc::default();
+        let builders = self
+            .contracts
+            .into_iter()
+            .map(|mut builder| {
+                builder.edit("project", |meta| {
+                    let merge = MetadataMergeStrategy::PreferExisting;
+                    let _ = meta.merge_from(self.shared.clone(), merge);
+                });
+                let name = builder.name().to_owned();
+                if self.references.iter().any(|(_, _, target)| *target == name) {
+                    let addresses = Rc::clone(&addresses);
+                    builder = builder.on_phase(Phase::PostDeploy, move |name, meta| {
+                        if let Some(address) = meta.get("address") {
+                            addresses.borrow_mut().insert(name.into(), address.clone());
+                        }
+                        Ok(())
+                    });
+                }
+                for (_, key, target) in self.references.iter().filter(|(c, ..)| *c == name) {
+                    builder = builder
+                        .depends_on(target)
+                        .on_phase(Phase::PreDeploy, resolve_reference(&addresses, key, target));
+                }
+                builder
+            })
+            .collect();
+
+        let mut registry = ContractRegistry::new();
+        let report = registry.deploy_all(builders)?;
+        Ok((registry, report))
+    }
+}
+
+// `ProjectBuilder` pre-deploy hook: copies the address `target` recorded after its own
+// deploy into `key`
+fn resolve_reference(
+    addresses: &Rc<RefCell<MetadataMap>>,
+    key: &str,
+    target: &str,
+) -> impl FnMut(&str, &mut MetadataMap) -> Result<(), HookError> + 'static {
+    let (addresses, key, target) = (Rc::clone(addresses), key.to_owned(), target.to_owned());
+    move |_, meta| {
+        let address = addresses.borrow().get(&target).cloned();
+        let address =
+            address.ok_or_else(|| HookError::new(format!("`{target}` has no deployed address")))?;
+        meta.insert(key.clone(), address);
+        Ok(())
+    }
+}
+
+type JobResult = Result<DeployedContract, ScrgError>;
+
+// Unit of work for `ContractRegistry::deploy_parallel`. Builders hold `Rc`s and can't cross
+// threads, so the job builds its contract on the worker that deploys it; only the declared
+// dependencies are known up front.
+struct DeployJob {
+    name: String,
+    dependencies: Vec<String>,
+    build: Box<dyn FnOnce() -> JobResult + Send>,
+}
+
+impl DeployJob {
+    fn new<F, E>(name: &str, build: F) -> Self
+    where
+        F: FnOnce() -> Result<ContractBuilder<Validated>, E> + Send + 'static,
+        E: Into<ScrgError>,
+    {
+        Self {
+            name: name.into(),
+            dependencies: Vec::new(),
+            build: Box::new(move || {
+                let deployed = build().map_err(Into::into)?.deploy()?;
+                Ok(deployed.into_deployed())
+            }),
+        }
+    }
+
+    // Should mirror the builder's own `depends_on` calls; this is what orders the waves
+    fn depends_on(mut self, dependency: &str) -> Self {
+        self.dependencies.push(dependency.into());
+        self
+    }
+}
+
+// Splits `jobs` into waves: each wave depends only on jobs in earlier waves
+fn deploy_waves(jobs: Vec<DeployJob>) -> Result<Vec<Vec<DeployJob>>, DependencyCycle> {
+    let order = {
+        let nodes: Vec<(&str, Vec<&str>)> = jobs
+            .iter()
+            .map(|job| {
+                let dependencies = job.dependencies.iter().map(String::as_str).collect();
+                (job.name.as_str(), dependencies)
+            })
+            .collect();
+        dependency_order(&nodes)?
+    };
+    let index: HashMap<String, usize> = jobs
+        .iter()
+        .enumerate()
+        .map(|(i, job)| (job.name.clone(), i))
+        .collect();
+    let mut level = vec![0; jobs.len()];
+    for &i in &order {
+        level[i] = jobs[i]
+            .dependencies
+            .iter()
+            .filter_map(|dependency| index.get(dependency))
+            .map(|&dependency| level[dependency] + 1)
+            .max()
+            .unwrap_or(0);
+    }
+
+    let mut waves: Vec<Vec<DeployJob>> = Vec::new();
+    for (job, level) in jobs.into_iter().zip(level) {
+        if waves.len() <= level {
+            waves.resize_with(level + 1, Vec::new);
+        }
+        waves[level].push(job);
+    }
+    Ok(waves)
+}
+
+// Runs a wave on at most `limit` scoped threads pulling from a shared queue
+#[cfg(all(feature = "std", not(feature = "rayon")))]
+fn run_wave(jobs: Vec<DeployJob>, limit: usize) -> Vec<(String, JobResult)> {
+    let workers = limit.clamp(1, jobs.len().max(1));
+    let queue = Mutex::new(jobs.into_iter());
+    let results = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let next = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+                let Some(job) = next else {
+                    break;
+                };
+                let result = (job.build)();
+                results
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push((job.name, result));
+            });
+        }
+    });
+    results.into_inner().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Without threads the jobs of a wave run one after another
+#[cfg(not(feature = "std"))]
+fn run_wave(jobs: Vec<DeployJob>, _limit: usize) -> Vec<(String, JobResult)> {
+    jobs.into_iter()
+        .map(|job| (job.name, (job.build)()))
+        .collect()
+}
+
+// Runs a wave on a dedicated rayon pool sized to `limit`
+#[cfg(feature = "rayon")]
+fn run_wave(jobs: Vec<DeployJob>, limit: usize) -> Vec<(String, JobResult)> {
+    use rayon::prelude::*;
+
+    let run = move || {
+        jobs.into_par_iter()
+            .map(|job| (job.name, (job.build)()))
+            .collect()
+    };
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(limit.max(1))
+        .build()
+    {
+        Ok(pool) => pool.install(run),
+        Err(_) => run(),
+    }
+}
+
+// How `ContractRegistry` stores its contracts; `Sorted` iterates alphabetically and serves
+// `range()` without a scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum RegistryOrder {
+    #[default]
+    Hashed,
+    Sorted,
+}
+
+// Name -> every registered version. Both forms serialize as a plain map, so stores stay
+// readable either way; a loaded map is `Hashed` until `with_order` says otherwise.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+enum ContractMap {
+    Hashed(HashMap<String, Vec<DeployedContract>>),
+    Sorted(BTreeMap<String, Vec<DeployedContract>>),
+}
+
+impl Default for ContractMap {
+    fn default() -> Self {
+        Self::new(RegistryOrder::default())
+    }
+}
+
+impl ContractMap {
+    fn new(order: RegistryOrder) -> Self {
+        match order {
+            RegistryOrder::Hashed => Self::Hashed(HashMap::new()),
+            RegistryOrder::Sorted => Self::Sorted(BTreeMap::new()),
+        }
+    }
+
+    fn order(&self) -> RegistryOrder {
+        match self {
+            Self::Hashed(_) => RegistryOrder::Hashed,
+            Self::Sorted(_) => RegistryOrder::Sorted,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Vec<DeployedContract>> {
+        match self {
+            Self::Hashed(map) => map.get(name),
+            Self::Sorted(map) => map.get(name),
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut Vec<DeployedContract>> {
+        match self {
+            Self::Hashed(map) => map.get_mut(name),
+            Self::Sorted(map) => map.get_mut(name),
+        }
+    }
+
+    // Versions of `name`, inserting an empty history first if it is new
+    fn versions_entry(&mut self, name: String) -> &mut Vec<DeployedContract> {
+        match self {
+            Self::Hashed(map) => map.entry(name).or_default(),
+            Self::Sorted(map) => map.entry(name).or_default(),
+        }
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Vec<DeployedContract>> {
+        match self {
+            Self::Hashed(map) => map.remove(name),
+            Self::Sorted(map) => map.remove(name),
+        }
+    }
+
+    fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Hashed(map) => map.len(),
+            Self::Sorted(map) => map.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&String, &Vec<DeployedContract>)> + '_> {
+        match self {
+            Self::Hashed(map) => Box::new(map.iter()),
+            Self::Sorted(map) => Box::new(map.iter()),
+        }
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &String> {
+        self.iter().map(|(name, _)| name)
+    }
+
+    fn values(&self) -> impl Iterator<Item = &Vec<DeployedContract>> {
+        self.iter().map(|(_, versions)| versions)
+    }
+
+    fn values_mut(&mut self) -> Box<dyn Iterator<Item = &mut Vec<DeployedContract>> + '_> {
+        match self {
+            Self::Hashed(map) => Box::new(map.values_mut()),
+            Self::Sorted(map) => Box::new(map.values_mut()),
+        }
+    }
+
+    // Entries with names inside `bounds`, in name order; `Hashed` scans and sorts
+    fn range<'a>(
+        &'a self,
+        bounds: (Bound<&'a str>, Bound<&'a str>),
+    ) -> Vec<(&'a String, &'a Vec<DeployedContract>)> {
+        match self {
+            Self::Hashed(map) => {
+                let mut entries: Vec<_> = map
+                    .iter()
+                    .filter(|(name, _)| RangeBounds::<str>::contains(&bounds, name.as_str()))
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                entries
+            }
+            Self::Sorted(map) => map.range::<str, _>(bounds).collect(),
+        }
+    }
+}
+
+impl Index<&str> for ContractMap {
+    type Output = Vec<DeployedContract>;
+
+    fn index(&self, name: &str) -> &Self::Output {
+        self.get(name).expect("no contract with this name")
+    }
+}
+
+impl IntoIterator for ContractMap {
+    type Item = (String, Vec<DeployedContract>);
+    type IntoIter = Box<dyn Iterator<Item = Self::Item>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        match self {
+            Self::Hashed(map) => Box::new(map.into_iter()),
+            Self::Sorted(map) => Box::new(map.into_iter()),
+        }
+    }
+}
+
+// Which contract `ContractRegistry::with_capacity_limit` drops first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EvictionOrder {
+    // Least recently registered or read through `latest`/`get`
+    LeastRecentlyUsed,
+    // Earliest `deployed_at` of the latest version
+    OldestDeployFirst,
+}
+
+// Name and every version of an evicted contract, e.g. for appending to a cold `RegistryStore`
+type EvictHook = Box<dyn FnMut(&str, &[DeployedContract])>;
+
+struct CapacityLimit {
+    capacity: usize,
+    order: EvictionOrder,
+    // name -> tick of last use; only maintained for `LeastRecentlyUsed`
+    last_used: RefCell<HashMap<String, u64>>,
+    clock: std::cell::Cell<u64>,
+}
+
+impl CapacityLimit {
+    fn touch(&self, name: &str) {
+        if self.order != EvictionOrder::LeastRecentlyUsed {
+            return;
+        }
+        let tick = self.clock.get() + 1;
+        self.clock.set(tick);
+        self.last_used.borrow_mut().insert(name.into(), tick);
+    }
+}
+
+// Registry of deployed contracts, keyed by name, holding every registered version
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct ContractRegistry {
+    contracts: ContractMap,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    callbacks: Vec<EventCallback>,
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    channels: Vec<mpsc::Sender<RegistryEvent>>,
+    // tag -> names of listed contracts carrying it; rebuilt on load
+    #[cfg_attr(feature = "serde", serde(skip))]
+    tag_index: HashMap<String, BTreeSet<String>>,
+    // ENS name -> listed contract carrying it; rebuilt on load
+    #[cfg_attr(feature = "serde", serde(skip))]
+    ens_index: HashMap<String, String>,
+    // metadata key -> rendered value -> contract names, for keys passed to `create_index`;
+    // indexes are declared per process and never persisted
+    #[cfg_attr(feature = "serde", serde(skip))]
+    indexes: HashMap<String, HashMap<String, BTreeSet<String>>>,
+    // content id -> contract name, covering every registered version
+    #[cfg_attr(feature = "serde", serde(skip))]
+    ids: HashMap<String, String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    denials: Vec<AccessDenied>,
+    // Never persisted: a loaded registry keeps its ciphertext until `with_encryption`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    encryption: Option<Encryption>,
+    // Never persisted either; compressed values load packed and unreadable until
+    // `with_compression`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    compression: Option<Compression>,
+    // Declared per process like the indexes
+    #[cfg_attr(feature = "serde", serde(skip))]
+    secrets: SecretKeys,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    limit: Option<CapacityLimit>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    evict_hooks: Vec<EvictHook>,
+    // Counted per process run, like the indexes
+    #[cfg_attr(feature = "serde", serde(skip))]
+    metrics: Arc<Metrics>,
+    // Every write is checked against it; see `acting_as`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    actor: Actor,
+}
+
+impl ContractRegistry {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    // Moves the current contents into `order`'s storage, e.g. `RegistryOrder::Sorted` for
+    // alphabetical iteration, stable exports and `range()`
+    fn with_order(mut self, order: RegistryOrder) -> Self {
+        if self.contracts.order() != order {
+            let mut contracts = ContractMap::new(order);
+            for (name, versions) in std::mem::take(&mut self.contracts) {
+                *contracts.versions_entry(name) = versions;
+            }
+            self.contracts = contracts;
+        }
+        self
+    }
+
+    // Checks dependencies, then returns the previously registered contract with the
+    // same name and version, if any
+    fn register(
+        &mut self,
+        contract: DeployedContract,
+    ) -> Result<Option<DeployedContract>, ScrgError> {
+        let actor = self.actor.clone();
+        self.register_as(&actor, contract)
+    }
+
+    // Checks every later write against `actor`'s role, e.g. before handing the registry to
+    // read-only code. It can only narrow: an `Admin` actor on a registry acting as a `Viewer`
+    // stays a `Viewer`, here and in the `_as` methods.
+    fn acting_as(mut self, actor: Actor) -> Self {
+        self.actor = actor.bounded_by(&self.actor);
+        self
+    }
+
+    // Registrations here, plus validation, deployment and hook counters of builders created
+    // with `builder`
+    fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    // A builder whose validation, deployment and hooks count towards `metrics()`
+    fn builder(&self, name: &str) -> ContractBuilder<Init> {
+        ContractBuilder::new(name).with_metrics(Arc::clone(&self.metrics))
+    }
+
+    fn check_dependencies(&self, contract: &DeployedContract) -> Result<(), RegistryError> {
+        for dependency in contract.dependencies() {
+            let Some(target) = self.latest(dependency) else {
+                return Err(RegistryError::MissingDependency {
+                    contract: contract.name.clone(),
+                    dependency: dependency.into(),
+                });
+            };
+            if !target.status().is_some_and(LifecycleStatus::is_live) {
+                return Err(RegistryError::DependencyNotDeployed {
+                    contract: contract.name.clone(),
+                    dependency: dependency.into(),
+                    status: target.status(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Registers without dependency checks, for loaders whose entries arrive in any order
+    fn restore(&mut self, mut contract: DeployedContract) -> Option<DeployedContract> {
+        if let Some(encryption) = &self.encryption {
+            encryption.seal_contract(&mut contract);
+        }
+        if let Some(compression) = &self.compression {
+            compression.compress_contract(&mut contract);
+        }
+        let name = contract.name.clone();
+        if let Some(id) = contract.id() {
+            self.ids.insert(id.into(), name.clone());
+        }
+        let current = self
+            .latest(&name)
+            .and_then(DeployedContract::version)
+            .cloned();
+        let version = contract.version().cloned();
+        let previous = insert_version(self.contracts.versions_entry(name.clone()), contract);
+        self.reindex(&name);
+        if let Some(limit) = &self.limit {
+            limit.touch(&name);
+        }
+        self.emit(registration_event(name.clone(), current, version));
+        self.enforce_capacity(&name);
+        previous
+    }
+
+    // Redacts `keys` on top of the defaults in `redacted()`, `export_csv` and
+    // `search_values`; content ids and signatures are unaffected
+    fn with_secret_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.secrets = keys.into_iter().fold(self.secrets, SecretKeys::with_key);
+        self
+    }
+
+    // Caps the number of contract names; beyond `capacity`, whole contracts are evicted in
+    // `order` and handed to the `on_evict` hooks. Applies to the current contents at once.
+    fn with_capacity_limit(mut self, capacity: usize, order: EvictionOrder) -> Self {
+        let limit = CapacityLimit {
+            capacity,
+            order,
+            last_used: RefCell::default(),
+            clock: std::cell::Cell::default(),
+        };
+        let mut names: Vec<(SystemTime, String)> = self
+            .iter()
+            .map(|contract| (contract.deployed_at(), contract.name.clone()))
+            .collect();
+        names.sort();
+        for (_, name) in &names {
+            limit.touch(name);
+        }
+        self.limit = Some(limit);
+        self.enforce_capacity("");
+        self
+    }
+
+    // Called with each evicted contract, after it has left the registry
+    fn on_evict<F>(&mut self, hook: F)
+    where
+        F: FnMut(&str, &[DeployedContract]) + 'static,
+    {
+        self.evict_hooks.push(Box::new(hook));
+    }
+
+    // `keep` (the contract just registered) is never the one evicted
+    fn enforce_capacity(&mut self, keep: &str) {
+        let Some(limit) = &self.limit else {
+            return;
+        };
+        let capacity = limit.capacity;
+        while self.contracts.len() > capacity {
+            let Some(victim) = self.eviction_candidate(keep) else {
+                return;
+            };
+            let evicted = self.remove_unchecked(&victim);
+            for hook in &mut self.evict_hooks {
+                hook(&victim, &evicted);
+            }
+        }
+    }
+
+    fn eviction_candidate(&self, keep: &str) -> Option<String> {
+        let limit = self.limit.as_ref()?;
+        let candidates = self.contracts.iter().filter(|(name, _)| *name != keep);
+        match limit.order {
+            EvictionOrder::LeastRecentlyUsed => {
+                let last_used = limit.last_used.borrow();
+                candidates
+                    .min_by_key(|(name, _)| last_used.get(*name).copied().unwrap_or(0))
+                    .map(|(name, _)| name.clone())
+            }
+            EvictionOrder::OldestDeployFirst => candidates
+                .min_by_key(|(_, versions)| {
+                    newest_version(versions).map(|i| versions[i].deployed_at())
+                })
+                .map(|(name, _)| name.clone()),
+        }
+    }
+
+    // Brings the tag and secondary indexes in line with the current listing entry for `name`
+    fn reindex(&mut self, name: &str) {
+        let unindex = |names: &mut BTreeSet<String>| {
+            names.remove(name);
+            !names.is_empty()
+        };
+        self.tag_index.retain(|_, names| unindex(names));
+        self.ens_index.retain(|_, owner| owner != name);
+        for index in self.indexes.values_mut() {
+            index.retain(|_, names| unindex(names));
+        }
+
+        let Some(contract) = self
+            .latest(name)
+            .filter(|contract| contract.status() != Some(LifecycleStatus::Archived))
+        else {
+            return;
+        };
+        let tags: Vec<String> = contract.tags().into_iter().map(str::to_owned).collect();
+        let ens = contract.ens().and_then(normalize_ens);
+        let values: Vec<(String, String)> = self
+            .indexes
+            .keys()
+            .filter_map(|key| Some((key.clone(), contract.get(key)?.to_string())))
+            .collect();
+
+        for tag in tags {
+            self.tag_index.entry(tag).or_default().insert(name.into());
+        }
+        if let Some(ens) = ens {
+            self.ens_index.insert(ens, name.into());
+        }
+        for (key, value) in values {
+            if let Some(index) = self.indexes.get_mut(&key) {
+                index.entry(value).or_default().insert(name.into());
+            }
+        }
+    }
+
+    fn reindex_all(&mut self) {
+        self.ids = self
+            .all_versions()
+            .filter_map(|contract| Some((contract.id()?.to_owned(), contract.name.clone())))
+            .collect();
+        let names: Vec<String> = self.contracts.keys().cloned().collect();
+        for name in names {
+            self.reindex(&name);
+        }
+    }
+
+    fn on_chain(&self, chain_id: u64) -> impl Iterator<Item = &DeployedContract> {
+        self.list()
+            .filter(move |contract| contract.is_on_chain(chain_id))
+    }
+
+    // Listed contracts tagged `tag`, in name order
+    fn by_tag(&self, tag: &str) -> impl Iterator<Item = &DeployedContract> {
+        self.tag_index
+            .get(tag)
+            .into_iter()
+            .flatten()
+            .filter_map(|name| self.latest(name))
+    }
+
+    // Listed contract registered under ENS `name`, compared case-insensitively. When two
+    // contracts claim one name, the last one indexed wins.
+    fn by_ens(&self, name: &str) -> Option<&DeployedContract> {
+        self.latest(self.ens_index.get(&normalize_ens(name)?)?)
+    }
+
+    // Maintained from then on by register, update and remove; re-creating is a no-op
+    fn create_index(&mut self, key: &str) {
+        if self.indexes.contains_key(key) {
+            return;
+        }
+        self.indexes.insert(key.into(), HashMap::new());
+        self.reindex_all();
+    }
+
+    fn drop_index(&mut self, key: &str) -> bool {
+        self.indexes.remove(key).is_some()
+    }
+
+    // Listed contracts whose `key` equals `value`; falls back to a scan if `key` is not indexed
+    fn find_by(&self, key: &str, value: impl Into<MetadataValue>) -> Vec<&DeployedContract> {
+        let value = value.into();
+        let Some(index) = self.indexes.get(key) else {
+            return self
+                .list()
+                .filter(|contract| contract.get(key) == Some(&value))
+                .collect();
+        };
+        // Rendered values can collide across types (an address and an equal string)
+        index
+            .get(&value.to_string())
+            .into_iter()
+            .flatten()
+            .filter_map(|name| self.latest(name))
+            .filter(|contract| contract.get(key) == Some(&value))
+            .collect()
+    }
+
+    // Deploys `builders` in dependency order; nothing is deployed if the batch has a cycle.
+    // Contracts downstream of a failure are skipped rather than attempted.
+    fn deploy_all(
+        &mut self,
+        builders: Vec<ContractBuilder<Init<AuthorSet>>>,
+    ) -> Result<DeployReport, ScrgError> {
+        let order = deploy_order(&builders)?;
+        let names: Vec<String> = builders.iter().map(|b| b.name().to_owned()).collect();
+        let mut pending: Vec<Option<ContractBuilder<Init<AuthorSet>>>> =
+            builders.into_iter().map(Some).collect();
+        let mut report = DeployReport::default();
+
+        for i in order {
+            let Some(builder) = pending[i].take() else {
+                continue;
+            };
+            let upstream = report.failed_upstream(dependency_names(&builder.borrow()));
+            if let Some(upstream) = upstream {
+                report.skipped.push((names[i].clone(), upstream));
+                continue;
+            }
+
+            let result = builder
+                .validate()
+                .and_then(|validated| self.deploy_and_register(validated));
+            match result {
+                Ok(_) => report.deployed.push(names[i].clone()),
+                Err(err) => report.failed.push((names[i].clone(), err)),
+            }
+        }
+        Ok(report)
+    }
+
+    // All-or-nothing `deploy_all`: deploys in dependency order and stops at the first failure.
+    // Every contract the group had registered is then removed again, with any versions it
+    // replaced put back, after the group's compensation hooks have run for it.
+    fn deploy_group(&mut self, group: DeployGroup) -> Result<Vec<String>, GroupDeployError> {
+        let DeployGroup {
+            builders,
+            mut compensations,
+        } = group;
+        let order = deploy_order(&builders).map_err(GroupDeployError::DependencyCycle)?;
+        let mut pending: Vec<_> = builders.into_iter().map(Some).collect();
+        // (name, versions registered before the group touched it)
+        let mut deployed: Vec<(String, Vec<DeployedContract>)> = Vec::new();
+
+        for i in order {
+            let Some(builder) = pending[i].take() else {
+                continue;
+            };
+            let name = builder.name().to_owned();
+            let before = self.versions(&name).to_vec();
+            let result = builder
+                .validate()
+                .and_then(|validated| self.deploy_and_register(validated));
+            if let Err(error) = result {
+                let mut rolled_back = Vec::new();
+                let mut compensation_failures = Vec::new();
+                for (name, before) in deployed.into_iter().rev() {
+                    if let Some(contract) = self.latest(&name).cloned() {
+                        for hook in &mut compensations {
+                            if let Err(err) = hook(&contract) {
+                                compensation_failures
+                                    .push((name.clone(), err.in_stage("compensation")));
+                            }
+                        }
+                    }
+                    self.remove_unchecked(&name);
+                    for version in before {
+                        self.restore(version);
+                    }
+                    rolled_back.push(name);
+                }
+                return Err(GroupDeployError::Aborted {
+                    contract: name,
+                    error: Box::new(error),
+                    rolled_back,
+                    compensation_failures,
+                });
+            }
+            deployed.push((name, before));
+        }
+        Ok(deployed.into_iter().map(|(name, _)| name).collect())
+    }
+
+    // Deploys and registers each builder in the given order, carrying on past failures;
+    // order matters when one builder depends on another in the same batch
+    fn register_all(
+        &mut self,
+        builders: impl IntoIterator<Item = ContractBuilder<Validated>>,
+    ) -> DeployReport {
+        let mut report = DeployReport::default();
+        for builder in builders {
+            let name = builder.name().to_owned();
+            match self.deploy_and_register(builder) {
+                Ok(()) => report.deployed.push(name),
+                Err(err) => report.failed.push((name, err)),
+            }
+        }
+        report
+    }
+
+    // Deploys independent jobs concurrently, at most `max_concurrency` at a time, one
+    // dependency wave after another; registration stays on the calling thread. As with
+    // `deploy_all`, jobs downstream of a failure are skipped.
+    fn deploy_parallel(
+        &mut self,
+        jobs: Vec<DeployJob>,
+        max_concurrency: usize,
+    ) -> Result<DeployReport, ScrgError> {
+        let mut report = DeployReport::default();
+        for wave in deploy_waves(jobs)? {
+            let ready = report.skip_downstream(wave);
+            let results = run_wave(ready, max_concurrency);
+            self.register_results(results, &mut report);
+        }
+        Ok(report)
+    }
+
+    // Same as `deploy_parallel`, with each job on tokio's blocking pool
+    #[cfg(feature = "tokio")]
+    async fn deploy_parallel_async(
+        &mut self,
+        jobs: Vec<DeployJob>,
+        max_concurrency: usize,
+    ) -> Result<DeployReport, ScrgError> {
+        let permits = Arc::new(tokio::sync::Semaphore::new(max_concurrency.max(1)));
+        let mut report = DeployReport::default();
+        for wave in deploy_waves(jobs)? {
+            let mut tasks = tokio::task::JoinSet::new();
+            for job in report.skip_downstream(wave) {
+                let permit = Arc::clone(&permits)
+                    .acquire_owned()
+                    .await
+                    .expect("the semaphore is never closed");
+                tasks.spawn_blocking(move || {
+                    let _permit = permit;
+                    (job.name, (job.build)())
+                });
+            }
+            let mut results = Vec::new();
+            while let Some(joined) = tasks.join_next().await {
+                match joined {
+                    Ok(result) => results.push(result),
+                    Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+                    Err(err) => panic!("deploy task cancelled: {err}"),
+                }
+            }
+            self.register_results(results, &mut report);
+        }
+        Ok(report)
+    }
+
+    fn register_results(&mut self, results: Vec<(String, JobResult)>, report: &mut DeployReport) {
+        for (name, result) in results {
+            let registered = result.and_then(|contract| self.register(contract));
+            match registered {
+                Ok(_) => report.deployed.push(name),
+                Err(err) => report.failed.push((name, err)),
+            }
+        }
+    }
+
+    fn deploy_and_register(
+        &mut self,
+        builder: ContractBuilder<Validated>,
+    ) -> Result<(), ScrgError> {
+        let deployed = builder.deploy()?;
+        self.register(deployed.into_deployed())?;
+        Ok(())
+    }
+
+    // Latest version of `name`
+    fn get(&self, name: &str) -> Option<&DeployedContract> {
+        self.latest(name)
+    }
+
+    // Any registered version with content id `id`, rolled back or not
+    fn by_id(&self, id: &str) -> Option<&DeployedContract> {
+        self.contracts
+            .get(self.ids.get(id)?)?
+            .iter()
+            .find(|contract| contract.id() == Some(id))
+    }
+
+    // Rolled-back versions stay in the history but are never "latest"
+    fn latest(&self, name: &str) -> Option<&DeployedContract> {
+        let versions = self.contracts.get(name)?;
+        let contract = versions.get(active_version(versions)?)?;
+        if let Some(limit) = &self.limit {
+            limit.touch(name);
+        }
+        Some(contract)
+    }
+
+    fn latest_mut(&mut self, name: &str) -> Option<&mut DeployedContract> {
+        let versions = self.contracts.get_mut(name)?;
+        let index = active_version(versions)?;
+        versions.get_mut(index)
+    }
+
+    // Marks the latest version `rolled_back` so the one before it becomes latest again
+    fn rollback(&mut self, name: &str) -> Result<&DeployedContract, ScrgError> {
+        let actor = self.actor.clone();
+        self.rollback_as(&actor, name)
+    }
+
+    fn rollback_unchecked(&mut self, name: &str) -> Result<&DeployedContract, ScrgError> {
+        let versions = self
+            .contracts
+            .get_mut(name)
+            .ok_or_else(|| RollbackError::UnknownContract(name.into()))?;
+        let failed =
+            active_version(versions).ok_or_else(|| RollbackError::UnknownContract(name.into()))?;
+        let restored = active_version(&versions[..failed])
+            .ok_or_else(|| RollbackError::NoPreviousVersion(name.into()))?;
+
+        let restored_version = versions[restored].version().cloned();
+        let old = versions[failed].metadata.get("status").cloned();
+        let rolled_back_to =
+            restored_version.map(|version| ("rolled_back_to", Some(version.into())));
+        versions[failed].edit(
+            "rollback",
+            [
+                ("status", Some(LifecycleStatus::RolledBack.as_str().into())),
+                ("rolled_back_at", Some(now().into())),
+            ]
+            .into_iter()
+            .chain(rolled_back_to),
+        );
+
+        self.emit(RegistryEvent::MetadataUpdated {
+            name: name.into(),
+            key: "status".into(),
+            old,
+            new: LifecycleStatus::RolledBack.as_str().into(),
+        });
+        self.reindex(name);
+        Ok(&self.contracts[name][restored])
+    }
+
+    fn get_version(&self, name: &str, version: &str) -> Option<&DeployedContract> {
+        let version: Version = version.parse().ok()?;
+        self.contracts
+            .get(name)?
+            .iter()
+            .find(|contract| contract.version() == Some(&version))
+    }
+
+    // Every registered version of `name`, oldest first
+    fn versions(&self, name: &str) -> &[DeployedContract] {
+        self.contracts.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    fn all_versions(&self) -> impl Iterator<Item = &DeployedContract> {
+        self.contracts.values().flatten()
+    }
+
+    // Latest version of every contract, archived ones included; see `list()` for the default view
+    fn iter(&self) -> Iter<'_> {
+        Iter {
+            entries: Box::new(self.contracts.values()),
+        }
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        self.contracts.keys().map(String::as_str)
+    }
+
+    // Latest version of every contract whose name falls in `range`, in name order, e.g.
+    // `range("A".."M")` for one page of an alphabetical listing
+    fn range<'a>(&'a self, range: impl RangeBounds<&'a str>) -> Vec<&'a DeployedContract> {
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+        self.contracts
+            .range(bounds)
+            .into_iter()
+            .filter_map(|(_, versions)| versions.get(active_version(versions)?))
+            .collect()
+    }
+
+    // Latest version of every contract whose name matches a shell-style glob (`*` for any run,
+    // `?` for one character), sorted by name, e.g. `search("Token*")`
+    fn search(&self, pattern: &str) -> Vec<&DeployedContract> {
+        let mut found: Vec<&DeployedContract> = self
+            .iter()
+            .filter(|contract| glob_match(pattern, contract.name()))
+            .collect();
+        found.sort_by_key(|contract| contract.name());
+        found
+    }
+
+    // Latest versions with a value under one of `keys` (any key when empty) whose display form
+    // matches `pattern`, sorted by name. Secret keys are never searched.
+    #[cfg(feature = "regex")]
+    fn search_values(&self, pattern: &regex::Regex, keys: &[&str]) -> Vec<&DeployedContract> {
+        let mut found: Vec<&DeployedContract> = self
+            .iter()
+            .filter(|contract| {
+                contract.metadata.iter().any(|(key, value)| {
+                    (keys.is_empty() || keys.contains(&key.as_str()))
+                        && !self.secrets.contains(key)
+                        && pattern.is_match(&self.secrets.show(key, value).to_string())
+                })
+            })
+            .collect();
+        found.sort_by_key(|contract| contract.name());
+        found
+    }
+
+    // One row per contract (latest version, sorted by name) after a header row; the `name`
+    // column comes first, then `columns` as metadata keys, left empty where a contract lacks one
+    #[cfg(feature = "std")]
+    fn export_csv(&self, mut writer: impl std::io::Write, columns: &[&str]) -> std::io::Result<()> {
+        let header = std::iter::once("name").chain(columns.iter().copied());
+        writeln!(writer, "{}", csv_row(header))?;
+        let mut contracts: Vec<&DeployedContract> = self.iter().collect();
+        contracts.sort_by_key(|contract| contract.name());
+        for contract in contracts {
+            let values: Vec<String> = columns
+                .iter()
+                .map(|key| {
+                    contract
+                        .get(key)
+                        .map(|value| self.secrets.show(key, value).to_string())
+                        .unwrap_or_default()
+                })
+                .collect();
+            let row = std::iter::once(contract.name()).chain(values.iter().map(String::as_str));
+            writeln!(writer, "{}", csv_row(row))?;
+        }
+        writer.flush()
+    }
+
+    // Each contract's keys in namespace `ns` (latest version, prefix stripped, secrets
+    // redacted), so one team can export its own fields; contracts with none are left out
+    fn export_namespace(&self, ns: &str) -> BTreeMap<String, MetadataMap> {
+        self.iter()
+            .map(|contract| (contract.name.clone(), contract.namespace(ns).to_map()))
+            .filter(|(_, fields)| !fields.is_empty())
+            .collect()
+    }
+
+    // From `self` to `other`, e.g. `staging.diff(&production)` lists what production lacks as
+    // `added`. Rolled-back-only contracts count as absent.
+    fn diff(&self, other: &Self) -> RegistryDiff {
+        let mut names: Vec<&str> = self.names().chain(other.names()).collect();
+        names.sort_unstable();
+        names.dedup();
+        let mut diff = RegistryDiff::default();
+        for name in names {
+            match (self.get(name), other.get(name)) {
+                (None, Some(_)) => diff.added.push(name.into()),
+                (Some(_), None) => diff.removed.push(name.into()),
+                (Some(a), Some(b)) => {
+                    let changes = self::diff(a, b);
+                    if !changes.is_empty() {
+                        diff.modified.push((name.into(), changes));
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+        diff
+    }
+
+    // Number of contract names, not versions
+    fn len(&self) -> usize {
+        self.contracts.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.contracts.is_empty()
+    }
+
+    // Latest version of every contract currently in `status`
+    fn with_status(&self, status: LifecycleStatus) -> impl Iterator<Item = &DeployedContract> {
+        self.contracts
+            .values()
+            .filter_map(|versions| versions.get(active_version(versions)?))
+            .filter(move |contract| contract.status() == Some(status))
+    }
+
+    // Default listing: latest version of every contract that is not archived
+    fn list(&self) -> impl Iterator<Item = &DeployedContract> {
+        self.contracts
+            .values()
+            .filter_map(|versions| versions.get(active_version(versions)?))
+            .filter(|contract| contract.status() != Some(LifecycleStatus::Archived))
+    }
+
+    fn archived(&self) -> impl Iterator<Item = &DeployedContract> {
+        self.with_status(LifecycleStatus::Archived)
+    }
+
+    // Filters the default listing, e.g. `query().where_key_eq("signer", addr).collect()`
+    fn query(&self) -> Query<'_> {
+        Query {
+            registry: self,
+            predicates: Vec::new(),
+        }
+    }
+
+    // Deprecates the latest version in place; `false` if `name` is unknown
+    fn deprecate(&mut self, name: &str, successor: Option<&str>) -> Result<bool, ScrgError> {
+        let actor = self.actor.clone();
+        self.deprecate_as(&actor, name, successor)
+    }
+
+    fn archive(&mut self, name: &str) -> Result<bool, ScrgError> {
+        let actor = self.actor.clone();
+        self.archive_as(&actor, name)
+    }
+
+    fn update_latest(
+        &mut self,
+        name: &str,
+        actor: &str,
+        writes: impl Iterator<Item = (&'static str, MetadataValue)>,
+    ) -> bool {
+        let Some(contract) = self.latest_mut(name) else {
+            return false;
+        };
+        let old = contract.metadata.get("status").cloned();
+        contract.edit(actor, writes.map(|(key, value)| (key, Some(value))));
+        let new = contract.metadata.get("status").cloned();
+        self.reindex(name);
+        if let Some(new) = new {
+            self.emit(RegistryEvent::MetadataUpdated {
+                name: name.into(),
+                key: "status".into(),
+                old,
+                new,
+            });
+        }
+        true
+    }
+
+    // Updates the latest version and returns the old value. Lifecycle keys are refused: use
+    // `archive`, `deprecate` and friends to change `status`.
+    fn update_metadata(
+        &mut self,
+        name: &str,
+        key: impl AsRef<str>,
+        value: impl Into<MetadataValue>,
+    ) -> Result<Option<MetadataValue>, ScrgError> {
+        let actor = self.actor.clone();
+        self.update_metadata_as(&actor, name, key.as_ref(), value)
+    }
+
+    // Merges `other` into the latest version of `name`, e.g. metadata arriving from an import.
+    // Each added or overwritten key is updated as by `update_metadata`; if any of them is
+    // refused, nothing is merged.
+    fn merge_metadata(
+        &mut self,
+        name: &str,
+        mut other: MetadataMap,
+        strategy: MetadataMergeStrategy,
+    ) -> Result<MetadataMergeReport, ScrgError> {
+        let actor = self.actor.clone();
+        self.authorize(&actor, Operation::UpdateMetadata, Some(name))?;
+        let contract = self
+            .latest(name)
+            .ok_or_else(|| RegistryError::UnknownContract(name.into()))?;
+        let current = self.opened_metadata(contract)?;
+        let mut merged = current.clone();
+        let report = merged
+            .merge_from(other.clone(), strategy)
+            .map_err(RegistryError::MetadataConflict)?;
+        let changed: Vec<&String> = report.added.iter().chain(&report.overwritten).collect();
+        for key in &changed {
+            check_not_reserved(key).map_err(WriteError::from)?;
+        }
+        contract
+            .limits
+            .check_changes(&current, &merged)
+            .map_err(WriteError::from)?;
+        for key in changed {
+            if let Some(value) = other.remove(key) {
+                self.update_metadata_by(name, key, value, "merge")?;
+            }
+        }
+        Ok(report)
+    }
+
+    fn update_metadata_by(
+        &mut self,
+        name: &str,
+        key: &str,
+        mut new: MetadataValue,
+        actor: &str,
+    ) -> Result<Option<MetadataValue>, ScrgError> {
+        check_not_reserved(key).map_err(WriteError::from)?;
+        let contract = self
+            .latest_mut(name)
+            .ok_or_else(|| RegistryError::UnknownContract(name.into()))?;
+        let old = contract.set(key, new.clone(), actor)?;
+        self.repack(name);
+        // Events reach watchers outside the registry, so sealed keys are not shown there
+        if self.seals(key) {
+            new = REDACTED.into();
+        }
+        self.reindex(name);
+        self.emit(RegistryEvent::MetadataUpdated {
+            name: name.into(),
+            key: key.into(),
+            old: old.clone(),
+            new,
+        });
+        Ok(old)
+    }
+
+    // Packs the latest version of `name` again after a write, as `restore` packs new entries
+    fn repack(&mut self, name: &str) {
+        let Some(versions) = self.contracts.get_mut(name) else {
+            return;
+        };
+        let Some(contract) = active_version(versions).and_then(|index| versions.get_mut(index))
+        else {
+            return;
+        };
+        if let Some(encryption) = &self.encryption {
+            encryption.seal_contract(contract);
+        }
+        if let Some(compression) = &self.compression {
+            compression.compress_contract(contract);
+        }
+    }
+
+    // Adds a per-network deployment to the latest version
+    fn record_deployment(
+        &mut self,
+        name: &str,
+        chain_id: u64,
+        record: DeploymentRecord,
+    ) -> Result<(), ScrgError> {
+        let actor = self.actor.clone();
+        self.record_deployment_as(&actor, name, chain_id, record)
+    }
+
+    fn record_deployment_by(
+        &mut self,
+        name: &str,
+        chain_id: u64,
+        record: DeploymentRecord,
+        actor: &str,
+    ) -> Result<(), ScrgError> {
+        let contract = self
+            .latest_mut(name)
+            .ok_or_else(|| RegistryError::UnknownContract(name.into()))?;
+        let old = contract.get("deployments").cloned();
+        contract
+            .add_deployment(chain_id, record, actor)
+            .map_err(WriteError::from)?;
+        let new = contract.get("deployments").cloned();
+        self.reindex(name);
+        if let Some(new) = new {
+            self.emit(RegistryEvent::MetadataUpdated {
+                name: name.into(),
+                key: "deployments".into(),
+                old,
+                new,
+            });
+        }
+        Ok(())
+    }
+
+    // Contracts whose newest version has expired: hidden from reads but held until purged
+    fn expired(&self) -> impl Iterator<Item = &DeployedContract> {
+        self.contracts
+            .values()
+            .filter_map(|versions| versions.get(newest_version(versions)?))
+            .filter(|contract| contract.is_expired())
+    }
+
+    // Removes every version of each expired contract
+    fn purge_expired(&mut self) -> Result<Vec<DeployedContract>, ScrgError> {
+        let actor = self.actor.clone();
+        self.authorize(&actor, Operation::Remove, None)?;
+        let names: Vec<String> = self
+            .expired()
+            .map(|contract| contract.name.clone())
+            .collect();
+        Ok(names
+            .iter()
+            .flat_map(|name| self.remove_unchecked(name))
+            .collect())
+    }
+
+    // Removes every version of `name`
+    fn remove(&mut self, name: &str) -> Result<Vec<DeployedContract>, ScrgError> {
+        let actor = self.actor.clone();
+        self.remove_as(&actor, name)
+    }
+
+    // For the registry's own removals: eviction, merges and group rollbacks
+    fn remove_unchecked(&mut self, name: &str) -> Vec<DeployedContract> {
+        let Some(removed) = self.contracts.remove(name) else {
+            return Vec::new();
+        };
+        for id in removed.iter().filter_map(DeployedContract::id) {
+            self.ids.remove(id);
+        }
+        if let Some(limit) = &self.limit {
+            limit.last_used.borrow_mut().remove(name);
+        }
+        self.reindex(name);
+        self.emit(RegistryEvent::ContractRemoved { name: name.into() });
+        removed
+    }
+
+    // Takes every contract only `other` has and resolves overlapping names by `strategy`.
+    // A replaced contract brings its whole version history; dependencies are not re-checked.
+    fn merge(
+        &mut self,
+        other: ContractRegistry,
+        strategy: MergeStrategy,
+    ) -> Result<MergeReport, ScrgError> {
+        let actor = self.actor.clone();
+        self.merge_as(&actor, other, strategy)
+    }
+
+    fn merge_unchecked(
+        &mut self,
+        other: ContractRegistry,
+        strategy: MergeStrategy,
+    ) -> Result<MergeReport, ScrgError> {
+        let mut theirs: Vec<(String, Vec<DeployedContract>)> =
+            other.contracts.into_iter().collect();
+        theirs.sort_by(|a, b| a.0.cmp(&b.0));
+        let conflicts: Vec<String> = theirs
+            .iter()
+            .filter(|(name, versions)| {
+                self.contracts
+                    .get(name)
+                    .is_some_and(|ours| !same_history(ours, versions))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        if strategy == MergeStrategy::Error && !conflicts.is_empty() {
+            return Err(MergeConflict(conflicts).into());
+        }
+
+        let mut report = MergeReport::default();
+        for (name, versions) in theirs {
+            let take = if !self.contracts.contains_key(&name) {
+                report.added.push(name.clone());
+                true
+            } else if !conflicts.contains(&name) {
+                false
+            } else {
+                match strategy {
+                    MergeStrategy::KeepTheirs => true,
+                    MergeStrategy::NewestWins => {
+                        let deployed_at = |versions: &[DeployedContract]| {
+                            active_version(versions).map(|i| versions[i].deployed_at())
+                        };
+                        deployed_at(&versions) > deployed_at(&self.contracts[name.as_str()])
+                    }
+                    MergeStrategy::KeepOurs | MergeStrategy::Error => false,
+                }
+            };
+            if !take {
+                report.kept.push(name);
+                continue;
+            }
+            if self.contracts.contains_key(&name) {
+                self.remove_unchecked(&name);
+                report.replaced.push(name);
+            }
+            for contract in versions {
+                self.restore(contract);
+            }
+        }
+        Ok(report)
+    }
+
+    // Guarded counterparts of the mutations above, which pass the registry's own actor: each
+    // checks `actor`'s role, bounded by the registry's, and records the denial if it is refused
+    fn authorize(
+        &mut self,
+        actor: &Actor,
+        operation: Operation,
+        contract: Option<&str>,
+    ) -> Result<(), RegistryError> {
+        let actor = actor.bounded_by(&self.actor);
+        actor.check(operation, contract).map_err(|denied| {
+            self.denials.push(denied.clone());
+            RegistryError::AccessDenied(denied)
+        })
+    }
+
+    fn register_as(
+        &mut self,
+        actor: &Actor,
+        contract: DeployedContract,
+    ) -> Result<Option<DeployedContract>, ScrgError> {
+        self.authorize(actor, Operation::Register, Some(&contract.name))?;
+        self.check_dependencies(&contract)?;
+        self.metrics.registrations.fetch_add(1, Ordering::Relaxed);
+        Ok(self.restore(contract))
+    }
+
+    fn update_metadata_as(
+        &mut self,
+        actor: &Actor,
+        name: &str,
+        key: &str,
+        value: impl Into<MetadataValue>,
+    ) -> Result<Option<MetadataValue>, ScrgError> {
+        self.authorize(actor, Operation::UpdateMetadata, Some(name))?;
+        self.update_metadata_by(name, key, value.into(), &actor.name)
+    }
+
+    fn record_deployment_as(
+        &mut self,
+        actor: &Actor,
+        name: &str,
+        chain_id: u64,
+        record: DeploymentRecord,
+    ) -> Result<(), ScrgError> {
+        self.authorize(actor, Operation::RecordDeployment, Some(name))?;
+        self.record_deployment_by(name, chain_id, record, &actor.name)
+    }
+
+    fn deprecate_as(
+        &mut self,
+        actor: &Actor,
+        name: &str,
+        successor: Option<&str>,
+    ) -> Result<bool, ScrgError> {
+        self.authorize(actor, Operation::Deprecate, Some(name))?;
+        Ok(self.update_latest(name, &actor.name, deprecation(successor)))
+    }
+
+    fn archive_as(&mut self, actor: &Actor, name: &str) -> Result<bool, ScrgError> {
+        self.authorize(actor, Operation::Archive, Some(name))?;
+        Ok(self.update_latest(name, &actor.name, archival()))
+    }
+
+    fn rollback_as(&mut self, actor: &Actor, name: &str) -> Result<&DeployedContract, ScrgError> {
+        self.authorize(actor, Operation::Rollback, Some(name))?;
+        self.rollback_unchecked(name)
+    }
+
+    fn remove_as(&mut self, actor: &Actor, name: &str) -> Result<Vec<DeployedContract>, ScrgError> {
+        self.authorize(actor, Operation::Remove, Some(name))?;
+        Ok(self.remove_unchecked(name))
+    }
+
+    fn merge_as(
+        &mut self,
+        actor: &Actor,
+        other: ContractRegistry,
+        strategy: MergeStrategy,
+    ) -> Result<MergeReport, ScrgError> {
+        self.authorize(actor, Operation::Merge, None)?;
+        self.merge_unchecked(other, strategy)
+    }
+
+    // Seals the live and deploy-time values of `keys` with `cipher` in every contract held
+    // now and every one added or updated later, and blanks them in audit logs as `***`;
+    // `get()` no longer returns them and exports carry only ciphertext. Signatures over
+    // sealed values verify through `verify_signature` here, not on the contract.
+    fn with_encryption<I, S>(mut self, cipher: impl Cipher + 'static, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let encryption = Encryption {
+            cipher: Box::new(cipher),
+            keys: keys.into_iter().map(Into::into).collect(),
+        };
+        for contract in self.contracts.values_mut().flatten() {
+            encryption.seal_contract(contract);
+        }
+        self.encryption = Some(encryption);
+        self
+    }
+
+    // Whether `with_encryption` covers `key`
+    fn seals(&self, key: &str) -> bool {
+        self.encryption
+            .as_ref()
+            .is_some_and(|encryption| encryption.keys.contains(key))
+    }
+
+    // Plaintext of `key` on the latest version of `name`; needs `Operation::RevealSecret`.
+    // Values that were never encrypted come back as they are.
+    fn reveal(
+        &mut self,
+        actor: &Actor,
+        name: &str,
+        key: &str,
+    ) -> Result<Option<MetadataValue>, ScrgError> {
+        self.authorize(actor, Operation::RevealSecret, Some(name))?;
+        let Some(contract) = self.latest(name) else {
+            return Ok(None);
+        };
+        match contract.packed.get(key) {
+            Some(Packed::Sealed(ciphertext)) => self.open(ciphertext).map(Some),
+            _ => Ok(contract.get(key).cloned()),
+        }
+    }
+
+    fn open(&self, ciphertext: &[u8]) -> Result<MetadataValue, ScrgError> {
+        let encryption = self
+            .encryption
+            .as_ref()
+            .ok_or_else(|| CipherError("the registry has no cipher".into()))
+            .map_err(RegistryError::Cipher)?;
+        encryption
+            .open(ciphertext)
+            .map_err(|err| RegistryError::Cipher(err).into())
+    }
+
+    // Live metadata of `contract` with its compressed values unpacked and its sealed ones
+    // opened, for checks that must see every key as written
+    fn opened_metadata(&self, contract: &DeployedContract) -> Result<MetadataMap, ScrgError> {
+        let mut metadata = MetadataMap::clone(&contract.metadata);
+        for (key, packed) in &contract.packed {
+            let value = match packed {
+                Packed::Sealed(ciphertext) => self.open(ciphertext)?,
+                Packed::Compressed(compressed) => {
+                    compressed.value().cloned().map_err(RegistryError::Codec)?
+                }
+            };
+            metadata.insert(key.clone(), value);
+        }
+        Ok(metadata)
+    }
+
+    // Stores string, address and bytes values over `threshold` bytes packed by `codec`, in
+    // the live metadata, deploy snapshot and audit log of every contract held now and every
+    // one added or updated later, and lets contracts loaded with compressed values unpack
+    // them. `get()`, `snapshot()` and the audit entry accessors unpack on first use.
+    fn with_compression(mut self, codec: impl Codec + 'static, threshold: usize) -> Self {
+        let compression = Compression {
+            codec: Arc::new(codec),
+            threshold,
+        };
+        for contract in self.contracts.values_mut().flatten() {
+            compression.compress_contract(contract);
+        }
+        self.compression = Some(compression);
+        self
+    }
+
+    // `key` on the latest version of `name` like `get()`, but a compressed value that cannot
+    // be unpacked is an error rather than missing
+    fn value(&self, name: &str, key: impl AsRef<str>) -> Result<Option<MetadataValue>, ScrgError> {
+        let key = key.as_ref();
+        let Some(contract) = self.latest(name) else {
+            return Ok(None);
+        };
+        match contract.packed.get(key) {
+            Some(Packed::Compressed(compressed)) => compressed
+                .value()
+                .map(|value| Some(value.clone()))
+                .map_err(|err| RegistryError::Codec(err).into()),
+            _ => Ok(contract.get(key).cloned()),
+        }
+    }
+
+    fn compression_stats(&self) -> CompressionStats {
+        self.all_versions()
+            .flat_map(DeployedContract::compressed_values)
+            .fold(CompressionStats::default(), |mut stats, compressed| {
+                stats.values += 1;
+                stats.original_bytes += compressed.original_len().unwrap_or(0);
+                stats.compressed_bytes += compressed.bytes.len();
+                stats
+            })
+    }
+
+    // Copy with every secret value (see `SecretKeys`) and the plaintext of every encrypted
+    // key replaced by `***`, for sharing through `to_json`, `to_toml` or a store. Those
+    // persist values as they are, since they also back reloading; sealed values stay as
+    // ciphertext.
+    fn redacted(&self) -> ContractRegistry {
+        let mut copy = ContractRegistry::new();
+        for contract in self.all_versions() {
+            let mut contract = contract.clone();
+            let compressed: Vec<_> = contract
+                .packed
+                .iter()
+                .filter(|(_, packed)| matches!(packed, Packed::Compressed(_)))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in compressed {
+                contract.unpack(&key);
+            }
+            contract.snapshot = contract.snapshot();
+            contract
+                .snapshot_packed
+                .retain(|_, packed| matches!(packed, Packed::Sealed(_)));
+            rewrite_values(&mut contract, |key, value| match self.seals(key) {
+                true => REDACTED.into(),
+                false => self.secrets.redact(key, value),
+            });
+            copy.restore(contract);
+        }
+        copy
+    }
+
+    // Every refused guarded operation, oldest first
+    fn denials(&self) -> &[AccessDenied] {
+        &self.denials
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.contracts.contains_key(name)
+    }
+
+    fn subscribe<F>(&mut self, callback: F)
+    where
+        F: FnMut(&RegistryEvent) + 'static,
+    {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    // Pushes every later event to the returned receiver, e.g. for a dashboard thread
+    #[cfg(feature = "std")]
+    fn watch(&mut self) -> mpsc::Receiver<RegistryEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.channels.push(tx);
+        rx
+    }
+
+    // Channels whose receiver was dropped are pruned on the next event
+    fn emit(&mut self, event: RegistryEvent) {
+        for callback in &mut self.callbacks {
+            callback(&event);
+        }
+        #[cfg(feature = "std")]
+        self.channels.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+}
+
+// Iterative wildcard match with backtracking to the last `*`
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// RFC 4180 quoting: fields containing a comma, quote or line break are wrapped in quotes
+fn csv_row<'a>(fields: impl IntoIterator<Item = &'a str>) -> String {
+    fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Borrowing iterator returned by `ContractRegistry::iter`
+struct Iter<'r> {
+    entries: Box<dyn Iterator<Item = &'r Vec<DeployedContract>> + 'r>,
+}
+
+impl<'r> Iterator for Iter<'r> {
+    type Item = &'r DeployedContract;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries
+            .by_ref()
+            .find_map(|versions| versions.get(active_version(versions)?))
+    }
+}
+
+impl<'r> IntoIterator for &'r ContractRegistry {
+    type Item = &'r DeployedContract;
+    type IntoIter = Iter<'r>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+type Predicate<'r> = Box<dyn Fn(&DeployedContract) -> bool + 'r>;
+
+// Conjunction of metadata predicates over `ContractRegistry::list`
+struct Query<'r> {
+    registry: &'r ContractRegistry,
+    predicates: Vec<Predicate<'r>>,
+}
+
+impl<'r> Query<'r> {
+    fn where_key_eq(mut self, key: &'r str, value: impl Into<MetadataValue>) -> Self {
+        let value = value.into();
+        self.predicates
+            .push(Box::new(move |contract| contract.get(key) == Some(&value)));
+        self
+    }
+
+    fn where_key_exists(mut self, key: &'r str) -> Self {
+        self.predicates
+            .push(Box::new(move |contract| contract.get(key).is_some()));
+        self
+    }
+
+    fn where_status(mut self, status: LifecycleStatus) -> Self {
+        self.predicates
+            .push(Box::new(move |contract| contract.status() == Some(status)));
+        self
+    }
+
+    fn where_chain_id(mut self, chain_id: u64) -> Self {
+        self.predicates
+            .push(Box::new(move |contract| contract.is_on_chain(chain_id)));
+        self
+    }
+
+    fn matching(mut self, predicate: impl Fn(&DeployedContract) -> bool + 'r) -> Self {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &'r DeployedContract> + '_ {
+        self.registry
+            .list()
+            .filter(|contract| self.predicates.iter().all(|predicate| predicate(contract)))
+    }
+
+    fn count(&self) -> usize {
+        self.iter().count()
+    }
+
+    fn collect(self) -> Vec<&'r DeployedContract> {
+        self.iter().collect()
+    }
+}
+
+// Thread-safe counterpart of `ContractRegistry`; clones share the same map
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+struct SharedRegistry {
+    contracts: Arc<RwLock<ContractMap>>,
+    watchers: Arc<Mutex<Vec<mpsc::Sender<RegistryEvent>>>>,
+    denials: Arc<Mutex<Vec<AccessDenied>>>,
+    // Per handle: clones made with `acting_as` share the map but not the actor
+    actor: Actor,
+}
+
+#[cfg(feature = "std")]
+impl SharedRegistry {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    // Every write is a single map operation, so a poisoned lock still guards consistent data
+    fn read(&self) -> RwLockReadGuard<'_, ContractMap> {
+        self.contracts
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, ContractMap> {
+        self.contracts
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn register(&self, contract: DeployedContract) -> Result<Option<DeployedContract>, ScrgError> {
+        self.register_as(&self.actor, contract)
+    }
+
+    // A handle on the same registry whose writes are checked against `actor`, e.g. for a
+    // read-only consumer. As with `ContractRegistry::acting_as`, it can only narrow.
+    fn acting_as(&self, actor: Actor) -> Self {
+        Self {
+            actor: actor.bounded_by(&self.actor),
+            ..self.clone()
+        }
+    }
+
+    fn register_unchecked(&self, contract: DeployedContract) -> Option<DeployedContract> {
+        let name = contract.name.clone();
+        let version = contract.version().cloned();
+        let (current, previous) = {
+            let mut contracts = self.write();
+            let versions = contracts.versions_entry(name.clone());
+            let current = active_version(versions).and_then(|i| versions[i].version().cloned());
+            (current, insert_version(versions, contract))
+        };
+        self.emit(registration_event(name, current, version));
+        previous
+    }
+
+    // Latest version of `name`
+    fn get(&self, name: &str) -> Option<DeployedContract> {
+        let contracts = self.read();
+        let versions = contracts.get(name)?;
+        versions.get(active_version(versions)?).cloned()
+    }
+
+    fn get_version(&self, name: &str, version: &str) -> Option<DeployedContract> {
+        let version: Version = version.parse().ok()?;
+        self.read()
+            .get(name)?
+            .iter()
+            .find(|contract| contract.version() == Some(&version))
+            .cloned()
+    }
+
+    fn purge_expired(&self) -> Result<Vec<DeployedContract>, ScrgError> {
+        self.authorize(&self.actor, Operation::Remove, None)?;
+        let names: Vec<String> = self
+            .read()
+            .iter()
+            .filter(|(_, versions)| {
+                newest_version(versions).is_some_and(|i| versions[i].is_expired())
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        Ok(names
+            .iter()
+            .flat_map(|name| self.remove_unchecked(name))
+            .collect())
+    }
+
+    fn remove(&self, name: &str) -> Result<Vec<DeployedContract>, ScrgError> {
+        self.remove_as(&self.actor, name)
+    }
+
+    fn remove_unchecked(&self, name: &str) -> Vec<DeployedContract> {
+        let removed = self.write().remove(name);
+        if removed.is_some() {
+            self.emit(RegistryEvent::ContractRemoved { name: name.into() });
+        }
+        removed.unwrap_or_default()
+    }
+
+    // `register`/`remove` for a given actor, bounded by the handle's as in `ContractRegistry`
+    fn authorize(
+        &self,
+        actor: &Actor,
+        operation: Operation,
+        contract: Option<&str>,
+    ) -> Result<(), RegistryError> {
+        let actor = actor.bounded_by(&self.actor);
+        actor.check(operation, contract).map_err(|denied| {
+            self.denials
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(denied.clone());
+            RegistryError::AccessDenied(denied)
+        })
+    }
+
+    fn register_as(
+        &self,
+        actor: &Actor,
+        contract: DeployedContract,
+    ) -> Result<Option<DeployedContract>, ScrgError> {
+        self.authorize(actor, Operation::Register, Some(&contract.name))?;
+        Ok(self.register_unchecked(contract))
+    }
+
+    fn remove_as(&self, actor: &Actor, name: &str) -> Result<Vec<DeployedContract>, ScrgError> {
+        self.authorize(actor, Operation::Remove, Some(name))?;
+        Ok(self.remove_unchecked(name))
+    }
+
+    fn denials(&self) -> Vec<AccessDenied> {
+        self.denials
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    // Receivers may live on any thread; all clones of this registry feed them
+    fn watch(&self) -> mpsc::Receiver<RegistryEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.watchers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(tx);
+        rx
+    }
+
+    // Sent after the map lock is released, so a receiver may read the registry right away
+    fn emit(&self, event: RegistryEvent) {
+        self.watchers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.read().contains_key(name)
+    }
+
+    // Copies the current contents into a single-threaded registry
+    fn snapshot(&self) -> ContractRegistry {
+        let contracts = self.read();
+        let mut registry = ContractRegistry::new().with_order(contracts.order());
+        for contract in contracts.values().flatten() {
+            registry.restore(contract.clone());
+        }
+        registry.denials = self.denials();
+        registry.actor = self.actor.clone();
+        registry
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ContractRegistry> for SharedRegistry {
+    fn from(registry: ContractRegistry) -> Self {
+        Self {
+            contracts: Arc::new(RwLock::new(registry.contracts)),
+            watchers: Arc::default(),
+            denials: Arc::new(Mutex::new(registry.denials)),
+            actor: registry.actor,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl DeployedContract {
+    fn to_json(&self) -> Result<String, ScrgError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    fn from_json(json: &str) -> Result<Self, ScrgError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ContractRegistry {
+    fn to_json(&self) -> Result<String, ScrgError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    fn from_json(json: &str) -> Result<Self, ScrgError> {
+        let mut registry: Self = serde_json::from_str(json)?;
+        registry.reindex_all();
+        Ok(registry)
+    }
+}
+
+// Binary snapshots: `SCRG`, a little-endian u16 format version, then the postcard encoding
+// of that version's layout. A layout only ever gains variants at the end; anything else
+// means a new format version with its own `Wire*V{n}` types, and `from_binary` keeps
+// decoding the old ones.
+#[cfg(feature = "binary")]
+const BINARY_MAGIC: [u8; 4] = *b"SCRG";
+
+#[cfg(feature = "binary")]
+const BINARY_VERSION: u16 = 1;
+
+#[cfg(feature = "binary")]
+#[derive(Debug)]
+enum BinaryError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Encode(postcard::Error),
+    Decode(postcard::Error),
+    InvalidValue(String),
+}
+
+#[cfg(feature = "binary")]
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a binary registry snapshot"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "binary snapshot format {version} is not supported (latest is {BINARY_VERSION})"
+            ),
+            Self::Encode(err) => write!(f, "binary snapshot encoding failed: {err}"),
+            Self::Decode(err) => write!(f, "binary snapshot is corrupt: {err}"),
+            Self::InvalidValue(reason) => write!(f, "binary snapshot holds {reason}"),
+        }
+    }
+}
+
+#[cfg(feature = "binary")]
+impl std::error::Error for BinaryError {}
+
+// Externally tagged, unlike `MetadataValue`'s serde form: postcard is not self-describing
+// and cannot decode adjacently tagged enums
+#[cfg(feature = "binary")]
+#[derive(serde::Serialize, serde::Deserialize)]
+enum WireValueV1 {
+    String(String),
+    Int(i64),
+    Bool(bool),
+    Timestamp(WireTime),
+    Address(String),
+    Bytes(Vec<u8>),
+    Version(String),
+    List(Vec<WireValueV1>),
+    Map(Vec<(String, WireValueV1)>),
+    Encrypted(Vec<u8>),
+    Compressed(Vec<u8>),
+}
+
+// Seconds and nanoseconds since the Unix epoch
+#[cfg(feature = "binary")]
+type WireTime = (u64, u32);
+
+#[cfg(feature = "binary")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WireAuditV1 {
+    key: String,
+    old: Option<WireValueV1>,
+    new: Option<WireValueV1>,
+    timestamp: WireTime,
+    actor: String,
+}
+
+#[cfg(feature = "binary")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WireContractV1 {
+    name: String,
+    deployed_at: WireTime,
+    metadata: Vec<(String, WireValueV1)>,
+    snapshot: Vec<(String, WireValueV1)>,
+    audit: Vec<WireAuditV1>,
+}
+
+// Every version of every contract, in registry order
+#[cfg(feature = "binary")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WireRegistryV1 {
+    sorted: bool,
+    contracts: Vec<WireContractV1>,
+}
+
+#[cfg(feature = "binary")]
+fn time_to_wire(time: SystemTime) -> WireTime {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or((0, 0), |since| (since.as_secs(), since.subsec_nanos()))
+}
+
+#[cfg(feature = "binary")]
+fn time_from_wire((secs, nanos): WireTime) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::new(secs, nanos)
+}
+
+// Sorted by key, so equal registries encode to equal bytes
+#[cfg(feature = "binary")]
+fn map_to_wire(map: &MetadataMap) -> Vec<(String, WireValueV1)> {
+    let mut entries: Vec<_> = map
+        .iter()
+        .map(|(key, value)| (key.clone(), WireValueV1::from(value)))
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+// Live or deploy-time metadata, with packed values as their own top-level wire variants so
+// the V1 layout stays as it was
+#[cfg(feature = "binary")]
+fn packed_to_wire(
+    map: &MetadataMap,
+    packed: &BTreeMap<String, Packed>,
+) -> Vec<(String, WireValueV1)> {
+    let mut entries = map_to_wire(map);
+    entries.extend(packed.iter().map(|(key, packed)| {
+        let value = match packed {
+            Packed::Sealed(sealed) => WireValueV1::Encrypted(sealed.clone()),
+            Packed::Compressed(compressed) => WireValueV1::Compressed(compressed.bytes.clone()),
+        };
+        (key.clone(), value)
+    }));
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+// Inverse of `packed_to_wire`
+#[cfg(feature = "binary")]
+fn packed_from_wire(
+    entries: Vec<(String, WireValueV1)>,
+) -> Result<(MetadataMap, BTreeMap<String, Packed>), BinaryError> {
+    let mut plain = Vec::new();
+    let mut packed = BTreeMap::new();
+    for (key, value) in entries {
+        match value {
+            WireValueV1::Encrypted(sealed) => {
+                packed.insert(key, Packed::Sealed(sealed));
+            }
+            WireValueV1::Compressed(bytes) => {
+                packed.insert(key, Packed::Compressed(CompressedValue::new(bytes, None)));
+            }
+            value => plain.push((key, value)),
+        }
+    }
+    Ok((map_from_wire(plain)?, packed))
+}
+
+#[cfg(feature = "binary")]
+fn map_from_wire(entries: Vec<(String, WireValueV1)>) -> Result<MetadataMap, BinaryError> {
+    entries
+        .into_iter()
+        .map(|(key, value)| Ok((key, value.try_into()?)))
+        .collect()
+}
+
+#[cfg(feature = "binary")]
+impl From<&MetadataValue> for WireValueV1 {
+    fn from(value: &MetadataValue) -> Self {
+        match value {
+            MetadataValue::String(s) => Self::String(s.clone()),
+            MetadataValue::Int(i) => Self::Int(*i),
+            MetadataValue::Bool(b) => Self::Bool(*b),
+            MetadataValue::Timestamp(t) => Self::Timestamp(time_to_wire(*t)),
+            MetadataValue::Address(a) => Self::Address(a.clone()),
+            MetadataValue::Bytes(bytes) => Self::Bytes(bytes.clone()),
+            MetadataValue::Version(v) => Self::Version(v.to_string()),
+            MetadataValue::List(items) => Self::List(items.iter().map(Self::from).collect()),
+            MetadataValue::Map(map) => Self::Map(map_to_wire(map)),
+        }
+    }
+}
+
+// Audited values may be compressed too, which the V1 layout already has a variant for
+#[cfg(feature = "binary")]
+impl From<&AuditValue> for WireValueV1 {
+    fn from(value: &AuditValue) -> Self {
+        match value {
+            AuditValue::Plain(value) => Self::from(value),
+            AuditValue::Compressed { compressed } => Self::Compressed(compressed.bytes.clone()),
+        }
+    }
+}
+
+#[cfg(feature = "binary")]
+impl TryFrom<WireValueV1> for AuditValue {
+    type Error = BinaryError;
+
+    fn try_from(value: WireValueV1) -> Result<Self, BinaryError> {
+        Ok(match value {
+            WireValueV1::Compressed(bytes) => Self::Compressed {
+                compressed: CompressedValue::new(bytes, None),
+            },
+            value => Self::Plain(value.try_into()?),
+        })
+    }
+}
+
+#[cfg(feature = "binary")]
+impl TryFrom<WireValueV1> for MetadataValue {
+    type Error = BinaryError;
+
+    fn try_from(value: WireValueV1) -> Result<Self, BinaryError> {
+        Ok(match value {
+            WireValueV1::String(s) => Self::String(s),
+            WireValueV1::Int(i) => Self::Int(i),
+            WireValueV1::Bool(b) => Self::Bool(b),
+            WireValueV1::Timestamp(t) => Self::Timestamp(time_from_wire(t)),
+            WireValueV1::Address(a) => Self::Address(a),
+            WireValueV1::Bytes(bytes) => Self::Bytes(bytes),
+            WireValueV1::Version(v) => Self::Version(
+                v.parse()
+                    .map_err(|err: VersionError| BinaryError::InvalidValue(err.to_string()))?,
+            ),
+            WireValueV1::List(items) => Self::List(
+                items
+                    .into_iter()
+                    .map(Self::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            WireValueV1::Map(entries) => Self::Map(map_from_wire(entries)?),
+            WireValueV1::Encrypted(_) | WireValueV1::Compressed(_) => {
+                return Err(BinaryError::InvalidValue(
+                    "a packed value outside top-level metadata".into(),
+                ))
+            }
+        })
+    }
+}
+
+#[cfg(feature = "binary")]
+impl From<&DeployedContract> for WireContractV1 {
+    fn from(contract: &DeployedContract) -> Self {
+        Self {
+            name: contract.name.clone(),
+            deployed_at: time_to_wire(contract.deployed_at),
+            metadata: packed_to_wire(&contract.metadata, &contract.packed),
+            snapshot: packed_to_wire(&contract.snapshot, &contract.snapshot_packed),
+            audit: contract
+                .audit
+                .iter()
+                .map(|entry| WireAuditV1 {
+                    key: entry.key.clone(),
+                    old: entry.old.as_ref().map(WireValueV1::from),
+                    new: entry.new.as_ref().map(WireValueV1::from),
+                    timestamp: time_to_wire(entry.timestamp),
+                    actor: entry.actor.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(feature = "binary")]
+impl TryFrom<WireContractV1> for DeployedContract {
+    type Error = BinaryError;
+
+    fn try_from(wire: WireContractV1) -> Result<Self, BinaryError> {
+        let value = |value: Option<WireValueV1>| value.map(AuditValue::try_from).transpose();
+        let audit: Vec<AuditEntry> = wire
+            .audit
+            .into_iter()
+            .map(|entry| {
+                Ok(AuditEntry {
+                    key: entry.key,
+                    old: value(entry.old)?,
+                    new: value(entry.new)?,
+                    timestamp: time_from_wire(entry.timestamp),
+                    actor: entry.actor,
+                })
+            })
+            .collect::<Result<_, BinaryError>>()?;
+        let (metadata, packed) = packed_from_wire(wire.metadata)?;
+        let (snapshot, snapshot_packed) = packed_from_wire(wire.snapshot)?;
+        Ok(Self {
+            name: wire.name,
+            metadata: Arc::new(metadata),
+            deployed_at: time_from_wire(wire.deployed_at),
+            audit: audit.into(),
+            snapshot: MetadataSnapshot(Arc::new(snapshot)),
+            limits: MetadataLimits::default(),
+            packed,
+            snapshot_packed,
+        })
+    }
+}
+
+#[cfg(feature = "binary")]
+impl ContractRegistry {
+    // Every version with its snapshot and audit log, like `to_json`, in a fraction of the
+    // size. Access denials and runtime settings (encryption, compression, indexes) are left
+    // out.
+    fn to_binary(&self) -> Result<Vec<u8>, ScrgError> {
+        let wire = WireRegistryV1 {
+            sorted: self.contracts.order() == RegistryOrder::Sorted,
+            contracts: self.all_versions().map(WireContractV1::from).collect(),
+        };
+        let body = postcard::to_allocvec(&wire).map_err(BinaryError::Encode)?;
+        Ok([&BINARY_MAGIC[..], &BINARY_VERSION.to_le_bytes(), &body].concat())
+    }
+
+    // Reads any format version up to `BINARY_VERSION`
+    fn from_binary(bytes: &[u8]) -> Result<Self, ScrgError> {
+        let Some([lo, hi, body @ ..]) = bytes.strip_prefix(&BINARY_MAGIC[..]) else {
+            return Err(BinaryError::BadMagic.into());
+        };
+        let wire: WireRegistryV1 = match u16::from_le_bytes([*lo, *hi]) {
+            1 => postcard::from_bytes(body).map_err(BinaryError::Decode)?,
+            version => return Err(BinaryError::UnsupportedVersion(version).into()),
+        };
+        let order = match wire.sorted {
+            true => RegistryOrder::Sorted,
+            false => RegistryOrder::Hashed,
+        };
+        let mut registry = Self::new().with_order(order);
+        for contract in wire.contracts {
+            let contract = DeployedContract::try_from(contract)?;
+            registry
+                .contracts
+                .versions_entry(contract.name.clone())
+                .push(contract);
+        }
+        registry.reindex_all();
+        Ok(registry)
+    }
+}
+
+#[derive(Debug)]
+enum StoreError {
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    Codec(String),
+    Backend(String),
+    Rehydrate(RehydrateError),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(err) => write!(f, "registry store I/O failed: {err}"),
+            Self::Codec(msg) => write!(f, "registry store encoding failed: {msg}"),
+            Self::Backend(msg) => write!(f, "registry store backend failed: {msg}"),
+            Self::Rehydrate(err) => write!(f, "stored contract cannot resume: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for StoreError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+// Crate-wide error returned by the builder's validate/upgrade/gas steps, the registry and
+// its access-checked variants, the stores and every import/export format. Traits users
+// implement (rules, hooks, ciphers, codecs, gas estimators), per-key helpers on metadata
+// (`set`, `get_as`, `fields`, signature checks) and the deploy calls whose errors hand state
+// back (`DeployFailure`, `GroupDeployError`, `OnchainError`) keep their precise types; all of
+// them convert into this one with `?`.
+#[derive(Debug)]
+enum ScrgError {
+    Validation(ValidationError),
+    Hook(HookError),
+    Upgrade(UpgradeError),
+    Gas(GasError),
+    Storage(StoreError),
+    Serialization(Box<dyn std::error::Error + Send + Sync>),
+    Registry(RegistryError),
+}
+
+impl fmt::Display for ScrgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Validation(err) => write!(f, "{err}"),
+            Self::Hook(err) => write!(f, "{err}"),
+            Self::Upgrade(err) => write!(f, "{err}"),
+            Self::Gas(err) => write!(f, "{err}"),
+            Self::Storage(err) => write!(f, "{err}"),
+            Self::Serialization(err) => write!(f, "serialization failed: {err}"),
+            Self::Registry(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for ScrgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Validation(err) => Some(err),
+            Self::Hook(err) => Some(err),
+            Self::Upgrade(err) => Some(err),
+            Self::Gas(err) => Some(err),
+            Self::Storage(err) => Some(err),
+            Self::Serialization(err) => Some(err.as_ref()),
+            Self::Registry(err) => Some(err),
+        }
+    }
+}
+
+impl From<ValidationError> for ScrgError {
+    fn from(err: ValidationError) -> Self {
+        Self::Validation(err)
+    }
+}
+
+impl From<VersionError> for ScrgError {
+    fn from(err: VersionError) -> Self {
+        Self::Validation(ValidationError::InvalidVersion(err))
+    }
+}
+
+impl From<HookError> for ScrgError {
+    fn from(err: HookError) -> Self {
+        Self::Hook(err)
+    }
+}
+
+// The builder handed back by the failure is dropped; match on `DeployFailure` to keep it
+impl From<DeployFailure> for ScrgError {
+    fn from(failure: DeployFailure) -> Self {
+        Self::Hook(failure.error)
+    }
+}
+
+impl From<UpgradeError> for ScrgError {
+    fn from(err: UpgradeError) -> Self {
+        Self::Upgrade(err)
+    }
+}
+
+impl From<GasError> for ScrgError {
+    fn from(err: GasError) -> Self {
+        Self::Gas(err)
+    }
+}
+
+impl From<StoreError> for ScrgError {
+    fn from(err: StoreError) -> Self {
+        Self::Storage(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for ScrgError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Storage(StoreError::Io(err))
+    }
+}
+
+impl From<RehydrateError> for ScrgError {
+    fn from(err: RehydrateError) -> Self {
+        Self::Storage(StoreError::Rehydrate(err))
+    }
+}
+
+impl From<FieldError> for ScrgError {
+    fn from(err: FieldError) -> Self {
+        Self::Serialization(Box::new(err))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for ScrgError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Serialization(Box::new(err))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<ArtifactError> for ScrgError {
+    fn from(err: ArtifactError) -> Self {
+        match err {
+            ArtifactError::Io(err) => err.into(),
+            err => Self::Serialization(Box::new(err)),
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml::ser::Error> for ScrgError {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::Serialization(Box::new(err))
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml::de::Error> for ScrgError {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Serialization(Box::new(err))
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<ConfigError> for ScrgError {
+    fn from(err: ConfigError) -> Self {
+        match err {
+            ConfigError::Io(err) => err.into(),
+            err => Self::Serialization(Box::new(err)),
+        }
+    }
+}
+
+#[cfg(feature = "binary")]
+impl From<BinaryError> for ScrgError {
+    fn from(err: BinaryError) -> Self {
+        Self::Serialization(Box::new(err))
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl From<YamlImportError> for ScrgError {
+    fn from(err: YamlImportError) -> Self {
+        Self::Serialization(Box::new(err))
+    }
+}
+
+impl From<RegistryError> for ScrgError {
+    fn from(err: RegistryError) -> Self {
+        Self::Registry(err)
+    }
+}
+
+impl From<WriteError> for ScrgError {
+    fn from(err: WriteError) -> Self {
+        Self::Registry(RegistryError::Write(err))
+    }
+}
+
+impl From<RollbackError> for ScrgError {
+    fn from(err: RollbackError) -> Self {
+        Self::Registry(RegistryError::Rollback(err))
+    }
+}
+
+impl From<DependencyCycle> for ScrgError {
+    fn from(err: DependencyCycle) -> Self {
+        Self::Registry(RegistryError::DependencyCycle(err))
+    }
+}
+
+impl From<MergeConflict> for ScrgError {
+    fn from(err: MergeConflict) -> Self {
+        Self::Registry(RegistryError::MergeConflict(err))
+    }
+}
+
+// As with `DeployFailure`, the list of rolled-back contracts is dropped
+impl From<GroupDeployError> for ScrgError {
+    fn from(err: GroupDeployError) -> Self {
+        match err {
+            GroupDeployError::DependencyCycle(cycle) => cycle.into(),
+            GroupDeployError::Aborted { error, .. } => *error,
+        }
+    }
+}
+
+// Persistence boundary for registries; object-safe so callers can hold a `Box<dyn RegistryStore>`
+trait RegistryStore {
+    fn save(&mut self, registry: &ContractRegistry) -> Result<(), ScrgError>;
+    fn load(&self) -> Result<ContractRegistry, ScrgError>;
+    fn append(&mut self, contract: &DeployedContract) -> Result<(), ScrgError>;
+    fn list(&self) -> Result<Vec<String>, ScrgError>;
+}
+
+// Whole registry in one JSON document; a missing file loads as an empty registry
+#[cfg(feature = "serde")]
+struct JsonFileStore {
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "serde")]
+impl JsonFileStore {
+    fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl RegistryStore for JsonFileStore {
+    fn save(&mut self, registry: &ContractRegistry) -> Result<(), ScrgError> {
+        let json = registry.to_json()?;
+        // Write-then-rename so a crash never leaves a half-written registry behind
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<ContractRegistry, ScrgError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(json) => ContractRegistry::from_json(&json),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(ContractRegistry::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn append(&mut self, contract: &DeployedContract) -> Result<(), ScrgError> {
+        let mut registry = self.load()?;
+        registry.restore(contract.clone());
+        self.save(&registry)
+    }
+
+    fn list(&self) -> Result<Vec<String>, ScrgError> {
+        let mut names: Vec<String> = self.load()?.names().map(String::from).collect();
+        names.sort();
+        Ok(names)
+    }
+}
+
+// What the sled and SQLite stores keep next to their per-key metadata rows, as one JSON
+// document per contract version
+#[cfg(any(feature = "sled", feature = "sqlite"))]
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct StoredState {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    audit: Vec<AuditEntry>,
+    // Left out while it still equals the metadata, which it then shares again on load
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snapshot: Option<MetadataSnapshot>,
+    #[serde(default, skip_serializing_if = "MetadataLimits::is_default")]
+    limits: MetadataLimits,
+    // Sealed and compressed values, tagged with their kind (`{"sealed": …}`), which the
+    // per-key rows only hold in plain form
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    packed: BTreeMap<String, Packed>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    snapshot_packed: BTreeMap<String, Packed>,
+}
+
+#[cfg(any(feature = "sled", feature = "sqlite"))]
+impl StoredState {
+    fn of(contract: &DeployedContract) -> Self {
+        let edited = *contract.snapshot != *contract.metadata;
+        Self {
+            audit: contract.audit.to_vec(),
+            snapshot: edited.then(|| contract.snapshot.clone()),
+            limits: contract.limits,
+            packed: contract.packed.clone(),
+            snapshot_packed: contract.snapshot_packed.clone(),
+        }
+    }
+
+    fn encode(contract: &DeployedContract) -> Result<String, StoreError> {
+        serde_json::to_string(&Self::of(contract)).map_err(|err| StoreError::Codec(err.to_string()))
+    }
+
+    fn decode(json: &[u8]) -> Result<Self, StoreError> {
+        serde_json::from_slice(json).map_err(|err| StoreError::Codec(err.to_string()))
+    }
+
+    fn restore(self, contract: &mut DeployedContract) {
+        contract.audit = self.audit.into();
+        if let Some(snapshot) = self.snapshot {
+            contract.snapshot = snapshot;
+        }
+        contract.limits = self.limits;
+        contract.packed = self.packed;
+        contract.snapshot_packed = self.snapshot_packed;
+    }
+}
+
+// Durable store: a `contracts` index tree (`name@version` -> deploy time + name), a `state`
+// tree (`name@version` -> `StoredState`) plus one `contract/<name@version>` tree of
+// JSON-encoded metadata values per contract version
+#[cfg(feature = "sled")]
+struct SledStore {
+    db: sled::Db,
+    index: sled::Tree,
+    state: sled::Tree,
+}
+
+#[cfg(feature = "sled")]
+impl SledStore {
+    fn open(path: impl AsRef<std::path::Path>) -> Result<Self, ScrgError> {
+        let db = sled::open(path).map_err(sled_error)?;
+        let index = db.open_tree("contracts").map_err(sled_error)?;
+        let state = db.open_tree("state").map_err(sled_error)?;
+        Ok(Self { db, index, state })
+    }
+
+    fn contract_tree(&self, key: &str) -> Result<sled::Tree, StoreError> {
+        self.db
+            .open_tree(format!("contract/{key}"))
+            .map_err(sled_error)
+    }
+
+    // Index entry, state and metadata are replaced in a single transaction, then flushed
+    fn write_contract(&self, contract: &DeployedContract) -> Result<(), StoreError> {
+        use sled::Transactional;
+
+        let key = contract.storage_key();
+        let tree = self.contract_tree(&key)?;
+        let mut batch = sled::Batch::default();
+        for existing in tree.iter().keys() {
+            batch.remove(existing.map_err(sled_error)?);
+        }
+        for (meta_key, value) in contract.metadata.iter() {
+            let encoded =
+                serde_json::to_vec(value).map_err(|err| StoreError::Codec(err.to_string()))?;
+            batch.insert(meta_key.as_bytes(), encoded);
+        }
+        let deployed_at = contract
+            .deployed_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let mut entry = deployed_at.to_be_bytes().to_vec();
+        entry.extend_from_slice(contract.name.as_bytes());
+        let state = StoredState::encode(contract)?;
+
+        (&self.index, &self.state, &tree)
+            .transaction(|(index, states, tree)| {
+                tree.apply_batch(&batch)?;
+                index.insert(key.as_bytes(), entry.as_slice())?;
+                states.insert(key.as_bytes(), state.as_bytes())?;
+                Ok::<_, sled::transaction::ConflictableTransactionError<()>>(())
+            })
+            .map_err(|err| StoreError::Backend(format!("{err:?}")))?;
+        self.db.flush().map_err(sled_error)?;
+        Ok(())
+    }
+
+    // Splits an index entry into (deploy time, contract name)
+    fn decode_entry(key: &str, entry: &[u8]) -> Result<(u64, String), StoreError> {
+        let corrupt = || StoreError::Codec(format!("corrupt index entry for `{key}`"));
+        let (secs, name) = entry.split_at_checked(8).ok_or_else(corrupt)?;
+        let secs = u64::from_be_bytes(secs.try_into().map_err(|_| corrupt())?);
+        let name = String::from_utf8(name.to_vec()).map_err(|_| corrupt())?;
+        Ok((secs, name))
+    }
+
+    fn read_contract(&self, key: &str, entry: &[u8]) -> Result<DeployedContract, StoreError> {
+        let (secs, name) = Self::decode_entry(key, entry)?;
+        let mut metadata = MetadataMap::new();
+        for item in self.contract_tree(key)?.iter() {
+            let (meta_key, value) = item.map_err(sled_error)?;
+            let meta_key = String::from_utf8(meta_key.to_vec())
+                .map_err(|err| StoreError::Codec(err.to_string()))?;
+            let value =
+                serde_json::from_slice(&value).map_err(|err| StoreError::Codec(err.to_string()))?;
+            metadata.insert(meta_key, value);
+        }
+        let mut contract = DeployedContract::new(
+            name,
+            metadata,
+            SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs),
+        );
+        // Entries written before the `state` tree existed load with the defaults
+        if let Some(state) = self.state.get(key.as_bytes()).map_err(sled_error)? {
+            StoredState::decode(&state)?.restore(&mut contract);
+        }
+        Ok(contract)
+    }
+
+    fn index_entries(&self) -> Result<Vec<(String, sled::IVec)>, StoreError> {
+        self.index
+            .iter()
+            .map(|item| {
+                let (key, entry) = item.map_err(sled_error)?;
+                let key = String::from_utf8(key.to_vec())
+                    .map_err(|err| StoreError::Codec(err.to_string()))?;
+                Ok((key, entry))
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "sled")]
+fn sled_error(err: sled::Error) -> StoreError {
+    StoreError::Backend(err.to_string())
+}
+
+#[cfg(feature = "sled")]
+impl RegistryStore for SledStore {
+    fn save(&mut self, registry: &ContractRegistry) -> Result<(), ScrgError> {
+        let live: std::collections::HashSet<String> = registry
+            .all_versions()
+            .map(DeployedContract::storage_key)
+            .collect();
+        for (key, _) in self.index_entries()? {
+            if !live.contains(&key) {
+                self.index.remove(key.as_bytes()).map_err(sled_error)?;
+                self.state.remove(key.as_bytes()).map_err(sled_error)?;
+                self.db
+                    .drop_tree(format!("contract/{key}"))
+                    .map_err(sled_error)?;
+            }
+        }
+        for contract in registry.all_versions() {
+            self.write_contract(contract)?;
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> Result<ContractRegistry, ScrgError> {
+        let mut registry = ContractRegistry::new();
+        for (key, entry) in self.index_entries()? {
+            registry.restore(self.read_contract(&key, &entry)?);
+        }
+        Ok(registry)
+    }
+
+    fn append(&mut self, contract: &DeployedContract) -> Result<(), ScrgError> {
+        Ok(self.write_contract(contract)?)
+    }
+
+    fn list(&self) -> Result<Vec<String>, ScrgError> {
+        let mut names = self
+            .index_entries()?
+            .into_iter()
+            .map(|(key, entry)| Self::decode_entry(&key, &entry).map(|(_, name)| name))
+            .collect::<Result<Vec<_>, _>>()?;
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+}
+
+// One row per contract version plus one row per metadata key, so reports are plain SQL, e.g.
+//   SELECT c.name FROM contracts c
+//   JOIN contract_metadata m ON m.contract = c.name AND m.version = c.version
+//   WHERE m.key = 'network' AND m.value = 'mainnet'
+//     AND c.deployed_at >= strftime('%s', 'now', '-7 days')
+#[cfg(feature = "sqlite")]
+const SQLITE_SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS contracts (
+        name        TEXT NOT NULL,
+        version     TEXT NOT NULL DEFAULT '',
+        deployed_at INTEGER NOT NULL,
+        state       TEXT NOT NULL DEFAULT '{}',
+        PRIMARY KEY (name, version)
+    );
+    CREATE TABLE IF NOT EXISTS contract_metadata (
+        contract TEXT NOT NULL,
+        version  TEXT NOT NULL DEFAULT '',
+        key      TEXT NOT NULL,
+        kind     TEXT NOT NULL,
+        value    TEXT NOT NULL,
+        json     TEXT NOT NULL,
+        PRIMARY KEY (contract, version, key),
+        FOREIGN KEY (contract, version) REFERENCES contracts(name, version) ON DELETE CASCADE
+    );
+    CREATE INDEX IF NOT EXISTS contract_metadata_key_value ON contract_metadata(key, value);
+";
+
+#[cfg(feature = "sqlite")]
+struct SqliteStore {
+    conn: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    fn open(path: impl AsRef<std::path::Path>) -> Result<Self, ScrgError> {
+        Self::with_connection(rusqlite::Connection::open(path).map_err(sqlite_error)?)
+    }
+
+    fn in_memory() -> Result<Self, ScrgError> {
+        Self::with_connection(rusqlite::Connection::open_in_memory().map_err(sqlite_error)?)
+    }
+
+    fn with_connection(conn: rusqlite::Connection) -> Result<Self, ScrgError> {
+        conn.execute_batch(SQLITE_SCHEMA).map_err(sqlite_error)?;
+        Ok(Self { conn })
+    }
+
+    // Runs a reporting query whose first column is a contract name
+    fn report(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<String>, StoreError> {
+        let mut stmt = self.conn.prepare(sql).map_err(sqlite_error)?;
+        let names = stmt
+            .query_map(params, |row| row.get(0))
+            .map_err(sqlite_error)?
+            .collect::<Result<_, _>>()
+            .map_err(sqlite_error)?;
+        Ok(names)
+    }
+
+    fn write_contract(
+        tx: &rusqlite::Transaction<'_>,
+        contract: &DeployedContract,
+    ) -> Result<(), StoreError> {
+        let deployed_at = contract
+            .deployed_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs() as i64);
+        let version = contract
+            .version()
+            .map(Version::to_string)
+            .unwrap_or_default();
+        let state = StoredState::encode(contract)?;
+        tx.execute(
+            "INSERT INTO contracts (name, version, deployed_at, state) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(name, version) DO UPDATE
+             SET deployed_at = excluded.deployed_at, state = excluded.state",
+            rusqlite::params![contract.name, version, deployed_at, state],
+        )
+        .map_err(sqlite_error)?;
+        tx.execute(
+            "DELETE FROM contract_metadata WHERE contract = ?1 AND version = ?2",
+            [&contract.name, &version],
+        )
+        .map_err(sqlite_error)?;
+        for (key, value) in contract.metadata.iter() {
+            let json =
+                serde_json::to_string(value).map_err(|err| StoreError::Codec(err.to_string()))?;
+            tx.execute(
+                "INSERT INTO contract_metadata (contract, version, key, kind, value, json)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                rusqlite::params![
+                    contract.name,
+                    version,
+                    key,
+                    value.type_name(),
+                    value.to_string(),
+                    json
+                ],
+            )
+            .map_err(sqlite_error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(err: rusqlite::Error) -> StoreError {
+    StoreError::Backend(err.to_string())
+}
+
+#[cfg(feature = "sqlite")]
+impl RegistryStore for SqliteStore {
+    fn save(&mut self, registry: &ContractRegistry) -> Result<(), ScrgError> {
+        let tx = self.conn.transaction().map_err(sqlite_error)?;
+        tx.execute("DELETE FROM contracts", [])
+            .map_err(sqlite_error)?;
+        for contract in registry.all_versions() {
+            Self::write_contract(&tx, contract)?;
+        }
+        tx.commit().map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<ContractRegistry, ScrgError> {
+        let mut contracts = self
+            .conn
+            .prepare("SELECT name, version, deployed_at, state FROM contracts")
+            .map_err(sqlite_error)?;
+        let mut metadata = self
+            .conn
+            .prepare("SELECT key, json FROM contract_metadata WHERE contract = ?1 AND version = ?2")
+            .map_err(sqlite_error)?;
+
+        let rows: Vec<(String, String, i64, String)> = contracts
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .map_err(sqlite_error)?
+            .collect::<Result<_, _>>()
+            .map_err(sqlite_error)?;
+
+        let mut registry = ContractRegistry::new();
+        for (name, version, deployed_at, state) in rows {
+            let entries: Vec<(String, String)> = metadata
+                .query_map([&name, &version], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(sqlite_error)?
+                .collect::<Result<_, _>>()
+                .map_err(sqlite_error)?;
+            let metadata = entries
+                .into_iter()
+                .map(|(key, json)| {
+                    serde_json::from_str(&json)
+                        .map(|value| (key, value))
+                        .map_err(|err| StoreError::Codec(err.to_string()))
+                })
+                .collect::<Result<_, _>>()?;
+            let mut contract = DeployedContract::new(
+                name,
+                metadata,
+                SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(deployed_at.max(0) as u64),
+            );
+            StoredState::decode(state.as_bytes())?.restore(&mut contract);
+            registry.restore(contract);
+        }
+        Ok(registry)
+    }
+
+    fn append(&mut self, contract: &DeployedContract) -> Result<(), ScrgError> {
+        let tx = self.conn.transaction().map_err(sqlite_error)?;
+        Self::write_contract(&tx, contract)?;
+        tx.commit().map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, ScrgError> {
+        Ok(self.report("SELECT DISTINCT name FROM contracts ORDER BY name", [])?)
+    }
+}
+
+// Sorted, hand-editable layout: one `[[contracts.<name>]]` table per contract version, with
+// metadata as plain TOML values (see `metadata_to_toml`)
+#[cfg(feature = "toml")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TomlRegistry {
+    contracts: std::collections::BTreeMap<String, Vec<TomlContract>>,
+}
+
+#[cfg(feature = "toml")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TomlContract {
+    deployed_at: toml::value::Datetime,
+    metadata: toml::Table,
+    // Ciphertexts and codec output as `0x` hex, keyed like `metadata`
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    encrypted: std::collections::BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    compressed: std::collections::BTreeMap<String, String>,
+    // Deploy-time metadata; left out while it still equals `metadata`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    snapshot: Option<toml::Table>,
+    // Sealed and compressed deploy-time values, like `encrypted` and `compressed`
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    snapshot_encrypted: std::collections::BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    snapshot_compressed: std::collections::BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    audit: Vec<TomlAuditEntry>,
+}
+
+#[cfg(feature = "toml")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TomlAuditEntry {
+    key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    old: Option<toml::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    new: Option<toml::Value>,
+    // Compressed audited values as `0x` hex, in place of `old` or `new`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    old_compressed: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    new_compressed: Option<String>,
+    timestamp: toml::value::Datetime,
+    actor: String,
+}
+
+#[cfg(feature = "toml")]
+fn audit_to_toml(value: Option<&AuditValue>) -> (Option<toml::Value>, Option<String>) {
+    match value {
+        Some(AuditValue::Plain(value)) => (Some(metadata_to_toml(value)), None),
+        Some(AuditValue::Compressed { compressed }) => (None, Some(hex_string(&compressed.bytes))),
+        None => (None, None),
+    }
+}
+
+#[cfg(feature = "toml")]
+fn audit_from_toml(
+    key: &str,
+    plain: Option<toml::Value>,
+    compressed: Option<String>,
+) -> Result<Option<AuditValue>, toml::de::Error> {
+    if let Some(hex) = compressed {
+        let compressed = CompressedValue::new(hex_from_toml(key, &hex)?, None);
+        return Ok(Some(AuditValue::Compressed { compressed }));
+    }
+    Ok(plain
+        .map(metadata_from_toml)
+        .transpose()?
+        .map(AuditValue::from))
+}
+
+// Plain TOML for a metadata value. Addresses and bytes are written as `0x` strings and
+// versions as their text, and `metadata_from_toml` infers the type back from the string:
+// a valid address, other `0x` hex, then a semantic version. Values whose text points
+// elsewhere (e.g. an `Address` that is not 40 hex digits) load as what the text suggests.
+#[cfg(feature = "toml")]
+fn metadata_to_toml(value: &MetadataValue) -> toml::Value {
+    use toml::Value;
+    match value {
+        MetadataValue::String(s) | MetadataValue::Address(s) => Value::String(s.clone()),
+        MetadataValue::Int(i) => Value::Integer(*i),
+        MetadataValue::Bool(b) => Value::Boolean(*b),
+        MetadataValue::Timestamp(t) => Value::Datetime(toml_datetime(*t)),
+        MetadataValue::Version(v) => Value::String(v.to_string()),
+        MetadataValue::Bytes(bytes) => Value::String(hex_string(bytes)),
+        MetadataValue::List(items) => Value::Array(items.iter().map(metadata_to_toml).collect()),
+        MetadataValue::Map(map) => Value::Table(table_to_toml(map)),
+    }
+}
+
+#[cfg(feature = "toml")]
+fn table_to_toml(map: &MetadataMap) -> toml::Table {
+    map.iter()
+        .map(|(k, v)| (k.clone(), metadata_to_toml(v)))
+        .collect()
+}
+
+#[cfg(feature = "toml")]
+fn metadata_from_toml(value: toml::Value) -> Result<MetadataValue, toml::de::Error> {
+    use toml::Value;
+    Ok(match value {
+        Value::String(s) => infer_string(s),
+        Value::Integer(i) => MetadataValue::Int(i),
+        Value::Boolean(b) => MetadataValue::Bool(b),
+        Value::Float(n) => MetadataValue::String(n.to_string()),
+        Value::Datetime(dt) => MetadataValue::Timestamp(time_from_toml(&dt)?),
+        Value::Array(items) => MetadataValue::List(
+            items
+                .into_iter()
+                .map(metadata_from_toml)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Table(table) => MetadataValue::Map(table_from_toml(table)?),
+    })
+}
+
+#[cfg(feature = "toml")]
+fn table_from_toml(table: toml::Table) -> Result<MetadataMap, toml::de::Error> {
+    table
+        .into_iter()
+        .map(|(k, v)| Ok((k, metadata_from_toml(v)?)))
+        .collect()
+}
+
+#[cfg(feature = "toml")]
+fn infer_string(s: String) -> MetadataValue {
+    if s.parse::<Address>().is_ok() {
+        return MetadataValue::Address(s);
+    }
+    if s.starts_with("0x") {
+        if let Ok(bytes) = decode_hex(&s) {
+            return MetadataValue::Bytes(bytes);
+        }
+    }
+    match s.parse::<Version>() {
+        Ok(version) => MetadataValue::Version(version),
+        Err(_) => MetadataValue::String(s),
+    }
+}
+
+#[cfg(feature = "toml")]
+fn hex_string(bytes: &[u8]) -> String {
+    MetadataValue::Bytes(bytes.to_vec()).to_string()
+}
+
+#[cfg(feature = "toml")]
+fn toml_datetime(time: SystemTime) -> toml::value::Datetime {
+    format_rfc3339_nanos(time)
+        .parse()
+        .expect("RFC 3339 output is a TOML datetime")
+}
+
+#[cfg(feature = "toml")]
+fn time_from_toml(datetime: &toml::value::Datetime) -> Result<SystemTime, toml::de::Error> {
+    parse_rfc3339(&datetime.to_string()).ok_or_else(|| {
+        serde::de::Error::custom(format!(
+            "`{datetime}` is not a UTC date-time with an offset"
+        ))
+    })
+}
+
+#[cfg(feature = "toml")]
+fn hex_from_toml(key: &str, hex: &str) -> Result<Vec<u8>, toml::de::Error> {
+    decode_hex(hex).map_err(|reason| serde::de::Error::custom(format!("`{key}`: {reason}")))
+}
+
+// Hex tables of sealed and compressed values, in that order
+#[cfg(feature = "toml")]
+type TomlPacked = (
+    std::collections::BTreeMap<String, String>,
+    std::collections::BTreeMap<String, String>,
+);
+
+#[cfg(feature = "toml")]
+fn packed_to_toml(packed: &BTreeMap<String, Packed>) -> TomlPacked {
+    let mut encrypted = std::collections::BTreeMap::new();
+    let mut compressed = std::collections::BTreeMap::new();
+    for (key, packed) in packed {
+        match packed {
+            Packed::Sealed(sealed) => {
+                encrypted.insert(key.clone(), hex_string(sealed));
+            }
+            Packed::Compressed(value) => {
+                compressed.insert(key.clone(), hex_string(&value.bytes));
+            }
+        }
+    }
+    (encrypted, compressed)
+}
+
+#[cfg(feature = "toml")]
+fn packed_from_toml(
+    (encrypted, compressed): TomlPacked,
+) -> Result<BTreeMap<String, Packed>, toml::de::Error> {
+    let mut packed = BTreeMap::new();
+    for (key, hex) in encrypted {
+        let sealed = hex_from_toml(&key, &hex)?;
+        packed.insert(key, Packed::Sealed(sealed));
+    }
+    for (key, hex) in compressed {
+        let bytes = hex_from_toml(&key, &hex)?;
+        packed.insert(key, Packed::Compressed(CompressedValue::new(bytes, None)));
+    }
+    Ok(packed)
+}
+
+#[cfg(feature = "toml")]
+impl TomlContract {
+    fn from_contract(contract: &DeployedContract) -> Self {
+        let (encrypted, compressed) = packed_to_toml(&contract.packed);
+        let (snapshot_encrypted, snapshot_compressed) = packed_to_toml(&contract.snapshot_packed);
+        let audit = contract
+            .audit
+            .iter()
+            .map(|entry| {
+                let (old, old_compressed) = audit_to_toml(entry.old.as_ref());
+                let (new, new_compressed) = audit_to_toml(entry.new.as_ref());
+                TomlAuditEntry {
+                    key: entry.key.clone(),
+                    old,
+                    new,
+                    old_compressed,
+                    new_compressed,
+                    timestamp: toml_datetime(entry.timestamp),
+                    actor: entry.actor.clone(),
+                }
+            })
+            .collect();
+        Self {
+            deployed_at: toml_datetime(contract.deployed_at),
+            metadata: table_to_toml(&contract.metadata),
+            encrypted,
+            compressed,
+            snapshot: (*contract.snapshot.0 != *contract.metadata)
+                .then(|| table_to_toml(&contract.snapshot.0)),
+            snapshot_encrypted,
+            snapshot_compressed,
+            audit,
+        }
+    }
+
+    fn into_contract(self, name: String) -> Result<DeployedContract, toml::de::Error> {
+        let metadata = table_from_toml(self.metadata)?;
+        let mut contract =
+            DeployedContract::new(name, metadata, time_from_toml(&self.deployed_at)?);
+        if let Some(snapshot) = self.snapshot {
+            contract.snapshot = MetadataSnapshot(Arc::new(table_from_toml(snapshot)?));
+        }
+        contract.packed = packed_from_toml((self.encrypted, self.compressed))?;
+        contract.snapshot_packed =
+            packed_from_toml((self.snapshot_encrypted, self.snapshot_compressed))?;
+        contract.audit = self
+            .audit
+            .into_iter()
+            .map(|entry| {
+                Ok(AuditEntry {
+                    old: audit_from_toml(&entry.key, entry.old, entry.old_compressed)?,
+                    new: audit_from_toml(&entry.key, entry.new, entry.new_compressed)?,
+                    key: entry.key,
+                    timestamp: time_from_toml(&entry.timestamp)?,
+                    actor: entry.actor,
+                })
+            })
+            .collect::<Result<_, toml::de::Error>>()?;
+        Ok(contract)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl ContractRegistry {
+    fn to_toml(&self) -> Result<String, ScrgError> {
+        let contracts = self
+            .contracts
+            .iter()
+            .map(|(name, versions)| {
+                let entries = versions.iter().map(TomlContract::from_contract).collect();
+                (name.clone(), entries)
+            })
+            .collect();
+        Ok(toml::to_string_pretty(&TomlRegistry { contracts })?)
+    }
+
+    fn from_toml(input: &str) -> Result<Self, ScrgError> {
+        let parsed: TomlRegistry = toml::from_str(input)?;
+        let mut registry = Self::new();
+        for (name, entries) in parsed.contracts {
+            for entry in entries {
+                registry.restore(entry.into_contract(name.clone())?);
+            }
+        }
+        Ok(registry)
+    }
+}
+
+// Project-level `scrg.toml`: deployment definitions kept in reviewable config, e.g.
+//   [networks.mainnet]
+//   chain_id = 1
+//   rpc_url = "https://eth.example"
+//
+//   [[contracts]]
+//   name = "TokenX"
+//   author = "azaM"
+//   version = "1.0.0"
+//   network = "mainnet"
+//   tags = ["defi"]
+//   depends_on = ["LibraryA"]
+//   hooks = ["stamp_block"]
+//   metadata = { symbol = "TKX" }
+#[cfg(feature = "toml")]
+#[derive(serde::Deserialize)]
+struct ProjectConfig {
+    #[serde(default)]
+    networks: std::collections::BTreeMap<String, ConfigNetwork>,
+    #[serde(default)]
+    contracts: Vec<ConfigContract>,
+}
+
+#[cfg(feature = "toml")]
+#[derive(serde::Deserialize)]
+struct ConfigNetwork {
+    chain_id: u64,
+    rpc_url: Option<String>,
+}
+
+#[cfg(feature = "toml")]
+#[derive(serde::Deserialize)]
+struct ConfigContract {
+    name: String,
+    author: String,
+    version: Option<String>,
+    network: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    depends_on: Vec<String>,
+    #[serde(default)]
+    hooks: Vec<String>,
+    #[serde(default)]
+    metadata: std::collections::BTreeMap<String, toml::Value>,
+}
+
+#[cfg(feature = "toml")]
+#[derive(Debug)]
+enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    UnknownNetwork { contract: String, network: String },
+    UnknownHook { contract: String, hook: String },
+}
+
+#[cfg(feature = "toml")]
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "cannot read project config: {err}"),
+            Self::Parse(err) => write!(f, "invalid project config: {err}"),
+            Self::UnknownNetwork { contract, network } => {
+                write!(f, "`{contract}` targets undefined network `{network}`")
+            }
+            Self::UnknownHook { contract, hook } => {
+                write!(f, "`{contract}` uses unregistered hook `{hook}`")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::UnknownNetwork { .. } | Self::UnknownHook { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+fn toml_to_metadata(value: toml::Value) -> MetadataValue {
+    use toml::Value;
+    match value {
+        Value::String(s) => s.into(),
+        Value::Integer(i) => i.into(),
+        Value::Boolean(b) => b.into(),
+        Value::Float(n) => n.to_string().into(),
+        Value::Datetime(dt) => dt.to_string().into(),
+        Value::Array(items) => {
+            MetadataValue::List(items.into_iter().map(toml_to_metadata).collect())
+        }
+        Value::Table(entries) => MetadataValue::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_metadata(v)))
+                .collect(),
+        ),
+    }
+}
+
+// Turns a `ProjectConfig` into builders; hooks are referenced by name in the config and
+// registered here in code, each with the phase it runs in
+#[cfg(feature = "toml")]
+struct ContractFactory {
+    config: ProjectConfig,
+    hooks: HashMap<String, (Phase, TemplateHook)>,
+}
+
+#[cfg(feature = "toml")]
+impl ContractFactory {
+    fn from_toml(input: &str) -> Result<Self, ScrgError> {
+        Ok(Self {
+            config: toml::from_str(input).map_err(ConfigError::Parse)?,
+            hooks: HashMap::new(),
+        })
+    }
+
+    fn load(path: impl AsRef<std::path::Path>) -> Result<Self, ScrgError> {
+        Self::from_toml(&std::fs::read_to_string(path).map_err(ConfigError::Io)?)
+    }
+
+    fn with_hook<F>(mut self, name: &str, phase: Phase, hook: F) -> Self
+    where
+        F: Fn(&str, &mut MetadataMap) -> Result<(), HookError> + 'static,
+    {
+        self.hooks.insert(name.into(), (phase, Rc::new(hook)));
+        self
+    }
+
+    // One builder per `[[contracts]]` entry, in file order, ready for `validate()` or
+    // `ContractRegistry::deploy_all`. Fails on the first undefined network or hook name.
+    fn builders(&self) -> Result<Vec<ContractBuilder<Init<AuthorSet>>>, ScrgError> {
+        self.config
+            .contracts
+            .iter()
+            .map(|entry| self.builder(entry))
+            .collect()
+    }
+
+    fn builder(
+        &self,
+        entry: &ConfigContract,
+    ) -> Result<ContractBuilder<Init<AuthorSet>>, ScrgError> {
+        let mut builder = ContractBuilder::new(&entry.name).with_author(&entry.author);
+        if let Some(version) = &entry.version {
+            builder = builder.with_version(version);
+        }
+        if let Some(name) = &entry.network {
+            let network =
+                self.config
+                    .networks
+                    .get(name)
+                    .ok_or_else(|| ConfigError::UnknownNetwork {
+                        contract: entry.name.clone(),
+                        network: name.clone(),
+                    })?;
+            let mut target = Network::new(name, network.chain_id);
+            if let Some(rpc_url) = &network.rpc_url {
+                target = target.with_rpc_url(rpc_url);
+            }
+            builder = builder.with_network(target);
+        }
+        for (key, value) in &entry.metadata {
+            builder = builder.with_metadata(key, toml_to_metadata(value.clone()));
+        }
+        if !entry.tags.is_empty() {
+            builder = builder.with_tags(entry.tags.iter().cloned());
+        }
+        for dependency in &entry.depends_on {
+            builder = builder.depends_on(dependency);
+        }
+        for name in &entry.hooks {
+            let (phase, hook) = self
+                .hooks
+                .get(name)
+                .ok_or_else(|| ConfigError::UnknownHook {
+                    contract: entry.name.clone(),
+                    hook: name.clone(),
+                })?;
+            let hook = Rc::clone(hook);
+            builder = builder.on_phase(*phase, move |name, meta| hook(name, meta));
+        }
+        Ok(builder)
+    }
+}
+
+// Bulk contract definitions, e.g.
+//   contracts:
+//     - name: TokenX
+//       author: azaM
+//       metadata: { network: mainnet }
+//       tags: [defi, v2]
+//       depends_on: [LibraryA]
+#[cfg(feature = "yaml")]
+#[derive(serde::Deserialize)]
+struct YamlDocument {
+    contracts: Vec<YamlContract>,
+}
+
+#[cfg(feature = "yaml")]
+#[derive(serde::Deserialize)]
+struct YamlContract {
+    name: String,
+    author: String,
+    #[serde(default)]
+    metadata: std::collections::BTreeMap<String, serde_yaml::Value>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    depends_on: Vec<String>,
+}
+
+#[cfg(feature = "yaml")]
+#[derive(Debug)]
+enum YamlImportError {
+    Parse(serde_yaml::Error),
+    // As in `DeployReport`: entries that failed, and entries skipped because of them
+    Contracts {
+        failed: Vec<(String, ScrgError)>,
+        skipped: Vec<(String, String)>,
+    },
+}
+
+#[cfg(feature = "yaml")]
+impl fmt::Display for YamlImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "invalid contract YAML: {err}"),
+            Self::Contracts { failed, skipped } => {
+                let count = failed.len() + skipped.len();
+                write!(f, "{count} contract(s) failed to import")?;
+                for (name, err) in failed {
+                    write!(f, "; {name}: {err}")?;
+                }
+                for (name, upstream) in skipped {
+                    write!(f, "; {name}: depends on `{upstream}`, which failed")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl std::error::Error for YamlImportError {}
+
+#[cfg(feature = "yaml")]
+fn yaml_to_metadata(value: serde_yaml::Value) -> MetadataValue {
+    use serde_yaml::Value;
+    match value {
+        Value::Null => MetadataValue::String(String::new()),
+        Value::Bool(b) => b.into(),
+        Value::Number(n) => n
+            .as_i64()
+            .map_or_else(|| n.to_string().into(), MetadataValue::Int),
+        Value::String(s) => s.into(),
+        Value::Sequence(items) => {
+            MetadataValue::List(items.into_iter().map(yaml_to_metadata).collect())
+        }
+        Value::Mapping(entries) => MetadataValue::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| {
+                    let key = k.as_str().map_or_else(|| format!("{k:?}"), str::to_owned);
+                    (key, yaml_to_metadata(v))
+                })
+                .collect(),
+        ),
+        Value::Tagged(tagged) => yaml_to_metadata(tagged.value),
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl ContractRegistry {
+    // Builds every entry and deploys them through `deploy_all`, so entries may list their
+    // dependencies in any order; any failure rejects the whole import
+    fn from_yaml(input: &str) -> Result<Self, ScrgError> {
+        let document: YamlDocument = serde_yaml::from_str(input).map_err(YamlImportError::Parse)?;
+        let builders = document
+            .contracts
+            .into_iter()
+            .map(|entry| {
+                let mut builder = ContractBuilder::new(&entry.name).with_author(&entry.author);
+                for (key, value) in entry.metadata {
+                    builder = builder.with_metadata(&key, yaml_to_metadata(value));
+                }
+                if !entry.tags.is_empty() {
+                    builder = builder.with_tags(entry.tags);
+                }
+                for dependency in &entry.depends_on {
+                    builder = builder.depends_on(dependency);
+                }
+                builder
+            })
+            .collect();
+
+        let mut registry = Self::new();
+        let report = registry.deploy_all(builders)?;
+        if report.is_success() {
+            Ok(registry)
+        } else {
+            Err(YamlImportError::Contracts {
+                failed: report.failed,
+                skipped: report.skipped,
+            }
+            .into())
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+const CLI_USAGE: &str = "\
+usage: scrg [--registry PATH] <command>
+
+commands:
+  help                          print this message
+  list                          latest version, version and status of every listed contract
+  show NAME                     all metadata of the latest version of NAME
+  add NAME --author AUTHOR [--version V] [--tag TAG]... [--depends-on NAME]... [--set KEY=VALUE]...
+  deploy NAME --version V [--hook-timestamp] [--hook-signer ADDRESS] [--set KEY=VALUE]...
+                                deploy a new version of NAME, keeping its author, tags and dependencies
+
+The registry defaults to `registry.json` in the current directory.";
+
+// `true`/`false`, integers and `0x…` addresses keep their type; everything else is a string.
+// Shared by CLI `--meta` values and `with_env_defaults`.
+fn parse_plain_value(raw: &str) -> MetadataValue {
+    if let Ok(b) = raw.parse::<bool>() {
+        b.into()
+    } else if let Ok(i) = raw.parse::<i64>() {
+        i.into()
+    } else if raw.starts_with("0x") {
+        MetadataValue::Address(raw.into())
+    } else {
+        raw.into()
+    }
+}
+
+#[cfg(feature = "cli")]
+#[derive(Default)]
+struct CliOptions {
+    author: Option<String>,
+    version: Option<String>,
+    tags: Vec<String>,
+    dependencies: Vec<String>,
+    metadata: Vec<(String, MetadataValue)>,
+    hook_timestamp: bool,
+    hook_signer: Option<String>,
+}
+
+#[cfg(feature = "cli")]
+impl CliOptions {
+    fn parse(args: &[String]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("`{flag}` expects a value"))
+            };
+            match flag.as_str() {
+                "--author" => options.author = Some(value()?),
+                "--version" => options.version = Some(value()?),
+                "--tag" => options.tags.push(value()?),
+                "--depends-on" => options.dependencies.push(value()?),
+                "--set" => {
+                    let pair = value()?;
+                    let (key, raw) = pair
+                        .split_once('=')
+                        .ok_or_else(|| format!("`--set {pair}` is not KEY=VALUE"))?;
+                    options.metadata.push((key.into(), parse_plain_value(raw)));
+                }
+                "--hook-timestamp" => options.hook_timestamp = true,
+                "--hook-signer" => options.hook_signer = Some(value()?),
+                other => return Err(format!("unknown option `{other}`\n\n{CLI_USAGE}").into()),
+            }
+        }
+        Ok(options)
+    }
+
+    fn build(self, name: &str, author: &str) -> Result<DeployedContract, ScrgError> {
+        let mut builder = ContractBuilder::new(name).with_author(author);
+        if let Some(version) = &self.version {
+            builder = builder.with_version(version);
+        }
+        if !self.tags.is_empty() {
+            builder = builder.with_tags(self.tags);
+        }
+        for dependency in &self.dependencies {
+            builder = builder.depends_on(dependency);
+        }
+        for (key, value) in self.metadata {
+            builder = builder.with_metadata(&key, value);
+        }
+
+        let mut validated = builder.validate()?;
+        if self.hook_timestamp {
+            validated = validated.add_hook(|meta| {
+                meta.insert("timestamp".into(), now().into());
+            });
+        }
+        if let Some(signer) = self.hook_signer {
+            validated = validated.add_hook(move |meta| {
+                meta.insert("signer".into(), MetadataValue::Address(signer.clone()));
+            });
+        }
+        Ok(validated.deploy()?.into_deployed())
+    }
+}
+
+// Entry point for `scrg <args>`; every mutating command saves the registry back
+#[cfg(feature = "cli")]
+fn run_cli(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if let [command] = args {
+        if matches!(command.as_str(), "help" | "--help" | "-h") {
+            println!("{CLI_USAGE}");
+            return Ok(());
+        }
+    }
+    let (path, args) = match args {
+        [flag, path, rest @ ..] if flag == "--registry" => (path.as_str(), rest),
+        _ => ("registry.json", args),
+    };
+    let mut store = JsonFileStore::new(path);
+    run_command(&mut store, args)
+}
+
+#[cfg(feature = "cli")]
+fn run_command(
+    store: &mut impl RegistryStore,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut registry = store.load()?;
+    match args {
+        [command] if command == "list" => {
+            let mut contracts: Vec<_> = registry.list().collect();
+            contracts.sort_by(|a, b| a.name().cmp(b.name()));
+            for contract in contracts {
+                let version = contract
+                    .version()
+                    .map_or_else(|| "-".into(), Version::to_string);
+                let status = contract.status().map_or("-", LifecycleStatus::as_str);
+                println!("{:<24} {version:<12} {status}", contract.name());
+            }
+        }
+        [command, name] if command == "show" => {
+            let contract = registry
+                .latest(name)
+                .ok_or_else(|| format!("contract `{name}` is not registered"))?;
+            let mut entries: Vec<_> = contract.metadata().iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            println!("📘 Contract Metadata ({}):", contract.name());
+            for (k, v) in entries {
+                println!("  {k}: {v}");
+            }
+        }
+        [command, name, rest @ ..] if command == "add" => {
+            let mut options = CliOptions::parse(rest)?;
+            let author = options.author.take().ok_or("`add` requires --author")?;
+            registry.register(options.build(name, &author)?)?;
+            store.save(&registry)?;
+            println!("added {name}");
+        }
+        [command, name, rest @ ..] if command == "deploy" => {
+            let mut options = CliOptions::parse(rest)?;
+            if options.version.is_none() {
+                return Err("`deploy` requires --version".into());
+            }
+            let current = registry
+                .latest(name)
+                .ok_or_else(|| format!("contract `{name}` is not registered"))?;
+            let author = match options.author.take() {
+                Some(author) => author,
+                None => current
+                    .get("author")
+                    .and_then(MetadataValue::as_str)
+                    .ok_or_else(|| format!("contract `{name}` has no author"))?
+                    .to_owned(),
+            };
+            options
+                .tags
+                .extend(current.tags().into_iter().map(str::to_owned));
+            options
+                .dependencies
+                .extend(current.dependencies().into_iter().map(str::to_owned));
+            let contract = options.build(name, &author)?;
+            let version = contract
+                .version()
+                .map(Version::to_string)
+                .unwrap_or_default();
+            registry.register(contract)?;
+            store.save(&registry)?;
+            println!("deployed {name}@{version}");
+        }
+        _ => return Err(CLI_USAGE.into()),
+    }
+    Ok(())
+}
+
+// Plain JSON for the language bindings: integers stay numbers, other numbers and `null` become strings
+#[cfg(any(feature = "wasm", feature = "ffi", feature = "http"))]
+fn json_to_metadata(value: serde_json::Value) -> MetadataValue {
+    use serde_json::Value;
+    match value {
+        Value::Null => MetadataValue::String(String::new()),
+        Value::Bool(b) => b.into(),
+        Value::Number(n) => n
+            .as_i64()
+            .map_or_else(|| n.to_string().into(), MetadataValue::Int),
+        Value::String(s) => s.into(),
+        Value::Array(items) => {
+            MetadataValue::List(items.into_iter().map(json_to_metadata).collect())
+        }
+        Value::Object(entries) => MetadataValue::Map(json_object_to_metadata(entries)),
+    }
+}
+
+#[cfg(any(feature = "wasm", feature = "ffi", feature = "http"))]
+fn json_object_to_metadata(entries: serde_json::Map<String, serde_json::Value>) -> MetadataMap {
+    entries
+        .into_iter()
+        .map(|(k, v)| (k, json_to_metadata(v)))
+        .collect()
+}
+
+// Timestamps, versions, addresses and bytes are rendered as in `Display`
+#[cfg(any(feature = "wasm", feature = "ffi", feature = "http"))]
+fn metadata_to_json(value: &MetadataValue) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        MetadataValue::Int(i) => (*i).into(),
+        MetadataValue::Bool(b) => (*b).into(),
+        MetadataValue::List(items) => Value::Array(items.iter().map(metadata_to_json).collect()),
+        MetadataValue::Map(map) => map_to_json(map),
+        other => Value::String(other.to_string()),
+    }
+}
+
+#[cfg(any(feature = "wasm", feature = "ffi", feature = "http"))]
+fn map_to_json(map: &MetadataMap) -> serde_json::Value {
+    serde_json::Value::Object(
+        map.iter()
+            .map(|(k, v)| (k.clone(), metadata_to_json(v)))
+            .collect(),
+    )
+}
+
+#[cfg(any(feature = "wasm", feature = "ffi"))]
+fn parse_json_map(json: &str) -> Result<MetadataMap, ScrgError> {
+    match json_to_metadata(serde_json::from_str(json)?) {
+        MetadataValue::Map(entries) => Ok(entries),
+        _ => Err(ScrgError::Serialization(
+            "metadata must be a JSON object".into(),
+        )),
+    }
+}
+
+// Assembles and validates a builder from plain metadata for the language bindings and
+// network services, which cannot hold the typestate; `author` is required and a string
+// `version` is parsed
+fn validate_metadata(
+    name: &str,
+    metadata: &MetadataMap,
+) -> Result<ContractBuilder<Validated>, ScrgError> {
+    let author = metadata
+        .get("author")
+        .and_then(MetadataValue::as_str)
+        .ok_or_else(|| ValidationError::MissingKey("author".into()))?;
+    let mut builder = ContractBuilder::new(name).with_author(author);
+    for (key, value) in metadata {
+        builder = match (key.as_str(), value.as_str()) {
+            ("author", _) => builder,
+            ("version", Some(version)) => builder.with_version(version),
+            _ => builder.with_metadata(key, value.clone()),
+        };
+    }
+    builder.validate()
+}
+
+fn deploy_metadata(name: &str, metadata: &MetadataMap) -> Result<DeployedContract, ScrgError> {
+    Ok(validate_metadata(name, metadata)?.deploy()?.into_deployed())
+}
+
+// Validates, deploys and registers; returns the deployed metadata
+fn register_metadata(
+    registry: &mut ContractRegistry,
+    name: &str,
+    metadata: &MetadataMap,
+) -> Result<MetadataMap, ScrgError> {
+    let contract = deploy_metadata(name, metadata)?;
+    let deployed = contract.metadata().clone();
+    registry.register(contract)?;
+    Ok(deployed)
+}
+
+// `ContractBuilder` for JavaScript. The typestate cannot cross the boundary, so metadata is
+// collected here and the real builder is assembled on `validate()`
+#[cfg(feature = "wasm")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = ContractBuilder)]
+pub struct JsContractBuilder {
+    name: String,
+    metadata: MetadataMap,
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_class = ContractBuilder)]
+impl JsContractBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            metadata: MetadataMap::new(),
+        }
+    }
+
+    #[wasm_bindgen(js_name = withAuthor)]
+    pub fn with_author(mut self, author: &str) -> Self {
+        self.metadata.insert("author".into(), author.into());
+        self
+    }
+
+    #[wasm_bindgen(js_name = withVersion)]
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.metadata.insert("version".into(), version.into());
+        self
+    }
+
+    // Merges the keys of a JSON object, e.g. `{"standard": "ERC20", "decimals": 18}`
+    #[wasm_bindgen(js_name = withMetadata)]
+    pub fn with_metadata(mut self, json: &str) -> Result<Self, wasm_bindgen::JsError> {
+        self.metadata.extend(parse_json_map(json)?);
+        Ok(self)
+    }
+
+    // Validated metadata as a JSON object; throws the `ValidationError` message otherwise
+    pub fn validate(&self) -> Result<String, wasm_bindgen::JsError> {
+        let validated = validate_metadata(&self.name, &self.metadata)?;
+        let json = map_to_json(&validated.borrow()).to_string();
+        Ok(json)
+    }
+}
+
+// `ContractRegistry` for JavaScript; contracts go in as `ContractBuilder`s and come out as JSON
+#[cfg(feature = "wasm")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = ContractRegistry)]
+pub struct JsContractRegistry {
+    inner: ContractRegistry,
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_class = ContractRegistry)]
+impl JsContractRegistry {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            inner: ContractRegistry::new(),
+        }
+    }
+
+    // Restores a registry saved with `toJson()`
+    #[wasm_bindgen(js_name = fromJson)]
+    pub fn from_json(json: &str) -> Result<Self, wasm_bindgen::JsError> {
+        Ok(Self {
+            inner: ContractRegistry::from_json(json)?,
+        })
+    }
+
+    // Validates, deploys and registers; returns the deployed metadata as JSON
+    pub fn register(
+        &mut self,
+        builder: &JsContractBuilder,
+    ) -> Result<String, wasm_bindgen::JsError> {
+        let deployed = register_metadata(&mut self.inner, &builder.name, &builder.metadata)?;
+        Ok(map_to_json(&deployed).to_string())
+    }
+
+    // Latest version of `name` as a JSON object, or `undefined`
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.inner
+            .latest(name)
+            .map(|contract| map_to_json(contract.metadata()).to_string())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.inner.names().map(str::to_owned).collect()
+    }
+
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> Result<String, wasm_bindgen::JsError> {
+        Ok(self.inner.to_json()?)
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl Default for JsContractRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Python exceptions: validation problems raise `ValueError`, everything else `RuntimeError`
+#[cfg(feature = "python")]
+impl From<ScrgError> for pyo3::PyErr {
+    fn from(err: ScrgError) -> Self {
+        match err {
+            ScrgError::Validation(_) => pyo3::exceptions::PyValueError::new_err(err.to_string()),
+            _ => pyo3::exceptions::PyRuntimeError::new_err(err.to_string()),
+        }
+    }
+}
+
+// `bool`, `int`, `str`, `bytes`, `list` and `dict` with `str` keys
+#[cfg(feature = "python")]
+fn py_to_metadata(value: &pyo3::Bound<'_, pyo3::PyAny>) -> pyo3::PyResult<MetadataValue> {
+    use pyo3::{
+        prelude::*,
+        types::{PyBool, PyBytes, PyDict, PyList},
+    };
+    if value.is_instance_of::<PyBool>() {
+        Ok(value.extract::<bool>()?.into())
+    } else if let Ok(i) = value.extract::<i64>() {
+        Ok(MetadataValue::Int(i))
+    } else if let Ok(s) = value.extract::<String>() {
+        Ok(s.into())
+    } else if let Ok(bytes) = value.downcast::<PyBytes>() {
+        Ok(MetadataValue::Bytes(bytes.as_bytes().to_vec()))
+    } else if let Ok(list) = value.downcast::<PyList>() {
+        list.iter()
+            .map(|item| py_to_metadata(&item))
+            .collect::<PyResult<_>>()
+            .map(MetadataValue::List)
+    } else if let Ok(dict) = value.downcast::<PyDict>() {
+        dict.iter()
+            .map(|(k, v)| Ok((k.extract::<String>()?, py_to_metadata(&v)?)))
+            .collect::<PyResult<_>>()
+            .map(MetadataValue::Map)
+    } else {
+        Err(pyo3::exceptions::PyTypeError::new_err(format!(
+            "unsupported metadata value {value}"
+        )))
+    }
+}
+
+// Timestamps, versions and addresses become strings as in `Display`
+#[cfg(feature = "python")]
+fn metadata_to_py(py: pyo3::Python<'_>, value: &MetadataValue) -> pyo3::PyResult<pyo3::PyObject> {
+    use pyo3::{prelude::*, types::PyBytes};
+    Ok(match value {
+        MetadataValue::Int(i) => (*i).into_py(py),
+        MetadataValue::Bool(b) => (*b).into_py(py),
+        MetadataValue::Bytes(bytes) => PyBytes::new_bound(py, bytes).into_py(py),
+        MetadataValue::List(items) => items
+            .iter()
+            .map(|item| metadata_to_py(py, item))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_py(py),
+        MetadataValue::Map(map) => map_to_py(py, map)?,
+        other => other.to_string().into_py(py),
+    })
+}
+
+#[cfg(feature = "python")]
+fn map_to_py(py: pyo3::Python<'_>, map: &MetadataMap) -> pyo3::PyResult<pyo3::PyObject> {
+    use pyo3::{prelude::*, types::PyDict};
+    let dict = PyDict::new_bound(py);
+    for (key, value) in map {
+        dict.set_item(key, metadata_to_py(py, value)?)?;
+    }
+    Ok(dict.into_py(py))
+}
+
+// `ContractBuilder` for Python. Setters return the builder so calls chain as in Rust:
+// `ContractBuilder("Token").with_author("alice").with_metadata("decimals", 18).validate()`
+#[cfg(feature = "python")]
+#[pyo3::pyclass(name = "ContractBuilder")]
+struct PyContractBuilder {
+    name: String,
+    metadata: MetadataMap,
+}
+
+#[cfg(feature = "python")]
+#[pyo3::pymethods]
+impl PyContractBuilder {
+    #[new]
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            metadata: MetadataMap::new(),
+        }
+    }
+
+    fn with_author<'py>(
+        mut slf: pyo3::PyRefMut<'py, Self>,
+        author: &str,
+    ) -> pyo3::PyRefMut<'py, Self> {
+        slf.metadata.insert("author".into(), author.into());
+        slf
+    }
+
+    fn with_version<'py>(
+        mut slf: pyo3::PyRefMut<'py, Self>,
+        version: &str,
+    ) -> pyo3::PyRefMut<'py, Self> {
+        slf.metadata.insert("version".into(), version.into());
+        slf
+    }
+
+    fn with_metadata<'py>(
+        mut slf: pyo3::PyRefMut<'py, Self>,
+        key: &str,
+        value: &pyo3::Bound<'_, pyo3::PyAny>,
+    ) -> pyo3::PyResult<pyo3::PyRefMut<'py, Self>> {
+        let value = py_to_metadata(value)?;
+        slf.metadata.insert(key.into(), value);
+        Ok(slf)
+    }
+
+    // Validated metadata as a `dict`; raises `ValueError` otherwise
+    fn validate(&self, py: pyo3::Python<'_>) -> pyo3::PyResult<pyo3::PyObject> {
+        let validated = validate_metadata(&self.name, &self.metadata)?;
+        let metadata = validated.borrow();
+        map_to_py(py, &metadata)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ContractBuilder({:?})", self.name)
+    }
+}
+
+// Registry hooks are `Rc`-based, so instances stay on the thread that created them
+#[cfg(feature = "python")]
+#[pyo3::pyclass(name = "ContractRegistry", unsendable)]
+#[derive(Default)]
+struct PyContractRegistry {
+    inner: ContractRegistry,
+}
+
+#[cfg(feature = "python")]
+#[pyo3::pymethods]
+impl PyContractRegistry {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    // Validates, deploys and registers; returns the deployed metadata as a `dict`
+    fn register(
+        &mut self,
+        py: pyo3::Python<'_>,
+        builder: &PyContractBuilder,
+    ) -> pyo3::PyResult<pyo3::PyObject> {
+        let deployed = register_metadata(&mut self.inner, &builder.name, &builder.metadata)?;
+        map_to_py(py, &deployed)
+    }
+
+    // Latest version of `name`, or `None`
+    fn get(&self, py: pyo3::Python<'_>, name: &str) -> pyo3::PyResult<Option<pyo3::PyObject>> {
+        self.inner
+            .latest(name)
+            .map(|contract| map_to_py(py, contract.metadata()))
+            .transpose()
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.inner.names().map(str::to_owned).collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.inner.latest(name).is_some()
+    }
+}
+
+// `import scrg`
+#[cfg(feature = "python")]
+#[pyo3::pymodule]
+fn scrg(module: &pyo3::Bound<'_, pyo3::types::PyModule>) -> pyo3::PyResult<()> {
+    use pyo3::prelude::*;
+    module.add_class::<PyContractBuilder>()?;
+    module.add_class::<PyContractRegistry>()?;
+    Ok(())
+}
+
+// C API. Registries are opaque handles; metadata crosses as NUL-terminated UTF-8 JSON.
+// Calls returning `ScrgStatus::Failed` or NULL leave a message for `scrg_last_error()`.
+#[cfg(feature = "ffi")]
+pub struct ScrgRegistry(ContractRegistry);
+
+#[cfg(feature = "ffi")]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrgStatus {
+    Ok = 0,
+    /// NULL handle or pointer, or a string that is not UTF-8
+    InvalidArgument = 1,
+    /// Validation, deployment or registration failed; see `scrg_last_error()`
+    Failed = 2,
+}
+
+#[cfg(feature = "ffi")]
+thread_local! {
+    static LAST_ERROR: RefCell<Option<std::ffi::CString>> = const { RefCell::new(None) };
+}
+
+#[cfg(feature = "ffi")]
+fn set_last_error(message: impl fmt::Display) {
+    let message = message.to_string().replace('\0', " ");
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = std::ffi::CString::new(message).ok());
+}
+
+#[cfg(feature = "ffi")]
+unsafe fn ffi_str<'a>(ptr: *const std::ffi::c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        set_last_error("unexpected NULL pointer");
+        return None;
+    }
+    let text = std::ffi::CStr::from_ptr(ptr).to_str();
+    if text.is_err() {
+        set_last_error("string is not valid UTF-8");
+    }
+    text.ok()
+}
+
+/// Creates an empty registry; free it with `scrg_registry_free`.
+#[cfg(feature = "ffi")]
+#[no_mangle]
+pub extern "C" fn scrg_registry_new() -> *mut ScrgRegistry {
+    Box::into_raw(Box::new(ScrgRegistry(ContractRegistry::new())))
+}
+
+/// # Safety
+/// `registry` is NULL or came from `scrg_registry_new` and is not used afterwards.
+#[cfg(feature = "ffi")]
+#[no_mangle]
+pub unsafe extern "C" fn scrg_registry_free(registry: *mut ScrgRegistry) {
+    if !registry.is_null() {
+        drop(Box::from_raw(registry));
+    }
+}
+
+/// Validates, deploys and registers `name` with the metadata in the JSON object
+/// `metadata_json`, which must carry `author`.
+///
+/// # Safety
+/// `registry` is a live handle and both strings are NUL-terminated.
+#[cfg(feature = "ffi")]
+#[no_mangle]
+pub unsafe extern "C" fn scrg_register(
+    registry: *mut ScrgRegistry,
+    name: *const std::ffi::c_char,
+    metadata_json: *const std::ffi::c_char,
+) -> ScrgStatus {
+    let (Some(registry), Some(name), Some(json)) =
+        (registry.as_mut(), ffi_str(name), ffi_str(metadata_json))
+    else {
+        return ScrgStatus::InvalidArgument;
+    };
+    match parse_json_map(json).and_then(|meta| register_metadata(&mut registry.0, name, &meta)) {
+        Ok(_) => ScrgStatus::Ok,
+        Err(err) => {
+            set_last_error(err);
+            ScrgStatus::Failed
+        }
+    }
+}
+
+/// Metadata of the latest version of `name` as a JSON object, or NULL if it is not
+/// registered. Free the result with `scrg_string_free`.
+///
+/// # Safety
+/// `registry` is a live handle and `name` is NUL-terminated.
+#[cfg(feature = "ffi")]
+#[no_mangle]
+pub unsafe extern "C" fn scrg_get_json(
+    registry: *const ScrgRegistry,
+    name: *const std::ffi::c_char,
+) -> *mut std::ffi::c_char {
+    let (Some(registry), Some(name)) = (registry.as_ref(), ffi_str(name)) else {
+        return std::ptr::null_mut();
+    };
+    let Some(contract) = registry.0.latest(name) else {
+        set_last_error(format!("contract `{name}` is not registered"));
+        return std::ptr::null_mut();
+    };
+    std::ffi::CString::new(map_to_json(contract.metadata()).to_string())
+        .map_or(std::ptr::null_mut(), std::ffi::CString::into_raw)
+}
+
+/// # Safety
+/// `text` is NULL or came from `scrg_get_json` and is not used afterwards.
+#[cfg(feature = "ffi")]
+#[no_mangle]
+pub unsafe extern "C" fn scrg_string_free(text: *mut std::ffi::c_char) {
+    if !text.is_null() {
+        drop(std::ffi::CString::from_raw(text));
+    }
+}
+
+/// Message of the last failure on this thread, or NULL; valid until the next failing call.
+#[cfg(feature = "ffi")]
+#[no_mangle]
+pub extern "C" fn scrg_last_error() -> *const std::ffi::c_char {
+    LAST_ERROR.with(|slot| {
+        slot.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |m| m.as_ptr())
+    })
+}
+
+// Wire types generated from `proto/scrg.proto` by `build.rs`; with `grpc` the file also
+// holds the tonic service
+#[cfg(feature = "protobuf")]
+mod pb {
+    include!(concat!(env!("OUT_DIR"), "/scrg.v1.rs"));
+}
+
+// A message that decodes but does not map onto registry types
+#[cfg(feature = "protobuf")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProtoError(String);
+
+#[cfg(feature = "protobuf")]
+impl fmt::Display for ProtoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid protobuf message: {}", self.0)
+    }
+}
+
+#[cfg(feature = "protobuf")]
+impl std::error::Error for ProtoError {}
+
+#[cfg(feature = "protobuf")]
+impl From<prost::DecodeError> for ProtoError {
+    fn from(err: prost::DecodeError) -> Self {
+        Self(err.to_string())
+    }
+}
+
+#[cfg(feature = "protobuf")]
+impl From<ProtoError> for ScrgError {
+    fn from(err: ProtoError) -> Self {
+        Self::Serialization(Box::new(err))
+    }
+}
+
+#[cfg(feature = "grpc")]
+impl From<ProtoError> for tonic::Status {
+    fn from(err: ProtoError) -> Self {
+        Self::invalid_argument(err.to_string())
+    }
+}
+
+#[cfg(feature = "grpc")]
+impl From<ScrgError> for tonic::Status {
+    fn from(err: ScrgError) -> Self {
+        match err {
+            ScrgError::Validation(_) => Self::invalid_argument(err.to_string()),
+            ScrgError::Registry(RegistryError::AccessDenied(_)) => {
+                Self::permission_denied(err.to_string())
+            }
+            ScrgError::Registry(_) => Self::failed_precondition(err.to_string()),
+            _ => Self::internal(err.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "protobuf")]
+fn value_to_pb(value: &MetadataValue) -> pb::Value {
+    use pb::value::Kind;
+    let kind = match value {
+        MetadataValue::String(s) => Kind::String(s.clone()),
+        MetadataValue::Int(i) => Kind::Int(*i),
+        MetadataValue::Bool(b) => Kind::Bool(*b),
+        MetadataValue::Timestamp(t) => Kind::Timestamp(time_to_pb(*t)),
+        MetadataValue::Address(a) => Kind::Address(a.clone()),
+        MetadataValue::Bytes(bytes) => Kind::Bytes(bytes.clone()),
+        MetadataValue::Version(v) => Kind::Version(v.to_string()),
+        MetadataValue::List(items) => Kind::List(pb::ValueList {
+            items: items.iter().map(value_to_pb).collect(),
+        }),
+        MetadataValue::Map(map) => Kind::Map(pb::ValueMap {
+            entries: map_to_pb(map),
+        }),
+    };
+    pb::Value { kind: Some(kind) }
+}
+
+#[cfg(feature = "protobuf")]
+fn map_to_pb(map: &MetadataMap) -> HashMap<String, pb::Value> {
+    map.iter()
+        .map(|(k, v)| (k.clone(), value_to_pb(v)))
+        .collect()
+}
+
+#[cfg(feature = "protobuf")]
+fn value_from_pb(value: pb::Value) -> Result<MetadataValue, ProtoError> {
+    use pb::value::Kind;
+    let kind = value
+        .kind
+        .ok_or_else(|| ProtoError("metadata value has no kind".into()))?;
+    Ok(match kind {
+        Kind::String(s) => s.into(),
+        Kind::Int(i) => MetadataValue::Int(i),
+        Kind::Bool(b) => b.into(),
+        Kind::Timestamp(time) => MetadataValue::Timestamp(time_from_pb(time)?),
+        Kind::Address(a) => MetadataValue::Address(a),
+        Kind::Bytes(bytes) => MetadataValue::Bytes(bytes),
+        Kind::Version(v) => MetadataValue::Version(
+            v.parse()
+                .map_err(|err: VersionError| ProtoError(err.to_string()))?,
+        ),
+        Kind::List(list) => MetadataValue::List(
+            list.items
+                .into_iter()
+                .map(value_from_pb)
+                .collect::<Result<_, _>>()?,
+        ),
+        Kind::Map(map) => MetadataValue::Map(map_from_pb(map.entries)?),
+        Kind::Encrypted(_) | Kind::Compressed(_) => {
+            return Err(ProtoError(
+                "packed values are only valid in top-level contract metadata".into(),
+            ))
+        }
+    })
+}
+
+#[cfg(feature = "protobuf")]
+fn map_from_pb(entries: HashMap<String, pb::Value>) -> Result<MetadataMap, ProtoError> {
+    entries
+        .into_iter()
+        .map(|(k, v)| Ok((k, value_from_pb(v)?)))
+        .collect()
+}
+
+#[cfg(feature = "protobuf")]
+fn contract_to_pb(contract: &DeployedContract) -> pb::Contract {
+    let mut metadata = map_to_pb(contract.metadata());
+    for (key, packed) in &contract.packed {
+        let kind = match packed {
+            Packed::Sealed(sealed) => pb::value::Kind::Encrypted(sealed.clone()),
+            Packed::Compressed(compressed) => pb::value::Kind::Compressed(compressed.bytes.clone()),
+        };
+        metadata.insert(key.clone(), pb::Value { kind: Some(kind) });
+    }
+    pb::Contract {
+        name: contract.name.clone(),
+        metadata,
+        deployed_at: Some(time_to_pb(contract.deployed_at)),
+    }
+}
+
+// Audit log and deploy-time snapshot are not part of the message; the snapshot restarts
+// from the received metadata
+#[cfg(feature = "protobuf")]
+fn contract_from_pb(contract: pb::Contract) -> Result<DeployedContract, ProtoError> {
+    use pb::value::Kind;
+    let deployed_at = contract
+        .deployed_at
+        .ok_or_else(|| ProtoError(format!("`{}` has no deploy time", contract.name)))?;
+    let mut metadata = HashMap::new();
+    let mut packed = BTreeMap::new();
+    for (key, value) in contract.metadata {
+        match value.kind {
+            Some(Kind::Encrypted(sealed)) => {
+                packed.insert(key, Packed::Sealed(sealed));
+            }
+            Some(Kind::Compressed(bytes)) => {
+                packed.insert(key, Packed::Compressed(CompressedValue::new(bytes, None)));
+            }
+            _ => {
+                metadata.insert(key, value);
+            }
+        }
+    }
+    let mut contract = DeployedContract::new(
+        contract.name,
+        map_from_pb(metadata)?,
+        time_from_pb(deployed_at)?,
+    );
+    contract.packed = packed;
+    Ok(contract)
+}
+
+#[cfg(feature = "protobuf")]
+fn metadata_to_pb(map: &MetadataMap) -> pb::Metadata {
+    pb::Metadata {
+        entries: map_to_pb(map),
+    }
+}
+
+#[cfg(feature = "protobuf")]
+fn metadata_from_pb(metadata: pb::Metadata) -> Result<MetadataMap, ProtoError> {
+    map_from_pb(metadata.entries)
+}
+
+// `google.protobuf.Timestamp` keeps the nanoseconds, so times round-trip exactly
+#[cfg(feature = "protobuf")]
+fn time_to_pb(time: SystemTime) -> prost_types::Timestamp {
+    time.into()
+}
+
+#[cfg(feature = "protobuf")]
+fn time_from_pb(time: prost_types::Timestamp) -> Result<SystemTime, ProtoError> {
+    SystemTime::try_from(time).map_err(|err| ProtoError(err.to_string()))
+}
+
+#[cfg(feature = "protobuf")]
+impl ContractRegistry {
+    // `RegistrySnapshot` message with every version of every contract, for services in
+    // other languages generated from `proto/scrg.proto`
+    fn to_protobuf(&self) -> Vec<u8> {
+        use prost::Message;
+        pb::RegistrySnapshot {
+            contracts: self.all_versions().map(contract_to_pb).collect(),
+        }
+        .encode_to_vec()
+    }
+
+    fn from_protobuf(bytes: &[u8]) -> Result<Self, ScrgError> {
+        use prost::Message;
+        let snapshot = pb::RegistrySnapshot::decode(bytes).map_err(ProtoError::from)?;
+        let mut registry = Self::new();
+        for contract in snapshot.contracts {
+            registry.restore(contract_from_pb(contract)?);
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(feature = "grpc")]
+fn event_to_pb(event: RegistryEvent) -> pb::Event {
+    use pb::event::Kind;
+    let kind = match event {
+        RegistryEvent::ContractRegistered { name } => {
+            Kind::Registered(pb::ContractRegistered { name })
+        }
+        RegistryEvent::ContractUpgraded { name, from, to } => {
+            Kind::Upgraded(pb::ContractUpgraded {
+                name,
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+        }
+        RegistryEvent::MetadataUpdated {
+            name,
+            key,
+            old,
+            new,
+        } => Kind::MetadataUpdated(pb::MetadataUpdated {
+            name,
+            key,
+            old: old.as_ref().map(value_to_pb),
+            new: Some(value_to_pb(&new)),
+        }),
+        RegistryEvent::ContractRemoved { name } => Kind::Removed(pb::ContractRemoved { name }),
+    };
+    pb::Event { kind: Some(kind) }
+}
+
+// gRPC front for a `SharedRegistry`; run it with `serve_grpc` or mount `into_service()` on an
+// existing tonic router. Other clones of the registry see every registration.
+#[cfg(feature = "grpc")]
+#[derive(Clone)]
+struct GrpcRegistry {
+    registry: SharedRegistry,
+}
+
+#[cfg(feature = "grpc")]
+impl GrpcRegistry {
+    fn new(registry: SharedRegistry) -> Self {
+        Self { registry }
+    }
+
+    fn into_service(self) -> pb::registry_server::RegistryServer<Self> {
+        pb::registry_server::RegistryServer::new(self)
+    }
+}
+
+#[cfg(feature = "grpc")]
+#[tonic::async_trait]
+impl pb::registry_server::Registry for GrpcRegistry {
+    async fn register(
+        &self,
+        request: tonic::Request<pb::RegisterRequest>,
+    ) -> Result<tonic::Response<pb::Contract>, tonic::Status> {
+        let request = request.into_inner();
+        let contract = deploy_metadata(&request.name, &map_from_pb(request.metadata)?)?;
+        let reply = contract_to_pb(&contract);
+        self.registry.register(contract)?;
+        Ok(tonic::Response::new(reply))
+    }
+
+    async fn get(
+        &self,
+        request: tonic::Request<pb::GetRequest>,
+    ) -> Result<tonic::Response<pb::Contract>, tonic::Status> {
+        let request = request.into_inner();
+        let contract = match &request.version {
+            Some(version) => self.registry.get_version(&request.name, version),
+            None => self.registry.get(&request.name),
+        };
+        contract
+            .map(|contract| tonic::Response::new(contract_to_pb(&contract)))
+            .ok_or_else(|| {
+                tonic::Status::not_found(format!("contract `{}` is not registered", request.name))
+            })
+    }
+
+    async fn list(
+        &self,
+        _: tonic::Request<pb::ListRequest>,
+    ) -> Result<tonic::Response<pb::ContractList>, tonic::Status> {
+        let contracts = self
+            .registry
+            .snapshot()
+            .list()
+            .map(contract_to_pb)
+            .collect();
+        Ok(tonic::Response::new(pb::ContractList { contracts }))
+    }
+
+    // Runs `ContractRegistry::query` over a snapshot
+    async fn query(
+        &self,
+        request: tonic::Request<pb::QueryRequest>,
+    ) -> Result<tonic::Response<pb::ContractList>, tonic::Status> {
+        let request = request.into_inner();
+        let status = match &request.status {
+            Some(raw) => Some(LifecycleStatus::parse(raw).ok_or_else(|| {
+                tonic::Status::invalid_argument(format!("unknown lifecycle status `{raw}`"))
+            })?),
+            None => None,
+        };
+        let equals = map_from_pb(request.where_eq)?;
+        let snapshot = self.registry.snapshot();
+        let mut query = snapshot.query();
+        for (key, value) in &equals {
+            query = query.where_key_eq(key, value.clone());
+        }
+        for key in &request.has_keys {
+            query = query.where_key_exists(key);
+        }
+        if let Some(status) = status {
+            query = query.where_status(status);
+        }
+        if let Some(chain_id) = request.chain_id {
+            query = query.where_chain_id(chain_id);
+        }
+        let contracts = query.iter().map(contract_to_pb).collect();
+        Ok(tonic::Response::new(pb::ContractList { contracts }))
+    }
+
+    type WatchStream = tokio_stream::wrappers::ReceiverStream<Result<pb::Event, tonic::Status>>;
+
+    async fn watch(
+        &self,
+        _: tonic::Request<pb::WatchRequest>,
+    ) -> Result<tonic::Response<Self::WatchStream>, tonic::Status> {
+        let events = self.registry.watch();
+        let (tx, rx) = tokio::sync::mpsc::channel(64);
+        // `SharedRegistry::watch` is a blocking std channel; the forwarding thread notices a
+        // disconnected client on the next event and exits
+        tokio::task::spawn_blocking(move || {
+            while let Ok(event) = events.recv() {
+                if tx.blocking_send(Ok(event_to_pb(event))).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(tonic::Response::new(
+            tokio_stream::wrappers::ReceiverStream::new(rx),
+        ))
+    }
+}
+
+#[cfg(feature = "grpc")]
+async fn serve_grpc(
+    registry: SharedRegistry,
+    addr: std::net::SocketAddr,
+) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(GrpcRegistry::new(registry).into_service())
+        .serve(addr)
+        .await
+}
+
+// REST front for a `SharedRegistry`:
+//   GET  /contracts                 latest version of every listed contract, sorted by name
+//   GET  /contracts/{name}          latest version of `name`
+//   POST /contracts                 `{"name", "metadata"}`: validate, deploy and register
+//   POST /contracts/{name}/deploy   `{"version", "metadata"}`: new version keeping the author,
+//                                   tags and dependencies of the latest one
+// Contracts are returned as `{"name", "metadata"}` and errors as `{"error"}`.
+#[cfg(feature = "http")]
+fn http_router(registry: SharedRegistry) -> axum::Router {
+    use axum::routing::{get, post};
+    axum::Router::new()
+        .route("/contracts", get(http_list).post(http_create))
+        .route("/contracts/:name", get(http_get))
+        .route("/contracts/:name/deploy", post(http_deploy))
+        .with_state(registry)
+}
+
+#[cfg(feature = "http")]
+async fn serve_http(registry: SharedRegistry, addr: std::net::SocketAddr) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, http_router(registry)).await
+}
+
+#[cfg(feature = "http")]
+struct HttpError(axum::http::StatusCode, String);
+
+#[cfg(feature = "http")]
+impl HttpError {
+    fn not_found(name: &str) -> Self {
+        Self(
+            axum::http::StatusCode::NOT_FOUND,
+            format!("contract `{name}` is not registered"),
+        )
+    }
+}
+
+#[cfg(feature = "http")]
+impl From<ScrgError> for HttpError {
+    fn from(err: ScrgError) -> Self {
+        use axum::http::StatusCode;
+        let status = match err {
+            ScrgError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ScrgError::Registry(RegistryError::AccessDenied(_)) => StatusCode::FORBIDDEN,
+            ScrgError::Registry(_) => StatusCode::CONFLICT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self(status, err.to_string())
+    }
+}
+
+#[cfg(feature = "http")]
+impl axum::response::IntoResponse for HttpError {
+    fn into_response(self) -> axum::response::Response {
+        let body = axum::Json(serde_json::json!({ "error": self.1 }));
+        (self.0, body).into_response()
+    }
+}
+
+#[cfg(feature = "http")]
+fn contract_to_json(contract: &DeployedContract) -> serde_json::Value {
+    serde_json::json!({
+        "name": contract.name,
+        "metadata": map_to_json(contract.metadata()),
+    })
+}
+
+#[cfg(feature = "http")]
+#[derive(serde::Deserialize)]
+struct NewContract {
+    name: String,
+    #[serde(default)]
+    metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+#[cfg(feature = "http")]
+#[derive(serde::Deserialize)]
+struct NewVersion {
+    version: String,
+    #[serde(default)]
+    metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+#[cfg(feature = "http")]
+async fn http_list(
+    axum::extract::State(registry): axum::extract::State<SharedRegistry>,
+) -> axum::Json<serde_json::Value> {
+    let snapshot = registry.snapshot();
+    let mut contracts: Vec<_> = snapshot.list().collect();
+    contracts.sort_by(|a, b| a.name.cmp(&b.name));
+    axum::Json(contracts.into_iter().map(contract_to_json).collect())
+}
+
+#[cfg(feature = "http")]
+async fn http_get(
+    axum::extract::State(registry): axum::extract::State<SharedRegistry>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> Result<axum::Json<serde_json::Value>, HttpError> {
+    let contract = registry
+        .get(&name)
+        .ok_or_else(|| HttpError::not_found(&name))?;
+    Ok(axum::Json(contract_to_json(&contract)))
+}
+
+#[cfg(feature = "http")]
+async fn http_create(
+    axum::extract::State(registry): axum::extract::State<SharedRegistry>,
+    axum::Json(body): axum::Json<NewContract>,
+) -> Result<(axum::http::StatusCode, axum::Json<serde_json::Value>), HttpError> {
+    let contract = deploy_metadata(&body.name, &json_object_to_metadata(body.metadata))?;
+    let reply = contract_to_json(&contract);
+    registry.register(contract)?;
+    Ok((axum::http::StatusCode::CREATED, axum::Json(reply)))
+}
+
+#[cfg(feature = "http")]
+async fn http_deploy(
+    axum::extract::State(registry): axum::extract::State<SharedRegistry>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    axum::Json(body): axum::Json<NewVersion>,
+) -> Result<(axum::http::StatusCode, axum::Json<serde_json::Value>), HttpError> {
+    let current = registry
+        .get(&name)
+        .ok_or_else(|| HttpError::not_found(&name))?;
+    let mut metadata: MetadataMap = ["author", "tags", "dependencies"]
+        .into_iter()
+        .filter_map(|key| Some((key.to_owned(), current.get(key)?.clone())))
+        .collect();
+    metadata.extend(json_object_to_metadata(body.metadata));
+    metadata.insert("version".into(), body.version.into());
+    let contract = deploy_metadata(&name, &metadata)?;
+    let reply = contract_to_json(&contract);
+    registry.register(contract)?;
+    Ok((axum::http::StatusCode::CREATED, axum::Json(reply)))
+}
+
+// Read-only GraphQL schema over a `SharedRegistry`; run queries with
+// `graphql_schema(registry).execute(query).await` or mount it in any async-graphql server
+// integration, e.g.
+//   { contracts(chainId: 1) { name signer: value(key: "signer") deployedAt } }
+#[cfg(feature = "graphql")]
+type GraphqlSchema = async_graphql::Schema<
+    GraphqlQuery,
+    async_graphql::EmptyMutation,
+    async_graphql::EmptySubscription,
+>;
+
+#[cfg(feature = "graphql")]
+fn graphql_schema(registry: SharedRegistry) -> GraphqlSchema {
+    async_graphql::Schema::build(
+        GraphqlQuery,
+        async_graphql::EmptyMutation,
+        async_graphql::EmptySubscription,
+    )
+    .data(registry)
+    .finish()
+}
+
+#[cfg(feature = "graphql")]
+struct GraphqlQuery;
+
+#[cfg(feature = "graphql")]
+#[async_graphql::Object(name = "Query")]
+impl GraphqlQuery {
+    // Latest version of `name`, or the given `version`
+    async fn contract(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        name: String,
+        version: Option<String>,
+    ) -> Option<GraphqlContract> {
+        let registry = ctx.data_unchecked::<SharedRegistry>();
+        let contract = match version {
+            Some(version) => registry.get_version(&name, &version),
+            None => registry.get(&name),
+        }?;
+        Some(GraphqlContract::new(contract, registry))
+    }
+
+    // Default listing sorted by name, narrowed by every filter given
+    async fn contracts(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        tag: Option<String>,
+        status: Option<String>,
+        chain_id: Option<u64>,
+        has_key: Option<String>,
+    ) -> async_graphql::Result<Vec<GraphqlContract>> {
+        let registry = ctx.data_unchecked::<SharedRegistry>();
+        let status = status
+            .map(|raw| {
+                LifecycleStatus::parse(&raw).ok_or(format!("unknown lifecycle status `{raw}`"))
+            })
+            .transpose()?;
+        let snapshot = registry.snapshot();
+        let mut query = snapshot.query();
+        if let Some(tag) = &tag {
+            query = query.matching(move |contract| contract.tags().contains(&tag.as_str()));
+        }
+        if let Some(status) = status {
+            query = query.where_status(status);
+        }
+        if let Some(chain_id) = chain_id {
+            query = query.where_chain_id(chain_id);
+        }
+        if let Some(key) = &has_key {
+            query = query.where_key_exists(key);
+        }
+        let mut contracts: Vec<_> = query
+            .iter()
+            .map(|contract| GraphqlContract::new(contract.clone(), registry))
+            .collect();
+        contracts.sort_by(|a, b| a.contract.name.cmp(&b.contract.name));
+        Ok(contracts)
+    }
+}
+
+// One contract version; keeps a registry handle to resolve dependencies and other versions
+#[cfg(feature = "graphql")]
+struct GraphqlContract {
+    contract: DeployedContract,
+    registry: SharedRegistry,
+}
+
+#[cfg(feature = "graphql")]
+impl GraphqlContract {
+    fn new(contract: DeployedContract, registry: &SharedRegistry) -> Self {
+        Self {
+            contract,
+            registry: registry.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "graphql")]
+#[derive(async_graphql::SimpleObject)]
+#[graphql(name = "MetadataEntry")]
+struct GraphqlMetadataEntry {
+    key: String,
+    // Rendered as in `Display`
+    value: String,
+    // `MetadataValue` variant, e.g. `timestamp`
+    kind: String,
+}
+
+#[cfg(feature = "graphql")]
+#[async_graphql::Object(name = "Contract")]
+impl GraphqlContract {
+    async fn name(&self) -> &str {
+        &self.contract.name
+    }
+
+    async fn version(&self) -> Option<String> {
+        self.contract.version().map(Version::to_string)
+    }
+
+    async fn status(&self) -> Option<&str> {
+        self.contract.status().map(LifecycleStatus::as_str)
+    }
+
+    async fn author(&self) -> Option<&str> {
+        self.contract.get("author").and_then(MetadataValue::as_str)
+    }
+
+    async fn tags(&self) -> Vec<&str> {
+        self.contract.tags()
+    }
+
+    async fn deployed_at(&self) -> Option<String> {
+        self.contract
+            .get("deployed_at")
+            .map(MetadataValue::to_string)
+    }
+
+    // Any metadata value as text, e.g. `value(key: "signer")`
+    async fn value(&self, key: String) -> Option<String> {
+        self.contract.get(&key).map(MetadataValue::to_string)
+    }
+
+    // Metadata sorted by key, limited to `keys` when given
+    async fn metadata(&self, keys: Option<Vec<String>>) -> Vec<GraphqlMetadataEntry> {
+        let mut entries: Vec<_> = self
+            .contract
+            .metadata()
+            .iter()
+            .filter(|(key, _)| keys.as_ref().is_none_or(|keys| keys.contains(key)))
+            .map(|(key, value)| GraphqlMetadataEntry {
+                key: key.clone(),
+                value: value.to_string(),
+                kind: value.type_name().into(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+
+    // Latest version of each dependency that is registered
+    async fn dependencies(&self) -> Vec<GraphqlContract> {
+        self.contract
+            .dependencies()
+            .into_iter()
+            .filter_map(|name| self.registry.get(name))
+            .map(|contract| Self::new(contract, &self.registry))
+            .collect()
+    }
+
+    // Every registered version of this contract, oldest first
+    async fn versions(&self) -> Vec<GraphqlContract> {
+        let contracts = self.registry.read();
+        contracts
+            .get(&self.contract.name)
+            .into_iter()
+            .flatten()
+            .map(|contract| Self::new(contract.clone(), &self.registry))
+            .collect()
+    }
+}
+
+// Errors go to stderr through `Display`, with a failing exit status
+#[cfg(feature = "std")]
+fn main() -> std::process::ExitCode {
+    match run() {
+        Ok(()) => std::process::ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "cli")]
+    {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if !args.is_empty() {
+            return run_cli(&args);
+        }
+    }
+
+    let contract = ContractBuilder::new("TokenX")
+        .with_author("azaM")
+        .with_version("1.0.0")
+        .validate()?
+        .add_hook(|meta| {
+            meta.insert("signer".into(), MetadataValue::Address("0xDEADBEEF".into()));
+        })
+        .deploy()?
+        .into_deployed();
+
+    let mut registry = ContractRegistry::new();
+    registry.register(contract)?;
+
+    if let Some(contract) = registry.get("TokenX") {
+        println!("{contract}");
+    }
+
+    #[cfg(feature = "serde")]
+    {
+        let restored = ContractRegistry::from_json(&registry.to_json()?)?;
+        println!(
+            "🔁 Round-tripped {} contract(s) through JSON",
+            restored.len()
+        );
+    }
+
+    Ok(())
+}
+
+// The tests lean on threads, env vars and files, so they need `std`
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn deployed(name: &str) -> DeployedContract {
+        ContractBuilder::new(name)
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .on_deploy(|_| {})
+            .unwrap()
+            .into_deployed()
+    }
+
+    #[test]
+    fn registry_tracks_contracts_by_name() {
+        let mut registry = ContractRegistry::new();
+        assert!(registry.register(deployed("TokenX")).unwrap().is_none());
+        assert!(registry.contains("TokenX"));
+        assert_eq!(
+            registry.get("TokenX").unwrap().get("status"),
+            Some(&"deployed".into())
+        );
+
+        let previous = registry.register(deployed("TokenX")).unwrap().unwrap();
+        assert_eq!(previous.name(), "TokenX");
+        assert_eq!(registry.remove("TokenX").unwrap().len(), 1);
+        assert!(!registry.contains("TokenX"));
+        assert!(registry.get("TokenX").is_none());
+    }
+
+    #[test]
+    fn into_deployed_detaches_the_metadata() {
+        let builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .on_deploy(|meta| {
+                meta.insert("signer".into(), "0xDEADBEEF".into());
+            })
+            .unwrap();
+        let shared = builder.metadata();
+        let mut contract = builder.into_deployed();
+        contract.set("signer", "0xBAD", "ops").unwrap();
+
+        assert_eq!(contract.name(), "TokenX");
+        assert_eq!(shared.borrow().get("signer"), Some(&"0xDEADBEEF".into()));
+        assert_eq!(contract.get("signer"), Some(&"0xBAD".into()));
+        assert_eq!(contract.get("author"), Some(&"azaM".into()));
+        assert!(contract.deployed_at() <= SystemTime::now());
+    }
+
+    #[test]
+    fn validate_reports_what_is_missing() {
+        assert!(matches!(
+            ContractBuilder::new(" ").with_author("azaM").validate(),
+            Err(ScrgError::Validation(ValidationError::EmptyName))
+        ));
+        // A missing author no longer compiles; see `with_author_unlocks_validate`
+        let missing = ValidationError::MissingKey("author".into());
+        assert_eq!(
+            missing.to_string(),
+            "required metadata key `author` is missing"
+        );
+
+        let long = "k".repeat(MAX_KEY_LEN + 1);
+        let too_long = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata(&long, "v")
+            .validate();
+        assert!(
+            matches!(too_long, Err(ScrgError::Validation(ValidationError::KeyTooLong { key, .. })) if key == long)
+        );
+    }
+
+    #[test]
+    fn rules_report_every_violation() {
+        let needs_symbol = |_: &str, meta: &MetadataMap| match meta.get("symbol") {
+            Some(_) => Ok(()),
+            None => Err(RuleViolation::new("symbol", "token needs a symbol")),
+        };
+        let short_name = |name: &str, _: &MetadataMap| match name.len() <= 6 {
+            true => Ok(()),
+            false => Err(RuleViolation::new("name", "at most 6 characters")),
+        };
+        let builder = || {
+            ContractBuilder::new("TokenXYZ")
+                .with_author("azaM")
+                .with_rule(Box::new(needs_symbol))
+                .with_rule(Box::new(short_name))
+        };
+
+        let err = builder().validate().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "2 validation rule(s) failed; symbol: token needs a symbol; name: at most 6 characters"
+        );
+        let err = builder().with_metadata("symbol", "TKX").validate();
+        assert!(matches!(
+            err,
+            Err(ScrgError::Validation(ValidationError::Rules(violations)))
+                if violations == [RuleViolation::new("name", "at most 6 characters")]
+        ));
+    }
+
+    #[test]
+    fn metadata_values_keep_their_type() {
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("decimals", 18)
+            .with_metadata("bytecode", vec![0x60, 0x80])
+            .validate()
+            .unwrap()
+            .on_deploy(|meta| {
+                meta.insert("deployed".into(), SystemTime::UNIX_EPOCH.into());
+            })
+            .unwrap()
+            .into_deployed();
+
+        assert_eq!(
+            contract.get("decimals").and_then(MetadataValue::as_int),
+            Some(18)
+        );
+        assert_eq!(
+            contract.get("validated").and_then(MetadataValue::as_bool),
+            Some(true)
+        );
+        assert_eq!(
+            contract.get("author").and_then(MetadataValue::as_str),
+            Some("azaM")
+        );
+        assert_eq!(contract.get("bytecode").unwrap().to_string(), "0x6080");
+        assert_eq!(
+            contract.get("deployed").unwrap().to_string(),
+            "1970-01-01T00:00:00Z"
+        );
+
+        let nested = MetadataValue::Map(MetadataMap::from([
+            (
+                "b".to_string(),
+                MetadataValue::List(vec![1.into(), true.into()]),
+            ),
+            ("a".to_string(), "x".into()),
+        ]));
+        assert_eq!(nested.to_string(), "{a: x, b: [1, true]}");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip_keeps_every_value_kind() {
+        let nested = MetadataMap::from([("chain".to_string(), MetadataValue::Int(1))]);
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("bytecode", vec![0x60, 0x80])
+            .with_metadata("owner", MetadataValue::Address("0xdeadbeef".into()))
+            .with_metadata(
+                "tags",
+                MetadataValue::List(vec!["erc20".into(), MetadataValue::Int(7)]),
+            )
+            .with_metadata("network", MetadataValue::Map(nested))
+            .validate()
+            .unwrap()
+            .on_deploy(|meta| {
+                meta.insert("deployed".into(), SystemTime::now().into());
+            })
+            .unwrap()
+            .into_deployed();
+
+        let restored = DeployedContract::from_json(&contract.to_json().unwrap()).unwrap();
+        assert_eq!(restored.name, contract.name);
+        assert_eq!(restored.deployed_at, contract.deployed_at);
+        assert_eq!(restored.metadata, contract.metadata);
+        assert!(contract.to_json().unwrap().contains(r#""type": "address""#));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn registry_json_round_trip_keeps_hook_inserted_keys() {
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .on_deploy(|meta| {
+                meta.insert("signer".into(), MetadataValue::Address("0xDEADBEEF".into()));
+            })
+            .unwrap()
+            .into_deployed();
+        let mut registry = ContractRegistry::new();
+        registry.register(contract).unwrap();
+
+        let restored = ContractRegistry::from_json(&registry.to_json().unwrap()).unwrap();
+        assert_eq!(restored.contracts.len(), 1);
+        let (before, after) = (
+            registry.get("TokenX").unwrap(),
+            restored.get("TokenX").unwrap(),
+        );
+        assert_eq!(after.metadata, before.metadata);
+        assert_eq!(
+            after.get("signer"),
+            Some(&MetadataValue::Address("0xDEADBEEF".into()))
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_layout_is_sorted_and_round_trips() {
+        let mut registry = ContractRegistry::new();
+        for name in ["Vault", "TokenX"] {
+            let contract = ContractBuilder::new(name)
+                .with_author("azaM")
+                .with_metadata("decimals", 18)
+                .validate()
+                .unwrap()
+                .on_deploy(|_| {})
+                .unwrap()
+                .into_deployed();
+            registry.register(contract).unwrap();
+        }
+
+        let toml = registry.to_toml().unwrap();
+        let token = toml.find("[[contracts.TokenX]]").unwrap();
+        assert!(token < toml.find("[[contracts.Vault]]").unwrap());
+
+        let restored = ContractRegistry::from_toml(&toml).unwrap();
+        let (before, after) = (
+            registry.get("Vault").unwrap(),
+            restored.get("Vault").unwrap(),
+        );
+        assert_eq!(after.metadata, before.metadata);
+        let secs = |contract: &DeployedContract| {
+            let since = contract.deployed_at.duration_since(SystemTime::UNIX_EPOCH);
+            since.unwrap().as_secs()
+        };
+        assert_eq!(secs(after), secs(before));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_import_builds_and_rejects_as_a_whole() {
+        let registry = ContractRegistry::from_yaml(
+            "contracts:\n  - name: TokenX\n    author: azaM\n    metadata: { decimals: 18 }\n    tags: [defi]\n",
+        )
+        .unwrap();
+        let token = registry.get("TokenX").unwrap();
+        assert_eq!(token.get("decimals"), Some(&MetadataValue::Int(18)));
+        assert_eq!(
+            token.get("tags"),
+            Some(&MetadataValue::List(vec!["defi".into()]))
+        );
+
+        let result = ContractRegistry::from_yaml(
+            "contracts:\n  - name: TokenX\n    author: azaM\n  - name: ' '\n    author: azaM\n",
+        );
+        assert!(
+            matches!(result, Err(ScrgError::Serialization(ref err)) if matches!(
+                err.downcast_ref(),
+                Some(YamlImportError::Contracts { failed, .. }) if failed.len() == 1
+            ))
+        );
+    }
+
+    // Saves a registry, appends a second contract and reads everything back
+    #[cfg(feature = "serde")]
+    fn exercise_store(store: &mut dyn RegistryStore) {
+        let mut registry = ContractRegistry::new();
+        let limits = MetadataLimits::new().with_max_keys(32);
+        let token = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("decimals", 18)
+            .with_limits(limits)
+            .validate()
+            .unwrap()
+            .on_deploy(|_| {})
+            .unwrap()
+            .into_deployed();
+        registry.register(token).unwrap();
+        registry.update_metadata("TokenX", "decimals", 6).unwrap();
+        store.save(&registry).unwrap();
+        store.append(&deployed("Vault")).unwrap();
+
+        assert_eq!(store.list().unwrap(), ["TokenX", "Vault"]);
+        let loaded = store.load().unwrap();
+        let (before, after) = (
+            registry.get("TokenX").unwrap(),
+            loaded.get("TokenX").unwrap(),
+        );
+        assert_eq!(after.get("decimals"), Some(&6.into()));
+        assert_eq!(after.snapshot().get("decimals"), Some(&18.into()));
+        assert_eq!(after.audit, before.audit);
+        assert_eq!(after.limits, limits);
+
+        let blob = "0".repeat(512);
+        let mut vault = deployed("Vault");
+        vault.set("api_key", "sk-live-123", "ops").unwrap();
+        vault.set("abi_blob", blob.as_str(), "ops").unwrap();
+        let mut packed = ContractRegistry::new()
+            .with_encryption(XorCipher(0x5a), ["api_key"])
+            .with_compression(RleCodec, 256);
+        packed.register(vault).unwrap();
+        store.save(&packed).unwrap();
+        let mut loaded = store
+            .load()
+            .unwrap()
+            .with_encryption(XorCipher(0x5a), ["api_key"])
+            .with_compression(RleCodec, 256);
+        let stored = loaded.get("Vault").unwrap();
+        assert!(matches!(
+            stored.packed.get("api_key"),
+            Some(Packed::Sealed(_))
+        ));
+        assert!(matches!(
+            stored.packed.get("abi_blob"),
+            Some(Packed::Compressed(_))
+        ));
+        assert_eq!(stored.get("abi_blob"), Some(&blob.as_str().into()));
+        assert_eq!(
+            loaded
+                .reveal(&Actor::default(), "Vault", "api_key")
+                .unwrap(),
+            Some("sk-live-123".into())
+        );
+
+        store.save(&ContractRegistry::new()).unwrap();
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("scrg-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_file_store_round_trip() {
+        let path = temp_path("registry.json");
+        let mut store = JsonFileStore::new(&path);
+        assert!(store.list().unwrap().is_empty());
+        exercise_store(&mut store);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_store_round_trip() {
+        let path = temp_path("sled");
+        exercise_store(&mut SledStore::open(&path).unwrap());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_store_round_trip_and_reports() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        exercise_store(&mut store);
+
+        let mut registry = ContractRegistry::new();
+        for (name, network) in [("TokenX", "mainnet"), ("Vault", "testnet")] {
+            let contract = ContractBuilder::new(name)
+                .with_author("azaM")
+                .with_metadata("network", network)
+                .validate()
+                .unwrap()
+                .on_deploy(|_| {})
+                .unwrap()
+                .into_deployed();
+            registry.register(contract).unwrap();
+        }
+        store.save(&registry).unwrap();
+        let mainnet = store
+            .report(
+                "SELECT contract FROM contract_metadata WHERE key = ?1 AND value = ?2",
+                ["network", "mainnet"],
+            )
+            .unwrap();
+        assert_eq!(mainnet, ["TokenX"]);
+    }
+
+    #[test]
+    fn shared_registry_clones_see_writes_from_other_threads() {
+        let shared = SharedRegistry::new();
+        let handles: Vec<_> = ["TokenX", "Vault", "Oracle"]
+            .into_iter()
+            .map(|name| {
+                let shared = shared.clone();
+                std::thread::spawn(move || shared.register(deployed(name)))
+            })
+            .collect();
+        for handle in handles {
+            assert!(handle.join().unwrap().unwrap().is_none());
+        }
+
+        assert!(shared.contains("Oracle"));
+        assert_eq!(shared.snapshot().contracts.len(), 3);
+        assert_eq!(shared.remove("Vault").unwrap().len(), 1);
+        assert!(shared.get("Vault").is_none());
+    }
+
+    // The futures under test never wait on anything, so one poll completes them
+    #[cfg(any(feature = "async", feature = "ethers", feature = "graphql"))]
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        match std::pin::pin!(future).poll(&mut cx) {
+            std::task::Poll::Ready(output) => output,
+            std::task::Poll::Pending => panic!("future was not ready after one poll"),
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn deploy_async_awaits_the_hook() {
+        let builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap();
+        let contract = block_on(builder.deploy_async(|mut meta| async move {
+            meta.insert("tx".into(), "0xabc".into());
+            meta
+        }))
+        .unwrap();
+        assert_eq!(contract.get("tx"), Some(&"0xabc".into()));
+        assert_eq!(contract.get("status"), Some(&"deployed".into()));
+    }
+
+    #[test]
+    fn deploy_hooks_run_in_registration_order() {
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .add_hook(|meta| {
+                meta.insert("trail".into(), "first".into());
+            })
+            .add_hook(|meta| {
+                let trail = format!("{}>second", meta["trail"]);
+                meta.insert("trail".into(), trail.into());
+            })
+            .on_deploy(|meta| {
+                let trail = format!("{}>last", meta["trail"]);
+                meta.insert("trail".into(), trail.into());
+            })
+            .unwrap()
+            .into_deployed();
+        assert_eq!(contract.get("trail"), Some(&"first>second>last".into()));
+    }
+
+    #[test]
+    fn failed_hook_hands_back_an_untouched_builder() {
+        let mut attempts = 0;
+        let builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .add_try_hook(move |meta| {
+                attempts += 1;
+                meta.insert("attempts".into(), attempts.into());
+                if attempts == 1 {
+                    Err(HookError::new("rpc timeout"))
+                } else {
+                    Ok(())
+                }
+            });
+
+        let Err(failure) = builder.deploy() else {
+            panic!("the first attempt should fail");
+        };
+        assert_eq!(
+            failure.error(),
+            &HookError::new("rpc timeout").in_stage("deploy")
+        );
+        let builder = failure.into_builder();
+        assert!(!builder.metadata.borrow().contains_key("attempts"));
+        assert!(!builder.metadata.borrow().contains_key("status"));
+
+        let contract = builder.deploy().unwrap().into_deployed();
+        assert_eq!(contract.get("attempts"), Some(&2.into()));
+    }
+
+    #[test]
+    fn hooks_run_phase_by_phase() {
+        let seen: Rc<RefCell<Vec<&str>>> = Rc::default();
+        let record = |phase: &'static str| {
+            let seen = Rc::clone(&seen);
+            move |_: &str, _: &mut MetadataMap| {
+                seen.borrow_mut().push(phase);
+                Ok(())
+            }
+        };
+        let deploy_seen = Rc::clone(&seen);
+        let deployed = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .on_phase(Phase::PostValidate, record("post_validate"))
+            .on_phase(Phase::PreValidate, record("pre_validate"))
+            .validate()
+            .unwrap()
+            .on_phase(Phase::PostDeploy, record("post_deploy"))
+            .on_phase(Phase::PreDeploy, |_, meta| match meta.get("status") {
+                None => Ok(()),
+                Some(status) => Err(HookError::new(format!("already {status}"))),
+            })
+            .on_phase(Phase::PreDeploy, record("pre_deploy"))
+            .add_hook(move |meta| {
+                assert_eq!(meta.get("status"), Some(&"deployed".into()));
+                deploy_seen.borrow_mut().push("deploy");
+            })
+            .deploy()
+            .unwrap();
+        assert_eq!(
+            *seen.borrow(),
+            [
+                "pre_validate",
+                "post_validate",
+                "pre_deploy",
+                "deploy",
+                "post_deploy"
+            ]
+        );
+        assert_eq!(
+            deployed.into_deployed().get("status"),
+            Some(&"deployed".into())
+        );
+
+        let rejected = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .on_phase(Phase::PreValidate, |_, _| Err(HookError::new("no audit")))
+            .validate();
+        assert!(matches!(
+            rejected,
+            Err(ScrgError::Validation(ValidationError::Hook(_)))
+        ));
+    }
+
+    #[test]
+    fn registry_events_reach_callbacks_and_channels() {
+        let mut registry = ContractRegistry::new();
+        let seen: Rc<RefCell<Vec<RegistryEvent>>> = Rc::default();
+        let sink = Rc::clone(&seen);
+        registry.subscribe(move |event| sink.borrow_mut().push(event.clone()));
+        let rx = registry.watch();
+        drop(registry.watch());
+
+        registry.register(deployed("TokenX")).unwrap();
+        assert!(matches!(
+            registry.update_metadata("TokenX", "decimals", 18),
+            Ok(None)
+        ));
+        assert!(registry.update_metadata("Vault", "decimals", 18).is_err());
+        registry.remove("TokenX").unwrap();
+
+        let expected = [
+            RegistryEvent::ContractRegistered {
+                name: "TokenX".into(),
+            },
+            RegistryEvent::MetadataUpdated {
+                name: "TokenX".into(),
+                key: "decimals".into(),
+                old: None,
+                new: 18.into(),
+            },
+            RegistryEvent::ContractRemoved {
+                name: "TokenX".into(),
+            },
+        ];
+        assert_eq!(*seen.borrow(), expected);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), expected);
+        assert_eq!(registry.channels.len(), 1);
+    }
+
+    #[test]
+    fn versions_follow_semver_precedence() {
+        let parse = |input: &str| input.parse::<Version>().unwrap();
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.2.0",
+            "2.0.0",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(parse(pair[0]) < parse(pair[1]), "{pair:?}");
+        }
+        assert_eq!(
+            parse("2.0.0-rc.1+build.5").to_string(),
+            "2.0.0-rc.1+build.5"
+        );
+        for bad in ["1.0", "01.0.0", "1.0.0-", "1.0.0-01", "1.0.0+", "1.0.0.0"] {
+            assert!(bad.parse::<Version>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn registry_keeps_every_version_sorted() {
+        let version = |v: &str| {
+            ContractBuilder::new("TokenX")
+                .with_author("azaM")
+                .with_version(v)
+                .validate()
+                .unwrap()
+                .deploy()
+                .unwrap()
+                .into_deployed()
+        };
+        let mut registry = ContractRegistry::new();
+        for v in ["1.1.0", "1.0.0", "1.1.0-rc.1"] {
+            assert!(registry.register(version(v)).unwrap().is_none());
+        }
+        assert!(registry.register(version("1.0.0")).unwrap().is_some());
+
+        let listed: Vec<String> = registry
+            .versions("TokenX")
+            .iter()
+            .map(|contract| contract.version().unwrap().to_string())
+            .collect();
+        assert_eq!(listed, ["1.0.0", "1.1.0-rc.1", "1.1.0"]);
+        assert_eq!(
+            registry.get("TokenX").unwrap().storage_key(),
+            "TokenX@1.1.0"
+        );
+        assert!(registry.get_version("TokenX", "1.1.0-rc.1").is_some());
+
+        let rejected = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_version("v1")
+            .validate();
+        assert!(matches!(
+            rejected,
+            Err(ScrgError::Validation(ValidationError::InvalidVersion(_)))
+        ));
+    }
+
+    #[test]
+    fn upgrade_bumps_the_version_and_runs_upgrade_hooks() {
+        let deployed = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_version("1.0.0")
+            .validate()
+            .unwrap()
+            .on_phase(Phase::Upgrade, |_, meta| {
+                meta.insert("migrated".into(), true.into());
+                Ok(())
+            })
+            .deploy()
+            .unwrap();
+
+        let upgraded = deployed.upgrade_to("1.1.0").unwrap();
+        assert_eq!(upgraded.borrow().get("migrated"), Some(&true.into()));
+        let rejected = upgraded.upgrade_to("1.0.5").map(|_| ());
+        assert!(matches!(
+            rejected,
+            Err(ScrgError::Upgrade(UpgradeError::NotNewer { .. }))
+        ));
+
+        let blocked = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_version("1.0.0")
+            .validate()
+            .unwrap()
+            .on_phase(Phase::Upgrade, |_, _| Err(HookError::new("paused")))
+            .deploy()
+            .unwrap()
+            .upgrade_to("2.0.0")
+            .map(|_| ());
+        assert!(matches!(
+            blocked,
+            Err(ScrgError::Upgrade(UpgradeError::Hook(_)))
+        ));
+    }
+
+    #[test]
+    fn upgraded_contract_records_its_previous_version() {
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_version("1.0.0")
+            .with_metadata("decimals", 18)
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .upgrade_to("1.1.0")
+            .unwrap()
+            .upgrade_to("2.0.0")
+            .unwrap()
+            .into_deployed();
+        assert_eq!(
+            contract.version().map(Version::to_string),
+            Some("2.0.0".into())
+        );
+        assert_eq!(
+            contract.get("previous_version"),
+            Some(&Version::new(1, 1, 0).into())
+        );
+        assert_eq!(contract.get("status"), Some(&"upgraded".into()));
+        assert_eq!(contract.get("decimals"), Some(&18.into()));
+    }
+
+    #[test]
+    fn paused_contracts_resume_and_filter_by_status() {
+        let paused = ContractBuilder::new("Vault")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .pause("exploit under investigation");
+        assert_eq!(
+            paused.pause_reason().as_deref(),
+            Some("exploit under investigation")
+        );
+
+        let mut registry = ContractRegistry::new();
+        registry.register(deployed("TokenX")).unwrap();
+        registry.register(paused.into_deployed()).unwrap();
+        let names = |status| {
+            let mut names: Vec<&str> = registry.with_status(status).map(|c| c.name()).collect();
+            names.sort_unstable();
+            names
+        };
+        assert_eq!(names(LifecycleStatus::Paused), ["Vault"]);
+        assert_eq!(names(LifecycleStatus::Deployed), ["TokenX"]);
+
+        let resumed = ContractBuilder::new("Vault")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .pause("maintenance")
+            .resume()
+            .into_deployed();
+        assert_eq!(resumed.status(), Some(LifecycleStatus::Deployed));
+        assert!(resumed.get("pause_reason").is_none());
+        assert!(resumed.get("paused_at").is_none());
+    }
+
+    #[test]
+    fn archived_contracts_drop_out_of_listings() {
+        let deprecated = ContractBuilder::new("TokenV1")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .deprecate(Some("TokenV2"));
+        assert_eq!(deprecated.successor().as_deref(), Some("TokenV2"));
+
+        let mut registry = ContractRegistry::new();
+        registry.register(deprecated.into_deployed()).unwrap();
+        registry.register(deployed("Vault")).unwrap();
+        registry.register(deployed("TokenV2")).unwrap();
+        assert!(registry.archive("Vault").unwrap());
+        assert!(!registry.archive("Missing").unwrap());
+
+        let mut listed: Vec<&str> = registry.list().map(|c| c.name()).collect();
+        listed.sort_unstable();
+        assert_eq!(listed, ["TokenV1", "TokenV2"]);
+        let archived: Vec<&str> = registry.archived().map(|c| c.name()).collect();
+        assert_eq!(archived, ["Vault"]);
+        assert!(registry.get("Vault").unwrap().get("archived_at").is_some());
+
+        assert!(registry.deprecate("TokenV2", None).unwrap());
+        let status = registry.get("TokenV2").and_then(DeployedContract::status);
+        assert_eq!(status, Some(LifecycleStatus::Deprecated));
+    }
+
+    fn release(name: &str, version: &str) -> DeployedContract {
+        ContractBuilder::new(name)
+            .with_author("azaM")
+            .with_version(version)
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed()
+    }
+
+    #[test]
+    fn rollback_restores_the_previous_version() {
+        let mut registry = ContractRegistry::new();
+        assert!(matches!(
+            registry.rollback("TokenX"),
+            Err(ScrgError::Registry(RegistryError::Rollback(
+                RollbackError::UnknownContract(name)
+            ))) if name == "TokenX"
+        ));
+        registry.register(release("TokenX", "1.0.0")).unwrap();
+        registry.register(release("TokenX", "1.1.0")).unwrap();
+
+        let restored = registry.rollback("TokenX").unwrap();
+        assert_eq!(
+            restored.version().map(Version::to_string),
+            Some("1.0.0".into())
+        );
+        assert_eq!(registry.versions("TokenX").len(), 2);
+        let failed = registry.get_version("TokenX", "1.1.0").unwrap();
+        assert_eq!(failed.status(), Some(LifecycleStatus::RolledBack));
+        assert_eq!(
+            failed.get("rolled_back_to"),
+            Some(&Version::new(1, 0, 0).into())
+        );
+        assert!(matches!(
+            registry.rollback("TokenX"),
+            Err(ScrgError::Registry(RegistryError::Rollback(
+                RollbackError::NoPreviousVersion(name)
+            ))) if name == "TokenX"
+        ));
+    }
+
+    #[test]
+    fn every_metadata_mutation_is_audited() {
+        let builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("decimals", 8)
+            .with_metadata("decimals", 18)
+            .validate()
+            .unwrap();
+        let trail: Vec<(&str, &str)> = builder
+            .audit_log()
+            .iter()
+            .map(|entry| (entry.actor.as_str(), entry.key.as_str()))
+            .collect();
+        assert_eq!(
+            trail,
+            [
+                ("builder", "author"),
+                ("builder", "decimals"),
+                ("builder", "decimals"),
+                ("validate", "validated"),
+                ("validate", "validated_at"),
+            ]
+        );
+        let bump = &builder.audit_log()[2];
+        assert_eq!(
+            (bump.old_value(), bump.new_value()),
+            (Some(&8.into()), Some(&18.into()))
+        );
+
+        let mut contract = builder.deploy().unwrap().into_deployed();
+        let logged = contract.audit_log().len();
+        assert_eq!(contract.set("decimals", 6, "ops"), Ok(Some(18.into())));
+        assert_eq!(contract.remove("decimals", "ops"), Ok(Some(6.into())));
+        let tail = &contract.audit_log()[logged..];
+        assert_eq!(tail.len(), 2);
+        assert_eq!(tail[1].new_value(), None);
+        assert!(tail.iter().all(|entry| entry.actor == "ops"));
+    }
+
+    #[test]
+    fn snapshot_keeps_the_deployed_metadata() {
+        let mut contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("decimals", 18)
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        let snapshot = contract.snapshot();
+        contract.set("decimals", 6, "ops").unwrap();
+
+        assert_eq!(snapshot.get("decimals"), Some(&18.into()));
+        assert_eq!(contract.snapshot(), snapshot);
+        assert_eq!(snapshot.get("status"), Some(&"deployed".into()));
+    }
+
+    #[test]
+    fn register_requires_live_dependencies() {
+        let proxy = ContractBuilder::new("Proxy")
+            .with_author("azaM")
+            .depends_on("TokenX")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(proxy.dependencies(), ["TokenX"]);
+
+        let mut registry = ContractRegistry::new();
+        assert!(matches!(
+            registry.register(proxy.clone()),
+            Err(ScrgError::Registry(RegistryError::MissingDependency { contract, dependency }))
+                if contract == "Proxy" && dependency == "TokenX"
+        ));
+
+        registry.register(deployed("TokenX")).unwrap();
+        registry.archive("TokenX").unwrap();
+        assert!(matches!(
+            registry.register(proxy.clone()),
+            Err(ScrgError::Registry(RegistryError::DependencyNotDeployed {
+                status: Some(LifecycleStatus::Archived),
+                ..
+            }))
+        ));
+
+        registry.register(deployed("TokenX")).unwrap();
+        assert!(matches!(registry.register(proxy), Ok(None)));
+    }
+
+    #[test]
+    fn deploy_all_orders_by_dependency_and_skips_downstream_failures() {
+        let builder = |name: &str| ContractBuilder::new(name).with_author("azaM");
+        let mut registry = ContractRegistry::new();
+        let report = registry
+            .deploy_all(vec![
+                builder("Proxy").depends_on("Vault"),
+                builder("Vault").depends_on("TokenX"),
+                builder("TokenX"),
+            ])
+            .unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.deployed, ["TokenX", "Vault", "Proxy"]);
+
+        let mut registry = ContractRegistry::new();
+        let report = registry
+            .deploy_all(vec![
+                builder("Vault").depends_on("TokenX"),
+                builder("TokenX")
+                    .on_phase(Phase::PreValidate, |_, _| Err(HookError::new("no audit"))),
+                builder("Oracle"),
+            ])
+            .unwrap();
+        assert_eq!(report.deployed, ["Oracle"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "TokenX");
+        assert_eq!(report.skipped, [("Vault".into(), "TokenX".into())]);
+        assert!(registry.get("Vault").is_none());
+
+        let cycle = registry.deploy_all(vec![
+            builder("A").depends_on("B"),
+            builder("B").depends_on("A"),
+        ]);
+        assert!(matches!(
+            cycle,
+            Err(ScrgError::Registry(RegistryError::DependencyCycle(DependencyCycle(path))))
+                if path == ["A", "B", "A"]
+        ));
+        assert!(registry.get("A").is_none());
+    }
+
+    #[test]
+    fn query_combines_predicates() {
+        let mut registry = ContractRegistry::new();
+        for (name, network, auditor) in [
+            ("TokenX", "mainnet", Some("Trail of Bits")),
+            ("Vault", "mainnet", None),
+            ("Oracle", "testnet", Some("Trail of Bits")),
+        ] {
+            let mut builder = ContractBuilder::new(name)
+                .with_author("azaM")
+                .with_metadata("network", network);
+            if let Some(auditor) = auditor {
+                builder = builder.with_metadata("auditor", auditor);
+            }
+            let contract = builder.validate().unwrap().deploy().unwrap();
+            registry.register(contract.into_deployed()).unwrap();
+        }
+        registry.archive("Oracle").unwrap();
+
+        let names = |query: Query<'_>| {
+            let mut names: Vec<&str> = query.collect().into_iter().map(|c| c.name()).collect();
+            names.sort_unstable();
+            names.into_iter().map(str::to_owned).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(registry.query().where_key_eq("network", "mainnet")),
+            ["TokenX", "Vault"]
+        );
+        assert_eq!(
+            names(
+                registry
+                    .query()
+                    .where_key_exists("auditor")
+                    .where_status(LifecycleStatus::Deployed)
+            ),
+            ["TokenX"]
+        );
+        let short = registry
+            .query()
+            .matching(|contract| contract.name().len() == 5);
+        assert_eq!(short.count(), 1);
+    }
+
+    #[test]
+    fn tag_index_follows_registry_changes() {
+        let tagged = |name: &str, tags: &[&str]| {
+            ContractBuilder::new(name)
+                .with_author("azaM")
+                .with_tags(tags.iter().copied())
+                .with_tags(["defi"])
+                .validate()
+                .unwrap()
+                .deploy()
+                .unwrap()
+                .into_deployed()
+        };
+        let token = tagged("TokenX", &["defi", "erc20"]);
+        assert_eq!(token.tags(), ["defi", "erc20"]);
+
+        let mut registry = ContractRegistry::new();
+        registry.register(token).unwrap();
+        registry.register(tagged("Vault", &["vault"])).unwrap();
+        let names = |registry: &ContractRegistry, tag| {
+            registry
+                .by_tag(tag)
+                .map(|c| c.name().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&registry, "defi"), ["TokenX", "Vault"]);
+        assert_eq!(names(&registry, "erc20"), ["TokenX"]);
+
+        registry.archive("Vault").unwrap();
+        assert_eq!(names(&registry, "defi"), ["TokenX"]);
+        registry.remove("TokenX").unwrap();
+        assert!(names(&registry, "erc20").is_empty());
+        assert!(registry.tag_index.is_empty());
+    }
+
+    #[test]
+    fn secondary_index_tracks_updates_and_matches_a_scan() {
+        let mut registry = ContractRegistry::new();
+        for (name, network) in [("TokenX", "mainnet"), ("Vault", "testnet")] {
+            let contract = ContractBuilder::new(name)
+                .with_author("azaM")
+                .with_metadata("network", network)
+                .validate()
+                .unwrap()
+                .deploy()
+                .unwrap();
+            registry.register(contract.into_deployed()).unwrap();
+        }
+        let names = |found: Vec<&DeployedContract>| {
+            found
+                .into_iter()
+                .map(|c| c.name().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let scanned = names(registry.find_by("network", "mainnet"));
+
+        registry.create_index("network");
+        assert_eq!(names(registry.find_by("network", "mainnet")), scanned);
+        registry
+            .update_metadata("Vault", "network", "mainnet")
+            .unwrap();
+        assert_eq!(
+            names(registry.find_by("network", "mainnet")),
+            ["TokenX", "Vault"]
+        );
+        assert!(registry.find_by("network", "testnet").is_empty());
+        assert!(registry
+            .find_by("network", MetadataValue::Address("mainnet".into()))
+            .is_empty());
+
+        registry.remove("TokenX").unwrap();
+        assert_eq!(names(registry.find_by("network", "mainnet")), ["Vault"]);
+        assert!(registry.drop_index("network"));
+        assert!(!registry.drop_index("network"));
+    }
+
+    contract_metadata! {
+        #[derive(Debug, PartialEq)]
+        struct TokenInfo {
+            license: String,
+            decimals: i64,
+        }
+    }
+
+    #[test]
+    fn typed_fields_round_trip_through_metadata() {
+        let info = TokenInfo {
+            license: "MIT".into(),
+            decimals: 18,
+        };
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_fields(info)
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(
+            contract.fields::<TokenInfo>(),
+            Ok(TokenInfo {
+                license: "MIT".into(),
+                decimals: 18,
+            })
+        );
+
+        let mut wrong = contract.clone();
+        wrong.set("decimals", "eighteen", "ops").unwrap();
+        assert_eq!(
+            wrong.fields::<TokenInfo>(),
+            Err(FieldError {
+                key: "decimals",
+                found: Some("string"),
+            })
+        );
+        wrong.remove("license", "ops").unwrap();
+        assert_eq!(
+            wrong.fields::<TokenInfo>().unwrap_err().to_string(),
+            "metadata field `license` is missing"
+        );
+    }
+
+    #[test]
+    fn contract_macro_runs_the_whole_pipeline() {
+        let token = contract! {
+            name: "TokenX",
+            author: "azaM",
+            version: "1.0.0",
+            tags: ["defi"],
+            metadata: { "network" => "mainnet" },
+            deploy: |meta| {
+                meta.insert("signer".into(), "0xDEADBEEF".into());
+            },
+        }
+        .unwrap();
+        assert_eq!(token.version(), Some(&Version::new(1, 0, 0)));
+        assert_eq!(token.tags(), ["defi"]);
+        assert_eq!(token.get("network"), Some(&"mainnet".into()));
+        assert_eq!(token.get("signer"), Some(&"0xDEADBEEF".into()));
+
+        let minimal = contract! { name: "Vault", author: "azaM" }.unwrap();
+        assert_eq!(minimal.status(), Some(LifecycleStatus::Deployed));
+        let invalid = contract! { name: "", author: "azaM" };
+        assert!(invalid.is_err());
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn cli_add_then_deploy_keeps_author_and_tags() {
+        let path = temp_path("cli.json");
+        let mut store = JsonFileStore::new(&path);
+        let run = |store: &mut JsonFileStore, line: &str| {
+            let args: Vec<String> = line.split_whitespace().map(str::to_owned).collect();
+            run_command(store, &args)
+        };
+        run(
+            &mut store,
+            "add TokenX --author azaM --version 1.0.0 --tag defi --set decimals=18",
+        )
+        .unwrap();
+        run(
+            &mut store,
+            "deploy TokenX --version 1.1.0 --hook-signer 0xABC",
+        )
+        .unwrap();
+        assert!(run(&mut store, "deploy TokenX").is_err());
+        assert!(run(&mut store, "show Missing").is_err());
+
+        let registry = store.load().unwrap();
+        assert_eq!(registry.versions("TokenX").len(), 2);
+        let latest = registry.get("TokenX").unwrap();
+        assert_eq!(latest.get("author"), Some(&"azaM".into()));
+        assert_eq!(latest.tags(), ["defi"]);
+        assert_eq!(
+            latest.get("signer"),
+            Some(&MetadataValue::Address("0xABC".into()))
+        );
+        assert_eq!(parse_plain_value("18"), MetadataValue::Int(18));
+        assert_eq!(parse_plain_value("yes"), "yes".into());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn timestamps_render_as_rfc3339() {
+        let at = |secs: i64| {
+            let offset = std::time::Duration::from_secs(secs.unsigned_abs());
+            if secs < 0 {
+                SystemTime::UNIX_EPOCH - offset
+            } else {
+                SystemTime::UNIX_EPOCH + offset
+            }
+        };
+        assert_eq!(format_rfc3339(at(0)), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(at(951_782_400)), "2000-02-29T00:00:00Z");
+        assert_eq!(format_rfc3339(at(1_751_119_380)), "2025-06-28T14:03:00Z");
+        assert_eq!(format_rfc3339(at(-1)), "1969-12-31T23:59:59Z");
+
+        let builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap();
+        let validated_at = builder.validated_at().unwrap();
+        let contract = builder.deploy().unwrap().into_deployed();
+        assert_eq!(contract.validated_at(), Some(validated_at));
+        assert!(contract.deployed_at() >= validated_at);
+        assert_eq!(
+            contract.get("deployed_at"),
+            Some(&contract.deployed_at().into())
+        );
+    }
+
+    // Deterministic stand-in: the "signature" is the first bytes of the payload
+    struct FixedSigner;
+
+    impl Signer for FixedSigner {
+        fn address(&self) -> String {
+            "0xS1GNER".into()
+        }
+
+        fn sign(&self, payload: &[u8]) -> Signature {
+            Signature(payload.iter().take(4).copied().collect())
+        }
+    }
+
+    #[test]
+    fn signer_covers_the_final_deploy_metadata() {
+        let mut contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .with_signer(FixedSigner)
+            .add_hook(|meta| {
+                meta.insert("network".into(), "mainnet".into());
+            })
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(contract.signer(), Some("0xS1GNER"));
+
+        let payload = contract.signing_payload();
+        let text = String::from_utf8(payload.clone()).unwrap();
+        assert!(text.starts_with("TokenX\n"));
+        assert!(text.contains("network:string=mainnet\n"));
+        assert!(text.contains("signer:address=0xS1GNER\n"));
+        assert!(!text.contains("signature"));
+        assert_eq!(contract.signature(), Some(FixedSigner.sign(&payload)));
+
+        contract.set("network", "testnet", "ops").unwrap();
+        assert_eq!(contract.signing_payload(), payload);
+        assert_eq!(contract.signature().unwrap().to_string(), "0x546f6b65");
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn secp256k1_signatures_verify_against_the_snapshot() {
+        // Well-known test key: the secret 1 maps to the generator point
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let signer = Secp256k1Signer::from_bytes(&secret).unwrap();
+        assert_eq!(
+            signer.address(),
+            "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+        );
+        let public_key = signer.verifying_key();
+
+        let mut contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .with_signer(signer)
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(contract.verify_signature(&public_key), Ok(()));
+        contract.set("network", "testnet", "ops").unwrap();
+        assert_eq!(contract.verify_signature(&public_key), Ok(()));
+
+        secret[31] = 2;
+        let other = Secp256k1Signer::from_bytes(&secret).unwrap();
+        assert_eq!(
+            contract.verify_signature(&other.verifying_key()),
+            Err(SignatureError::SignerMismatch)
+        );
+        assert_eq!(
+            deployed("Vault").verify_signature(&public_key),
+            Err(SignatureError::Unsigned)
+        );
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn sha256_matches_nist_vectors() {
+        let vectors = [
+            (
+                "",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                "abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ];
+        for (input, digest) in vectors {
+            assert_eq!(hex(&sha256(input.as_bytes())), digest, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn content_ids_find_every_registered_version() {
+        let mut registry = ContractRegistry::new();
+        registry.register(release("TokenX", "1.0.0")).unwrap();
+        registry.register(release("TokenX", "1.1.0")).unwrap();
+        let old = registry.get_version("TokenX", "1.0.0").unwrap();
+        let id = old.id().unwrap().to_owned();
+        assert_eq!(id.len(), 64);
+        assert_eq!(id, content_id("TokenX", &old.snapshot()));
+
+        registry.rollback("TokenX").unwrap();
+        let latest_id = registry.get("TokenX").unwrap().id().unwrap().to_owned();
+        assert_eq!(latest_id, id);
+        let newer = registry.get_version("TokenX", "1.1.0").unwrap();
+        let newer_id = newer.id().unwrap().to_owned();
+        assert_ne!(newer_id, id);
+        assert_eq!(
+            registry
+                .by_id(&newer_id)
+                .and_then(DeployedContract::version),
+            Some(&Version::new(1, 1, 0))
+        );
+
+        registry.remove("TokenX").unwrap();
+        assert!(registry.by_id(&id).is_none());
+    }
+
+    #[test]
+    fn every_instance_gets_its_own_uuid_v7() {
+        let uuid = Uuid::now_v7();
+        let text = uuid.to_string();
+        assert_eq!(text.len(), 36);
+        assert_eq!(&text[14..15], "7");
+        assert!(matches!(&text[19..20], "8" | "9" | "a" | "b"));
+        assert!(Uuid::now_v7() != uuid);
+
+        let builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_version("1.0.0")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap();
+        let first = builder.borrow()["uuid"].to_string();
+        let upgraded = builder.upgrade_to("1.1.0").unwrap().into_deployed();
+        assert_ne!(upgraded.uuid(), Some(first.as_str()));
+        let (a, b) = (deployed("TokenX"), deployed("TokenX"));
+        assert_ne!(a.uuid(), b.uuid());
+    }
+
+    #[test]
+    fn keccak256_matches_known_digests() {
+        let multi_block = [b'a'; 200];
+        let vectors: [(&[u8], &str); 3] = [
+            (
+                b"",
+                "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+            ),
+            (
+                b"abc",
+                "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45",
+            ),
+            (
+                &multi_block,
+                "96ea54061def936c4be90b518992fdc6f12f535068a256229aca54267b4d084d",
+            ),
+        ];
+        for (input, digest) in vectors {
+            assert_eq!(hex(&keccak256(input)), digest, "{} bytes", input.len());
+        }
+    }
+
+    #[test]
+    fn addresses_are_checksummed_and_verified() {
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let lower = checksummed.to_lowercase();
+        let address: Address = lower.parse().unwrap();
+        assert_eq!(address.to_string(), checksummed);
+        assert_eq!(checksummed.parse::<Address>(), Ok(address));
+        assert!(lower
+            .to_uppercase()
+            .replace("0X", "0x")
+            .parse::<Address>()
+            .is_ok());
+        let tampered = checksummed.replace("aA", "Aa");
+        assert_eq!(
+            tampered.parse::<Address>().unwrap_err().reason,
+            "EIP-55 checksum mismatch"
+        );
+        assert!("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+            .parse::<Address>()
+            .is_err());
+
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("address", lower.as_str())
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(
+            contract.get("address"),
+            Some(&MetadataValue::Address(checksummed.into()))
+        );
+        let rejected = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("signer", tampered.as_str())
+            .validate();
+        assert!(matches!(
+            rejected,
+            Err(ScrgError::Validation(ValidationError::InvalidAddress { ref key, .. })) if key == "signer"
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    const ERC20_ABI: &str = r#"[
+        {"type": "constructor", "inputs": []},
+        {"name": "transfer", "stateMutability": "nonpayable",
+         "inputs": [{"name": "to", "type": "address"}, {"name": "amount", "type": "uint256"}],
+         "outputs": [{"name": "", "type": "bool"}]},
+        {"type": "event", "name": "Transfer", "anonymous": false,
+         "inputs": [{"name": "from", "type": "address", "indexed": true},
+                    {"name": "to", "type": "address", "indexed": true},
+                    {"name": "value", "type": "uint256"}]}
+    ]"#;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn abi_is_parsed_at_validation_and_read_back() {
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_abi(ERC20_ABI)
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        let abi = contract.abi().unwrap();
+        assert_eq!(abi.functions().len(), 1);
+        let transfer = abi.function("transfer").unwrap();
+        assert_eq!(transfer.signature(), "transfer(address,uint256)");
+        assert_eq!(transfer.state_mutability, "nonpayable");
+        let event = abi.event("Transfer").unwrap();
+        let indexed: Vec<bool> = event.inputs.iter().map(|p| p.indexed).collect();
+        assert_eq!(indexed, [true, true, false]);
+        assert_eq!(Abi::from_metadata(&abi.clone().into()), Some(abi));
+
+        let rejected = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_abi("{not json")
+            .validate();
+        assert!(matches!(
+            rejected,
+            Err(ScrgError::Validation(ValidationError::InvalidAbi(_)))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn artifacts_from_hardhat_and_foundry_become_builders() {
+        let hardhat = temp_path("Token.hardhat.json");
+        std::fs::write(
+            &hardhat,
+            r#"{"contractName": "TokenX", "sourceName": "contracts/TokenX.sol",
+                "abi": [], "bytecode": "0x6080", "deployedBytecode": "0x"}"#,
+        )
+        .unwrap();
+        let contract = ContractBuilder::from_artifact(&hardhat)
+            .unwrap()
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(contract.name(), "TokenX");
+        assert_eq!(contract.get("artifact_format"), Some(&"hardhat".into()));
+        assert_eq!(
+            contract.get("bytecode"),
+            Some(&MetadataValue::Bytes(vec![0x60, 0x80]))
+        );
+        assert!(contract.get("deployed_bytecode").is_none());
+
+        let foundry = temp_path("Vault.json");
+        let solc = r#"{"compiler": {"version": "0.8.24"},
+            "settings": {"compilationTarget": {"src/Vault.sol": "Vault"},
+                         "optimizer": {"enabled": true, "runs": 200}, "evmVersion": "cancun"}}"#;
+        let artifact = serde_json::json!({
+            "abi": [],
+            "bytecode": {"object": "0x00"},
+            "metadata": solc,
+        });
+        std::fs::write(&foundry, artifact.to_string()).unwrap();
+        let builder = ContractBuilder::from_artifact(&foundry).unwrap();
+        assert_eq!(builder.name(), "Vault");
+        let meta = builder.borrow();
+        assert_eq!(meta.get("source"), Some(&"src/Vault.sol".into()));
+        assert_eq!(meta.get("compiler_version"), Some(&"0.8.24".into()));
+        assert_eq!(meta.get("optimizer_runs"), Some(&200.into()));
+        assert_eq!(meta.get("evm_version"), Some(&"cancun".into()));
+        drop(meta);
+
+        std::fs::write(&foundry, r#"{"bytecode": "0x608"}"#).unwrap();
+        assert!(matches!(
+            ContractBuilder::from_artifact(&foundry),
+            Err(ScrgError::Serialization(err))
+                if matches!(err.downcast_ref(), Some(ArtifactError::Invalid(_)))
+        ));
+        assert_eq!(decode_hex("zz"), Err("non-hex character"));
+        std::fs::remove_file(hardhat).unwrap();
+        std::fs::remove_file(foundry).unwrap();
+    }
+
+    #[test]
+    fn bytecode_is_decoded_size_checked_and_hashed() {
+        let with_code = |hex: &str| {
+            ContractBuilder::new("TokenX")
+                .with_author("azaM")
+                .with_bytecode(hex)
+                .validate()
+        };
+        assert!(matches!(
+            with_code("0x").map(|_| ()),
+            Err(ScrgError::Validation(ValidationError::InvalidBytecode(
+                "empty"
+            )))
+        ));
+        assert!(matches!(
+            with_code("0x6g").map(|_| ()),
+            Err(ScrgError::Validation(ValidationError::InvalidBytecode(
+                "non-hex character"
+            )))
+        ));
+        let too_large = "00".repeat(MAX_BYTECODE_LEN + 1);
+        assert!(matches!(
+            with_code(&too_large).map(|_| ()),
+            Err(ScrgError::Validation(ValidationError::BytecodeTooLarge { len, .. })) if len == MAX_BYTECODE_LEN + 1
+        ));
+
+        let contract = with_code("616263")
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(contract.bytecode(), Some(&b"abc"[..]));
+        assert_eq!(
+            contract.bytecode_hash().as_deref(),
+            Some("0x4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45")
+        );
+        assert!(deployed("Vault").bytecode_hash().is_none());
+    }
+
+    #[test]
+    fn artifacts_are_checked_per_runtime() {
+        let validate = |artifact: ContractArtifact| {
+            ContractBuilder::new("TokenX")
+                .with_author("azaM")
+                .with_artifact(artifact)
+                .validate()
+                .map(|validated| validated.deploy().unwrap().into_deployed())
+        };
+        let mut wasm = WASM_HEADER.to_vec();
+        wasm.extend([0x01, 0x04]);
+        let contract = validate(ContractArtifact::WasmModule(wasm.clone())).unwrap();
+        assert_eq!(contract.get("runtime"), Some(&"wasm".into()));
+        assert_eq!(
+            contract.artifact(),
+            Some(ContractArtifact::WasmModule(wasm))
+        );
+        assert!(contract.bytecode_hash().is_some());
+
+        assert!(matches!(
+            validate(ContractArtifact::WasmModule(vec![0x60, 0x80])),
+            Err(ScrgError::Validation(ValidationError::InvalidBytecode(_)))
+        ));
+        let oversized = vec![0; MAX_BYTECODE_LEN + 1];
+        assert!(matches!(
+            validate(ContractArtifact::EvmBytecode(oversized.clone())),
+            Err(ScrgError::Validation(ValidationError::BytecodeTooLarge { ref runtime, .. })) if runtime == "evm"
+        ));
+        let move_vm = ContractArtifact::from_parts("move", oversized);
+        assert_eq!(move_vm.runtime(), "move");
+        assert!(validate(move_vm).is_ok());
+    }
+
+    #[cfg(feature = "ethers")]
+    #[test]
+    fn deploy_onchain_needs_evm_bytecode_before_any_rpc() {
+        let wallet: ethers::signers::LocalWallet =
+            "0000000000000000000000000000000000000000000000000000000000000001"
+                .parse()
+                .unwrap();
+        for artifact in [
+            None,
+            Some(ContractArtifact::WasmModule(WASM_HEADER.to_vec())),
+        ] {
+            let mut builder = ContractBuilder::new("TokenX").with_author("azaM");
+            if let Some(artifact) = artifact {
+                builder = builder.with_artifact(artifact);
+            }
+            let validated = builder.validate().unwrap();
+            // An empty mock answers nothing, so reaching the node would fail differently
+            let (provider, _mock) = ethers::providers::Provider::mocked();
+            let result = block_on(validated.deploy_onchain(provider, wallet.clone()));
+            assert!(matches!(result, Err(OnchainError::NoBytecode)));
+        }
+    }
+
+    #[test]
+    fn estimate_gas_records_a_limit_within_budget() {
+        let builder = |budget: Option<u64>, limit: Option<u64>| {
+            let mut builder = ContractBuilder::new("TokenX")
+                .with_author("azaM")
+                .with_bytecode("0x600001");
+            if let Some(budget) = budget {
+                builder = builder.with_gas_budget(budget);
+            }
+            if let Some(limit) = limit {
+                builder = builder.with_gas_limit(limit);
+            }
+            builder.validate().unwrap()
+        };
+        // 53_000 base + 16 + 4 + 16 calldata + 3 * 200 deposit
+        assert_eq!(
+            intrinsic_creation_gas("TokenX", &builder(None, None).borrow()),
+            Ok(53_636)
+        );
+
+        let mut estimated_builder = builder(Some(60_000), None);
+        assert_eq!(
+            estimated_builder
+                .estimate_gas(intrinsic_creation_gas)
+                .unwrap(),
+            53_636
+        );
+        let meta = estimated_builder.borrow();
+        assert_eq!(meta.get("estimated_gas"), Some(&53_636.into()));
+        assert_eq!(meta.get("gas_limit"), Some(&(53_636 * 120 / 100).into()));
+        drop(meta);
+
+        let mut tight = builder(Some(50_000), None);
+        let over_budget = tight.estimate_gas(intrinsic_creation_gas);
+        assert!(matches!(
+            over_budget,
+            Err(ScrgError::Gas(GasError::OverBudget {
+                estimated: 53_636,
+                budget: 50_000
+            }))
+        ));
+        assert_eq!(tight.borrow().get("estimated_gas"), None);
+        assert!(tight.deploy().is_ok());
+        let over_limit = builder(None, Some(53_000)).estimate_gas(intrinsic_creation_gas);
+        assert!(matches!(
+            over_limit,
+            Err(ScrgError::Gas(GasError::OverLimit { .. }))
+        ));
+        let failing = |_: &str, _: &MetadataMap| Err(HookError::new("node offline"));
+        assert!(matches!(
+            builder(None, None).estimate_gas(failing),
+            Err(ScrgError::Gas(GasError::Estimate(_)))
+        ));
+    }
+
+    #[test]
+    fn contracts_filter_by_chain_id() {
+        let on = |name: &str, network: Network| {
+            ContractBuilder::new(name)
+                .with_author("azaM")
+                .with_network(network)
+                .validate()
+                .unwrap()
+                .deploy()
+                .unwrap()
+                .into_deployed()
+        };
+        let mainnet = Network::new("mainnet", 1).with_rpc_url("https://eth.example");
+        let token = on("TokenX", mainnet.clone());
+        assert_eq!(token.network(), Some(mainnet));
+        assert_eq!(token.chain_id(), Some(1));
+
+        let mut registry = ContractRegistry::new();
+        registry.register(token).unwrap();
+        registry
+            .register(on("Vault", Network::new("sepolia", 11_155_111)))
+            .unwrap();
+        registry.register(deployed("Oracle")).unwrap();
+        let on_mainnet: Vec<&str> = registry.on_chain(1).map(|c| c.name()).collect();
+        assert_eq!(on_mainnet, ["TokenX"]);
+        assert_eq!(registry.query().where_chain_id(11_155_111).count(), 1);
+        assert!(registry.get("Oracle").unwrap().network().is_none());
+    }
+
+    #[test]
+    fn deployment_records_are_kept_per_chain() {
+        let record = |block: u64| DeploymentRecord {
+            address: "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+                .parse()
+                .unwrap(),
+            tx_hash: format!("0x{block:064x}"),
+            block,
+            timestamp: SystemTime::UNIX_EPOCH,
+        };
+        let mut registry = ContractRegistry::new();
+        registry.register(deployed("TokenX")).unwrap();
+        let events = registry.watch();
+        registry
+            .record_deployment("TokenX", 1, record(100))
+            .unwrap();
+        registry.record_deployment("TokenX", 10, record(7)).unwrap();
+        assert!(matches!(
+            registry.record_deployment("Vault", 1, record(1)),
+            Err(ScrgError::Registry(RegistryError::UnknownContract(_)))
+        ));
+        assert_eq!(events.try_iter().count(), 2);
+
+        let mut contract = registry.get("TokenX").unwrap().clone();
+        assert_eq!(contract.deployment(10), Some(record(7)));
+        let chains: Vec<u64> = contract.deployments().into_keys().collect();
+        assert_eq!(chains, [1, 10]);
+        assert_eq!(
+            contract.add_deployment(1, record(200), "ops"),
+            Ok(Some(record(100)))
+        );
+        assert_eq!(registry.on_chain(10).count(), 1);
+        assert_eq!(registry.query().where_chain_id(5).count(), 0);
+    }
+
+    #[test]
+    fn dry_run_reports_changes_without_deploying() {
+        let mut builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .add_hook(|meta| {
+                meta.insert("supply".into(), "1000".into());
+            });
+        let audit_len = builder.audit_log().len();
+        let dry_run = builder.deploy_dry_run().unwrap();
+        let mut keys: Vec<&str> = dry_run.changed_keys().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["deployed_at", "status", "supply"]);
+        assert!(dry_run.changes.iter().all(|entry| entry.actor == "dry_run"));
+        assert_eq!(dry_run.metadata.get("supply"), Some(&"1000".into()));
+
+        assert_eq!(builder.metadata.borrow().get("supply"), None);
+        assert_eq!(builder.audit_log().len(), audit_len);
+        let deployed = builder.deploy().unwrap().into_deployed();
+        assert_eq!(deployed.get("supply"), Some(&"1000".into()));
+    }
+
+    #[test]
+    fn erc20_template_presets_metadata_and_requires_a_symbol() {
+        let template = ContractTemplate::erc20();
+        assert_eq!(template.name(), "ERC20");
+        let missing = ContractBuilder::from_template(&template, "TokenX")
+            .with_author("azaM")
+            .validate();
+        assert!(matches!(
+            missing,
+            Err(ScrgError::Validation(ValidationError::Hook(_)))
+        ));
+
+        let deployed = ContractBuilder::from_template(&template, "TokenX")
+            .with_author("azaM")
+            .with_metadata("symbol", "TKX")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(deployed.get("template"), Some(&"ERC20".into()));
+        assert_eq!(deployed.get("standard"), Some(&"ERC20".into()));
+        assert_eq!(deployed.get("decimals"), Some(&18.into()));
+        assert_eq!(deployed.tags(), ["token", "erc20"]);
+    }
+
+    #[test]
+    fn validated_metadata_rehydrates_into_a_builder() {
+        let builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap();
+        let stored = builder.metadata.borrow().clone();
+        let resumed = ContractBuilder::from_metadata("TokenX", stored.clone()).unwrap();
+        let deployed = resumed.deploy().unwrap().into_deployed();
+        assert_eq!(deployed.get("author"), Some(&"azaM".into()));
+
+        let mut unvalidated = stored.clone();
+        unvalidated.remove("validated");
+        assert!(matches!(
+            ContractBuilder::from_metadata("TokenX", unvalidated),
+            Err(ScrgError::Storage(StoreError::Rehydrate(
+                RehydrateError::NotValidated
+            )))
+        ));
+        let mut no_author = stored.clone();
+        no_author.remove("author");
+        assert!(matches!(
+            ContractBuilder::from_metadata("TokenX", no_author),
+            Err(ScrgError::Storage(StoreError::Rehydrate(
+                RehydrateError::MissingKey(key)
+            ))) if key == "author"
+        ));
+        assert!(matches!(
+            ContractBuilder::from_metadata("TokenX", deployed.metadata().clone()),
+            Err(ScrgError::Storage(StoreError::Rehydrate(
+                RehydrateError::AlreadyDeployed(status)
+            ))) if status == "deployed"
+        ));
+    }
+
+    #[test]
+    fn builders_and_contracts_display_their_state() {
+        let builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("symbol", "TKX");
+        assert!(builder.to_string().starts_with(
+            "📘 Contract Metadata (TokenX, init):\n  author: azaM\n  symbol: TKX\n  uuid: "
+        ));
+        let debug = format!("{builder:?}");
+        assert!(debug.starts_with("ContractBuilder { name: \"TokenX\", state: \"init\""));
+
+        let validated = builder.validate().unwrap();
+        assert!(validated.to_string().contains("(TokenX, validated)"));
+        let contract = validated.deploy().unwrap().into_deployed();
+        let display = contract.to_string();
+        assert!(display.starts_with("📘 Contract Metadata (TokenX, deployed):"));
+        assert!(display.contains("\n  status: deployed"));
+        assert!(format!("{contract:?}").contains("status: Some(Deployed)"));
+    }
+
+    #[test]
+    fn registry_iterates_latest_versions() {
+        let mut registry = ContractRegistry::new();
+        assert!(registry.is_empty());
+        registry.register(deployed("TokenX")).unwrap();
+        registry.register(deployed("Vault")).unwrap();
+        assert!(registry.archive("Vault").unwrap());
+        assert_eq!(registry.len(), 2);
+
+        let mut names: Vec<&str> = registry.names().collect();
+        names.sort_unstable();
+        assert_eq!(names, ["TokenX", "Vault"]);
+        let mut iterated: Vec<&str> = (&registry).into_iter().map(|c| c.name()).collect();
+        iterated.sort_unstable();
+        assert_eq!(iterated, ["TokenX", "Vault"]);
+        assert_eq!(registry.list().count(), 1);
+    }
+
+    #[test]
+    fn register_all_reports_each_failure_and_carries_on() {
+        let validated = |name: &str, dependency: Option<&str>| {
+            let builder = ContractBuilder::new(name).with_author("azaM");
+            match dependency {
+                Some(dependency) => builder.depends_on(dependency),
+                None => builder,
+            }
+            .validate()
+            .unwrap()
+        };
+        let mut registry = ContractRegistry::new();
+        let report = registry.register_all([
+            validated("Vault", Some("TokenX")),
+            validated("TokenX", None),
+            validated("Router", Some("TokenX")),
+        ]);
+        assert_eq!(report.deployed, ["TokenX", "Router"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "Vault");
+        assert!(registry.get("Vault").is_none());
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn deploy_parallel_runs_waves_and_skips_downstream_failures() {
+        let job = |name: &'static str, author: &'static str| {
+            DeployJob::new(name, move || {
+                ContractBuilder::new(name).with_author(author).validate()
+            })
+        };
+        let jobs = vec![
+            job("Router", "azaM")
+                .depends_on("TokenX")
+                .depends_on("Oracle"),
+            job("TokenX", "azaM"),
+            DeployJob::new("Oracle", || {
+                Err::<ContractBuilder<Validated>, _>(HookError::new("price feed unreachable"))
+            }),
+            job("Vault", "azaM").depends_on("TokenX"),
+        ];
+        let mut registry = ContractRegistry::new();
+        let report = registry.deploy_parallel(jobs, 2).unwrap();
+        let mut deployed = report.deployed.clone();
+        deployed.sort_unstable();
+        assert_eq!(deployed, ["TokenX", "Vault"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "Oracle");
+        assert_eq!(report.skipped, [("Router".to_owned(), "Oracle".to_owned())]);
+
+        let cycle = vec![
+            job("A", "azaM").depends_on("B"),
+            job("B", "azaM").depends_on("A"),
+        ];
+        assert!(registry.deploy_parallel(cycle, 2).is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn deploy_parallel_async_registers_every_wave() {
+        let jobs = ["TokenX", "Vault", "Router"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let job = DeployJob::new(name, move || {
+                    ContractBuilder::new(name).with_author("azaM").validate()
+                });
+                match i {
+                    0 => job,
+                    _ => job.depends_on("TokenX"),
+                }
+            })
+            .collect();
+        let mut registry = ContractRegistry::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let report = runtime
+            .block_on(registry.deploy_parallel_async(jobs, 1))
+            .unwrap();
+        assert!(report.is_success());
+        assert_eq!(report.deployed[0], "TokenX");
+        assert_eq!(registry.len(), 3);
+    }
+
+    #[test]
+    fn schema_reports_every_violating_field() {
+        let schema = MetadataSchema::new()
+            .field("symbol", FieldSchema::new().required().of_type("string"))
+            .field("network", FieldSchema::new().one_of(["mainnet", "sepolia"]))
+            .field("audited", FieldSchema::new().of_type("bool"));
+        let rejected = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("network", "goerli")
+            .with_metadata("audited", "yes")
+            .with_schema(schema.clone())
+            .validate();
+        let Err(ScrgError::Validation(ValidationError::Schema(violations))) = rejected else {
+            panic!("expected schema violations");
+        };
+        let keys: Vec<&str> = violations.iter().map(SchemaViolation::key).collect();
+        assert_eq!(keys, ["audited", "network", "symbol"]);
+        assert_eq!(
+            violations[2],
+            SchemaViolation::Missing {
+                key: "symbol".into()
+            }
+        );
+
+        let accepted = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("symbol", "TKX")
+            .with_metadata("network", "sepolia")
+            .with_schema(schema)
+            .validate();
+        assert!(accepted.is_ok());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn schema_patterns_match_the_display_form() {
+        let schema = MetadataSchema::new().field(
+            "symbol",
+            FieldSchema::new().matching("^[A-Z]{3,5}$").unwrap(),
+        );
+        let rejected = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("symbol", "tkx")
+            .with_schema(schema)
+            .validate();
+        assert!(matches!(
+            rejected,
+            Err(ScrgError::Validation(ValidationError::Schema(violations)))
+                if matches!(&violations[0], SchemaViolation::PatternMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn with_author_unlocks_validate() {
+        // `ContractBuilder::<Init>::validate` does not exist; only the author step reaches it
+        let draft: ContractBuilder<Init<NoAuthor>> =
+            ContractBuilder::new("TokenX").with_metadata("symbol", "TKX");
+        let authored: ContractBuilder<Init<AuthorSet>> = draft.with_author("azaM");
+        assert!(authored.to_string().contains("(TokenX, init)"));
+        let validated = authored.with_author("Trail of Bits").validate().unwrap();
+        assert_eq!(
+            validated.metadata.borrow().get("author"),
+            Some(&"Trail of Bits".into())
+        );
+    }
+
+    #[test]
+    fn invalidate_and_reset_return_to_init() {
+        let validated = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap();
+        let draft = validated.invalidate().with_metadata("symbol", "TKX");
+        {
+            let meta = draft.metadata.borrow();
+            assert_eq!(meta.get("validated"), None);
+            assert_eq!(meta.get("author"), Some(&"azaM".into()));
+        }
+        let deployed = draft.validate().unwrap().deploy().unwrap();
+        let reset = deployed.reset();
+        {
+            let meta = reset.metadata.borrow();
+            assert!(STATE_KEYS.iter().all(|key| !meta.contains_key(*key)));
+            assert_eq!(meta.get("symbol"), Some(&"TKX".into()));
+        }
+        assert!(reset.audit_log().iter().any(|entry| entry.actor == "reset"));
+        assert!(reset.validate().unwrap().deploy().is_ok());
+    }
+
+    #[test]
+    fn key_history_is_bounded_and_oldest_first() {
+        let mut contract = deployed("TokenX");
+        for price in 0..MAX_KEY_HISTORY + 5 {
+            contract.set("price", price.to_string(), "oracle").unwrap();
+        }
+        contract.set("owner", "0xBEEF", "ops").unwrap();
+        let history = contract.history("price");
+        assert_eq!(history.len(), MAX_KEY_HISTORY);
+        assert_eq!(history[0].new_value(), Some(&"5".into()));
+        assert_eq!(
+            history.last().unwrap().new_value(),
+            Some(&(MAX_KEY_HISTORY + 4).to_string().into())
+        );
+        assert!(history.iter().all(|entry| entry.actor == "oracle"));
+        assert_eq!(contract.history("owner").len(), 1);
+        // Trimming the history leaves the audit log whole
+        let prices = contract
+            .audit_log()
+            .iter()
+            .filter(|entry| entry.key == "price");
+        assert_eq!(prices.count(), MAX_KEY_HISTORY + 5);
+        assert_eq!(contract.audit_log().last().unwrap().key, "owner");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn key_history_is_rebuilt_from_a_json_audit_log() {
+        let mut contract = deployed("TokenX");
+        for price in 0..MAX_KEY_HISTORY + 5 {
+            contract.set("price", price.to_string(), "oracle").unwrap();
+        }
+        let json = serde_json::to_string(&contract).unwrap();
+        let loaded: DeployedContract = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.audit_log(), contract.audit_log());
+        assert_eq!(loaded.history("price"), contract.history("price"));
+    }
+
+    #[test]
+    fn diffs_list_added_removed_and_changed_keys() {
+        let mut before = deployed("TokenX");
+        before.set("network", "mainnet", "ops").unwrap();
+        let mut after = before.clone();
+        after.set("symbol", "TKX", "ops").unwrap();
+        after.set("author", "Trail of Bits", "ops").unwrap();
+        after.remove("network", "ops").unwrap();
+        let changes = diff(&before, &after);
+        assert_eq!(changes.added, [("symbol".to_owned(), "TKX".into())]);
+        assert_eq!(changes.removed, [("network".to_owned(), "mainnet".into())]);
+        assert_eq!(
+            changes.to_string(),
+            "+ symbol: TKX\n- network: mainnet\n~ author: azaM -> Trail of Bits"
+        );
+
+        let mut staging = ContractRegistry::new();
+        let mut production = ContractRegistry::new();
+        staging.register(before).unwrap();
+        staging.register(deployed("Vault")).unwrap();
+        production.register(after).unwrap();
+        production.register(deployed("Router")).unwrap();
+        let registry_diff = staging.diff(&production);
+        assert_eq!(registry_diff.added, ["Router"]);
+        assert_eq!(registry_diff.removed, ["Vault"]);
+        assert_eq!(registry_diff.modified.len(), 1);
+        let ignored = registry_diff.ignoring(&["symbol", "network", "author"]);
+        assert!(ignored.modified.is_empty());
+        assert!(!ignored.is_empty());
+    }
+
+    #[test]
+    fn csv_export_quotes_fields_and_leaves_gaps_empty() {
+        let mut registry = ContractRegistry::new();
+        let mut token = deployed("TokenX");
+        token
+            .set("description", "Fixed supply, \"TKX\"", "ops")
+            .unwrap();
+        registry.register(token).unwrap();
+        registry.register(deployed("Vault")).unwrap();
+        let mut out = Vec::new();
+        registry
+            .export_csv(&mut out, &["author", "description"])
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "name,author,description\n\
+             TokenX,azaM,\"Fixed supply, \"\"TKX\"\"\"\n\
+             Vault,azaM,\n"
+        );
+    }
+
+    #[test]
+    fn merge_strategies_resolve_conflicting_histories() {
+        let ours = || {
+            let mut registry = ContractRegistry::new();
+            registry.register(release("TokenX", "1.0.0")).unwrap();
+            registry
+        };
+        let theirs = || {
+            let mut registry = ContractRegistry::new();
+            registry.register(release("TokenX", "2.0.0")).unwrap();
+            registry.register(release("Vault", "1.0.0")).unwrap();
+            registry
+        };
+        let version = |registry: &ContractRegistry| {
+            registry
+                .get("TokenX")
+                .and_then(DeployedContract::version)
+                .map(Version::to_string)
+        };
+
+        let mut refused = ours();
+        assert!(matches!(
+            refused.merge(theirs(), MergeStrategy::Error),
+            Err(ScrgError::Registry(RegistryError::MergeConflict(MergeConflict(names))))
+                if names == ["TokenX"]
+        ));
+        assert!(refused.get("Vault").is_none());
+
+        let mut kept = ours();
+        let report = kept.merge(theirs(), MergeStrategy::KeepOurs).unwrap();
+        assert_eq!(report.added, ["Vault"]);
+        assert_eq!(report.kept, ["TokenX"]);
+        assert_eq!(version(&kept), Some("1.0.0".into()));
+
+        let mut replaced = ours();
+        let report = replaced.merge(theirs(), MergeStrategy::KeepTheirs).unwrap();
+        assert_eq!(report.replaced, ["TokenX"]);
+        assert_eq!(version(&replaced), Some("2.0.0".into()));
+        assert_eq!(replaced.versions("TokenX").len(), 1);
+
+        let mut newest = ours();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let report = newest.merge(theirs(), MergeStrategy::NewestWins).unwrap();
+        assert_eq!(report.replaced, ["TokenX"]);
+        assert_eq!(version(&newest), Some("2.0.0".into()));
+    }
+
+    #[test]
+    fn watchers_see_upgrades_on_both_registries() {
+        let upgraded = RegistryEvent::ContractUpgraded {
+            name: "TokenX".into(),
+            from: "1.0.0".parse().unwrap(),
+            to: "1.1.0".parse().unwrap(),
+        };
+        let mut registry = ContractRegistry::new();
+        let rx = registry.watch();
+        registry.register(release("TokenX", "1.0.0")).unwrap();
+        registry.register(release("TokenX", "1.1.0")).unwrap();
+        let events: Vec<_> = rx.try_iter().collect();
+        assert_eq!(events[1], upgraded);
+
+        let shared = SharedRegistry::default();
+        let rx = shared.watch();
+        let writer = shared.clone();
+        std::thread::spawn(move || {
+            writer.register(release("TokenX", "1.0.0")).unwrap();
+            writer.register(release("TokenX", "1.1.0")).unwrap();
+            writer.remove("TokenX").unwrap();
+        })
+        .join()
+        .unwrap();
+        let events: Vec<_> = rx.try_iter().collect();
+        assert_eq!(
+            events,
+            [
+                RegistryEvent::ContractRegistered {
+                    name: "TokenX".into()
+                },
+                upgraded,
+                RegistryEvent::ContractRemoved {
+                    name: "TokenX".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn metrics_count_lifecycle_steps_and_export_prometheus() {
+        let mut registry = ContractRegistry::new();
+        let failed = registry.builder(" ").with_author("azaM").validate();
+        assert!(failed.is_err());
+        let contract = registry
+            .builder("TokenX")
+            .with_author("azaM")
+            .on_phase(Phase::PreValidate, |_, _| Ok(()))
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap();
+        registry.register(contract.into_deployed()).unwrap();
+        // Builders created elsewhere, and other registries, count nothing here
+        let other = ContractBuilder::new("Other").with_author("azaM");
+        other.validate().unwrap().deploy().unwrap();
+        ContractRegistry::new().register(deployed("Vault")).unwrap();
+
+        let metrics = registry.metrics();
+        assert_eq!(metrics.validations, 2);
+        assert_eq!(metrics.validation_failures, 1);
+        assert_eq!(metrics.deployments, 1);
+        assert_eq!(metrics.deployment_failures, 0);
+        assert_eq!(metrics.registrations, 1);
+        let stages: Vec<_> = metrics.hooks.iter().map(|t| (t.stage, t.runs)).collect();
+        assert_eq!(stages, [("pre_validate", 1)]);
+
+        let text = metrics.to_prometheus();
+        assert!(text.contains("# TYPE scrg_registrations_total counter\n"));
+        assert!(text.contains("scrg_registrations_total 1\n"));
+        assert!(text.contains("scrg_hook_runs_total{stage=\"pre_validate\"} 1\n"));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_spans_cover_validate_deploy_and_hooks() {
+        use tracing::{span, Event, Metadata, Subscriber};
+
+        // Records the name of every span opened while it is the default subscriber
+        #[derive(Clone, Default)]
+        struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+        impl Subscriber for SpanNames {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(span.metadata().name());
+                span::Id::from_u64(names.len() as u64)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let names = SpanNames::default();
+        tracing::subscriber::with_default(names.clone(), || {
+            ContractBuilder::new("TokenX")
+                .with_author("azaM")
+                .with_version("1.0.0")
+                .on_phase(Phase::PreValidate, |_, _| Ok(()))
+                .validate()
+                .unwrap()
+                .deploy()
+                .unwrap()
+                .upgrade("1.1.0")
+                .unwrap();
+        });
+        assert_eq!(
+            *names.0.lock().unwrap(),
+            [
+                "contract.validate",
+                "contract.hook",
+                "contract.deploy",
+                "contract.upgrade"
+            ]
+        );
+    }
+
+    #[test]
+    fn scrg_error_wraps_every_stage_with_its_source() {
+        use std::error::Error as _;
+
+        let from_validation: ScrgError = ValidationError::EmptyName.into();
+        assert!(matches!(from_validation, ScrgError::Validation(_)));
+        assert!(from_validation.source().is_some());
+
+        let cycle: ScrgError = DependencyCycle(vec!["A".into(), "B".into()]).into();
+        assert!(matches!(
+            cycle,
+            ScrgError::Registry(RegistryError::DependencyCycle(_))
+        ));
+        assert_eq!(
+            cycle.to_string(),
+            DependencyCycle(vec!["A".into(), "B".into()]).to_string()
+        );
+
+        let failure = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .add_try_hook(|_| Err(HookError::new("rpc down")))
+            .deploy();
+        let Err(failure) = failure else {
+            panic!("the hook rejects the deploy");
+        };
+        let error: ScrgError = failure.into();
+        assert!(matches!(error, ScrgError::Hook(_)));
+
+        let io: ScrgError = std::io::Error::other("disk full").into();
+        assert!(matches!(io, ScrgError::Storage(StoreError::Io(_))));
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn wasm_bindings_round_trip_json_metadata() {
+        let builder = JsContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_version("1.0.0")
+            .with_metadata(r#"{"decimals": 18, "standard": "ERC20"}"#)
+            .unwrap();
+        let validated: serde_json::Value =
+            serde_json::from_str(&builder.validate().unwrap()).unwrap();
+        assert_eq!(validated["decimals"], 18);
+        assert_eq!(validated["version"], "1.0.0");
+        assert_eq!(validated["validated"], true);
+
+        let mut registry = JsContractRegistry::new();
+        registry.register(&builder).unwrap();
+        assert_eq!(registry.names(), ["TokenX"]);
+        let stored: serde_json::Value =
+            serde_json::from_str(&registry.get("TokenX").unwrap()).unwrap();
+        assert_eq!(stored["standard"], "ERC20");
+        assert!(registry.get("Vault").is_none());
+        let restored = JsContractRegistry::from_json(&registry.inner.to_json().unwrap()).unwrap();
+        assert_eq!(restored.names(), ["TokenX"]);
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn c_api_registers_and_reads_back_json() {
+        use std::ffi::{CStr, CString};
+
+        let name = CString::new("TokenX").unwrap();
+        let registry = scrg_registry_new();
+        unsafe {
+            let metadata = CString::new(r#"{"author": "azaM", "decimals": 18}"#).unwrap();
+            assert_eq!(
+                scrg_register(registry, name.as_ptr(), metadata.as_ptr()),
+                ScrgStatus::Ok
+            );
+            let json = scrg_get_json(registry, name.as_ptr());
+            let stored: serde_json::Value =
+                serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            assert_eq!(stored["decimals"], 18);
+            scrg_string_free(json);
+
+            let anonymous = CString::new(r#"{"decimals": 18}"#).unwrap();
+            assert_eq!(
+                scrg_register(registry, name.as_ptr(), anonymous.as_ptr()),
+                ScrgStatus::Failed
+            );
+            let message = CStr::from_ptr(scrg_last_error()).to_str().unwrap();
+            assert!(message.contains("author"));
+            assert_eq!(
+                scrg_register(registry, std::ptr::null(), anonymous.as_ptr()),
+                ScrgStatus::InvalidArgument
+            );
+            let vault = CString::new("Vault").unwrap();
+            assert!(scrg_get_json(registry, vault.as_ptr()).is_null());
+            scrg_registry_free(registry);
+        }
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn python_bindings_convert_metadata_both_ways() {
+        use pyo3::{prelude::*, types::PyDict};
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let mut builder = PyContractBuilder::new("TokenX");
+            builder.metadata.insert("author".into(), "azaM".into());
+            let decimals = 18i64.into_py(py);
+            builder.metadata.insert(
+                "decimals".into(),
+                py_to_metadata(decimals.bind(py)).unwrap(),
+            );
+
+            let mut registry = PyContractRegistry::new();
+            let deployed = registry.register(py, &builder).unwrap();
+            let deployed = deployed.downcast_bound::<PyDict>(py).unwrap();
+            let stored: i64 = deployed
+                .get_item("decimals")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(stored, 18);
+            assert_eq!(registry.__len__(), 1);
+            assert!(registry.__contains__("TokenX"));
+            assert!(registry.get(py, "Vault").unwrap().is_none());
+
+            let anonymous = PyContractBuilder::new("Vault");
+            let err = anonymous.validate(py).unwrap_err();
+            assert!(err.is_instance_of::<pyo3::exceptions::PyValueError>(py));
+        });
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn grpc_service_registers_gets_and_queries() {
+        use pb::registry_server::Registry as _;
+
+        let shared = SharedRegistry::default();
+        let service = GrpcRegistry::new(shared.clone());
+        let metadata = |symbol: &str| {
+            let mut meta = MetadataMap::new();
+            meta.insert("author".into(), "azaM".into());
+            meta.insert("symbol".into(), symbol.into());
+            meta.insert("version".into(), "1.0.0".into());
+            map_to_pb(&meta)
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            for (name, symbol) in [("TokenX", "TKX"), ("Vault", "VLT")] {
+                let request = pb::RegisterRequest {
+                    name: name.into(),
+                    metadata: metadata(symbol),
+                };
+                service
+                    .register(tonic::Request::new(request))
+                    .await
+                    .unwrap();
+            }
+            let anonymous = pb::RegisterRequest {
+                name: "Router".into(),
+                metadata: HashMap::new(),
+            };
+            let rejected = service.register(tonic::Request::new(anonymous)).await;
+            assert_eq!(rejected.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+            let get = pb::GetRequest {
+                name: "TokenX".into(),
+                version: Some("1.0.0".into()),
+            };
+            let contract = service.get(tonic::Request::new(get)).await.unwrap();
+            let symbol = value_from_pb(contract.into_inner().metadata["symbol"].clone());
+            assert_eq!(symbol.unwrap(), "TKX".into());
+            let missing = pb::GetRequest {
+                name: "Router".into(),
+                version: None,
+            };
+            let missing = service.get(tonic::Request::new(missing)).await;
+            assert_eq!(missing.unwrap_err().code(), tonic::Code::NotFound);
+
+            let mut where_eq = HashMap::new();
+            where_eq.insert("symbol".to_owned(), value_to_pb(&"VLT".into()));
+            let query = pb::QueryRequest {
+                where_eq,
+                has_keys: vec!["author".into()],
+                status: Some("deployed".into()),
+                chain_id: None,
+            };
+            let found = service.query(tonic::Request::new(query)).await.unwrap();
+            let names: Vec<String> = found
+                .into_inner()
+                .contracts
+                .into_iter()
+                .map(|contract| contract.name)
+                .collect();
+            assert_eq!(names, ["Vault"]);
+        });
+        assert!(shared.contains("TokenX"));
+    }
+
+    // Sends one HTTP/1.1 request and returns the status code and body
+    #[cfg(feature = "http")]
+    fn http_request(
+        addr: std::net::SocketAddr,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, String) {
+        use std::io::{Read, Write};
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_owned())
+            .unwrap_or_default();
+        (status, body)
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn http_routes_create_get_and_deploy() {
+        let registry = SharedRegistry::default();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        runtime.spawn(async move {
+            axum::serve(listener, http_router(registry)).await.unwrap();
+        });
+
+        let created = http_request(
+            addr,
+            "POST",
+            "/contracts",
+            r#"{"name": "TokenX", "metadata": {"author": "azaM", "version": "1.0.0"}}"#,
+        );
+        assert_eq!(created.0, 201);
+        let rejected = http_request(addr, "POST", "/contracts", r#"{"name": "Vault"}"#);
+        assert_eq!(rejected.0, 422);
+        assert!(rejected.1.contains("author"));
+
+        let upgraded = http_request(
+            addr,
+            "POST",
+            "/contracts/TokenX/deploy",
+            r#"{"version": "1.1.0", "metadata": {"decimals": 18}}"#,
+        );
+        assert_eq!(upgraded.0, 201);
+        let (status, body) = http_request(addr, "GET", "/contracts/TokenX", "");
+        assert_eq!(status, 200);
+        let contract: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(contract["metadata"]["version"], "1.1.0");
+        assert_eq!(contract["metadata"]["author"], "azaM");
+        assert_eq!(contract["metadata"]["decimals"], 18);
+
+        assert_eq!(http_request(addr, "GET", "/contracts/Vault", "").0, 404);
+        let (_, listed) = http_request(addr, "GET", "/contracts", "");
+        let listed: serde_json::Value = serde_json::from_str(&listed).unwrap();
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "graphql")]
+    #[test]
+    fn graphql_resolves_contracts_versions_and_dependencies() {
+        let registry = SharedRegistry::default();
+        registry.register(release("TokenX", "1.0.0")).unwrap();
+        registry.register(release("TokenX", "1.1.0")).unwrap();
+        let vault = ContractBuilder::new("Vault")
+            .with_author("azaM")
+            .with_tags(["defi"])
+            .depends_on("TokenX")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap();
+        registry.register(vault.into_deployed()).unwrap();
+
+        let schema = graphql_schema(registry);
+        let response = block_on(schema.execute(
+            r#"{
+                contracts(tag: "defi") { name author dependencies { name version } }
+                contract(name: "TokenX") { versions { version } meta: metadata(keys: ["author"]) { key value kind } }
+            }"#,
+        ));
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data,
+            async_graphql::value!({
+                "contracts": [{
+                    "name": "Vault",
+                    "author": "azaM",
+                    "dependencies": [{ "name": "TokenX", "version": "1.1.0" }],
+                }],
+                "contract": {
+                    "versions": [{ "version": "1.0.0" }, { "version": "1.1.0" }],
+                    "meta": [{ "key": "author", "value": "azaM", "kind": "string" }],
+                },
+            })
+        );
+
+        let invalid = block_on(schema.execute(r#"{ contracts(status: "lost") { name } }"#));
+        assert_eq!(invalid.errors.len(), 1);
+    }
+
+    #[test]
+    fn roles_gate_registry_operations() {
+        let viewer = Actor::new("guest", Role::Viewer);
+        let deployer = Actor::new("ci", Role::Deployer);
+        let admin = Actor::new("ops", Role::Admin);
+        let mut registry = ContractRegistry::new();
+
+        assert!(matches!(
+            registry.register_as(&viewer, release("TokenX", "1.0.0")),
+            Err(ScrgError::Registry(RegistryError::AccessDenied(_)))
+        ));
+        registry
+            .register_as(&deployer, release("TokenX", "1.0.0"))
+            .unwrap();
+        registry
+            .register_as(&deployer, release("TokenX", "1.1.0"))
+            .unwrap();
+        registry
+            .update_metadata_as(&deployer, "TokenX", "symbol", "TKX")
+            .unwrap();
+        let symbol = registry.get("TokenX").unwrap().history("symbol");
+        assert_eq!(symbol[0].actor, "ci");
+        assert!(registry.rollback_as(&deployer, "TokenX").is_err());
+        registry.rollback_as(&admin, "TokenX").unwrap();
+
+        let denied: Vec<_> = registry
+            .denials()
+            .iter()
+            .map(|d| d.actor.as_str())
+            .collect();
+        assert_eq!(denied, ["guest", "ci"]);
+        assert_eq!(
+            registry.denials()[1].to_string(),
+            "`ci` (deployer) may not roll back `TokenX`"
+        );
+
+        let shared = SharedRegistry::default();
+        assert!(shared.register_as(&viewer, deployed("Vault")).is_err());
+        shared.register_as(&deployer, deployed("Vault")).unwrap();
+        assert!(shared.remove_as(&deployer, "Vault").is_err());
+        assert_eq!(shared.remove_as(&admin, "Vault").unwrap().len(), 1);
+        assert_eq!(shared.denials().len(), 2);
+    }
+
+    #[test]
+    fn viewers_cannot_write_through_the_plain_methods() {
+        fn denied<T>(result: Result<T, ScrgError>) -> bool {
+            matches!(
+                result,
+                Err(ScrgError::Registry(RegistryError::AccessDenied(_)))
+            )
+        }
+        let viewer = Actor::new("guest", Role::Viewer);
+        let admin = Actor::new("ops", Role::Admin);
+
+        let mut owner = ContractRegistry::new();
+        owner.register(deployed("TokenX")).unwrap();
+        let mut registry = owner.acting_as(viewer.clone());
+        assert!(denied(registry.register(deployed("Vault"))));
+        assert!(denied(registry.update_metadata("TokenX", "symbol", "TKX")));
+        assert!(denied(registry.archive("TokenX")));
+        assert!(denied(registry.remove("TokenX")));
+        // Naming an admin, or re-binding one, does not widen the viewer's handle
+        assert!(denied(registry.register_as(&admin, deployed("Vault"))));
+        let mut registry = registry.acting_as(admin.clone());
+        assert!(denied(registry.remove("TokenX")));
+        assert!(registry.get("TokenX").is_some());
+        assert_eq!(registry.denials().len(), 6);
+        assert!(registry.denials().iter().all(|d| d.role == Role::Viewer));
+
+        let shared = SharedRegistry::new();
+        shared.register(deployed("TokenX")).unwrap();
+        let reader = shared.acting_as(viewer);
+        assert!(denied(reader.register(deployed("Vault"))));
+        assert!(denied(reader.remove("TokenX")));
+        assert!(denied(reader.register_as(&admin, deployed("Vault"))));
+        assert!(denied(reader.acting_as(admin).remove("TokenX")));
+        assert!(reader.get("TokenX").is_some());
+        assert!(!shared.contains("Vault"));
+        assert_eq!(shared.denials().len(), 4);
+    }
+
+    // Reversible stand-in for a real cipher; rejects input it did not produce
+    struct XorCipher(u8);
+
+    impl Cipher for XorCipher {
+        fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+            let mut sealed: Vec<u8> = plaintext.iter().map(|b| b ^ self.0).collect();
+            sealed.push(self.0);
+            sealed
+        }
+
+        fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, CipherError> {
+            match ciphertext.split_last() {
+                Some((&key, sealed)) if key == self.0 => {
+                    Ok(sealed.iter().map(|b| b ^ self.0).collect())
+                }
+                _ => Err(CipherError("wrong key".into())),
+            }
+        }
+    }
+
+    #[test]
+    fn sensitive_keys_are_sealed_and_revealed_by_role() {
+        let mut contract = deployed("TokenX");
+        contract.set("api_key", "sk-live-123", "ops").unwrap();
+        let mut registry = ContractRegistry::new().with_encryption(XorCipher(0x5a), ["api_key"]);
+        registry.register(contract).unwrap();
+        let stored = registry.get("TokenX").unwrap();
+        assert_eq!(stored.get("api_key"), None);
+        assert!(matches!(
+            stored.packed.get("api_key"),
+            Some(Packed::Sealed(_))
+        ));
+
+        let deployer = Actor::new("ci", Role::Deployer);
+        assert_eq!(
+            registry.reveal(&deployer, "TokenX", "api_key").unwrap(),
+            Some("sk-live-123".into())
+        );
+        let viewer = Actor::new("guest", Role::Viewer);
+        assert!(registry.reveal(&viewer, "TokenX", "api_key").is_err());
+
+        registry
+            .update_metadata("TokenX", "api_key", "sk-live-456")
+            .unwrap();
+        assert_eq!(
+            registry.reveal(&deployer, "TokenX", "api_key").unwrap(),
+            Some("sk-live-456".into())
+        );
+        assert_eq!(
+            registry.reveal(&deployer, "TokenX", "author").unwrap(),
+            Some("azaM".into())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sealed_values_stay_out_of_exports() {
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("api_key", "sk-live-123")
+            .validate()
+            .unwrap()
+            .on_deploy(|_| {})
+            .unwrap()
+            .into_deployed();
+        let mut registry = ContractRegistry::new().with_encryption(XorCipher(0x5a), ["api_key"]);
+        registry.register(contract).unwrap();
+        registry
+            .update_metadata("TokenX", "api_key", "sk-live-456")
+            .unwrap();
+        let stored = registry.get("TokenX").unwrap();
+        assert_eq!(stored.snapshot().get("api_key"), None);
+        assert!(matches!(
+            stored.snapshot_packed.get("api_key"),
+            Some(Packed::Sealed(_))
+        ));
+        assert_eq!(
+            stored.history("api_key").last().unwrap().new_value(),
+            Some(&REDACTED.into())
+        );
+
+        let json = registry.to_json().unwrap();
+        let path = temp_path("sealed.json");
+        JsonFileStore::new(&path).save(&registry).unwrap();
+        let file = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        for export in [&json, &file] {
+            assert!(!export.contains("sk-live"), "{export}");
+        }
+
+        let mut loaded = ContractRegistry::from_json(&json)
+            .unwrap()
+            .with_encryption(XorCipher(0x5a), ["api_key"]);
+        assert_eq!(
+            loaded
+                .reveal(&Actor::default(), "TokenX", "api_key")
+                .unwrap(),
+            Some("sk-live-456".into())
+        );
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn aes_gcm_rejects_a_wrong_key() {
+        let cipher = AesGcmCipher::new(&[7; 32]);
+        let sealed = cipher.encrypt(b"sk-live-123");
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), b"sk-live-123");
+        assert_ne!(sealed[12..], b"sk-live-123"[..]);
+        assert!(AesGcmCipher::new(&[8; 32]).decrypt(&sealed).is_err());
+        assert!(cipher.decrypt(&sealed[..4]).is_err());
+    }
+
+    #[test]
+    fn secret_keys_are_redacted_when_formatted() {
+        let mut contract = deployed("TokenX");
+        contract.set("private_key", "0xdeadbeef", "ops").unwrap();
+        contract.set("wallet.mnemonic", "tok-991", "ops").unwrap();
+        for shown in [format!("{contract}"), format!("{contract:?}")] {
+            assert!(!shown.contains("0xdeadbeef"), "{shown}");
+            assert!(!shown.contains("tok-991"), "{shown}");
+            assert!(shown.contains("***"), "{shown}");
+        }
+
+        let mut rotated = contract.clone();
+        rotated.set("private_key", "0xfeedface", "ops").unwrap();
+        let changes = diff(&contract, &rotated);
+        assert_eq!(changes.to_string(), "~ private_key: *** -> ***");
+        assert!(!format!("{changes:?}").contains("0xfeedface"));
+        assert!(!format!("{:?}", rotated.audit.last().unwrap()).contains("0xdeadbeef"));
+
+        let mut registry = ContractRegistry::new();
+        registry.register(contract).unwrap();
+        let shared = registry.redacted();
+        let kept = shared.get("TokenX").unwrap();
+        assert_eq!(kept.get("private_key"), Some(&"***".into()));
+        assert_eq!(kept.get("author"), Some(&"azaM".into()));
+        assert!(kept
+            .audit
+            .iter()
+            .all(|entry| entry.key != "private_key" || entry.new_value() == Some(&"***".into())));
+        assert_eq!(
+            registry.get("TokenX").unwrap().get("private_key"),
+            Some(&"0xdeadbeef".into())
+        );
+    }
+
+    #[test]
+    fn expired_contracts_are_hidden_then_purged() {
+        let mut registry = ContractRegistry::new();
+        registry
+            .register(deployed("Faucet").with_ttl(Duration::ZERO))
+            .unwrap();
+        registry
+            .register(deployed("TokenX").with_ttl(Duration::from_secs(3600)))
+            .unwrap();
+        assert!(registry.get("Faucet").is_none());
+        assert!(registry.get("TokenX").is_some());
+        let expired: Vec<_> = registry.expired().map(|c| c.name.as_str()).collect();
+        assert_eq!(expired, ["Faucet"]);
+        assert_eq!(registry.len(), 2);
+
+        let purged = registry.purge_expired().unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].name, "Faucet");
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.expired().count(), 0);
+
+        let shared = SharedRegistry::new();
+        shared
+            .register(deployed("Faucet").with_ttl(Duration::ZERO))
+            .unwrap();
+        shared.register(deployed("TokenX")).unwrap();
+        assert!(shared.get("Faucet").is_none());
+        assert_eq!(shared.purge_expired().unwrap().len(), 1);
+        assert!(shared.get("TokenX").is_some());
+    }
+
+    #[test]
+    fn capacity_limit_evicts_by_order_and_calls_hooks() {
+        let evicted = Rc::new(RefCell::new(Vec::new()));
+        let mut registry =
+            ContractRegistry::new().with_capacity_limit(2, EvictionOrder::LeastRecentlyUsed);
+        let seen = Rc::clone(&evicted);
+        registry.on_evict(move |name, versions| {
+            seen.borrow_mut().push((name.to_string(), versions.len()));
+        });
+        registry.register(deployed("TokenA")).unwrap();
+        registry.register(deployed("TokenB")).unwrap();
+        assert!(registry.latest("TokenA").is_some());
+        registry.register(deployed("TokenC")).unwrap();
+        assert_eq!(*evicted.borrow(), [("TokenB".to_string(), 1)]);
+        assert!(registry.latest("TokenB").is_none());
+        assert_eq!(registry.len(), 2);
+
+        let mut old = deployed("Old");
+        old.deployed_at -= Duration::from_secs(60);
+        let mut registry = ContractRegistry::new();
+        registry.register(deployed("Fresh")).unwrap();
+        registry.register(old).unwrap();
+        registry.register(deployed("Newest")).unwrap();
+        let registry = registry.with_capacity_limit(2, EvictionOrder::OldestDeployFirst);
+        assert!(registry.latest("Old").is_none());
+        assert!(registry.latest("Fresh").is_some());
+        assert!(registry.latest("Newest").is_some());
+    }
+
+    #[test]
+    fn glob_search_matches_contract_names() {
+        assert!(glob_match("Token*", "TokenX"));
+        assert!(glob_match("*Vault", "EthVault"));
+        assert!(glob_match("T?ken?", "TokenX"));
+        assert!(glob_match("*a*a*", "banana"));
+        assert!(!glob_match("Token?", "Token"));
+        assert!(!glob_match("*Vault", "VaultKeeper"));
+
+        let mut registry = ContractRegistry::new();
+        for name in ["TokenB", "Vault", "TokenA"] {
+            registry.register(deployed(name)).unwrap();
+        }
+        let names: Vec<_> = registry.search("Token*").iter().map(|c| c.name()).collect();
+        assert_eq!(names, ["TokenA", "TokenB"]);
+        assert!(registry.search("Nope*").is_empty());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn value_search_skips_secret_keys() {
+        let mut registry = ContractRegistry::new();
+        let mut token = deployed("TokenX");
+        token.set("network", "sepolia", "ops").unwrap();
+        token.set("api_key", "sepolia-key", "ops").unwrap();
+        registry.register(token).unwrap();
+        let mut vault = deployed("Vault");
+        vault.set("api_key", "sepolia-key", "ops").unwrap();
+        registry.register(vault).unwrap();
+
+        let pattern = regex::Regex::new("^sepolia").unwrap();
+        let names: Vec<_> = registry
+            .search_values(&pattern, &[])
+            .iter()
+            .map(|c| c.name())
+            .collect();
+        assert_eq!(names, ["TokenX"]);
+        assert!(registry.search_values(&pattern, &["author"]).is_empty());
+    }
+
+    #[test]
+    fn range_queries_agree_for_both_orders() {
+        let mut registry = ContractRegistry::new();
+        for name in ["Vault", "TokenB", "Alpha", "TokenA", "Zeta"] {
+            registry.register(deployed(name)).unwrap();
+        }
+        let names = |contracts: Vec<&DeployedContract>| -> Vec<String> {
+            contracts.iter().map(|c| c.name().to_string()).collect()
+        };
+        let hashed = names(registry.range("Token".."Vault"));
+        assert_eq!(hashed, ["TokenA", "TokenB"]);
+
+        let registry = registry.with_order(RegistryOrder::Sorted);
+        assert_eq!(names(registry.range("Token".."Vault")), hashed);
+        assert_eq!(names(registry.range("Vault"..)), ["Vault", "Zeta"]);
+        assert_eq!(names(registry.range(..="Alpha")), ["Alpha"]);
+        let listed: Vec<_> = registry.names().collect();
+        assert_eq!(listed, ["Alpha", "TokenA", "TokenB", "Vault", "Zeta"]);
+    }
+
+    #[test]
+    fn cloned_versions_share_metadata_until_edited() {
+        let v1 = deployed("TokenX");
+        let mut v2 = v1.clone();
+        assert!(v2.shares_metadata(&v1));
+        v2.set("network", "sepolia", "ops").unwrap();
+        assert!(!v2.shares_metadata(&v1));
+        assert_eq!(v1.get("network"), None);
+        assert_eq!(v2.get("network"), Some(&"sepolia".into()));
+        assert_eq!(v2.snapshot.get("network"), None);
+        assert_eq!(v2.into_metadata().get("network"), Some(&"sepolia".into()));
+    }
+
+    #[test]
+    fn snapshot_shares_metadata_and_edits_copy_once() {
+        let mut contract = deployed("TokenX");
+        assert!(Arc::ptr_eq(&contract.metadata, &contract.snapshot.0));
+        contract.set("network", "sepolia", "ops").unwrap();
+        assert!(!Arc::ptr_eq(&contract.metadata, &contract.snapshot.0));
+        let edited = Arc::as_ptr(&contract.metadata);
+        contract.set("network", "mainnet", "ops").unwrap();
+        contract.remove("network", "ops").unwrap();
+        assert_eq!(Arc::as_ptr(&contract.metadata), edited);
+        assert_eq!(contract.history("network").len(), 3);
+        assert_eq!(contract.snapshot().get("network"), None);
+
+        let mut registry = ContractRegistry::new();
+        registry.register(deployed("Vault")).unwrap();
+        let stored = registry.get("Vault").unwrap();
+        assert!(Arc::ptr_eq(&stored.metadata, &stored.snapshot.0));
+    }
+
+    #[test]
+    fn weak_handles_do_not_keep_builders_alive() {
+        let builder = ContractBuilder::new("TokenX").with_author("azaM");
+        let handle = builder.downgrade();
+        assert_eq!(handle.name(), "TokenX");
+        assert!(handle.is_alive());
+        let metadata = handle.upgrade().unwrap();
+        assert_eq!(metadata.borrow().get("author"), Some(&"azaM".into()));
+        drop(metadata);
+
+        let deployed = builder.validate().unwrap().deploy().unwrap();
+        assert!(handle.is_alive());
+        let contract = deployed.into_deployed();
+        assert!(!handle.is_alive());
+        assert!(handle.upgrade().is_none());
+        assert_eq!(contract.get("author"), Some(&"azaM".into()));
+        assert!(format!("{handle:?}").contains("alive: false"));
+    }
+
+    #[test]
+    fn builder_states_report_their_names() {
+        assert_eq!(<Init<NoAuthor> as BuilderState>::NAME, "init");
+        assert_eq!(<Validated as BuilderState>::NAME, "validated");
+        assert_eq!(<Archived as BuilderState>::NAME, "archived");
+        let builder = ContractBuilder::new("TokenX").with_author("azaM");
+        assert!(format!("{builder:?}").contains("state: \"init\""));
+    }
+
+    #[cfg(feature = "custom-states")]
+    #[test]
+    fn custom_states_advance_along_declared_edges() {
+        custom_state!(Audited, "audited", from: [Deployed]);
+        impl TransitionTo<Upgraded> for Audited {}
+
+        let audited: ContractBuilder<Audited> = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .advance();
+        assert!(format!("{audited:?}").contains("state: \"audited\""));
+        let upgraded: ContractBuilder<Upgraded> = audited.advance();
+        assert_eq!(upgraded.borrow().get("author"), Some(&"azaM".into()));
+    }
+
+    #[test]
+    fn middleware_wraps_deploy_hooks_outermost_first() {
+        let trace = Rc::new(RefCell::new(Vec::new()));
+        let (outer, inner, hook) = (Rc::clone(&trace), Rc::clone(&trace), Rc::clone(&trace));
+        let mut attempts = 0;
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .add_try_hook(move |meta| {
+                attempts += 1;
+                hook.borrow_mut().push(format!("hook {attempts}"));
+                if attempts == 1 {
+                    return Err(HookError::new("rpc timeout"));
+                }
+                meta.insert("attempts".into(), attempts.to_string().into());
+                Ok(())
+            })
+            .with_middleware(move |meta, next| {
+                outer.borrow_mut().push("outer".to_string());
+                next(meta)
+            })
+            .with_middleware(move |meta, next| {
+                inner.borrow_mut().push("retry".to_string());
+                next(meta).or_else(|_| next(meta))
+            })
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(*trace.borrow(), ["outer", "retry", "hook 1", "hook 2"]);
+        assert_eq!(contract.get("attempts"), Some(&"2".into()));
+    }
+
+    #[test]
+    fn on_validate_sees_normalized_metadata() {
+        let validated = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("address", "0x52908400098527886E0F7030069857D2E4169EE7")
+            .on_validate(|meta| {
+                let address = meta.get("address").cloned().unwrap();
+                meta.insert("owner".into(), address);
+                Ok(())
+            })
+            .validate()
+            .unwrap();
+        let meta = validated.borrow();
+        assert!(matches!(
+            meta.get("address"),
+            Some(MetadataValue::Address(_))
+        ));
+        assert_eq!(meta.get("owner"), meta.get("address"));
+        drop(meta);
+
+        let refused = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .on_validate(|_| Err(HookError::new("license missing")))
+            .validate();
+        assert!(matches!(
+            refused,
+            Err(ScrgError::Validation(ValidationError::Hook(error))) if error.to_string().contains("license missing")
+        ));
+    }
+
+    #[test]
+    fn constraints_report_one_violation_each() {
+        let constraints = Constraints::new()
+            .require("network")
+            .one_of("network", ["mainnet", "sepolia"])
+            .require("license");
+        let result = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("network", "goerli")
+            .with_constraints(constraints.clone())
+            .validate();
+        let Err(ScrgError::Validation(ValidationError::Rules(violations))) = result else {
+            panic!("expected rule violations");
+        };
+        let rules: Vec<_> = violations.iter().map(|v| v.rule.as_str()).collect();
+        assert_eq!(rules, ["one_of", "require"]);
+        assert!(violations[0].message.contains("goerli"));
+
+        assert!(ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("network", "sepolia")
+            .with_metadata("license", "MIT")
+            .with_constraints(constraints)
+            .validate()
+            .is_ok());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn constraints_match_patterns_and_report_bad_ones() {
+        let validate = |owner: &str, pattern: &str| {
+            ContractBuilder::new("TokenX")
+                .with_author("azaM")
+                .with_metadata("owner", owner)
+                .with_constraints(Constraints::new().matches("owner", pattern))
+                .validate()
+        };
+        assert!(validate("0x52908400098527886E0F7030069857D2E4169EE7", ADDRESS_RE).is_ok());
+        assert!(matches!(
+            validate("vitalik.eth", ADDRESS_RE),
+            Err(ScrgError::Validation(ValidationError::Rules(v))) if v[0].rule == "matches"
+        ));
+        assert!(matches!(
+            validate("0x00", "(unclosed"),
+            Err(ScrgError::Validation(ValidationError::Rules(v))) if v[0].message.starts_with("`owner`:")
+        ));
+    }
+
+    #[test]
+    fn env_defaults_fill_only_missing_keys() {
+        std::env::set_var("SCRG_ENV_TEST_AUTHOR", "ci-bot");
+        std::env::set_var("SCRG_ENV_TEST_NETWORK", "sepolia");
+        std::env::set_var("SCRG_ENV_TEST_CHAIN_ID", "11155111");
+        let builder = ContractBuilder::new("TokenX")
+            .with_metadata("network", "mainnet")
+            .with_env_defaults("SCRG_ENV_TEST")
+            .with_default_author("fallback");
+        let meta = builder.borrow();
+        assert_eq!(meta.get("author"), Some(&"ci-bot".into()));
+        assert_eq!(meta.get("network"), Some(&"mainnet".into()));
+        assert_eq!(meta.get("chain_id"), Some(&MetadataValue::Int(11155111)));
+        drop(meta);
+
+        let builder = ContractBuilder::new("TokenX").with_default_author("fallback");
+        assert_eq!(builder.borrow().get("author"), Some(&"fallback".into()));
+        for var in ["AUTHOR", "NETWORK", "CHAIN_ID"] {
+            std::env::remove_var(format!("SCRG_ENV_TEST_{var}"));
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn factory_builds_contracts_from_project_config() {
+        const CONFIG: &str = r#"
+            [networks.sepolia]
+            chain_id = 11155111
+            rpc_url = "https://sepolia.example"
+
+            [[contracts]]
+            name = "TokenX"
+            author = "azaM"
+            version = "1.0.0"
+            network = "sepolia"
+            tags = ["defi"]
+            hooks = ["stamp_block"]
+            metadata = { symbol = "TKX", decimals = 18 }
+
+            [[contracts]]
+            name = "Vault"
+            author = "azaM"
+            depends_on = ["TokenX"]
+        "#;
+        let factory = ContractFactory::from_toml(CONFIG).unwrap().with_hook(
+            "stamp_block",
+            Phase::PreDeploy,
+            |_, meta| {
+                meta.insert("block".into(), 19_000_000.into());
+                Ok(())
+            },
+        );
+        let contracts: Vec<_> = factory
+            .builders()
+            .unwrap()
+            .into_iter()
+            .map(|builder| {
+                builder
+                    .validate()
+                    .unwrap()
+                    .deploy()
+                    .unwrap()
+                    .into_deployed()
+            })
+            .collect();
+        let token = &contracts[0];
+        assert_eq!(token.chain_id(), Some(11155111));
+        assert_eq!(token.tags(), ["defi"]);
+        assert_eq!(token.get("symbol"), Some(&"TKX".into()));
+        assert_eq!(token.get("decimals"), Some(&MetadataValue::Int(18)));
+        assert_eq!(token.get("block"), Some(&MetadataValue::Int(19_000_000)));
+        assert_eq!(contracts[1].dependencies(), ["TokenX"]);
+
+        let missing = ContractFactory::from_toml(CONFIG).unwrap().builders();
+        assert!(matches!(
+            missing,
+            Err(ScrgError::Serialization(err)) if matches!(
+                err.downcast_ref(),
+                Some(ConfigError::UnknownHook { hook, .. }) if hook == "stamp_block"
+            )
+        ));
+        let unknown = ContractFactory::from_toml(
+            "[[contracts]]\nname = \"TokenX\"\nauthor = \"azaM\"\nnetwork = \"mainnet\"",
+        )
+        .unwrap()
+        .builders();
+        assert_eq!(
+            unknown.err().unwrap().to_string(),
+            "serialization failed: `TokenX` targets undefined network `mainnet`"
+        );
+    }
+
+    #[test]
+    fn profiles_overlay_template_metadata() {
+        let template = ContractTemplate::new("erc20")
+            .with_metadata("network", Network::new("sepolia", 11155111))
+            .with_tags(["token"])
+            .with_profile(
+                "prod",
+                Profile::new()
+                    .with_network(Network::new("mainnet", 1))
+                    .with_tags(["prod"])
+                    .with_signer(FixedSigner),
+            );
+        let contract = ContractBuilder::from_template(&template, "TokenX")
+            .for_profile("prod")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(contract.chain_id(), Some(1));
+        assert_eq!(contract.tags(), ["token", "prod"]);
+        assert_eq!(contract.get("profile"), Some(&"prod".into()));
+        assert!(contract.signature().is_some());
+
+        let staging = ContractBuilder::from_template(&template, "TokenX")
+            .for_profile("staging")
+            .with_author("azaM");
+        assert_eq!(
+            staging.borrow().get("network"),
+            template.metadata.get("network")
+        );
+        assert!(matches!(
+            staging.validate(),
+            Err(ScrgError::Validation(ValidationError::UnknownProfile(name))) if name == "staging"
+        ));
+    }
+
+    #[test]
+    fn project_shares_metadata_and_resolves_references() {
+        const TOKEN: &str = "0x52908400098527886E0F7030069857D2E4169EE7";
+        let (registry, report) = ProjectBuilder::new("azaM")
+            .with_license("MIT")
+            .with_network(Network::new("sepolia", 11155111))
+            .contract("Vault", |builder| {
+                builder.with_metadata("license", "BUSL-1.1")
+            })
+            .contract("TokenX", |builder| builder.with_metadata("address", TOKEN))
+            .reference("Vault", "token", "TokenX")
+            .build()
+            .unwrap();
+        assert!(report.is_success(), "{report:?}");
+        assert_eq!(report.deployed, ["TokenX", "Vault"]);
+        let vault = registry.latest("Vault").unwrap();
+        let token = registry.latest("TokenX").unwrap();
+        assert_eq!(vault.get("license"), Some(&"BUSL-1.1".into()));
+        assert_eq!(token.get("license"), Some(&"MIT".into()));
+        assert_eq!(vault.chain_id(), Some(11155111));
+        assert_eq!(vault.get("token"), token.get("address"));
+        assert_eq!(vault.dependencies(), ["TokenX"]);
+
+        let dangling = ProjectBuilder::new("azaM")
+            .contract("Vault", |builder| builder)
+            .reference("Vault", "token", "TokenX")
+            .build();
+        assert!(matches!(
+            dangling,
+            Err(ScrgError::Registry(RegistryError::MissingDependency { dependency, .. })) if dependency == "TokenX"
+        ));
+    }
+
+    #[test]
+    fn deploy_group_undoes_earlier_contracts() {
+        let mut registry = ContractRegistry::new();
+        registry.register(release("TokenX", "1.0.0")).unwrap();
+
+        let compensated: Rc<RefCell<Vec<String>>> = Rc::default();
+        let log = Rc::clone(&compensated);
+        let group = DeployGroup::new()
+            .add(
+                ContractBuilder::new("TokenX")
+                    .with_author("azaM")
+                    .with_version("2.0.0"),
+            )
+            .add(
+                ContractBuilder::new("Vault")
+                    .with_author("azaM")
+                    .depends_on("TokenX")
+                    .on_phase(Phase::PreValidate, |_, _| Err(HookError::new("no audit"))),
+            )
+            .on_rollback(move |contract| {
+                log.borrow_mut().push(contract.name.clone());
+                Ok(())
+            });
+        let Err(GroupDeployError::Aborted {
+            contract,
+            rolled_back,
+            ..
+        }) = registry.deploy_group(group)
+        else {
+            panic!("the group should abort on `Vault`");
+        };
+        assert_eq!(contract, "Vault");
+        assert_eq!(rolled_back, ["TokenX"]);
+        assert_eq!(*compensated.borrow(), ["TokenX"]);
+        assert_eq!(registry.versions("TokenX").len(), 1);
+        assert_eq!(
+            registry
+                .get("TokenX")
+                .unwrap()
+                .version()
+                .map(Version::to_string),
+            Some("1.0.0".into())
+        );
+
+        let group = DeployGroup::new()
+            .add(
+                ContractBuilder::new("Vault")
+                    .with_author("azaM")
+                    .depends_on("TokenX"),
+            )
+            .add(
+                ContractBuilder::new("TokenX")
+                    .with_author("azaM")
+                    .with_version("2.0.0"),
+            );
+        assert_eq!(registry.deploy_group(group).unwrap(), ["TokenX", "Vault"]);
+        assert_eq!(registry.versions("TokenX").len(), 2);
+    }
+
+    #[test]
+    fn namespaces_scope_dotted_keys() {
+        let mut meta = MetadataMap::new();
+        meta.insert("build".into(), "release".into());
+        let mut audit = meta.namespace_mut("audit");
+        audit.insert("firm", "Trail");
+        audit.insert("signing_key", "sk-123");
+        meta.namespace_mut("chain.l2").insert("address", "0x01");
+        assert_eq!(meta.namespaces(), BTreeSet::from(["audit", "chain"]));
+        assert_eq!(meta.namespace("audit").get("firm"), Some(&"Trail".into()));
+        assert_eq!(meta.namespace("chain").len(), 1);
+        assert!(meta
+            .namespace("chain")
+            .iter()
+            .any(|(key, _)| key == "l2.address"));
+        assert!(meta.namespace("build").is_empty());
+
+        let nested = meta.nested();
+        let Some(MetadataValue::Map(chain)) = nested.get("chain") else {
+            panic!("`chain` should nest");
+        };
+        assert!(matches!(chain.get("l2"), Some(MetadataValue::Map(l2)) if l2.len() == 1));
+        assert_eq!(nested.get("build"), Some(&"release".into()));
+
+        let mut contract = deployed("TokenX");
+        contract.set("audit.firm", "Trail", "ops").unwrap();
+        contract.set("audit.api_key", "sk-123", "ops").unwrap();
+        let mut registry = ContractRegistry::new();
+        registry.register(contract).unwrap();
+        registry.register(deployed("Vault")).unwrap();
+        let exported = registry.export_namespace("audit");
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported["TokenX"].get("api_key"), Some(&"***".into()));
+        assert_eq!(exported["TokenX"].get("firm"), Some(&"Trail".into()));
+
+        meta.namespace_mut("audit").clear();
+        assert!(meta.namespace("audit").is_empty());
+        assert!(meta.namespace("chain.l2").get("address").is_some());
+    }
+
+    #[test]
+    fn get_as_converts_with_descriptive_errors() {
+        let mut contract = deployed("TokenX");
+        contract
+            .set("block_number", MetadataValue::Int(19_000_000), "ops")
+            .unwrap();
+        contract.set("decimals", " 18 ", "ops").unwrap();
+        contract
+            .set("supply", MetadataValue::Int(-1), "ops")
+            .unwrap();
+        contract
+            .set("owner", "0x52908400098527886E0F7030069857D2E4169EE7", "ops")
+            .unwrap();
+        assert_eq!(contract.get_as::<u64>("block_number"), Ok(19_000_000));
+        assert_eq!(contract.get_as::<u32>("decimals"), Ok(18));
+        assert_eq!(contract.get_as::<String>("author"), Ok("azaM".into()));
+        assert!(contract.get_as::<Address>("owner").is_ok());
+
+        assert_eq!(
+            contract.get_as::<u64>("supply").unwrap_err().to_string(),
+            "metadata field `supply`: -1 is out of range for u64"
+        );
+        assert_eq!(
+            contract.get_as::<u64>("author").unwrap_err().to_string(),
+            "metadata field `author`: `azaM` is not a valid u64: invalid digit found in string"
+        );
+        assert_eq!(
+            contract
+                .get_as::<bool>("block_number")
+                .unwrap_err()
+                .to_string(),
+            "metadata field `block_number`: unexpected type int"
+        );
+        assert_eq!(
+            contract.get_as::<u64>("gas"),
+            Err(GetAsError::Missing("gas".into()))
+        );
+        assert!(contract.get_as::<Address>("author").is_err());
+    }
+
+    #[test]
+    fn meta_keys_work_wherever_strings_do() {
+        assert_eq!(MetaKey::DeployedAt.to_string(), "deployed_at");
+        assert_eq!(REQUIRED_KEYS, ["author"]);
+        let builder = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata(MetaKey::Version, "1.2.0");
+        assert_eq!(builder.get_as::<String>(MetaKey::Author), Ok("azaM".into()));
+
+        let mut contract = builder
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(contract.get(MetaKey::Author), contract.get("author"));
+        assert!(contract.get(MetaKey::DeployedAt).is_some());
+        assert_eq!(
+            contract.version().map(Version::to_string),
+            Some("1.2.0".into())
+        );
+        contract.set(MetaKey::Signer, "0xS1GNER", "ops").unwrap();
+        assert_eq!(contract.history(MetaKey::Signer).len(), 1);
+        assert_eq!(
+            contract.remove(MetaKey::Signer, "ops"),
+            Ok(Some("0xS1GNER".into()))
+        );
+    }
+
+    #[test]
+    fn lifecycle_keys_are_reserved() {
+        let preset = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("status", "deployed")
+            .validate();
+        assert!(matches!(
+            preset,
+            Err(ScrgError::Validation(ValidationError::ReservedKey(ReservedKey(key)))) if key == "status"
+        ));
+
+        let failure = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .add_hook(|meta| {
+                meta.insert("deployed_at".into(), 0.into());
+            })
+            .deploy()
+            .unwrap_err();
+        assert!(failure
+            .to_string()
+            .contains("hook wrote reserved key: `deployed_at`"));
+
+        let mut deployed = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap();
+        assert_eq!(
+            deployed.set("id", "forged", "ops"),
+            Err(WriteError::Reserved(ReservedKey("id".into())))
+        );
+        assert!(deployed.remove("status", "ops").is_err());
+        assert!(deployed.set("network", "mainnet", "ops").is_ok());
+    }
+
+    #[test]
+    fn metadata_merges_follow_the_strategy() {
+        let base: MetadataMap = [("network", "sepolia"), ("license", "MIT")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.into()))
+            .collect();
+        let incoming: MetadataMap = [
+            ("network", "mainnet"),
+            ("license", "MIT"),
+            ("symbol", "TKX"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.into()))
+        .collect();
+
+        let mut kept = base.clone();
+        let report = kept
+            .merge_from(incoming.clone(), MetadataMergeStrategy::PreferExisting)
+            .unwrap();
+        assert_eq!(report.added, ["symbol"]);
+        assert_eq!(report.kept, ["network"]);
+        assert_eq!(kept.get("network"), Some(&"sepolia".into()));
+
+        let mut overwritten = base.clone();
+        let report = overwritten
+            .merge_from(incoming.clone(), MetadataMergeStrategy::Overwrite)
+            .unwrap();
+        assert_eq!(report.overwritten, ["network"]);
+        assert_eq!(overwritten.get("network"), Some(&"mainnet".into()));
+
+        let mut untouched = base.clone();
+        assert_eq!(
+            untouched.merge_from(incoming.clone(), MetadataMergeStrategy::ErrorOnConflict),
+            Err(MetadataConflict(vec!["network".into()]))
+        );
+        assert_eq!(untouched, base);
+
+        let mut registry = ContractRegistry::new();
+        registry.register(deployed("TokenX")).unwrap();
+        let report = registry
+            .merge_metadata("TokenX", incoming, MetadataMergeStrategy::Overwrite)
+            .unwrap();
+        assert_eq!(report.added, ["license", "network", "symbol"]);
+        let token = registry.latest("TokenX").unwrap();
+        assert_eq!(token.get("symbol"), Some(&"TKX".into()));
+        assert!(token.audit.iter().any(|entry| entry.actor == "merge"));
+        assert!(matches!(
+            registry.merge_metadata("Vault", base, MetadataMergeStrategy::Overwrite),
+            Err(ScrgError::Registry(RegistryError::UnknownContract(name))) if name == "Vault"
+        ));
+    }
+
+    #[test]
+    fn merged_metadata_is_compared_with_sealed_and_compressed_values() {
+        let blob = "0".repeat(512);
+        let mut contract = deployed("TokenX");
+        contract.set("api_key", "sk-live-123", "ops").unwrap();
+        contract.set("abi_blob", blob.as_str(), "ops").unwrap();
+        let mut registry = ContractRegistry::new()
+            .with_encryption(XorCipher(0x5a), ["api_key"])
+            .with_compression(RleCodec, 256);
+        registry.register(contract).unwrap();
+
+        let same = MetadataMap::from([
+            ("api_key".to_string(), MetadataValue::from("sk-live-123")),
+            ("abi_blob".to_string(), MetadataValue::from(blob.as_str())),
+        ]);
+        let report = registry
+            .merge_metadata("TokenX", same, MetadataMergeStrategy::ErrorOnConflict)
+            .unwrap();
+        assert_eq!(report, MetadataMergeReport::default());
+
+        let changed = MetadataMap::from([
+            ("api_key".to_string(), MetadataValue::from("sk-live-456")),
+            ("abi_blob".to_string(), MetadataValue::from("1".repeat(512))),
+        ]);
+        assert!(matches!(
+            registry.merge_metadata(
+                "TokenX",
+                changed.clone(),
+                MetadataMergeStrategy::ErrorOnConflict
+            ),
+            Err(ScrgError::Registry(RegistryError::MetadataConflict(MetadataConflict(keys))))
+                if keys == ["abi_blob", "api_key"]
+        ));
+        let report = registry
+            .merge_metadata("TokenX", changed, MetadataMergeStrategy::PreferExisting)
+            .unwrap();
+        assert_eq!(report.kept, ["abi_blob", "api_key"]);
+        assert!(report.added.is_empty());
+        assert_eq!(
+            registry.value("TokenX", "abi_blob").unwrap(),
+            Some(blob.as_str().into())
+        );
+        assert_eq!(
+            registry
+                .reveal(&Actor::default(), "TokenX", "api_key")
+                .unwrap(),
+            Some("sk-live-123".into())
+        );
+    }
+
+    #[test]
+    fn metadata_limits_bound_hooks_setters_and_validation() {
+        let limits = MetadataLimits::new()
+            .with_max_keys(6)
+            .with_max_value_len(40);
+        let oversized = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_limits(limits)
+            .with_metadata("note", "x".repeat(41))
+            .validate();
+        assert!(matches!(
+            oversized,
+            Err(ScrgError::Validation(ValidationError::LimitExceeded(
+                LimitExceeded::ValueTooLarge { len: 41, .. }
+            )))
+        ));
+        let long_key = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_limits(MetadataLimits::new().with_max_key_len(4))
+            .validate();
+        assert!(matches!(
+            long_key,
+            Err(ScrgError::Validation(ValidationError::KeyTooLong {
+                max: 4,
+                ..
+            }))
+        ));
+
+        let runaway = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_limits(limits)
+            .on_phase(Phase::PostValidate, |_, meta| {
+                for i in 0..8 {
+                    meta.insert(format!("k{i}"), true.into());
+                }
+                Ok(())
+            })
+            .validate();
+        assert!(matches!(
+            runaway,
+            Err(ScrgError::Validation(ValidationError::Hook(err))) if err.to_string().contains("above the limit of 6")
+        ));
+
+        let mut deployed = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_limits(limits)
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap();
+        assert!(matches!(
+            deployed.set("note", "y".repeat(64), "ops"),
+            Err(WriteError::Limit(LimitExceeded::ValueTooLarge { .. }))
+        ));
+        assert!(matches!(
+            deployed.set("status", "paused", "ops"),
+            Err(WriteError::Reserved(_))
+        ));
+    }
+
+    // Run-length pairs of (count, byte); enough to shrink the repetitive values used here
+    struct RleCodec;
+
+    impl Codec for RleCodec {
+        fn compress(&self, data: &[u8]) -> Vec<u8> {
+            let mut out = Vec::new();
+            for chunk in data.chunk_by(|a, b| a == b) {
+                for run in chunk.chunks(u8::MAX as usize) {
+                    out.extend([run.len() as u8, run[0]]);
+                }
+            }
+            out
+        }
+
+        fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+            if !data.len().is_multiple_of(2) {
+                return Err(CodecError("odd run-length stream".into()));
+            }
+            Ok(data
+                .chunks(2)
+                .flat_map(|pair| std::iter::repeat_n(pair[1], pair[0] as usize))
+                .collect())
+        }
+    }
+
+    #[test]
+    fn large_values_are_compressed_and_unpacked_on_read() {
+        let abi = "[".to_string() + &"0".repeat(4096) + "]";
+        let mut contract = deployed("TokenX");
+        contract.set("abi_blob", abi.as_str(), "ops").unwrap();
+        let mut registry = ContractRegistry::new().with_compression(RleCodec, 256);
+        registry.register(contract).unwrap();
+
+        let stored = registry.latest("TokenX").unwrap();
+        assert!(!stored.metadata().contains_key("abi_blob"));
+        assert!(matches!(
+            stored.packed.get("abi_blob"),
+            Some(Packed::Compressed(_))
+        ));
+        assert_eq!(stored.get("author"), Some(&"azaM".into()));
+        assert_eq!(stored.get("abi_blob"), Some(&abi.as_str().into()));
+        assert_eq!(
+            registry.value("TokenX", "abi_blob").unwrap(),
+            Some(abi.as_str().into())
+        );
+
+        // The live value and the audited one that set it
+        let stats = registry.compression_stats();
+        assert_eq!(stats.values, 2);
+        assert_eq!(stats.original_bytes, 2 * abi.len());
+        assert!(stats.ratio() < 0.1, "{stats:?}");
+        assert!(stats.saved_bytes() > 4000);
+
+        registry
+            .update_metadata("TokenX", "notes", "n".repeat(1000))
+            .unwrap();
+        assert!(matches!(
+            registry.latest("TokenX").unwrap().packed.get("notes"),
+            Some(Packed::Compressed(_))
+        ));
+        let plain = ContractRegistry::new();
+        assert_eq!(plain.value("TokenX", "abi_blob").unwrap(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn compression_covers_snapshot_and_audit_in_exports() {
+        let (abi, next) = ("0".repeat(4096), "1".repeat(4096));
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("abi_blob", abi.as_str())
+            .validate()
+            .unwrap()
+            .on_deploy(|_| {})
+            .unwrap()
+            .into_deployed();
+        let mut plain = ContractRegistry::new();
+        let mut packed = ContractRegistry::new().with_compression(RleCodec, 256);
+        for registry in [&mut plain, &mut packed] {
+            registry.register(contract.clone()).unwrap();
+            registry
+                .update_metadata("TokenX", "abi_blob", next.as_str())
+                .unwrap();
+        }
+
+        let (plain_json, packed_json) = (plain.to_json().unwrap(), packed.to_json().unwrap());
+        // Five copies in plain form: live, snapshot and three audited values
+        assert!(plain_json.len() > 5 * abi.len());
+        assert!(
+            2 * packed_json.len() < plain_json.len(),
+            "{} vs {}",
+            packed_json.len(),
+            plain_json.len()
+        );
+
+        let loaded = ContractRegistry::from_json(&packed_json)
+            .unwrap()
+            .with_compression(RleCodec, 256);
+        for registry in [&packed, &loaded] {
+            let stored = registry.latest("TokenX").unwrap();
+            assert!(matches!(
+                stored.snapshot_packed.get("abi_blob"),
+                Some(Packed::Compressed(_))
+            ));
+            assert_eq!(
+                stored.snapshot().get("abi_blob"),
+                Some(&abi.as_str().into())
+            );
+            let change = stored.history("abi_blob").pop().unwrap();
+            assert_eq!(change.old_value(), Some(&abi.as_str().into()));
+            assert_eq!(change.new_value(), Some(&next.as_str().into()));
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn zstd_round_trips_metadata() {
+        let codec = ZstdCodec::new(3);
+        let data = "0x6080604052".repeat(200);
+        let packed = codec.compress(data.as_bytes());
+        assert!(packed.len() < data.len() / 10);
+        assert_eq!(codec.decompress(&packed).unwrap(), data.as_bytes());
+        assert!(codec.decompress(b"not zstd").is_err());
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn binary_round_trip_keeps_versions() {
+        let mut registry = ContractRegistry::new().with_order(RegistryOrder::Sorted);
+        registry.register(release("TokenX", "1.0.0")).unwrap();
+        let mut upgrade = release("TokenX", "1.1.0");
+        upgrade.set("bytecode", vec![0x60, 0x80], "ops").unwrap();
+        registry.register(upgrade).unwrap();
+        registry.rollback("TokenX").unwrap();
+
+        let bytes = registry.to_binary().unwrap();
+        assert_eq!(&bytes[..6], b"SCRG\x01\x00");
+        let loaded = ContractRegistry::from_binary(&bytes).unwrap();
+        let snapshots = |registry: &ContractRegistry| {
+            let versions = registry.versions("TokenX").iter();
+            versions.map(DeployedContract::snapshot).collect::<Vec<_>>()
+        };
+        assert_eq!(snapshots(&loaded), snapshots(&registry));
+        assert_eq!(
+            loaded.versions("TokenX")[1].audit,
+            registry.versions("TokenX")[1].audit
+        );
+        assert_eq!(
+            loaded
+                .get("TokenX")
+                .unwrap()
+                .version()
+                .map(Version::to_string),
+            Some("1.0.0".into())
+        );
+        assert_eq!(loaded.to_binary().unwrap(), bytes);
+        assert!(matches!(
+            ContractRegistry::from_binary(b"not a registry"),
+            Err(ScrgError::Serialization(err))
+                if matches!(err.downcast_ref(), Some(BinaryError::BadMagic))
+        ));
+        assert!(matches!(
+            ContractRegistry::from_binary(b"SCRG\x09\x00"),
+            Err(ScrgError::Serialization(err))
+                if matches!(err.downcast_ref(), Some(BinaryError::UnsupportedVersion(9)))
+        ));
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn protobuf_snapshot_round_trips_every_version() {
+        let mut registry = ContractRegistry::new();
+        registry.register(release("TokenX", "1.0.0")).unwrap();
+        let mut upgrade = release("TokenX", "1.1.0");
+        upgrade.set("bytecode", vec![0x60, 0x80], "ops").unwrap();
+        upgrade
+            .set("tags", MetadataValue::List(vec!["defi".into()]), "ops")
+            .unwrap();
+        registry.register(upgrade).unwrap();
+
+        let loaded = ContractRegistry::from_protobuf(&registry.to_protobuf()).unwrap();
+        assert_eq!(loaded.versions("TokenX").len(), 2);
+        let (sent, received) = (
+            registry.get("TokenX").unwrap(),
+            loaded.get("TokenX").unwrap(),
+        );
+        assert_eq!(received.version(), sent.version());
+        assert_eq!(received.get("bytecode"), sent.get("bytecode"));
+        assert_eq!(received.get("tags"), sent.get("tags"));
+        assert_eq!(
+            time_to_pb(received.deployed_at()),
+            time_to_pb(sent.deployed_at())
+        );
+
+        let meta = MetadataMap::from([("decimals".to_string(), MetadataValue::Int(18))]);
+        assert_eq!(metadata_from_pb(metadata_to_pb(&meta)).unwrap(), meta);
+        let empty = pb::Value { kind: None };
+        assert_eq!(
+            value_from_pb(empty).unwrap_err().to_string(),
+            "invalid protobuf message: metadata value has no kind"
+        );
+        assert!(ContractRegistry::from_protobuf(b"\xff\xff").is_err());
+    }
+
+    // Serves one request like a Kubo node's `/api/v0/add`, returning the request it received
+    #[cfg(feature = "ipfs")]
+    fn fake_ipfs_node(reply: &'static str) -> (String, std::thread::JoinHandle<String>) {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                    continue;
+                };
+                let len = head
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase()
+                            .strip_prefix("content-length: ")?
+                            .parse()
+                            .ok()
+                    })
+                    .unwrap_or(0);
+                if n == 0 || body.len() >= len {
+                    break;
+                }
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{reply}",
+                reply.len()
+            )
+            .unwrap();
+            String::from_utf8_lossy(&request).into_owned()
+        });
+        (url, server)
+    }
+
+    #[cfg(feature = "ipfs")]
+    #[test]
+    fn pin_to_ipfs_records_the_cid_of_canonical_metadata() {
+        let (url, server) = fake_ipfs_node(r#"{"Name":"metadata.json","Hash":"bafkreitest"}"#);
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("api_key", "sk-live-123")
+            .pin_to_ipfs(IpfsClient::new(&url))
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(contract.get(IPFS_CID_KEY), Some(&"bafkreitest".into()));
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /api/v0/add?pin=true&cid-version=1 "));
+        assert!(request.contains(r#""name":"TokenX""#));
+        assert!(!request.contains("sk-live-123"));
+
+        let mut meta = MetadataMap::from([
+            ("b".to_string(), MetadataValue::Int(2)),
+            ("a".to_string(), MetadataValue::Int(1)),
+            (IPFS_CID_KEY.to_string(), "bafyold".into()),
+        ]);
+        let first = canonical_json("TokenX", &meta).unwrap();
+        meta.remove(IPFS_CID_KEY);
+        assert_eq!(canonical_json("TokenX", &meta).unwrap(), first);
+        assert!(String::from_utf8(first).unwrap().contains(r#""a":{"#));
+
+        let (url, server) = fake_ipfs_node(r#"{"Hash":""}"#);
+        let failed = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .pin_to_ipfs(IpfsClient::new(&url))
+            .validate()
+            .unwrap()
+            .deploy();
+        assert!(failed.unwrap_err().to_string().contains("no CID"));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn ens_names_are_normalized_and_indexed() {
+        assert_eq!(
+            normalize_ens(" TokenX.MyDAO.eth "),
+            Some("tokenx.mydao.eth".into())
+        );
+        assert_eq!(normalize_ens("eth"), None);
+        assert_eq!(normalize_ens("token..eth"), None);
+        assert_eq!(normalize_ens("my token.eth"), None);
+
+        let token = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_ens("TokenX.MyDAO.eth")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        assert_eq!(token.ens(), Some("tokenx.mydao.eth"));
+        let mut registry = ContractRegistry::new();
+        registry.register(token).unwrap();
+        registry.register(deployed("Vault")).unwrap();
+        assert_eq!(
+            registry
+                .by_ens("TOKENX.mydao.eth")
+                .map(DeployedContract::name),
+            Some("TokenX")
+        );
+        assert!(registry.by_ens("vault.mydao.eth").is_none());
+        registry.remove("TokenX").unwrap();
+        assert!(registry.by_ens("tokenx.mydao.eth").is_none());
+
+        let invalid = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_ens("not an ens name")
+            .validate();
+        assert!(matches!(
+            invalid,
+            Err(ScrgError::Validation(ValidationError::InvalidEnsName(_)))
+        ));
+    }
+
+    #[cfg(feature = "ethers")]
+    #[test]
+    fn resolve_ens_needs_a_name() {
+        let (provider, _mock) = ethers::providers::Provider::mocked();
+        let validated = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap();
+        assert!(matches!(
+            block_on(validated.resolve_ens(&provider)),
+            Err(OnchainError::NoEnsName)
+        ));
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn protobuf_round_trip_keeps_nanoseconds() {
+        let at = SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        let mut contract = release("TokenX", "1.0.0");
+        contract.set("audited_at", at, "ops").unwrap();
+        let mut registry = ContractRegistry::new();
+        registry.register(contract).unwrap();
+
+        let restored = ContractRegistry::from_protobuf(&registry.to_protobuf()).unwrap();
+        let (before, after) = (
+            registry.get("TokenX").unwrap(),
+            restored.get("TokenX").unwrap(),
+        );
+        assert_eq!(after.deployed_at, before.deployed_at);
+        assert_eq!(after.get("audited_at"), Some(&MetadataValue::Timestamp(at)));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_round_trip_uses_plain_values() {
+        let at = SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 250_000_000);
+        let mut contract = release("TokenX", "1.2.3");
+        contract.set("audited_at", at, "ops").unwrap();
+        contract.set("bytecode", vec![0x60, 0x80], "ops").unwrap();
+        contract
+            .set(
+                "tags",
+                MetadataValue::List(vec!["erc20".into(), MetadataValue::Int(7)]),
+                "ops",
+            )
+            .unwrap();
+        let mut registry = ContractRegistry::new();
+        registry.register(contract).unwrap();
+        registry.update_metadata("TokenX", "symbol", "TKX").unwrap();
+
+        let toml = registry.to_toml().unwrap();
+        assert!(!toml.contains("type ="), "{toml}");
+        assert!(toml.contains("version = \"1.2.3\""), "{toml}");
+        assert!(
+            toml.contains("audited_at = 2023-11-14T22:13:20.25Z"),
+            "{toml}"
+        );
+
+        let restored = ContractRegistry::from_toml(&toml).unwrap();
+        let (before, after) = (
+            registry.get("TokenX").unwrap(),
+            restored.get("TokenX").unwrap(),
+        );
+        assert_eq!(after.metadata, before.metadata);
+        assert_eq!(after.deployed_at, before.deployed_at);
+        assert_eq!(after.snapshot, before.snapshot);
+        assert_eq!(after.audit.len(), before.audit.len());
+        assert_eq!(after.audit.last().unwrap().key, "symbol");
+    }
+
+    #[test]
+    fn version_precedence_ignores_build_metadata() {
+        let v = |s: &str| s.parse::<Version>().unwrap();
+        assert_eq!(v("1.0.0+a"), v("1.0.0+b"));
+        assert_eq!(v("1.0.0+a").cmp(&v("1.0.0")), std::cmp::Ordering::Equal);
+        assert!(v("1.0.0-alpha") < v("1.0.0-alpha.1"));
+        assert!(v("1.0.0-alpha.1") < v("1.0.0-alpha.beta"));
+        assert!(v("1.0.0-beta.2") < v("1.0.0-beta.11"));
+        assert!(v("1.0.0-rc.1") < v("1.0.0"));
+        assert!(v("1.0.0") < v("1.0.1"));
+        assert_eq!(v("1.0.0+build.5").to_string(), "1.0.0+build.5");
+
+        let hash = |version: &Version| {
+            use std::hash::{BuildHasher, BuildHasherDefault};
+            BuildHasherDefault::<std::collections::hash_map::DefaultHasher>::default()
+                .hash_one(version)
+        };
+        assert_eq!(hash(&v("1.0.0+a")), hash(&v("1.0.0+b")));
+    }
+
+    #[test]
+    fn build_metadata_does_not_make_a_new_version() {
+        let mut registry = ContractRegistry::new();
+        registry.register(release("TokenX", "1.0.0+a")).unwrap();
+        let previous = registry.register(release("TokenX", "1.0.0+b")).unwrap();
+        assert!(previous.is_some());
+        assert_eq!(registry.versions("TokenX").len(), 1);
+        assert!(registry.get_version("TokenX", "1.0.0").is_some());
+
+        let upgraded = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_version("1.0.0+a")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .upgrade("1.0.0+b");
+        assert!(matches!(
+            upgraded,
+            Err(ScrgError::Upgrade(UpgradeError::NotNewer { .. }))
+        ));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_import_deploys_dependencies_first() {
+        let yaml = "
+contracts:
+  - name: TokenX
+    author: azaM
+    depends_on: [LibraryA]
+  - name: LibraryA
+    author: azaM
+";
+        let registry = ContractRegistry::from_yaml(yaml).unwrap();
+        assert!(registry.get("TokenX").is_some());
+        assert!(registry.get("LibraryA").is_some());
+
+        let cyclic = "
+contracts:
+  - { name: A, author: azaM, depends_on: [B] }
+  - { name: B, author: azaM, depends_on: [A] }
+";
+        assert!(matches!(
+            ContractRegistry::from_yaml(cyclic),
+            Err(ScrgError::Registry(RegistryError::DependencyCycle(_)))
+        ));
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn cli_help_succeeds_and_unknown_commands_fail() {
+        let args =
+            |line: &str| -> Vec<String> { line.split_whitespace().map(str::to_owned).collect() };
+        for help in ["help", "--help", "-h"] {
+            assert!(run_cli(&args(help)).is_ok());
+        }
+        let path = temp_path("cli-help.json");
+        let line = format!("--registry {} frobnicate", path.display());
+        assert!(run_cli(&args(&line)).is_err());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn timestamps_serialize_as_rfc3339() {
+        let at = SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 250_000_000);
+        let json = serde_json::to_string(&MetadataValue::Timestamp(at)).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"timestamp","value":"2023-11-14T22:13:20.25Z"}"#
+        );
+        let offset = r#"{"type":"timestamp","value":"2023-11-14T23:13:20.25+01:00"}"#;
+        let parsed: MetadataValue = serde_json::from_str(offset).unwrap();
+        assert_eq!(parsed, MetadataValue::Timestamp(at));
+
+        let before_epoch = SystemTime::UNIX_EPOCH - Duration::new(86_400, 500_000_000);
+        assert_eq!(format_rfc3339_nanos(before_epoch), "1969-12-30T23:59:59.5Z");
+        assert_eq!(parse_rfc3339("1969-12-30T23:59:59.5Z"), Some(before_epoch));
+        assert_eq!(parse_rfc3339("2023-11-14T22:13:20"), None);
+
+        let contract = release("TokenX", "1.0.0");
+        let json: serde_json::Value = serde_json::from_str(&contract.to_json().unwrap()).unwrap();
+        assert!(json["deployed_at"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn dry_run_leaves_the_builder_untouched() {
+        let mut validated = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .validate()
+            .unwrap()
+            .add_hook(|meta| {
+                meta.insert("network".into(), "mainnet".into());
+            });
+        let before = validated.borrow().clone();
+
+        let dry_run = validated.deploy_dry_run().unwrap();
+        assert_eq!(*validated.borrow(), before);
+        assert!(dry_run.changed_keys().any(|key| key == "network"));
+        assert_eq!(dry_run.metadata.get("status"), Some(&"deployed".into()));
+
+        let deployed = validated.deploy().unwrap().into_deployed();
+        assert_eq!(deployed.get("network"), Some(&"mainnet".into()));
+    }
+
+    #[test]
+    fn hook_errors_name_the_stage_that_failed() {
+        let validated = || {
+            ContractBuilder::new("TokenX")
+                .with_author("azaM")
+                .validate()
+                .unwrap()
+        };
+        let queued = validated()
+            .add_try_hook(|_| Err(HookError::new("rpc unreachable")))
+            .deploy()
+            .unwrap_err();
+        assert_eq!(
+            queued.error().to_string(),
+            "deploy hook failed: rpc unreachable"
+        );
+
+        let post = validated()
+            .on_phase(Phase::PostDeploy, |_, _| Err(HookError::new("no receipt")))
+            .deploy()
+            .unwrap_err();
+        assert_eq!(
+            post.error().to_string(),
+            "post_deploy hook failed: no receipt"
+        );
+
+        let failure = validated()
+            .add_hook(|meta| {
+                meta.insert("status".into(), "archived".into());
+            })
+            .deploy()
+            .unwrap_err();
+        assert!(failure
+            .error()
+            .to_string()
+            .starts_with("deploy hook failed: hook wrote reserved key"));
+        assert_eq!(failure.into_builder().borrow().get("status"), None);
+    }
+
+    #[test]
+    fn env_defaults_skip_lifecycle_keys() {
+        std::env::set_var("SCRG_ENV_LIFECYCLE_NETWORK", "sepolia");
+        std::env::set_var("SCRG_ENV_LIFECYCLE_STATUS", "archived");
+        std::env::set_var("SCRG_ENV_LIFECYCLE_VALIDATED", "true");
+        let validated = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_env_defaults("SCRG_ENV_LIFECYCLE")
+            .validate()
+            .unwrap();
+        let meta = validated.borrow();
+        assert_eq!(meta.get("network"), Some(&"sepolia".into()));
+        assert_eq!(meta.get("status"), None);
+    }
+
+    #[test]
+    fn lifecycle_keys_cannot_be_written_after_deploy() {
+        let mut contract = release("TokenX", "1.0.0");
+        assert!(matches!(
+            contract.set("status", "archived", "test"),
+            Err(WriteError::Reserved(_))
+        ));
+        assert!(contract.remove("id", "test").is_err());
+        assert_eq!(contract.set("symbol", "TKX", "test").unwrap(), None);
+
+        let mut registry = ContractRegistry::new();
+        registry.register(contract).unwrap();
+        assert!(matches!(
+            registry.update_metadata("TokenX", "status", "archived"),
+            Err(ScrgError::Registry(RegistryError::Write(
+                WriteError::Reserved(_)
+            )))
+        ));
+        assert!(registry.get("TokenX").is_some());
+
+        let other = MetadataMap::from([
+            ("network".to_string(), MetadataValue::from("mainnet")),
+            ("validated".to_string(), MetadataValue::Bool(false)),
+        ]);
+        let merged = registry.merge_metadata("TokenX", other, MetadataMergeStrategy::Overwrite);
+        assert!(merged.is_err());
+        assert_eq!(registry.get("TokenX").unwrap().get("network"), None);
+        assert!(matches!(
+            registry.update_metadata("Missing", "symbol", "TKX"),
+            Err(ScrgError::Registry(RegistryError::UnknownContract(_)))
+        ));
+    }
+
+    #[test]
+    fn limits_follow_the_contract_into_the_registry() {
+        let limits = MetadataLimits::new().with_max_value_len(80);
+        let mut contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_limits(limits)
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        let long = "x".repeat(81);
+        assert!(matches!(
+            contract.set("notes", long.as_str(), "test"),
+            Err(WriteError::Limit(LimitExceeded::ValueTooLarge { .. }))
+        ));
+
+        let mut registry = ContractRegistry::new();
+        registry.register(contract).unwrap();
+        assert!(matches!(
+            registry.update_metadata("TokenX", "notes", long.as_str()),
+            Err(ScrgError::Registry(RegistryError::Write(
+                WriteError::Limit(_)
+            )))
+        ));
+        let other = MetadataMap::from([("notes".to_string(), MetadataValue::from(long))]);
+        assert!(registry
+            .merge_metadata("TokenX", other, MetadataMergeStrategy::Overwrite)
+            .is_err());
+        registry
+            .update_metadata("TokenX", "notes", "short")
+            .unwrap();
+        assert_eq!(registry.get("TokenX").unwrap().limits, limits);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_keeps_custom_limits_only() {
+        let plain = release("TokenX", "1.0.0");
+        assert!(!plain.to_json().unwrap().contains("limits"));
+
+        let mut limited = plain.clone();
+        limited.limits = MetadataLimits::new().with_max_keys(32);
+        let restored = DeployedContract::from_json(&limited.to_json().unwrap()).unwrap();
+        assert_eq!(restored.limits, limited.limits);
+    }
+
+    #[cfg(all(feature = "crypto", feature = "encryption"))]
+    #[test]
+    fn encrypted_contracts_keep_verifying() {
+        let signer = Secp256k1Signer::from_bytes(&[7; 32]).unwrap();
+        let public_key = signer.verifying_key();
+        let contract = ContractBuilder::new("Vault")
+            .with_author("azaM")
+            .with_version("1.0.0")
+            .with_metadata("api_key", "hunter2")
+            .validate()
+            .unwrap()
+            .with_signer(signer)
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        let mut registry =
+            ContractRegistry::new().with_encryption(AesGcmCipher::new(&[1; 32]), ["api_key"]);
+        registry.register(contract).unwrap();
+
+        let stored = registry.latest("Vault").unwrap();
+        assert_eq!(stored.get("api_key"), None);
+        assert!(matches!(
+            stored.packed.get("api_key"),
+            Some(Packed::Sealed(_))
+        ));
+        assert_eq!(
+            stored.verify_signature(&public_key),
+            Err(SignatureError::Sealed)
+        );
+        assert_eq!(
+            registry.verify_signature("Vault", &public_key),
+            Some(Ok(()))
+        );
+
+        assert!(matches!(
+            registry.update_metadata("Vault", "api_key", "rotated"),
+            Ok(None)
+        ));
+        let stored = registry.latest("Vault").unwrap();
+        assert_eq!(stored.get("api_key"), None);
+        assert_eq!(
+            registry.verify_signature("Vault", &public_key),
+            Some(Ok(()))
+        );
+        let mut other =
+            ContractRegistry::new().with_encryption(AesGcmCipher::new(&[2; 32]), ["api_key"]);
+        other.restore(stored.clone());
+        assert_eq!(
+            other.verify_signature("Vault", &public_key),
+            Some(Err(SignatureError::Sealed))
+        );
+        let admin = Actor::new("ops", Role::Admin);
+        assert_eq!(
+            registry.reveal(&admin, "Vault", "api_key").unwrap(),
+            Some("rotated".into())
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_values_read_back_through_the_accessors() {
+        let code = vec![0x60; 4096];
+        let source = "contract TokenX {}\n".repeat(200);
+        let mut contract = release("TokenX", "1.0.0");
+        contract.set("bytecode", code.clone(), "ops").unwrap();
+        contract.set("source", source.as_str(), "ops").unwrap();
+        let hash = contract.bytecode_hash();
+        let mut registry = ContractRegistry::new().with_compression(ZstdCodec::new(3), 1024);
+        registry.register(contract).unwrap();
+
+        let stored = registry.latest("TokenX").unwrap();
+        assert!(!stored.metadata().contains_key("bytecode"));
+        assert!(matches!(
+            stored.packed.get("bytecode"),
+            Some(Packed::Compressed(_))
+        ));
+        assert_eq!(stored.bytecode(), Some(&code[..]));
+        assert_eq!(stored.bytecode_hash(), hash);
+        assert_eq!(stored.get("source"), Some(&source.clone().into()));
+        let stats = registry.compression_stats();
+        assert_eq!(stats.values, 4);
+        assert!(stats.saved_bytes() > 0);
+
+        let old = registry
+            .update_metadata("TokenX", "source", "short")
+            .unwrap();
+        assert_eq!(old, Some(source.clone().into()));
+        let stored = registry.latest("TokenX").unwrap();
+        assert_eq!(stored.get("source"), Some(&"short".into()));
+        let change = stored.history("source").pop().unwrap();
+        assert_eq!(change.old_value(), Some(&source.into()));
+    }
+
+    #[cfg(all(feature = "compression", feature = "serde"))]
+    #[test]
+    fn loaded_compressed_values_wait_for_a_codec() {
+        let code = vec![0x60; 4096];
+        let mut contract = release("TokenX", "1.0.0");
+        contract.set("bytecode", code.clone(), "ops").unwrap();
+        let mut registry = ContractRegistry::new().with_compression(ZstdCodec::new(3), 1024);
+        registry.register(contract).unwrap();
+
+        let loaded = ContractRegistry::from_json(&registry.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.latest("TokenX").unwrap().bytecode(), None);
+        assert!(matches!(
+            loaded.value("TokenX", "bytecode"),
+            Err(ScrgError::Registry(RegistryError::Codec(_)))
+        ));
+        let loaded = loaded.with_compression(ZstdCodec::new(3), 1024);
+        assert_eq!(loaded.latest("TokenX").unwrap().bytecode(), Some(&code[..]));
+    }
+
+    #[test]
+    fn content_ids_cover_nested_secrets() {
+        let deploy =
+            |key: &str| MetadataValue::Map(MetadataMap::from([("private_key".into(), key.into())]));
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("deploy", deploy("0xaaaa"))
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        let mut tampered = contract.metadata().clone();
+        tampered.insert("deploy".into(), deploy("0xbbbb"));
+        assert_eq!(
+            content_id("TokenX", contract.metadata()),
+            contract.id().unwrap()
+        );
+        assert_ne!(content_id("TokenX", &tampered), contract.id().unwrap());
+        assert!(!contract.to_string().contains("0xaaaa"));
+        assert!(!format!("{contract:?}").contains("0xaaaa"));
+    }
+
+    #[test]
+    fn secrets_are_matched_by_namespace_and_registry() {
+        let contract = ContractBuilder::new("TokenX")
+            .with_author("azaM")
+            .with_metadata("deploy.private_key", "0xaaaa")
+            .with_metadata("rpc_token", "tok-123")
+            .validate()
+            .unwrap()
+            .deploy()
+            .unwrap()
+            .into_deployed();
+        let id = contract.id().map(str::to_owned);
+        assert!(!contract.to_string().contains("0xaaaa"));
+        assert!(contract.to_string().contains("tok-123"));
+
+        let mut registry = ContractRegistry::new().with_secret_keys(["rpc_token"]);
+        registry.register(contract).unwrap();
+        let shared = registry.redacted();
+        let copy = shared.latest("TokenX").unwrap();
+        assert_eq!(copy.get("deploy.private_key"), Some(&REDACTED.into()));
+        assert_eq!(copy.get("rpc_token"), Some(&REDACTED.into()));
+        assert_eq!(copy.snapshot().get("rpc_token"), Some(&REDACTED.into()));
+
+        let mut csv = Vec::new();
+        registry.export_csv(&mut csv, &["rpc_token"]).unwrap();
+        assert!(!String::from_utf8(csv).unwrap().contains("tok-123"));
+        assert_eq!(
+            registry.latest("TokenX").unwrap().id().map(str::to_owned),
+            id
+        );
+    }
+}
@@ -0,0 +1,18 @@
+// Generates the `protobuf` wire types from `proto/scrg.proto`, plus the service with `grpc`;
+// nothing to do otherwise
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "protobuf")]
+    {
+        // Vendored `protoc` and well-known types unless the environment provides its own
+        if std::env::var_os("PROTOC").is_none() {
+            std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+        }
+        let include = protoc_bin_vendored::include_path()?;
+        let includes = [std::path::PathBuf::from("proto"), include];
+        #[cfg(feature = "grpc")]
+        tonic_build::configure().compile(&["proto/scrg.proto"], &includes)?;
+        #[cfg(not(feature = "grpc"))]
+        prost_build::compile_protos(&["proto/scrg.proto"], &includes)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+#ifndef SCRG_H
+#define SCRG_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum ScrgStatus {
+  ScrgStatus_Ok = 0,
+  // NULL handle or pointer, or a string that is not UTF-8
+  ScrgStatus_InvalidArgument = 1,
+  // Validation, deployment or registration failed; see `scrg_last_error()`
+  ScrgStatus_Failed = 2,
+} ScrgStatus;
+
+typedef struct ScrgRegistry ScrgRegistry;
+
+#ifdef __cplusplus
+extern "C" {
+#endif  // __cplusplus
+
+// Creates an empty registry; free it with `scrg_registry_free`.
+struct ScrgRegistry *scrg_registry_new(void);
+
+// # Safety
+// `registry` is NULL or came from `scrg_registry_new` and is not used afterwards.
+void scrg_registry_free(struct ScrgRegistry *registry);
+
+// Validates, deploys and registers `name` with the metadata in the JSON object
+// `metadata_json`, which must carry `author`.
+//
+// # Safety
+// `registry` is a live handle and both strings are NUL-terminated.
+enum ScrgStatus scrg_register(struct ScrgRegistry *registry,
+                              const char *name,
+                              const char *metadata_json);
+
+// Metadata of the latest version of `name` as a JSON object, or NULL if it is not
+// registered. Free the result with `scrg_string_free`.
+//
+// # Safety
+// `registry` is a live handle and `name` is NUL-terminated.
+char *scrg_get_json(const struct ScrgRegistry *registry, const char *name);
+
+// # Safety
+// `text` is NULL or came from `scrg_get_json` and is not used afterwards.
+void scrg_string_free(char *text);
+
+// Message of the last failure on this thread, or NULL; valid until the next failing call.
+const char *scrg_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SCRG_H */
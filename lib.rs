@@ -0,0 +1,9 @@
+// Library build of `SCRG.rs`. It exists so the core builder and registry can compile as
+// `no_std` + `alloc` with `--no-default-features`; the binary needs `std`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[path = "SCRG.rs"]
+mod scrg;